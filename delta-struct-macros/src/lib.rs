@@ -8,14 +8,13 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use proc_macro2::{Span, TokenTree};
+use proc_macro2::TokenTree;
 use proc_macro_error::{abort_call_site, proc_macro_error};
 use quote::{format_ident, quote};
 use std::{iter::FromIterator, str::FromStr};
 use syn::{
-    parse_macro_input, punctuated::Punctuated, Attribute, Data, DeriveInput, Fields, Ident, Lit,
-    Meta, MetaList, MetaNameValue, NestedMeta, Path, PredicateType, Token, TraitBound,
-    TraitBoundModifier, Type, TypeParamBound, WherePredicate,
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Fields, Ident, Lit, Meta,
+    MetaList, MetaNameValue, NestedMeta, Path, Type, WhereClause, WherePredicate,
 };
 
 /// How a single field is diffed, and therefore how it is represented on the
@@ -30,35 +29,134 @@ enum FieldType {
     /// entries sharing a key are diffed with the value's own `Delta` rather
     /// than recorded as a removal plus an addition.
     UnorderedDelta,
+    /// A bag of key/value entries like [`FieldType::UnorderedDelta`], whose
+    /// values are compared with `PartialEq` and sent whole when they differ.
+    UnorderedScalar,
+    /// A bag of counted elements: like [`FieldType::Unordered`], except that
+    /// an element can be there more than once, and the delta records by how
+    /// much each element's count changed.
+    Multiset,
     /// Compared with `!=` and replaced wholesale.
     Scalar,
+    /// A number whose delta is the signed difference between its two values,
+    /// added on rather than replacing.
+    Counter,
     /// Diffed recursively via the field type's own `Delta` implementation.
     Delta,
+    /// An `Option` whose value is diffed like a [`FieldType::Delta`] while it
+    /// stays `Some`, and sent whole when it appears.
+    OptionalDelta,
+    /// A sequence of records paired by a key field of each record: like
+    /// [`FieldType::UnorderedDelta`], except that the key comes from inside
+    /// the item rather than from the collection.
+    Keyed,
+    /// Diffed by the user's own `Strategy` implementation, named with `with`
+    /// rather than chosen with `field_type`.
+    Strategy,
+    /// A `String`, diffed like [`FieldType::Ordered`] over its characters or
+    /// lines, with splices at byte offsets.
+    Text,
+    /// A sequence aligned like [`FieldType::Ordered`], or by a key field like
+    /// [`FieldType::Keyed`], whose items on both sides are then diffed with
+    /// their own `Delta` rather than replaced.
+    OrderedDelta,
+    /// A `Vec` of records paired by a key field like [`FieldType::Keyed`],
+    /// whose delta keeps their order: changes, removals and insertions, and
+    /// the fewest moves that reorder the records that stayed.
+    KeyedOrdered,
+    /// An array or a `Vec` whose items are paired by position and diffed with
+    /// their own `Delta`, with a splice at the end when the length changes.
+    Indexed,
 }
 
-const VALID_FIELD_TYPES: &str =
-    "\"ordered\", \"unordered\", \"unordered-delta\", \"delta\", or \"scalar\"";
+const VALID_FIELD_TYPES: &str = "\"ordered\", \"ordered-delta\", \"unordered\", \"unordered-delta\", \"unordered-scalar\", \"multiset\", \"keyed\", \"keyed-ordered\", \"indexed\", \"delta\", \"optional-delta\", \"text\", \"counter\", or \"scalar\"";
 
-/// One field of the source struct, as the code generators want it: its name
-/// (or, for a tuple struct, its index), its declared type, how it is diffed,
-/// and the tokens to emit above the field it turns into.
-type Field = (String, Type, FieldType, String);
+/// One field of the source type, as the code generators want it.
+#[derive(Clone)]
+struct Field {
+    /// The field's name or, in a tuple struct or variant, its index.
+    name: String,
+    /// The field's declared type.
+    ty: Type,
+    /// How the field is diffed.
+    field_type: FieldType,
+    /// The tokens to emit above the field it turns into.
+    delta_leader: String,
+    /// Whether the field was marked `skip`, and so has no delta field at all.
+    skip: bool,
+    /// For a `keyed`, `keyed-ordered` or keyed `ordered-delta` field, the
+    /// item's key field and the key's type.
+    key: Option<(proc_macro2::TokenStream, Type)>,
+    /// The `bound` attribute, replacing the predicates the field's type
+    /// would otherwise put on the impl.
+    bound: Option<Vec<WherePredicate>>,
+    /// Whether the container is `reversible`, so that the field's delta also
+    /// records what it replaces.
+    reversible: bool,
+    /// For a `scalar` field, the function deciding whether its two values are
+    /// equal, in place of `==`.
+    eq_with: Option<syn::Path>,
+    /// For a `with` field, the type implementing `Strategy` for it.
+    with: Option<syn::Path>,
+    /// For a `text` field, the `Granularity` variant it is diffed at.
+    granularity: Option<Ident>,
+    /// For a `counter` field, the `Overflow` variant it is applied with.
+    overflow: Option<Ident>,
+    /// For an `ordered` field with an `algorithm` or a `deadline`, the
+    /// `DiffOptions` it is diffed with.
+    diff_options: Option<proc_macro2::TokenStream>,
+    /// For an `ordered` or `unordered` field with `replace_if_cheaper` or a
+    /// `replace_cost`, the function weighing each element.
+    replace: Option<proc_macro2::TokenStream>,
+    /// For an `ordered` field, whether it was marked `in_place`, and so is
+    /// applied through `SpliceTarget` rather than rebuilt.
+    in_place: bool,
+}
+
+/// What a single `#[delta_struct(...)]` says, before the container's `default`
+/// has been used to fill in a missing `field_type`.
+#[derive(Default)]
+struct Attrs {
+    field_type: Option<FieldType>,
+    delta_leader: String,
+    skip: bool,
+    key: Option<String>,
+    key_type: Option<String>,
+    bound: Option<String>,
+    reversible: bool,
+    eq_with: Option<String>,
+    with: Option<String>,
+    granularity: Option<String>,
+    overflow: Option<String>,
+    algorithm: Option<String>,
+    deadline: Option<String>,
+    replace_if_cheaper: bool,
+    replace_cost: Option<String>,
+    in_place: bool,
+}
 
-/// One field as it comes back from attribute parsing, before the container's
-/// `default` has been used to fill in a missing `field_type`.
+/// One field as it comes back from attribute parsing: its name, its type, and
+/// its attributes or the reason they could not be read.
 type ParsedField = (String, Type, ParsedAttrs);
 
-/// The `(field type, delta_leader)` pair a single `#[delta_struct(...)]`
-/// yields, or the reason it could not be read.
-type ParsedAttrs = Result<(Option<FieldType>, String), FieldTypeError>;
+/// The [`Attrs`] a single `#[delta_struct(...)]` yields, or the reason it
+/// could not be read.
+type ParsedAttrs = Result<Attrs, FieldTypeError>;
 
-/// Derives `Delta`, generating a `{Self}Delta` struct that holds only the
+/// Derives `Delta`, generating a `{Self}Delta` type that holds only the
 /// changed parts of a value plus the trait implementation that produces and
 /// applies one.
 ///
-/// The generated struct takes the visibility and generic parameters of the
-/// type it is derived on, and all of its fields are `pub`. A tuple struct's
-/// delta is a tuple struct in turn, with its fields in the same positions.
+/// The generated type takes the visibility and generic parameters of the type
+/// it is derived on. For a struct it is a struct whose fields are all `pub`; a
+/// tuple struct's delta is a tuple struct in turn, with its fields in the same
+/// positions. For an enum it is an enum with a `Replace` variant carrying a
+/// whole new value, for when the variant changed, plus one variant per source
+/// variant with fields, carrying that variant's field deltas. The generated
+/// type implements `Compose` whenever its fields' deltas do, and
+/// `ChangedPaths` whenever its `delta` fields' deltas do. A struct also gets a
+/// `{Type}Field` enum naming its fields, which its delta's `changed_fields()`
+/// returns a set of.
 ///
 /// See the [`delta-struct`](https://docs.rs/delta-struct) crate documentation
 /// for the full picture, including trait bounds, serde usage, and limitations;
@@ -69,7 +167,9 @@ type ParsedAttrs = Result<(Option<FieldType>, String), FieldTypeError>;
 /// | Attribute | Effect |
 /// | --- | --- |
 /// | `default = "<field type>"` | Field type for fields that don't specify one. Defaults to `"scalar"`. |
-/// | `delta_leader = "<tokens>"` | Tokens emitted directly above the generated struct — derives, doc comments, anything. |
+/// | `delta_leader = "<tokens>"` | Tokens emitted directly above the generated type — derives, doc comments, anything. |
+/// | `bound = "<predicates>"` | Where predicates for the `Delta` impl, replacing every bound the fields would have inferred. `""` adds none. |
+/// | `reversible` | Records the old side of every change too, and implements `Invert` for the generated type. See the `invert` module. |
 ///
 /// # Field attributes
 ///
@@ -77,6 +177,20 @@ type ParsedAttrs = Result<(Option<FieldType>, String), FieldTypeError>;
 /// | --- | --- |
 /// | `field_type = "<field type>"` | How this field is diffed. Overrides the container's `default`. |
 /// | `delta_leader = "<tokens>"` | Tokens emitted directly above the generated field. |
+/// | `key = "<field>"`, `key_type = "<type>"` | For a `keyed` or `keyed-ordered` field: the item field records are paired by, and its type. Both are required there, optional together on an `ordered-delta` field, and rejected elsewhere. |
+/// | `bound = "<predicates>"` | Where predicates for the `Delta` impl, replacing the ones inferred from this field's type. |
+/// | `with = "<path>"` | Diffs the field with the named type's `Strategy` implementation instead of a field type. Rejected alongside `field_type`. |
+/// | `eq_with = "<path>"` | For a `scalar` field: a `fn(&T, &T) -> bool` deciding whether the two values are equal, in place of `==`. `T` then needs no `PartialEq`. Rejected on other field types. |
+/// | `granularity = "chars"` or `"lines"` | For a `text` field: whether its diff compares characters, the default, or whole lines. Rejected on other field types. |
+/// | `overflow = "checked"` or `"wrapping"` | For a `counter` field: whether an increment that would take it past its bounds is skipped and refused by `try_apply_delta`, the default, or wraps around. Rejected on other field types. |
+/// | `algorithm = "myers"`, `"patience"` or `"lcs"` | For an `ordered` field: the algorithm its diff runs. Myers by default. Rejected on other field types. |
+/// | `deadline = "<n>ms"` | For an `ordered` field: how long its diff may take, in `s`, `ms`, `us` or `ns`, before it settles for a coarser edit script. Rejected on other field types. |
+/// | `replace_if_cheaper` | For an `ordered` or `unordered` field: the delta becomes an `OrReplace`, which sends the new collection whole when that has fewer elements than the delta touches. The field then also needs `T: FromIterator<Item>`, and an `unordered` one `Item: PartialEq`. Rejected on other field types. |
+/// | `replace_cost = "<path>"` | As `replace_if_cheaper`, weighing each element with a `fn(&Item) -> usize` rather than counting it. |
+/// | `in_place` | For an `ordered` field: applies deltas through `seq::apply_in_place`, splicing the collection rather than rebuilding it. The field then needs `T: SpliceTarget<Item>` in place of `FromIterator<Item>`. Rejected on other field types. |
+/// | `skip` | Leaves the field out of the generated type entirely: it is never compared, never sent, and untouched by `apply_delta`. In an enum the field also needs `T: Default`, which a change of variant sends in its place. The `Fingerprint` derive leaves it out too. |
+///
+/// On an enum, field attributes go on the fields of each variant.
 ///
 /// # Field types
 ///
@@ -84,11 +198,21 @@ type ParsedAttrs = Result<(Option<FieldType>, String), FieldTypeError>;
 ///
 /// | Value | Delta representation | Requires |
 /// | --- | --- | --- |
-/// | `"scalar"` | `Option<T>` | `T: PartialEq` |
+/// | `"scalar"` | `Option<T>` | `T: PartialEq`, unless it has `eq_with` |
+/// | `"counter"` | `Option<<T as Counter>::Increment>`, the signed difference | `T: Counter`, which every primitive number is |
 /// | `"unordered"` | `BagDelta<Item>`, an `add` and a `remove` | `T: IntoIterator + Extend<Item> + TryIndex<Item, Output = Item>` |
 /// | `"unordered-delta"` | `MapDelta<Key, Value, <Value as Delta>::Output>`, an `add`, a `remove`, and a `change` | `T: IntoIterator + Extend<Item> + TryIndexMut<Key, Output = Value> Item: MapEntry` (so `(K, V)`), `Value: Delta` |
-/// | `"ordered"` | `SeqDelta<Item>`, a Myers edit script | `T: IntoIterator + FromIterator<Item>`, `Item: Hash + Eq` |
+/// | `"unordered-scalar"` | `MapDelta<Key, Value, Value>`, an `add`, a `remove`, and a `change` carrying each differing value whole | `T: IntoIterator + Extend<Item> + TryIndexMut<Key, Output = Value>`, `Item: MapEntry`, `Value: PartialEq` |
+/// | `"multiset"` | `MultisetDelta<Element>`, an `add` and a `remove` of `(Element, usize)` count changes | `T: IntoIterator + Multiset<Element>`, `Item: MapEntry<Value = usize>` (so `(Element, usize)`) |
+/// | `"keyed"` | `MapDelta<KeyType, Item, <Item as Delta>::Output>`, an `add`, a `remove`, and a `change` | `T: IntoIterator`, `T = Vec<Item>` to apply, `Item: Delta`, `KeyType: Hash + Eq + Clone` |
+/// | `"keyed-ordered"` | `KeyedOrderedDelta<Item, <Item as Delta>::Output>`, a list of changes, removals, insertions and moves | `T = Vec<Item>`, `Item: Delta`, `KeyType: Hash + Eq` |
+/// | `"indexed"` | `PositionalDelta<Item, <Item as Delta>::Output>`, a change per position that differs and a `tail` splice for a change in length | `T: IntoIterator + Slots<Item>`, `Item: Delta` |
+/// | `"ordered"` | `SeqDelta<Item>`, a Myers edit script | `T: IntoIterator + FromIterator<Item>`, or `SpliceTarget<Item>` with `in_place`, `&T: IntoIterator`, `Item: Hash + Eq` |
+/// | `"ordered-delta"` | `OrderedDelta<Item, <Item as Delta>::Output>`, `splices` and a `change` per kept item | `T: IntoIterator + FromIterator<Item>`, `&T: IntoIterator<Item = &Item>`, `Item: Delta`, and `Item: Hash + Eq` or, with a key, `KeyType: Hash + Eq` |
 /// | `"delta"` | `Option<<T as Delta>::Output>` | `T: Delta` |
+/// | `"optional-delta"` | `Option<OptionDelta<V, <V as Delta>::Output>>`: `Set`, `Clear`, or `Change` | `T = Option<V>`, `V: Delta` |
+/// | `"text"` | `TextDelta`, splices at byte offsets | `T = String` |
+/// | `with = "S"` | `<S as Strategy<T>>::Delta` | `S: Strategy<T>` |
 ///
 /// # Example
 ///
@@ -106,16 +230,69 @@ type ParsedAttrs = Result<(Option<FieldType>, String), FieldTypeError>;
 #[proc_macro_derive(Delta, attributes(delta_struct))]
 #[proc_macro_error]
 pub fn derive_delta(input: TokenStream) -> TokenStream {
+    TokenStream::from(expand(parse_macro_input!(input as DeriveInput), false))
+}
+
+/// Derives `DeltaRef`, the borrowing counterpart of `Delta`: an
+/// implementation of `delta_ref(&old, &new)` that clones only what changed.
+///
+/// It reads the same `#[delta_struct(...)]` attributes as the `Delta` derive,
+/// which it has to sit alongside, and produces the same delta type. On top of
+/// what `Delta` requires, each field needs what borrowing its field type takes:
+///
+/// | Field type | Also requires |
+/// | --- | --- |
+/// | `"scalar"` | `T: Clone` |
+/// | `"unordered"` | `&T: IntoIterator<Item = &Item>`, `Item: Clone` |
+/// | `"unordered-delta"` | `&T: IntoIterator<Item = (&Key, &Value)>`, `Key: Clone`, `Value: DeltaRef + Clone` |
+/// | `"unordered-scalar"` | `&T: IntoIterator<Item = (&Key, &Value)>`, `Key: Clone`, `Value: Clone` |
+/// | `"multiset"` | `&T: IntoIterator<Item = (&Element, &usize)>`, `Element: Clone` |
+/// | `"keyed"`, `"keyed-ordered"` | `Item: DeltaRef + Clone` |
+/// | `"ordered"` | `&T: IntoIterator<Item = &Item>`, `Item: Clone` |
+/// | `"ordered-delta"`, `"indexed"` | `Item: DeltaRef + Clone` |
+/// | `"delta"` | `T: DeltaRef` |
+/// | `"text"`, `"counter"` | nothing more |
+///
+/// An enum also has to be `Clone` itself, since a change of variant sends the
+/// new value whole.
+///
+/// ```ignore
+/// use delta_struct::{Delta, DeltaRef};
+///
+/// #[derive(Delta, DeltaRef)]
+/// struct Device {
+///     #[delta_struct(field_type = "unordered")]
+///     services: std::collections::HashSet<String>,
+///     online: bool,
+/// }
+/// ```
+#[proc_macro_derive(DeltaRef, attributes(delta_struct))]
+#[proc_macro_error]
+pub fn derive_delta_ref(input: TokenStream) -> TokenStream {
+    TokenStream::from(expand(parse_macro_input!(input as DeriveInput), true))
+}
+
+/// Expands either derive. The two share everything up to the diff itself:
+/// `by_ref` selects an `impl DeltaRef` diffing through borrows, and otherwise
+/// the delta type and the `impl Delta` that consumes its arguments.
+fn expand(input: DeriveInput, by_ref: bool) -> proc_macro2::TokenStream {
     let DeriveInput {
         attrs,
         vis,
         ident,
         mut generics,
         data,
-    } = parse_macro_input!(input as DeriveInput);
-    let (default_field_type, delta_leader) =
+    } = input;
+    let (default_field_type, delta_leader, container_bound, reversible) =
         match get_fieldtype_from_attrs(attrs.into_iter(), "default") {
-            Ok((v, delta_leader)) => (v.unwrap_or(FieldType::Scalar), delta_leader),
+            Ok(attrs) => (
+                attrs.field_type.unwrap_or(FieldType::Scalar),
+                attrs.delta_leader,
+                attrs
+                    .bound
+                    .map(|bound| parse_bound(&bound, &ident.to_string())),
+                attrs.reversible,
+            ),
             Err(_) => {
                 abort_call_site!(
                     "delta_struct(default = ...) for {} is not an accepted value, expected {}.",
@@ -124,314 +301,2067 @@ pub fn derive_delta(input: TokenStream) -> TokenStream {
                 );
             }
         };
-
-    let (named, fields) = match data {
-        Data::Struct(strukt) => match strukt.fields {
-            Fields::Named(named) => (
-                true,
-                collect_results(
-                    named.named.into_iter().map(|field| {
-                        (
-                            field.ident.unwrap().to_string(),
-                            field.ty,
-                            get_fieldtype_from_attrs(field.attrs.into_iter(), "field_type"),
-                        )
-                    }),
-                    default_field_type,
-                ),
-            ),
-            Fields::Unnamed(unnamed) => (
-                false,
-                collect_results(
-                    unnamed.unnamed.into_iter().enumerate().map(|(i, field)| {
-                        (
-                            i.to_string(),
-                            field.ty,
-                            get_fieldtype_from_attrs(field.attrs.into_iter(), "field_type"),
-                        )
+    let delta_leader = match proc_macro2::TokenStream::from_str(&delta_leader) {
+        Ok(v) => v,
+        Err(e) => {
+            abort_call_site!("error parsing delta leader as token stream {}", e);
+        }
+    };
+    let delta_ident = format_ident!("{}Delta", ident);
+    // The delta type repeats the source type's generics verbatim, bounds and
+    // all, since its fields can project through them — `<T as Delta>::Output`
+    // for a delta field, `<T as IntoIterator>::Item` for an unordered one. Grab
+    // the where clause before the field predicates below are pushed onto it;
+    // those are the impl's business, not the type's.
+    let og_where_clause = generics.where_clause.clone();
+    let delta_generics = generics.clone();
+    let type_params = generics
+        .type_params()
+        .map(|t| t.ident.clone())
+        .collect::<Vec<_>>();
+    let (
+        delta_type,
+        delta_body,
+        apply_body,
+        check_body,
+        invert_body,
+        compose_body,
+        changed_body,
+        field_items,
+        predicates,
+        invert_predicates,
+        compose_predicates,
+        changed_predicates,
+    ) = match data {
+        Data::Struct(strukt) => {
+            let (named, fields) =
+                parse_fields(strukt.fields, default_field_type, reversible, &ident);
+            let predicates = field_predicates(&fields, &type_params, by_ref);
+            let invert_predicates = invert_predicates(&fields, &type_params);
+            let compose_predicates = compose_predicates(&fields, &type_params);
+            let changed_predicates = changed_paths_predicates(&fields, &type_params);
+            let field_items =
+                field_enum(&vis, &ident, &delta_ident, &delta_generics, named, &fields);
+            let delta_fields = delta_fields(named, true, fields.iter().cloned());
+            let (delta_invert_let, delta_inverted) =
+                delta_invert_fields(named, fields.iter().cloned());
+            let (delta_first, delta_second, delta_composed) =
+                delta_compose_fields(named, fields.iter().cloned());
+            let (delta_changed_let, delta_changed_paths) =
+                delta_changed_fields(named, fields.iter().cloned());
+            let (delta_compute_let, delta_compute_fields) =
+                delta_compute_fields(named, by_ref, fields.iter().cloned(), |og_ident| {
+                    let og_ident: proc_macro2::TokenStream = FromStr::from_str(og_ident).unwrap();
+                    (quote!(old.#og_ident), quote!(new.#og_ident))
+                });
+            let (delta_check_let, delta_check_actions) =
+                delta_check_fields(named, None, fields.iter().cloned(), |og_ident| {
+                    let og_ident: proc_macro2::TokenStream = FromStr::from_str(og_ident).unwrap();
+                    quote!(self.#og_ident)
+                });
+            let (delta_apply_let, delta_apply_actions) =
+                delta_apply_fields(named, fields.into_iter(), |og_ident| {
+                    let og_ident: proc_macro2::TokenStream = FromStr::from_str(og_ident).unwrap();
+                    quote!(self.#og_ident)
+                });
+            // A tuple struct's delta is a tuple struct too, which means the
+            // declaration, the initializer, and the destructuring pattern all
+            // have to switch from braces to parentheses together. Two things
+            // differ beyond the brackets: a tuple struct puts its `where`
+            // clause *after* the fields and ends in a semicolon, and its
+            // constructor lives in the value namespace, which `Self::Output` —
+            // an associated type — cannot reach, so the initializer and
+            // pattern name the struct itself and let inference supply its
+            // generics.
+            let (
+                delta_struct,
+                delta_compute_init,
+                delta_apply_pattern,
+                delta_check_pattern,
+                delta_changed_pattern,
+                delta_invert,
+                delta_compose,
+            ) = if named {
+                (
+                    quote! {
+                        #delta_leader
+                        #vis struct #delta_ident #generics #og_where_clause {
+                            #delta_fields
+                        }
+                    },
+                    quote!(Self::Output { #delta_compute_fields }),
+                    quote!(Self::Output { #delta_apply_let }),
+                    quote!(Self::Output { #delta_check_let }),
+                    quote!(Self { #delta_changed_let }),
+                    quote! {
+                        let Self { #delta_invert_let } = self;
+                        Self { #delta_inverted }
+                    },
+                    quote! {
+                        let Self { #delta_first } = first;
+                        let Self { #delta_second } = second;
+                        Self { #delta_composed }
+                    },
+                )
+            } else {
+                (
+                    quote! {
+                        #delta_leader
+                        #vis struct #delta_ident #generics (#delta_fields) #og_where_clause;
+                    },
+                    quote!(#delta_ident(#delta_compute_fields)),
+                    quote!(#delta_ident(#delta_apply_let)),
+                    quote!(#delta_ident(#delta_check_let)),
+                    quote!(#delta_ident(#delta_changed_let)),
+                    quote! {
+                        let #delta_ident(#delta_invert_let) = self;
+                        #delta_ident(#delta_inverted)
+                    },
+                    quote! {
+                        let #delta_ident(#delta_first) = first;
+                        let #delta_ident(#delta_second) = second;
+                        #delta_ident(#delta_composed)
+                    },
+                )
+            };
+            (
+                delta_struct,
+                quote! {
+                    let mut delta_is_some = false;
+                    #delta_compute_let
+                    if delta_is_some {
+                        Some(#delta_compute_init)
+                    } else {
+                        None
+                    }
+                },
+                quote! {
+                    let #delta_apply_pattern = delta;
+                    #delta_apply_actions
+                },
+                quote! {
+                    let #delta_check_pattern = delta;
+                    #delta_check_actions
+                    Ok(())
+                },
+                delta_invert,
+                delta_compose,
+                quote! {
+                    let #delta_changed_pattern = self;
+                    #[allow(unused_mut)]
+                    let mut delta_changed_paths = ::std::vec::Vec::new();
+                    #delta_changed_paths
+                    delta_changed_paths
+                },
+                field_items,
+                predicates,
+                invert_predicates,
+                compose_predicates,
+                changed_predicates,
+            )
+        }
+        Data::Enum(enom) => {
+            let variants = enom
+                .variants
+                .into_iter()
+                .map(|variant| {
+                    if variant.ident == "Replace" {
+                        abort_call_site!(
+                            "{}::Replace clashes with the variant {} uses for a change of variant. Rename it to derive delta_struct::Delta.",
+                            ident,
+                            delta_ident
+                        );
+                    }
+                    let (named, fields) = parse_fields(
+                        variant.fields,
+                        default_field_type,
+                        reversible,
+                        &format!("{}::{}", ident, variant.ident),
+                    );
+                    (variant.ident, named, fields)
+                })
+                .collect::<Vec<_>>();
+            let mut predicates = variants
+                .iter()
+                .flat_map(|(_, _, fields)| field_predicates(fields, &type_params, by_ref))
+                .collect::<Vec<_>>();
+            if by_ref {
+                // A change of variant clones the new value whole into `Replace`.
+                let (_, ty_generics, _) = generics.split_for_impl();
+                predicates.push(parse_quote!(#ident #ty_generics: ::std::clone::Clone));
+            }
+            // A change of variant resets the new value's skipped fields before
+            // sending it, and that is where the applying side gets them from.
+            predicates.extend(
+                variants
+                    .iter()
+                    .flat_map(|(_, _, fields)| fields)
+                    .filter(|field| {
+                        field.skip
+                            && field.bound.is_none()
+                            && mentions_type_param(field, &type_params)
+                    })
+                    .map(|field| -> WherePredicate {
+                        let ty = &field.ty;
+                        parse_quote!(#ty: ::std::default::Default)
                     }),
-                    default_field_type,
-                ),
-            ),
-            Fields::Unit => (false, Ok(vec![])),
-        },
-        _ => {
+            );
+            let invert_predicates = variants
+                .iter()
+                .flat_map(|(_, _, fields)| invert_predicates(fields, &type_params))
+                .collect();
+            let mut compose_predicates = variants
+                .iter()
+                .flat_map(|(_, _, fields)| compose_predicates(fields, &type_params))
+                .collect::<Vec<_>>();
+            // Composing a `Replace` with a variant's delta applies the
+            // delta to the replacement value, and the reverse, for a
+            // reversible enum, rolls the old value back with its inverse.
+            let (_, ty_generics, _) = generics.split_for_impl();
+            compose_predicates.push(parse_quote!(#ident #ty_generics: Delta<Output = Self>));
+            if reversible {
+                compose_predicates.push(parse_quote!(Self: ::delta_struct::Invert));
+            }
+            let changed_predicates = variants
+                .iter()
+                .flat_map(|(_, _, fields)| changed_paths_predicates(fields, &type_params))
+                .collect();
+            let (
+                delta_type,
+                delta_body,
+                apply_body,
+                check_body,
+                invert_body,
+                compose_body,
+                changed_body,
+            ) = enum_delta(
+                &vis,
+                &ident,
+                &generics,
+                &delta_ident,
+                delta_leader,
+                variants,
+                by_ref,
+                reversible,
+            );
+            (
+                delta_type,
+                delta_body,
+                apply_body,
+                check_body,
+                invert_body,
+                compose_body,
+                changed_body,
+                quote!(),
+                predicates,
+                invert_predicates,
+                compose_predicates,
+                changed_predicates,
+            )
+        }
+        Data::Union(_) => {
             abort_call_site!(
-                "delta_struct::Delta may only be derived for struct types currently. {} is not a struct type."
-            , ident)
+                "delta_struct::Delta may only be derived for struct and enum types. {} is a union.",
+                ident
+            )
         }
     };
-    let fields = match fields {
-        Ok(fields) => fields,
+    // Each field contributes the predicates its field type actually needs, as
+    // worked out by `field_predicates`. A container `bound` replaces all of
+    // them at once.
+    let predicates = container_bound.unwrap_or(predicates);
+    generics.make_where_clause().predicates.extend(predicates);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    if by_ref {
+        return quote! {
+            impl #impl_generics DeltaRef for #ident #ty_generics #where_clause {
+                fn delta_ref(old: &Self, new: &Self) -> Option<Self::Output> {
+                    #delta_body
+                }
+            }
+        };
+    }
+    let delta_impl = quote! {
+      impl #impl_generics Delta for #ident #ty_generics #where_clause  {
+          // `ty_generics` and not `generics`: the latter renders parameter
+          // bounds too, which are not allowed in a type position.
+          type Output = #delta_ident #ty_generics;
+
+          fn delta(old: Self, new: Self) -> Option<Self::Output> {
+            #delta_body
+          }
+
+          fn apply_delta(&mut self, delta: Self::Output) {
+            #apply_body
+          }
+
+          fn check_delta(&self, delta: &Self::Output) -> Result<(), ::delta_struct::ApplyError> {
+            #check_body
+          }
+      }
+    };
+    // Only a reversible delta records enough to be inverted. Inverting is
+    // the delta type's business rather than the source type's, so the impl
+    // carries the delta type's own where clause plus what its fields need.
+    let invert_impl = if reversible {
+        let mut delta_generics = delta_generics.clone();
+        delta_generics
+            .make_where_clause()
+            .predicates
+            .extend(invert_predicates);
+        let (impl_generics, ty_generics, where_clause) = delta_generics.split_for_impl();
+        quote! {
+            impl #impl_generics ::delta_struct::Invert for #delta_ident #ty_generics #where_clause {
+                fn invert(self) -> Self {
+                    #invert_body
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+    // Every delta type composes, as far as its fields' deltas do, and lists
+    // its paths, as far as its `delta` fields' deltas do.
+    let mut changed_generics = delta_generics.clone();
+    changed_generics
+        .make_where_clause()
+        .predicates
+        .extend(changed_predicates);
+    let (changed_impl_generics, changed_ty_generics, changed_where_clause) =
+        changed_generics.split_for_impl();
+    let changed_impl = quote! {
+        impl #changed_impl_generics ::delta_struct::ChangedPaths
+            for #delta_ident #changed_ty_generics #changed_where_clause
+        {
+            fn changed_paths(&self) -> ::std::vec::Vec<::std::vec::Vec<::delta_struct::PathSegment>> {
+                #changed_body
+            }
+        }
+    };
+    let mut compose_generics = delta_generics;
+    compose_generics
+        .make_where_clause()
+        .predicates
+        .extend(compose_predicates);
+    let (impl_generics, ty_generics, where_clause) = compose_generics.split_for_impl();
+    let compose_impl = quote! {
+        impl #impl_generics ::delta_struct::Compose for #delta_ident #ty_generics #where_clause {
+            fn compose(first: Self, second: Self) -> Self {
+                #compose_body
+            }
+        }
+    };
+    quote! {
+        #delta_type
+
+        #delta_impl
+
+        #invert_impl
+
+        #compose_impl
+
+        #changed_impl
+
+        #field_items
+    }
+}
+
+/// Parses the fields of a struct or of one enum variant, resolving each
+/// field's type against the container default and aborting with every bad
+/// field named if any of them could not be read.
+///
+/// `owner` names what the fields belong to in that message — the struct, or
+/// `Enum::Variant`. Returns whether the fields are named alongside them.
+/// `reversible` is the container's flag, which every field carries.
+fn parse_fields(
+    fields: Fields,
+    default_field_type: FieldType,
+    reversible: bool,
+    owner: &dyn std::fmt::Display,
+) -> (bool, Vec<Field>) {
+    let (named, fields) = match fields {
+        Fields::Named(named) => (
+            true,
+            collect_results(
+                named.named.into_iter().map(|field| {
+                    (
+                        field.ident.unwrap().to_string(),
+                        field.ty,
+                        get_fieldtype_from_attrs(field.attrs.into_iter(), "field_type"),
+                    )
+                }),
+                default_field_type,
+            ),
+        ),
+        Fields::Unnamed(unnamed) => (
+            false,
+            collect_results(
+                unnamed.unnamed.into_iter().enumerate().map(|(i, field)| {
+                    (
+                        i.to_string(),
+                        field.ty,
+                        get_fieldtype_from_attrs(field.attrs.into_iter(), "field_type"),
+                    )
+                }),
+                default_field_type,
+            ),
+        ),
+        Fields::Unit => (false, Ok(vec![])),
+    };
+    match fields {
+        Ok(mut fields) => {
+            for field in &mut fields {
+                field.reversible = reversible;
+            }
+            (named, fields)
+        }
         Err(bad_fields) => {
             let bad_fields = format!("{:?}", bad_fields);
             abort_call_site!(
                 "delta_struct(field_type = ...) for fields in {}: {} are not valid values. Expected {}.",
-                ident,
+                owner,
                 bad_fields,
                 VALID_FIELD_TYPES
             )
         }
+    }
+}
+
+/// Emits the delta enum for an enum, along with the bodies of `Delta::delta`,
+/// `Delta::apply_delta`, `Delta::check_delta`, `Invert::invert`,
+/// `Compose::compose` and `ChangedPaths::changed_paths`, as `(declaration,
+/// delta body, apply body, check body, invert body, compose body, changed
+/// paths body)`.
+///
+/// An old and a new value of the same variant are diffed field by field, each
+/// field according to its field type, exactly as a struct's would be. Values
+/// of different variants have nothing to diff against each other, so the new
+/// one travels whole in `Replace`, its skipped fields reset to their
+/// defaults: they are local to each side, so the sender's are not sent, and
+/// the receiver has no values of its own for a variant it did not hold. Unit
+/// variants get no variant of their own: two equal ones produce no delta, and
+/// anything else is a `Replace`.
+///
+/// With `by_ref`, the delta body is that of `DeltaRef::delta_ref` instead,
+/// matching on borrows and cloning the new value into a `Replace`. A
+/// `reversible` enum's `Replace` carries the old value as well, in a `Change`.
+///
+/// Checking refuses a variant's delta for a value holding another variant,
+/// and a variant's delta puts the variant ahead of each path it changes. Two
+/// deltas of the same variant compose field by field, and a `Replace` on
+/// either side of a composition absorbs the other delta into its value.
+#[allow(clippy::too_many_arguments)] // Everything the delta enum is built from
+fn enum_delta(
+    vis: &syn::Visibility,
+    ident: &Ident,
+    generics: &syn::Generics,
+    delta_ident: &Ident,
+    delta_leader: proc_macro2::TokenStream,
+    variants: Vec<(Ident, bool, Vec<Field>)>,
+    by_ref: bool,
+    reversible: bool,
+) -> (
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+) {
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+    let mut delta_variants = Vec::new();
+    let mut compute_arms = Vec::new();
+    let mut apply_arms = Vec::new();
+    let mut invert_arms = Vec::new();
+    let mut compose_arms = Vec::new();
+    let mut check_arms = Vec::new();
+    let mut changed_arms = Vec::new();
+    let mut reset_arms = Vec::new();
+    for (variant, named, fields) in variants {
+        if fields.iter().any(|field| field.skip) {
+            let (bindings, locals): (Vec<_>, Vec<_>) = fields
+                .iter()
+                .map(|field| {
+                    let local = format_ident!("skipped_{}", field.name);
+                    match (named, field.skip) {
+                        (true, false) => (quote!(), None),
+                        (true, true) => {
+                            let og_ident = format_ident!("{}", field.name);
+                            (quote!(#og_ident: #local,), Some(local))
+                        }
+                        (false, false) => (quote!(_,), None),
+                        (false, true) => (quote!(#local,), Some(local)),
+                    }
+                })
+                .unzip();
+            let locals = locals.into_iter().flatten();
+            let reset_pattern = if named {
+                quote!(Self::#variant { #(#bindings)* .. })
+            } else {
+                quote!(Self::#variant( #(#bindings)* ))
+            };
+            reset_arms.push(quote! {
+                #reset_pattern => {
+                    #(*#locals = ::std::default::Default::default();)*
+                }
+            });
+        }
+        if fields.iter().all(|field| field.skip) {
+            compute_arms.push(quote! {
+                (Self::#variant, Self::#variant) => None,
+            });
+            continue;
+        }
+        // A variant's fields are reached through the locals its patterns bind,
+        // prefixed so that the old value's, the new value's, and the delta's
+        // can all be in scope at once.
+        let binding = |prefix: &str, og_ident: &str| {
+            if named {
+                format_ident!("{}_{}", prefix, og_ident)
+            } else {
+                format_ident!("{}_field_{}", prefix, og_ident)
+            }
+        };
+        let pattern = |prefix: &str, binds: &dyn Fn(&Field) -> bool| {
+            // Skipped fields bind nothing, and neither do those `binds`
+            // rejects: a named one is left to the `..`, and a positional one
+            // still needs its slot, held by a `_`.
+            let bindings = fields.iter().map(|field| {
+                let local = binding(prefix, &field.name);
+                match (named, !field.skip && binds(field)) {
+                    (true, false) => quote!(),
+                    (true, true) => {
+                        let og_ident = format_ident!("{}", field.name);
+                        quote!(#og_ident: #local,)
+                    }
+                    (false, false) => quote!(_,),
+                    (false, true) => quote!(#local,),
+                }
+            });
+            if named {
+                quote!(Self::#variant { #(#bindings)* .. })
+            } else {
+                quote!(Self::#variant( #(#bindings)* ))
+            }
+        };
+        let every = |_: &Field| true;
+        let (old_pattern, new_pattern, this_pattern) = (
+            pattern("old", &every),
+            pattern("new", &every),
+            pattern("this", &every),
+        );
+        // Only the fields whose deltas can fail to fit are checked.
+        let checked_pattern = pattern("this", &|field| field.field_type != FieldType::Scalar);
+
+        let delta_fields = delta_fields(named, false, fields.iter().cloned());
+        let (delta_compute_let, delta_compute_fields) =
+            delta_compute_fields(named, by_ref, fields.iter().cloned(), |og_ident| {
+                let (old, new) = (binding("old", og_ident), binding("new", og_ident));
+                // Matching on borrows binds references, which the field
+                // diffs want dereferenced back into places.
+                if by_ref {
+                    (quote!((*#old)), quote!((*#new)))
+                } else {
+                    (quote!(#old), quote!(#new))
+                }
+            });
+        let (_, delta_inverted) = delta_invert_fields(named, fields.iter().cloned());
+        let (delta_first, delta_second, delta_composed) =
+            delta_compose_fields(named, fields.iter().cloned());
+        let (delta_changed_let, delta_changed_paths) =
+            delta_changed_fields(named, fields.iter().cloned());
+        let (delta_check_let, delta_check_actions) =
+            delta_check_fields(named, Some(&variant), fields.iter().cloned(), |og_ident| {
+                let this = binding("this", og_ident);
+                quote!((*#this))
+            });
+        let (delta_apply_let, delta_apply_actions) =
+            delta_apply_fields(named, fields.into_iter(), |og_ident| {
+                let this = binding("this", og_ident);
+                quote!((*#this))
+            });
+        let (declaration, init, delta_pattern, check_pattern, changed_pattern, inverted, composed) =
+            if named {
+                (
+                    quote!(#variant { #delta_fields }),
+                    quote!(#delta_ident::#variant { #delta_compute_fields }),
+                    quote!(#delta_ident::#variant { #delta_apply_let }),
+                    quote!(#delta_ident::#variant { #delta_check_let }),
+                    quote!(#delta_ident::#variant { #delta_changed_let }),
+                    quote!(#delta_ident::#variant { #delta_inverted }),
+                    quote! {
+                        (#delta_ident::#variant { #delta_first }, #delta_ident::#variant { #delta_second }) => {
+                            #delta_ident::#variant { #delta_composed }
+                        }
+                    },
+                )
+            } else {
+                (
+                    quote!(#variant(#delta_fields)),
+                    quote!(#delta_ident::#variant(#delta_compute_fields)),
+                    quote!(#delta_ident::#variant(#delta_apply_let)),
+                    quote!(#delta_ident::#variant(#delta_check_let)),
+                    quote!(#delta_ident::#variant(#delta_changed_let)),
+                    quote!(#delta_ident::#variant(#delta_inverted)),
+                    quote! {
+                        (#delta_ident::#variant(#delta_first), #delta_ident::#variant(#delta_second)) => {
+                            #delta_ident::#variant(#delta_composed)
+                        }
+                    },
+                )
+            };
+        invert_arms.push(quote!(#delta_pattern => #inverted,));
+        compose_arms.push(composed);
+        let variant_name = variant.to_string();
+        changed_arms.push(quote! {
+            #changed_pattern => {
+                let mut delta_changed_paths = ::std::vec::Vec::new();
+                #delta_changed_paths
+                for path in &mut delta_changed_paths {
+                    path.insert(0, ::delta_struct::PathSegment::Variant(#variant_name));
+                }
+                delta_changed_paths
+            }
+        });
+        check_arms.push(quote! {
+            #check_pattern => {
+                #[allow(unreachable_patterns)]
+                match self {
+                    #checked_pattern => {
+                        #delta_check_actions
+                        Ok(())
+                    }
+                    _ => Err(
+                        ::delta_struct::ApplyError::new(::delta_struct::Problem::WrongVariant)
+                            .within(::delta_struct::PathSegment::Variant(#variant_name)),
+                    ),
+                }
+            }
+        });
+        delta_variants.push(declaration);
+        compute_arms.push(quote! {
+            (#old_pattern, #new_pattern) => {
+                let mut delta_is_some = false;
+                #delta_compute_let
+                if delta_is_some {
+                    Some(#init)
+                } else {
+                    None
+                }
+            }
+        });
+        apply_arms.push(quote! {
+            #delta_pattern => {
+                // A delta for some other variant than the one `self` holds has
+                // nothing to land on, and is ignored.
+                #[allow(unreachable_patterns)]
+                match self {
+                    #this_pattern => {
+                        #delta_apply_actions
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+    let replaced = if reversible {
+        quote!(::delta_struct::Change<#ident #ty_generics>)
+    } else {
+        quote!(#ident #ty_generics)
     };
-    let delta_leader = match proc_macro2::TokenStream::from_str(&delta_leader) {
-        Ok(v) => v,
-        Err(e) => {
-            abort_call_site!("error parsing delta leader as token stream {}", e);
+    let declaration = quote! {
+        #delta_leader
+        #vis enum #delta_ident #generics #where_clause {
+            Replace(#replaced),
+            #(#delta_variants,)*
         }
     };
-    let delta_ident = format_ident!("{}Delta", ident);
-    let delta_fields = delta_fields(named, fields.iter().cloned());
-    // The delta struct repeats the source type's generics verbatim, bounds and
-    // all, since its fields can project through them — `<T as Delta>::Output`
-    // for a delta field, `<T as IntoIterator>::Item` for an unordered one. Grab
-    // the where clause before the `PartialEq` predicates below are pushed onto
-    // it; those are the impl's business, not the struct's.
-    let og_where_clause = generics.where_clause.clone();
-    let (delta_compute_let, delta_compute_fields) =
-        delta_compute_fields(named, fields.iter().cloned());
-    let (delta_apply_let, delta_apply_actions) = delta_apply_fields(named, fields.into_iter());
-    // A tuple struct's delta is a tuple struct too, which means the
-    // declaration, the initializer, and the destructuring pattern all have to
-    // switch from braces to parentheses together. Two things differ beyond the
-    // brackets: a tuple struct puts its `where` clause *after* the fields and
-    // ends in a semicolon, and its constructor lives in the value namespace,
-    // which `Self::Output` — an associated type — cannot reach, so the
-    // initializer and pattern name the struct itself and let inference supply
-    // its generics.
-    let (delta_struct, delta_compute_init, delta_apply_pattern) = if named {
+    let (scrutinee, replacement) = match (by_ref, reversible) {
+        // Only an enum with skipped fields has anything to reset.
+        _ if !reset_arms.is_empty() => {
+            let reset = |value: Ident| {
+                quote! {
+                    #[allow(unreachable_patterns)]
+                    match &mut #value {
+                        #(#reset_arms)*
+                        _ => {}
+                    }
+                }
+            };
+            let taken = |value: &str| {
+                let value = format_ident!("{}", value);
+                if by_ref {
+                    quote!(::std::clone::Clone::clone(#value))
+                } else {
+                    quote!(#value)
+                }
+            };
+            let (old, new) = (taken("old"), taken("new"));
+            let reset_new = reset(format_ident!("new"));
+            let replacement = if reversible {
+                let reset_old = reset(format_ident!("old"));
+                quote! {{
+                    let (mut old, mut new) = (#old, #new);
+                    #reset_old
+                    #reset_new
+                    ::delta_struct::Change { old, new }
+                }}
+            } else {
+                quote! {{
+                    let mut new = #new;
+                    #reset_new
+                    new
+                }}
+            };
+            (if by_ref { quote!(*old) } else { quote!(old) }, replacement)
+        }
+        (true, true) => (
+            quote!(*old),
+            quote! {
+                ::delta_struct::Change {
+                    old: ::std::clone::Clone::clone(old),
+                    new: ::std::clone::Clone::clone(new),
+                }
+            },
+        ),
+        (true, false) => (quote!(*old), quote!(::std::clone::Clone::clone(new))),
+        (false, true) => (quote!(old), quote!(::delta_struct::Change { old, new })),
+        (false, false) => (quote!(old), quote!(new)),
+    };
+    let delta_body = if compute_arms.is_empty() {
+        // An enum with no variants has no values, so there is nothing to
+        // match on beyond the fact that this can never be called.
+        quote!(match #scrutinee {})
+    } else {
+        quote! {
+            // The fallback is unreachable when the enum has a single variant,
+            // which the arms above then cover completely.
+            #[allow(unreachable_patterns)]
+            match (old, new) {
+                #(#compute_arms)*
+                (old, new) => Some(#delta_ident::Replace(#replacement)),
+            }
+        }
+    };
+    let replaced_value = if reversible {
+        quote!(value.new)
+    } else {
+        quote!(value)
+    };
+    let apply_body = quote! {
+        match delta {
+            #delta_ident::Replace(value) => *self = #replaced_value,
+            #(#apply_arms)*
+        }
+    };
+    let invert_body = quote! {
+        match self {
+            #delta_ident::Replace(change) => {
+                #delta_ident::Replace(::delta_struct::Invert::invert(change))
+            }
+            #(#invert_arms)*
+        }
+    };
+    // A reversible `Replace` keeps the value from before the other delta
+    // too, which for a delta coming first means undoing it on the old value
+    // the `Replace` recorded.
+    let (replaced_twice, replaced_first, replaced_second) = if reversible {
         (
+            quote!(::delta_struct::Compose::compose(first, second)),
+            quote!(value.new.apply_delta(second)),
             quote! {
-                #delta_leader
-                #vis struct #delta_ident #generics #og_where_clause {
-                    #delta_fields
+                (first, #delta_ident::Replace(mut value)) => {
+                    value.old.apply_delta(::delta_struct::Invert::invert(first));
+                    #delta_ident::Replace(value)
+                }
+            },
+        )
+    } else {
+        (
+            quote!(second),
+            quote!(value.apply_delta(second)),
+            quote!((_, second @ #delta_ident::Replace(_)) => second,),
+        )
+    };
+    let compose_body = quote! {
+        // The fallback only meets deltas of two different variants, which
+        // consecutive deltas never are; the second one would have been
+        // ignored anyway.
+        #[allow(unreachable_patterns)]
+        match (first, second) {
+            (#delta_ident::Replace(first), #delta_ident::Replace(second)) => {
+                #delta_ident::Replace(#replaced_twice)
+            }
+            (#delta_ident::Replace(mut value), second) => {
+                #replaced_first;
+                #delta_ident::Replace(value)
+            }
+            #replaced_second
+            #(#compose_arms)*
+            (first, _) => first,
+        }
+    };
+    let check_body = quote! {
+        match delta {
+            #delta_ident::Replace(_) => Ok(()),
+            #(#check_arms)*
+        }
+    };
+    // A replacement changes the whole value, which is the empty path.
+    let changed_body = quote! {
+        match self {
+            #delta_ident::Replace(_) => ::std::vec![::std::vec::Vec::new()],
+            #(#changed_arms)*
+        }
+    };
+    (
+        declaration,
+        delta_body,
+        apply_body,
+        check_body,
+        invert_body,
+        compose_body,
+        changed_body,
+    )
+}
+
+/// Emits the field declarations of the generated delta struct, or of one
+/// variant of a generated delta enum.
+///
+/// Fields marked `skip` are left out. For the rest, `name` is the source
+/// field's name or, for tuple structs, its index, and `named` says
+/// which of the two it is, and so whether these declarations are about to be
+/// wrapped in braces or in parentheses: a tuple struct's delta is a tuple
+/// struct too, and its fields are positional rather than named. `public` marks
+/// each field `pub`, which a struct wants and an enum variant forbids.
+fn delta_fields(
+    named: bool,
+    public: bool,
+    iter: impl Iterator<Item = Field>,
+) -> proc_macro2::TokenStream {
+    let vis = if public { quote!(pub) } else { quote!() };
+    FromIterator::from_iter(iter.filter(|field| !field.skip).map(|field| {
+        let Field {
+            name: ident,
+            delta_leader: field_leader,
+            ..
+        } = &field;
+        let field_leader = proc_macro2::TokenStream::from_str(field_leader).unwrap();
+        let declared_ty = declared_type(&field);
+        if named {
+            let ident = format_ident!("{}", ident);
+            quote! {
+                #field_leader
+                #vis #ident: #declared_ty,
+            }
+        } else {
+            quote! {
+                #field_leader
+                #vis #declared_ty,
+            }
+        }
+    }))
+}
+
+/// The type a field turns into on the generated delta type.
+///
+/// A `reversible` field records the old side of each change as well: both
+/// values of a `scalar`, and the removed values and items of a map or a
+/// sequence. `unordered`, `multiset` and `delta` fields look the same either
+/// way, the first two because their deltas record both sides already — see
+/// [`records_both_sides`] — and the last because it is whatever the field
+/// type's own delta is.
+fn declared_type(field: &Field) -> proc_macro2::TokenStream {
+    let ty = &field.ty;
+    let (
+        map_delta,
+        seq_delta,
+        ordered_delta,
+        keyed_ordered_delta,
+        positional_delta,
+        option_delta,
+        text_delta,
+        scalar,
+    ) = if field.reversible {
+        (
+            quote!(::delta_struct::ReversibleMapDelta),
+            quote!(::delta_struct::ReversibleSeqDelta),
+            quote!(::delta_struct::ReversibleOrderedDelta),
+            quote!(::delta_struct::ReversibleKeyedOrderedDelta),
+            quote!(::delta_struct::ReversiblePositionalDelta),
+            quote!(::delta_struct::ReversibleOptionDelta),
+            quote!(::delta_struct::ReversibleTextDelta),
+            quote!(::delta_struct::Change<#ty>),
+        )
+    } else {
+        (
+            quote!(::delta_struct::MapDelta),
+            quote!(::delta_struct::SeqDelta),
+            quote!(::delta_struct::OrderedDelta),
+            quote!(::delta_struct::KeyedOrderedDelta),
+            quote!(::delta_struct::PositionalDelta),
+            quote!(::delta_struct::OptionDelta),
+            quote!(::delta_struct::TextDelta),
+            quote!(#ty),
+        )
+    };
+    let item = quote!(<#ty as ::std::iter::IntoIterator>::Item);
+    let bag_delta = quote!(::delta_struct::BagDelta<#item>);
+    match field.field_type {
+        FieldType::Ordered | FieldType::Unordered if field.replace.is_some() => {
+            let delta = match field.field_type {
+                FieldType::Ordered => quote!(#seq_delta<#item>),
+                _ => bag_delta,
+            };
+            let replacement = if field.reversible {
+                quote!(::delta_struct::Change<::std::vec::Vec<#item>>)
+            } else {
+                quote!(::std::vec::Vec<#item>)
+            };
+            quote!(::delta_struct::OrReplace<#delta, #replacement>)
+        }
+        FieldType::Ordered => quote!(#seq_delta<#item>),
+        FieldType::Unordered => bag_delta,
+        FieldType::Multiset => {
+            let element = quote!(<#item as ::delta_struct::MapEntry>::Key);
+            quote!(::delta_struct::MultisetDelta<#element>)
+        }
+        FieldType::UnorderedDelta => {
+            // The field's own type names the collection, not its key and
+            // value; `MapEntry` is what projects those back out of the item
+            // type so the delta field can be spelled at all.
+            let entry = quote!(<#ty as ::std::iter::IntoIterator>::Item);
+            let key = quote!(<#entry as ::delta_struct::MapEntry>::Key);
+            let value = quote!(<#entry as ::delta_struct::MapEntry>::Value);
+            quote!(#map_delta<#key, #value, <#value as Delta>::Output>)
+        }
+        FieldType::UnorderedScalar => {
+            let key = quote!(<#item as ::delta_struct::MapEntry>::Key);
+            let value = quote!(<#item as ::delta_struct::MapEntry>::Value);
+            let changed = if field.reversible {
+                quote!(::delta_struct::Change<#value>)
+            } else {
+                value.clone()
+            };
+            quote!(#map_delta<#key, #value, #changed>)
+        }
+        FieldType::Keyed => {
+            let (_, key_type) = field.key.as_ref().unwrap();
+            quote!(#map_delta<#key_type, #item, <#item as Delta>::Output>)
+        }
+        FieldType::Scalar => quote!(::std::option::Option<#scalar>),
+        // An increment undoes by negation, so it needs no old side.
+        FieldType::Counter => {
+            quote!(::std::option::Option<<#ty as ::delta_struct::counter::Counter>::Increment>)
+        }
+        FieldType::Delta => quote!(::std::option::Option<<#ty as Delta>::Output>),
+        FieldType::OptionalDelta => {
+            // `Option<T>` iterates over its `T`, which is how to name it.
+            let value = quote!(<#ty as ::std::iter::IntoIterator>::Item);
+            quote!(::std::option::Option<#option_delta<#value, <#value as Delta>::Output>>)
+        }
+        FieldType::Strategy => {
+            let strategy = strategy(field);
+            quote!(#strategy::Delta)
+        }
+        FieldType::Text => text_delta,
+        FieldType::OrderedDelta => {
+            quote!(#ordered_delta<#item, <#item as Delta>::Output>)
+        }
+        FieldType::KeyedOrdered => {
+            quote!(#keyed_ordered_delta<#item, <#item as Delta>::Output>)
+        }
+        FieldType::Indexed => {
+            quote!(#positional_delta<#item, <#item as Delta>::Output>)
+        }
+    }
+}
+
+/// A `with` field's strategy, as the qualified path its items are reached
+/// through: `<Strategy as ::delta_struct::Strategy<FieldType>>`.
+fn strategy(field: &Field) -> proc_macro2::TokenStream {
+    let (ty, with) = (&field.ty, &field.with);
+    quote!(<#with as ::delta_struct::Strategy<#ty>>)
+}
+
+/// The local a field's delta is bound to: the field's own name where it has
+/// one, and `field_0`, `field_1`, … where it does not.
+fn delta_local(named: bool, og_ident: &str) -> Ident {
+    if named {
+        format_ident!("{}", og_ident)
+    } else {
+        format_ident!("field_{}", og_ident)
+    }
+}
+
+/// Emits the body of `Delta::delta`, as `(statements, initializer fields)`.
+///
+/// The statements bind one local per generated field and set `delta_is_some`
+/// whenever they find a real change; the initializer fields then move those
+/// locals into the delta. Fields arrive in the same shape as in
+/// [`delta_fields`], and `access` turns a field's name into the expressions
+/// reaching it on the old and the new value — `old.foo` inside a
+/// struct, a pattern binding inside an enum's match arm.
+///
+/// With `by_ref` the statements are those of `DeltaRef::delta_ref`: `old`
+/// and `new` are only borrowed, and whatever the delta keeps of `new` is
+/// cloned out of it.
+fn delta_compute_fields(
+    named: bool,
+    by_ref: bool,
+    iter: impl Iterator<Item = Field>,
+    access: impl Fn(&str) -> (proc_macro2::TokenStream, proc_macro2::TokenStream),
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    iter.filter(|field| !field.skip)
+        .map(|field| {
+            let (og_ident, field_ty) = (&field.name, field.field_type);
+            let ident = delta_local(named, og_ident);
+            let (old, new) = access(og_ident);
+            let (diff, args, kept_old, kept_new, delta) = if by_ref {
+                (
+                    quote!(diff_ref),
+                    quote!(&#old, &#new),
+                    quote!(::std::clone::Clone::clone(&#old)),
+                    quote!(::std::clone::Clone::clone(&#new)),
+                    quote!(DeltaRef::delta_ref),
+                )
+            } else {
+                (
+                    quote!(diff),
+                    quote!(#old, #new),
+                    quote!(#old),
+                    quote!(#new),
+                    quote!(Delta::delta),
+                )
+            };
+            // A bag records both sides whatever the mode, so `unordered`
+            // has no reversible variant of its diff, and nor has `multiset`.
+            let diff = if field.reversible && !records_both_sides(field_ty) {
+                format_ident!("{}_reversible", diff.to_string())
+            } else {
+                format_ident!("{}", diff.to_string())
+            };
+            let kept = if field.reversible {
+                quote!(::delta_struct::Change { old: #kept_old, new: #kept_new })
+            } else {
+                kept_new
+            };
+            let statements = match field_ty {
+                // Text is diffed through borrows either way, since its delta
+                // holds slices of `new` rather than `new` itself.
+                FieldType::Text => {
+                    let diff = if field.reversible {
+                        quote!(diff_reversible)
+                    } else {
+                        quote!(diff)
+                    };
+                    let granularity = &field.granularity;
+                    quote! {
+                        let #ident = ::delta_struct::text::#diff(
+                            &#old,
+                            &#new,
+                            ::delta_struct::text::Granularity::#granularity,
+                        );
+                        delta_is_some = delta_is_some || !#ident.is_empty();
+                    }
+                }
+                // A key, when there is one, picks the `_by_key` variant of
+                // whichever diff the mode calls for.
+                FieldType::OrderedDelta => {
+                    let (diff, key) = match &field.key {
+                        Some(_) => {
+                            let key = key_accessor(&field.ty, &field.key);
+                            let diff = format_ident!(
+                                "diff{}_by_key{}",
+                                if by_ref { "_ref" } else { "" },
+                                if field.reversible { "_reversible" } else { "" }
+                            );
+                            (diff, Some(quote!(, #key)))
+                        }
+                        None => (diff, None),
+                    };
+                    quote! {
+                        let #ident = ::delta_struct::ordered_delta::#diff(#args #key);
+                        delta_is_some = delta_is_some || !#ident.is_empty();
+                    }
+                }
+                // The `replace` module has its own diffs, named for the
+                // module whose delta they fall back from.
+                FieldType::Ordered | FieldType::Unordered if field.replace.is_some() => {
+                    let diff = format_ident!(
+                        "diff_{}{}{}",
+                        collection_module(field_ty),
+                        if by_ref { "_ref" } else { "" },
+                        if field.reversible { "_reversible" } else { "" }
+                    );
+                    let options = match (field_ty, &field.diff_options) {
+                        (FieldType::Ordered, Some(options)) => Some(quote!(#options,)),
+                        (FieldType::Ordered, None) => {
+                            Some(quote!(::delta_struct::seq::DiffOptions::default(),))
+                        }
+                        _ => None,
+                    };
+                    let cost = &field.replace;
+                    quote! {
+                        let #ident = ::delta_struct::replace::#diff(#args, #options #cost);
+                        delta_is_some = delta_is_some || !#ident.is_empty();
+                    }
+                }
+                FieldType::Ordered if field.diff_options.is_some() => {
+                    let diff = format_ident!("{}_with", diff.to_string());
+                    let options = &field.diff_options;
+                    quote! {
+                        let #ident = ::delta_struct::seq::#diff(#args, #options);
+                        delta_is_some = delta_is_some || !#ident.is_empty();
+                    }
+                }
+                FieldType::Ordered
+                | FieldType::Unordered
+                | FieldType::UnorderedDelta
+                | FieldType::UnorderedScalar
+                | FieldType::Multiset
+                | FieldType::Indexed => {
+                    let module = collection_module(field_ty);
+                    quote! {
+                        let #ident = ::delta_struct::#module::#diff(#args);
+                        delta_is_some = delta_is_some || !#ident.is_empty();
+                    }
+                }
+                FieldType::Scalar => {
+                    let changed = match &field.eq_with {
+                        Some(eq_with) => quote!(!#eq_with(&#old, &#new)),
+                        None => quote!(#old != #new),
+                    };
+                    quote! {
+                        let #ident = if #changed {
+                            delta_is_some = true;
+                            Some(#kept)
+                        } else {
+                            None
+                        };
+                    }
+                }
+                // The difference is all a counter keeps, so it is taken
+                // through borrows either way.
+                FieldType::Counter => quote! {
+                    let #ident = ::delta_struct::counter::diff(&#old, &#new);
+                    delta_is_some = delta_is_some || #ident.is_some();
+                },
+                FieldType::Delta => quote! {
+                    let #ident = #delta(#args);
+                    delta_is_some = delta_is_some || #ident.is_some();
+                },
+                FieldType::OptionalDelta => quote! {
+                    let #ident = ::delta_struct::option::#diff(#args);
+                    delta_is_some = delta_is_some || #ident.is_some();
+                },
+                FieldType::Keyed | FieldType::KeyedOrdered => {
+                    let module = match field_ty {
+                        FieldType::Keyed => format_ident!("keyed"),
+                        _ => collection_module(field_ty),
+                    };
+                    let key = key_accessor(&field.ty, &field.key);
+                    quote! {
+                        let #ident = ::delta_struct::#module::#diff(#args, #key);
+                        delta_is_some = delta_is_some || !#ident.is_empty();
+                    }
+                }
+                FieldType::Strategy => {
+                    let strategy = strategy(&field);
+                    let diff = if by_ref {
+                        quote!(diff_ref)
+                    } else {
+                        quote!(diff)
+                    };
+                    quote! {
+                        let #ident = #strategy::#diff(#args);
+                        delta_is_some = delta_is_some || !#strategy::is_empty(&#ident);
+                    }
+                }
+            };
+            // The locals are listed in declaration order, so this reads as a field
+            // shorthand inside braces and as a positional argument inside parens —
+            // whichever bracket the caller wraps it in.
+            (statements, quote!(#ident,))
+        })
+        .unzip()
+}
+
+/// Emits the body of `Delta::apply_delta`, as `(destructuring pattern,
+/// statements)`.
+///
+/// The pattern takes the delta apart into locals and the statements write
+/// each change back into the target. Fields arrive in the same shape as in
+/// [`delta_fields`], and `access` turns a field's name into the place
+/// expression the change is written to — `self.foo` inside a struct,
+/// `(*this_foo)` inside an enum's match arm.
+fn delta_apply_fields(
+    named: bool,
+    iter: impl Iterator<Item = Field>,
+    access: impl Fn(&str) -> proc_macro2::TokenStream,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    iter.filter(|field| !field.skip)
+        .map(|field| {
+            let (og_ident, field_ty) = (&field.name, field.field_type);
+            let ident = delta_local(named, og_ident);
+            let target = access(og_ident);
+            let statements = match field_ty {
+                FieldType::Ordered | FieldType::Unordered if field.replace.is_some() => {
+                    let apply = collection_apply(&field);
+                    let forward = forward(&field, &ident);
+                    quote! {
+                        ::delta_struct::replace::apply(&mut #target, #forward, #apply);
+                    }
+                }
+                FieldType::Ordered
+                | FieldType::OrderedDelta
+                | FieldType::KeyedOrdered
+                | FieldType::Indexed
+                | FieldType::Unordered
+                | FieldType::UnorderedDelta
+                | FieldType::UnorderedScalar
+                | FieldType::Multiset
+                | FieldType::Text => {
+                    let apply = collection_apply(&field);
+                    let forward = forward(&field, &ident);
+                    quote! {
+                        #apply(&mut #target, #forward);
+                    }
+                }
+                FieldType::Scalar => {
+                    let value = if field.reversible {
+                        quote!(v.new)
+                    } else {
+                        quote!(v)
+                    };
+                    quote! {
+                        if let Some(v) = #ident {
+                            #target = #value;
+                        }
+                    }
+                }
+                FieldType::Counter => {
+                    let overflow = &field.overflow;
+                    quote! {
+                        ::delta_struct::counter::apply(
+                            &mut #target,
+                            #ident,
+                            ::delta_struct::counter::Overflow::#overflow,
+                        );
+                    }
+                }
+                FieldType::Delta => quote! {
+                    if let Some(v) = #ident {
+                        #target.apply_delta(v);
+                    }
+                },
+                FieldType::OptionalDelta => {
+                    let forward = forward(&field, &format_ident!("v"));
+                    quote! {
+                        if let Some(v) = #ident {
+                            ::delta_struct::option::apply(&mut #target, #forward);
+                        }
+                    }
+                }
+                FieldType::Keyed => {
+                    let key = key_accessor(&field.ty, &field.key);
+                    let forward = forward(&field, &ident);
+                    quote! {
+                        ::delta_struct::keyed::apply(&mut #target, #forward, #key);
+                    }
+                }
+                FieldType::Strategy => {
+                    let strategy = strategy(&field);
+                    quote! {
+                        #strategy::apply(&mut #target, #ident);
+                    }
+                }
+            };
+            // Binds one local per field, in declaration order — see the matching
+            // note in `delta_compute_fields` about braces versus parens.
+            (quote!(#ident,), statements)
+        })
+        .unzip()
+}
+
+/// Emits the body of `Delta::check_delta`, as `(destructuring pattern,
+/// statements)`.
+///
+/// The pattern borrows each field's delta out of the delta being checked,
+/// except a `scalar` field's, which always fits. The statements check each of
+/// those against the place `access` returns, as in [`delta_apply_fields`],
+/// and return the first error with its field — and `variant`, inside an
+/// enum — added to its path.
+fn delta_check_fields(
+    named: bool,
+    variant: Option<&Ident>,
+    iter: impl Iterator<Item = Field>,
+    access: impl Fn(&str) -> proc_macro2::TokenStream,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let variant = variant.map(|variant| {
+        let variant = variant.to_string();
+        quote!(.within(::delta_struct::PathSegment::Variant(#variant)))
+    });
+    iter.filter(|field| !field.skip)
+        .map(|field| {
+            let (og_ident, field_ty) = (&field.name, field.field_type);
+            let ident = delta_local(named, og_ident);
+            let target = access(og_ident);
+            let check = if field.reversible && !records_both_sides(field_ty) {
+                format_ident!("check_reversible")
+            } else {
+                format_ident!("check")
+            };
+            let result = match field_ty {
+                FieldType::Scalar => None,
+                FieldType::Ordered | FieldType::Unordered if field.replace.is_some() => {
+                    let module = collection_module(field_ty);
+                    Some(quote! {
+                        ::delta_struct::replace::check(
+                            &#target,
+                            #ident,
+                            ::delta_struct::#module::#check,
+                        )
+                    })
                 }
-            },
-            quote!(Self::Output { #delta_compute_fields }),
-            quote!(Self::Output { #delta_apply_let }),
-        )
+                FieldType::Ordered
+                | FieldType::OrderedDelta
+                | FieldType::KeyedOrdered
+                | FieldType::Indexed
+                | FieldType::Unordered
+                | FieldType::UnorderedDelta
+                | FieldType::UnorderedScalar
+                | FieldType::Multiset
+                | FieldType::Text => {
+                    let module = collection_module(field_ty);
+                    Some(quote!(::delta_struct::#module::#check(&#target, #ident)))
+                }
+                FieldType::Counter => {
+                    let overflow = &field.overflow;
+                    Some(quote! {
+                        ::delta_struct::counter::check(
+                            &#target,
+                            #ident,
+                            ::delta_struct::counter::Overflow::#overflow,
+                        )
+                    })
+                }
+                FieldType::Delta => Some(quote! {
+                    match #ident {
+                        Some(delta) => Delta::check_delta(&#target, delta),
+                        None => Ok(()),
+                    }
+                }),
+                FieldType::OptionalDelta => Some(quote! {
+                    match #ident {
+                        Some(delta) => ::delta_struct::option::#check(&#target, delta),
+                        None => Ok(()),
+                    }
+                }),
+                FieldType::Keyed => {
+                    let key = key_accessor(&field.ty, &field.key);
+                    Some(quote!(::delta_struct::keyed::#check(&#target, #ident, #key)))
+                }
+                FieldType::Strategy => {
+                    let strategy = strategy(&field);
+                    Some(quote!(#strategy::check(&#target, #ident)))
+                }
+            };
+            let binding = match (&result, named) {
+                (Some(_), true) => quote!(#ident,),
+                (None, true) => quote!(#ident: _,),
+                (Some(_), false) => quote!(#ident,),
+                (None, false) => quote!(_,),
+            };
+            let statements = result.map(|result| {
+                quote! {
+                    #result.map_err(|error| {
+                        error.within(::delta_struct::PathSegment::Field(#og_ident)) #variant
+                    })?;
+                }
+            });
+            (binding, quote!(#statements))
+        })
+        .unzip()
+}
+
+/// The delta a collection field's `apply` takes, out of the local `ident`
+/// holding the field's delta: a reversible map or sequence delta, or a
+/// reversible replacement, drops the parts only undoing needs first.
+///
+/// An `unordered-scalar` field's changes turn from `Change`s into plain
+/// values on the way, which no method of the generic map delta can do.
+fn forward(field: &Field, ident: &Ident) -> proc_macro2::TokenStream {
+    if !field.reversible || (records_both_sides(field.field_type) && field.replace.is_none()) {
+        quote!(#ident)
+    } else if field.field_type == FieldType::UnorderedScalar {
+        quote!(::delta_struct::scalar_map::into_forward(#ident))
     } else {
-        (
-            quote! {
-                #delta_leader
-                #vis struct #delta_ident #generics (#delta_fields) #og_where_clause;
-            },
-            quote!(#delta_ident(#delta_compute_fields)),
-            quote!(#delta_ident(#delta_apply_let)),
-        )
-    };
-    // Scalar and unordered fields compare values with `==`, so every type
-    // parameter picks up a `PartialEq` bound on the impl. This is broader than
-    // strictly necessary — a parameter used only by a `delta` field does not
-    // need it.
-    let partial_eq_types = generics
-        .type_params()
-        .map(|t| t.ident.clone())
-        .collect::<Vec<_>>();
-    let where_clause = generics.make_where_clause();
-    for ty in partial_eq_types {
-        let mut bounds = Punctuated::new();
-        let mut segments = Punctuated::new();
-        segments.push(Ident::new("std", Span::call_site()).into());
-        segments.push(Ident::new("cmp", Span::call_site()).into());
-        segments.push(Ident::new("PartialEq", Span::call_site()).into());
-        bounds.push(TypeParamBound::Trait(TraitBound {
-            paren_token: None,
-            modifier: TraitBoundModifier::None,
-            lifetimes: None,
-            path: Path {
-                leading_colon: Some(Token!(::)(Span::call_site())),
-                segments,
-            },
-        }));
-        where_clause
-            .predicates
-            .push(WherePredicate::Type(PredicateType {
-                lifetimes: None,
-                bounded_ty: Type::Verbatim(<Ident as Into<TokenTree>>::into(ty).into()),
-                colon_token: Token!(:)(Span::call_site()),
-                bounds,
-            }));
+        quote!(#ident.into_forward())
     }
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-    let delta_impl = quote! {
-      impl #impl_generics Delta for #ident #ty_generics #where_clause  {
-          // `ty_generics` and not `generics`: the latter renders parameter
-          // bounds too, which are not allowed in a type position.
-          type Output = #delta_ident #ty_generics;
-
-          fn delta(old: Self, new: Self) -> Option<Self::Output> {
-           let mut delta_is_some = false;
-           #delta_compute_let
-           if delta_is_some {
-               Some(#delta_compute_init)
-           } else {
-               None
-           }
-          }
+}
 
-          fn apply_delta(&mut self, delta: Self::Output) {
-            let #delta_apply_pattern = delta;
-            #delta_apply_actions
-          }
-      }
-    };
-    let output = quote! {
-        #delta_struct
+/// Whether a field type's delta records the old side of every change
+/// whatever the mode, so that a `reversible` field has the same delta, diff
+/// and check as any other: a bag's, and a multiset's.
+fn records_both_sides(field_type: FieldType) -> bool {
+    matches!(field_type, FieldType::Unordered | FieldType::Multiset)
+}
 
-        #delta_impl
-    };
-    TokenStream::from(output)
+/// Emits the body of `Invert::invert` for a reversible delta, as
+/// `(destructuring pattern, initializer fields)`: the pattern binds each
+/// field's delta to a local, as in [`delta_apply_fields`], and the
+/// initializer puts each one back inverted.
+///
+/// A `counter` field's increment is a primitive, whose own `Invert` would
+/// not negate it, so its module inverts it instead.
+fn delta_invert_fields(
+    named: bool,
+    iter: impl Iterator<Item = Field>,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    iter.filter(|field| !field.skip)
+        .map(|field| {
+            let ident = delta_local(named, &field.name);
+            let inverted = match field.field_type {
+                FieldType::Counter => quote!(::delta_struct::counter::invert(#ident)),
+                _ => quote!(::delta_struct::Invert::invert(#ident)),
+            };
+            if named {
+                (quote!(#ident,), quote!(#ident: #inverted,))
+            } else {
+                (quote!(#ident,), quote!(#inverted,))
+            }
+        })
+        .unzip()
 }
 
-/// Emits the field declarations of the generated delta struct.
+/// Emits the body of `Compose::compose` for a delta, as `(first pattern,
+/// second pattern, initializer fields)`: the patterns bind each field's delta
+/// in `first` and in `second` to a local, and the initializer composes each
+/// pair.
 ///
-/// Fields arrive as `(name, type, field type, delta_leader)`, where `name` is
-/// the source field's name or, for tuple structs, its index. `named` says
-/// which of the two it is, and so whether these declarations are about to be
-/// wrapped in braces or in parentheses: a tuple struct's delta is a tuple
-/// struct too, and its fields are positional rather than named.
-fn delta_fields(named: bool, iter: impl Iterator<Item = Field>) -> proc_macro2::TokenStream {
-    FromIterator::from_iter(iter.map(|(ident, ty, field_ty, field_leader)| {
-        let field_leader = proc_macro2::TokenStream::from_str(&field_leader).unwrap();
-        let declared_ty = match field_ty {
-            FieldType::Ordered => {
-                quote!(::delta_struct::SeqDelta<<#ty as ::std::iter::IntoIterator>::Item>)
-            }
-            FieldType::Unordered => {
-                quote!(::delta_struct::BagDelta<<#ty as ::std::iter::IntoIterator>::Item>)
-            }
-            FieldType::UnorderedDelta => {
-                // The field's own type names the collection, not its key and
-                // value; `MapEntry` is what projects those back out of the
-                // item type so the delta field can be spelled at all.
-                let entry = quote!(<#ty as ::std::iter::IntoIterator>::Item);
-                let key = quote!(<#entry as ::delta_struct::MapEntry>::Key);
-                let value = quote!(<#entry as ::delta_struct::MapEntry>::Value);
-                quote!(::delta_struct::MapDelta<#key, #value, <#value as Delta>::Output>)
-            }
-            FieldType::Scalar => quote!(::std::option::Option<#ty>),
-            FieldType::Delta => quote!(::std::option::Option<<#ty as Delta>::Output>),
+/// A `scalar` field's later value simply wins, or with `reversible` keeps the
+/// earlier old side; a `delta` field composes the two deltas when both are
+/// there; an `unordered-scalar` field's map delta is composed by its module,
+/// since its values are no deltas, and so are a `counter` field's increments,
+/// which add up; everything else is a collection delta that
+/// composes itself.
+fn delta_compose_fields(
+    named: bool,
+    iter: impl Iterator<Item = Field>,
+) -> (
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+) {
+    let mut first_pattern = proc_macro2::TokenStream::new();
+    let mut second_pattern = proc_macro2::TokenStream::new();
+    let mut composed = proc_macro2::TokenStream::new();
+    for field in iter.filter(|field| !field.skip) {
+        let ident = delta_local(named, &field.name);
+        let (first, second) = (
+            format_ident!("first_{}", ident),
+            format_ident!("second_{}", ident),
+        );
+        let compose = match field.field_type {
+            FieldType::Scalar if !field.reversible => quote!(::delta_struct::compose::replace),
+            FieldType::Scalar | FieldType::Delta => quote!(::delta_struct::compose::option),
+            FieldType::OptionalDelta if field.reversible => {
+                quote!(::delta_struct::option::compose_reversible)
+            }
+            FieldType::OptionalDelta => quote!(::delta_struct::option::compose),
+            FieldType::UnorderedScalar if field.reversible => {
+                quote!(::delta_struct::scalar_map::compose_reversible)
+            }
+            FieldType::UnorderedScalar => quote!(::delta_struct::scalar_map::compose),
+            FieldType::Counter => quote!(::delta_struct::counter::compose),
+            _ => quote!(::delta_struct::Compose::compose),
         };
+        let value = quote!(#compose(#first, #second));
         if named {
-            let ident = format_ident!("{}", ident);
-            quote! {
-                #field_leader
-                pub #ident: #declared_ty,
-            }
+            first_pattern.extend(quote!(#ident: #first,));
+            second_pattern.extend(quote!(#ident: #second,));
+            composed.extend(quote!(#ident: #value,));
         } else {
-            quote! {
-                #field_leader
-                pub #declared_ty,
-            }
+            first_pattern.extend(quote!(#first,));
+            second_pattern.extend(quote!(#second,));
+            composed.extend(quote!(#value,));
         }
-    }))
+    }
+    (first_pattern, second_pattern, composed)
+}
+
+/// Whether a field's delta, reached through the reference `delta`, carries a
+/// change: a `scalar`, `counter`, `delta` or `optional-delta` field's is an
+/// `Option`, a `with` field's strategy says, and every other field's is a collection delta that can be
+/// empty.
+fn field_changed(field: &Field, delta: &Ident) -> proc_macro2::TokenStream {
+    match field.field_type {
+        FieldType::Scalar | FieldType::Counter | FieldType::Delta | FieldType::OptionalDelta => {
+            quote!(#delta.is_some())
+        }
+        FieldType::Strategy => {
+            let strategy = strategy(field);
+            quote!(!#strategy::is_empty(#delta))
+        }
+        _ => quote!(!#delta.is_empty()),
+    }
 }
 
-/// Emits the body of `Delta::delta`, as `(statements, struct initializer)`.
+/// Emits a struct's `{Type}Field` enum with its `Field` impl, and the
+/// `changed_fields` method of the struct's delta.
 ///
-/// The statements bind one local per generated field and set `delta_is_some`
-/// whenever they find a real change; the initializer then moves those locals
-/// into the delta struct. Fields arrive in the same shape as in
-/// [`delta_fields`].
-fn delta_compute_fields(
+/// Each field that is not skipped gets a variant, named after the field in
+/// `UpperCamelCase` or, in a tuple struct, `Field` and its index. The method
+/// destructures the delta rather than reaching into it by field, since a
+/// tuple struct's delta closes up the slots of skipped fields.
+fn field_enum(
+    vis: &syn::Visibility,
+    ident: &Ident,
+    delta_ident: &Ident,
+    delta_generics: &syn::Generics,
     named: bool,
-    iter: impl Iterator<Item = Field>,
-) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    iter.map(|(og_ident, _ty, field_ty, _field_leader)| {
-        let ident = if named {
-            format_ident!("{}", og_ident)
-        } else {
-            format_ident!("field_{}", og_ident)
-        };
-        let og_ident: proc_macro2::TokenStream = FromStr::from_str(&og_ident).unwrap();
-        let statements = match field_ty {
-            FieldType::Ordered | FieldType::Unordered | FieldType::UnorderedDelta => {
-                let module = collection_module(field_ty);
-                quote! {
-                    let #ident = ::delta_struct::#module::diff(old.#og_ident, new.#og_ident);
-                    delta_is_some = delta_is_some || !#ident.is_empty();
+    fields: &[Field],
+) -> proc_macro2::TokenStream {
+    let field_ident = format_ident!("{}Field", ident);
+    let fields = fields
+        .iter()
+        .filter(|field| !field.skip)
+        .collect::<Vec<_>>();
+    let variants = fields
+        .iter()
+        .map(|field| {
+            if named {
+                let camel = field
+                    .name
+                    .trim_start_matches("r#")
+                    .split('_')
+                    .flat_map(|word| {
+                        let mut chars = word.chars();
+                        chars.next().map(|first| first.to_uppercase().chain(chars))
+                    })
+                    .flatten()
+                    .collect::<String>();
+                format_ident!("{}", camel)
+            } else {
+                format_ident!("Field{}", field.name)
+            }
+        })
+        .collect::<Vec<_>>();
+    let names = fields.iter().map(|field| &field.name).collect::<Vec<_>>();
+    let indices = 0..fields.len();
+    let locals = fields
+        .iter()
+        .map(|field| delta_local(named, &field.name))
+        .collect::<Vec<_>>();
+    let changed = fields
+        .iter()
+        .zip(&locals)
+        .map(|(field, local)| field_changed(field, local));
+    let pattern = if named {
+        quote!(Self { #(#locals,)* })
+    } else {
+        quote!(#delta_ident(#(#locals,)*))
+    };
+    let (impl_generics, ty_generics, where_clause) = delta_generics.split_for_impl();
+    quote! {
+        #[allow(dead_code)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #vis enum #field_ident {
+            #(#variants,)*
+        }
+
+        impl ::delta_struct::Field for #field_ident {
+            const ALL: &'static [Self] = &[#(Self::#variants,)*];
+
+            fn index(self) -> usize {
+                match self {
+                    #(Self::#variants => #indices,)*
                 }
             }
-            FieldType::Scalar => quote! {
-                let #ident = if old.#og_ident != new.#og_ident {
-                    delta_is_some = true;
-                    Some(new.#og_ident)
-                } else {
-                    None
-                };
-            },
-            FieldType::Delta => quote! {
-                let #ident = Delta::delta(old.#og_ident, new.#og_ident);
-                delta_is_some = delta_is_some || #ident.is_some();
-            },
-        };
-        // The locals are listed in declaration order, so this reads as a field
-        // shorthand inside braces and as a positional argument inside parens —
-        // whichever bracket the caller wraps it in.
-        (statements, quote!(#ident,))
-    })
-    .unzip()
+
+            fn name(self) -> &'static str {
+                match self {
+                    #(Self::#variants => #names,)*
+                }
+            }
+        }
+
+        impl #impl_generics #delta_ident #ty_generics #where_clause {
+            /// The fields this delta carries a change for.
+            #[allow(dead_code)]
+            #vis fn changed_fields(&self) -> ::delta_struct::FieldSet<#field_ident> {
+                let #pattern = self;
+                #[allow(unused_mut)]
+                let mut changed_fields = ::delta_struct::FieldSet::new();
+                #(
+                    if #changed {
+                        changed_fields.insert(#field_ident::#variants);
+                    }
+                )*
+                changed_fields
+            }
+        }
+    }
 }
 
-/// Emits the body of `Delta::apply_delta`, as `(destructuring pattern,
-/// statements)`.
-///
-/// The pattern takes the delta struct apart into locals and the statements
-/// write each change back into `self`. Fields arrive in the same shape as in
-/// [`delta_fields`].
-fn delta_apply_fields(
+/// Emits the body of `ChangedPaths::changed_paths`, as `(destructuring
+/// pattern, statements)`: the pattern binds a reference to each field's
+/// delta, and the statements push the path of every field that changed onto
+/// `delta_changed_paths`, or the nested paths of a `delta` field.
+fn delta_changed_fields(
     named: bool,
     iter: impl Iterator<Item = Field>,
 ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    iter.map(|(og_ident, _ty, field_ty, _field_leader)| {
-        let ident = if named {
-            format_ident!("{}", og_ident)
-        } else {
-            format_ident!("field_{}", og_ident)
-        };
-        let og_ident: proc_macro2::TokenStream = FromStr::from_str(&og_ident).unwrap();
-        let statements = match field_ty {
-            FieldType::Ordered | FieldType::Unordered | FieldType::UnorderedDelta => {
-                let module = collection_module(field_ty);
-                quote! {
-                    ::delta_struct::#module::apply(&mut self.#og_ident, #ident);
+    iter.filter(|field| !field.skip)
+        .map(|field| {
+            let og_ident = &field.name;
+            let ident = delta_local(named, og_ident);
+            let segment = quote!(::delta_struct::PathSegment::Field(#og_ident));
+            let statement = match field.field_type {
+                FieldType::Delta => quote! {
+                    if let Some(delta) = #ident {
+                        delta_changed_paths.extend(
+                            ::delta_struct::fields::nested_paths(#segment, delta),
+                        );
+                    }
+                },
+                _ => {
+                    let changed = field_changed(&field, &ident);
+                    quote! {
+                        if #changed {
+                            delta_changed_paths.push(::std::vec![#segment]);
+                        }
+                    }
+                }
+            };
+            (quote!(#ident,), statement)
+        })
+        .unzip()
+}
+
+/// The predicates the `Delta` impl needs for `fields`: what each field's
+/// field type asks of its declared type, and nothing for a skipped field.
+///
+/// A field whose type mentions none of `type_params` contributes nothing,
+/// since a bound on a concrete type only repeats what the compiler checks
+/// anyway — and would drag private field types into a public impl's where
+/// clause. A field's own `bound` attribute replaces what it would contribute.
+///
+/// With `by_ref`, these are the predicates of the `DeltaRef` impl instead:
+/// everything `Delta` needs, plus what diffing through borrows does.
+fn field_predicates(fields: &[Field], type_params: &[Ident], by_ref: bool) -> Vec<WherePredicate> {
+    fields
+        .iter()
+        .filter(|field| !field.skip)
+        .flat_map(|field| match &field.bound {
+            Some(bound) => bound.clone(),
+            None if mentions_type_param(field, type_params) => {
+                let mut predicates = field_type_predicates(field);
+                if by_ref {
+                    predicates.extend(field_type_ref_predicates(field));
                 }
+                predicates
+            }
+            None => Vec::new(),
+        })
+        .collect()
+}
+
+/// What a field's field type requires of its declared type — the "Requires"
+/// column of the table on [`derive_delta`], spelled as where predicates.
+fn field_type_predicates(field: &Field) -> Vec<WherePredicate> {
+    let ty = &field.ty;
+    let item: Type = parse_quote!(<#ty as ::std::iter::IntoIterator>::Item);
+    match field.field_type {
+        // An `eq_with` function stands in for `PartialEq`, and its
+        // signature asks for whatever it needs.
+        FieldType::Scalar if field.eq_with.is_some() => Vec::new(),
+        FieldType::Scalar => vec![parse_quote!(#ty: ::std::cmp::PartialEq)],
+        FieldType::Counter => vec![parse_quote!(#ty: ::delta_struct::counter::Counter)],
+        FieldType::Delta => vec![parse_quote!(#ty: Delta)],
+        // The field has to be an `Option` to be diffed at all, which, as for
+        // `keyed`, is the type checker's to say. Stating that it iterates
+        // would only stop the compiler from seeing its `Item` is its value.
+        FieldType::OptionalDelta => vec![parse_quote!(#item: Delta)],
+        FieldType::Unordered => {
+            let mut predicates: Vec<WherePredicate> = vec![parse_quote! {
+                #ty: ::std::iter::IntoIterator
+                    + ::std::iter::Extend<#item>
+                    + ::delta_struct::TryIndex<#item, Output = #item>
+            }];
+            // A replacement is collected into the field, and composed with
+            // a later delta by scanning for what that removes.
+            if field.replace.is_some() {
+                predicates.push(parse_quote!(#ty: ::std::iter::FromIterator<#item>));
+                predicates.push(parse_quote!(#item: ::std::cmp::PartialEq));
+            }
+            predicates
+        }
+        FieldType::UnorderedDelta => {
+            let key: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Key);
+            let value: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Value);
+            vec![
+                parse_quote! {
+                    #ty: ::std::iter::IntoIterator
+                        + ::std::iter::Extend<#item>
+                        + ::delta_struct::TryIndexMut<#key, Output = #value>
+                },
+                parse_quote!(#item: ::delta_struct::MapEntry),
+                parse_quote!(#value: Delta),
+            ]
+        }
+        FieldType::UnorderedScalar => {
+            let key: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Key);
+            let value: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Value);
+            vec![
+                parse_quote! {
+                    #ty: ::std::iter::IntoIterator
+                        + ::std::iter::Extend<#item>
+                        + ::delta_struct::TryIndexMut<#key, Output = #value>
+                },
+                parse_quote!(#item: ::delta_struct::MapEntry),
+                parse_quote!(#value: ::std::cmp::PartialEq),
+            ]
+        }
+        FieldType::Multiset => {
+            let element: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Key);
+            vec![
+                parse_quote!(#ty: ::std::iter::IntoIterator + ::delta_struct::Multiset<#element>),
+                parse_quote!(#item: ::delta_struct::MapEntry<Value = usize>),
+            ]
+        }
+        FieldType::Ordered => {
+            let mut predicates: Vec<WherePredicate> = vec![
+                parse_quote!(#ty: ::std::iter::IntoIterator),
+                parse_quote!(#item: ::std::hash::Hash + ::std::cmp::Eq),
+                // Checking a delta counts the items through a borrow.
+                parse_quote!(for<'delta_check> &'delta_check #ty: ::std::iter::IntoIterator),
+            ];
+            if field.in_place {
+                predicates.push(parse_quote!(#ty: ::delta_struct::seq::SpliceTarget<#item>));
+            }
+            // A replacement is collected into the field rather than spliced.
+            if !field.in_place || field.replace.is_some() {
+                predicates.push(parse_quote!(#ty: ::std::iter::FromIterator<#item>));
+            }
+            predicates
+        }
+        FieldType::Keyed => {
+            // The field has to be a `Vec` to be applied at all, which no
+            // predicate can say; the type checker will.
+            let (_, key_type) = field.key.as_ref().unwrap();
+            vec![
+                parse_quote!(#item: Delta),
+                parse_quote! {
+                    #key_type: ::std::hash::Hash + ::std::cmp::Eq + ::std::clone::Clone
+                },
+            ]
+        }
+        FieldType::Strategy => {
+            let with = &field.with;
+            vec![parse_quote!(#with: ::delta_struct::Strategy<#ty>)]
+        }
+        // Only a `String` will do, which the type checker says well enough.
+        FieldType::Text => Vec::new(),
+        FieldType::OrderedDelta => {
+            let aligned: Type = match &field.key {
+                Some((_, key_type)) => key_type.clone(),
+                None => item.clone(),
+            };
+            vec![
+                parse_quote!(#ty: ::std::iter::IntoIterator + ::std::iter::FromIterator<#item>),
+                parse_quote!(#item: Delta),
+                parse_quote!(#aligned: ::std::hash::Hash + ::std::cmp::Eq),
+                // Checking a delta reaches the changed items through a borrow.
+                parse_quote! {
+                    for<'delta_check> &'delta_check #ty:
+                        ::std::iter::IntoIterator<Item = &'delta_check #item>
+                },
+            ]
+        }
+        FieldType::KeyedOrdered => {
+            // As for `keyed`, only a `Vec` will do, and the type checker
+            // says so. The key is never cloned, since ops carry positions.
+            let (_, key_type) = field.key.as_ref().unwrap();
+            vec![
+                parse_quote!(#item: Delta),
+                parse_quote!(#key_type: ::std::hash::Hash + ::std::cmp::Eq),
+            ]
+        }
+        FieldType::Indexed => vec![
+            parse_quote!(#ty: ::std::iter::IntoIterator + ::delta_struct::indexed::Slots<#item>),
+            parse_quote!(#item: Delta),
+        ],
+    }
+}
+
+/// What diffing a field through borrows requires on top of
+/// [`field_type_predicates`] — the table on [`derive_delta_ref`], spelled as
+/// where predicates.
+fn field_type_ref_predicates(field: &Field) -> Vec<WherePredicate> {
+    let ty = &field.ty;
+    let item: Type = parse_quote!(<#ty as ::std::iter::IntoIterator>::Item);
+    match field.field_type {
+        FieldType::Scalar => vec![parse_quote!(#ty: ::std::clone::Clone)],
+        FieldType::Delta => vec![parse_quote!(#ty: DeltaRef)],
+        FieldType::OptionalDelta => vec![parse_quote!(#item: DeltaRef + ::std::clone::Clone)],
+        FieldType::Unordered | FieldType::Ordered => vec![
+            parse_quote!(for<'delta_ref> &'delta_ref #ty: ::std::iter::IntoIterator<Item = &'delta_ref #item>),
+            parse_quote!(#item: ::std::clone::Clone),
+        ],
+        FieldType::UnorderedDelta => {
+            let key: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Key);
+            let value: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Value);
+            vec![
+                parse_quote! {
+                    for<'delta_ref> &'delta_ref #ty: ::std::iter::IntoIterator<
+                        Item = (&'delta_ref #key, &'delta_ref #value),
+                    >
+                },
+                parse_quote!(#key: ::std::clone::Clone),
+                parse_quote!(#value: DeltaRef + ::std::clone::Clone),
+            ]
+        }
+        FieldType::UnorderedScalar => {
+            let key: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Key);
+            let value: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Value);
+            vec![
+                parse_quote! {
+                    for<'delta_ref> &'delta_ref #ty: ::std::iter::IntoIterator<
+                        Item = (&'delta_ref #key, &'delta_ref #value),
+                    >
+                },
+                parse_quote!(#key: ::std::clone::Clone),
+                parse_quote!(#value: ::std::clone::Clone),
+            ]
+        }
+        FieldType::Multiset => {
+            let element: Type = parse_quote!(<#item as ::delta_struct::MapEntry>::Key);
+            vec![
+                parse_quote! {
+                    for<'delta_ref> &'delta_ref #ty: ::std::iter::IntoIterator<
+                        Item = (&'delta_ref #element, &'delta_ref usize),
+                    >
+                },
+                parse_quote!(#element: ::std::clone::Clone),
+            ]
+        }
+        FieldType::Keyed | FieldType::KeyedOrdered => {
+            vec![parse_quote!(#item: DeltaRef + ::std::clone::Clone)]
+        }
+        // `Strategy::diff_ref` clones both sides unless the strategy says
+        // otherwise, and asks for `Clone` either way.
+        FieldType::Strategy => vec![parse_quote!(#ty: ::std::clone::Clone)],
+        FieldType::Text | FieldType::Counter => Vec::new(),
+        FieldType::OrderedDelta | FieldType::Indexed => vec![
+            parse_quote!(for<'delta_ref> &'delta_ref #ty: ::std::iter::IntoIterator<Item = &'delta_ref #item>),
+            parse_quote!(#item: DeltaRef + ::std::clone::Clone),
+        ],
+    }
+}
+
+/// The predicates the `Invert` impl of a reversible delta type needs: that
+/// each field's delta can be inverted, for the fields whose types mention one
+/// of `type_params`. A `counter` field's increment always can, as long as
+/// the field is a counter at all.
+fn invert_predicates(fields: &[Field], type_params: &[Ident]) -> Vec<WherePredicate> {
+    fields
+        .iter()
+        .filter(|field| !field.skip && mentions_type_param(field, type_params))
+        .map(|field| match field.field_type {
+            FieldType::Counter => {
+                let ty = &field.ty;
+                parse_quote!(#ty: ::delta_struct::counter::Counter)
             }
-            FieldType::Scalar => quote! {
-                if let Some(v) = #ident {
-                    self.#og_ident = v;
+            _ => {
+                let declared = declared_type(field);
+                parse_quote!(#declared: ::delta_struct::Invert)
+            }
+        })
+        .collect()
+}
+
+/// The predicates the `Compose` impl of a delta type needs: that each
+/// field's delta composes. A `scalar` field's always does.
+///
+/// Unlike the other impls', these are stated for concrete field types too,
+/// so that a type whose field deltas cannot compose — a bag of floats, say —
+/// goes without `Compose` rather than failing to derive `Delta` at all. The
+/// compiler rejects an impl outright over a predicate that mentions no
+/// parameter and does not hold, unless it sits under a binder like
+/// `for<'compose>`, which leaves it to be checked wherever the impl is used.
+fn compose_predicates(fields: &[Field], type_params: &[Ident]) -> Vec<WherePredicate> {
+    fields
+        .iter()
+        .filter(|field| !field.skip)
+        .filter_map(|field| {
+            let ty = &field.ty;
+            let mut bound = quote!(::delta_struct::Compose);
+            let composed = match field.field_type {
+                FieldType::Scalar => return None,
+                FieldType::Delta => quote!(<#ty as Delta>::Output),
+                // Composing a reversible change with a clear rolls the
+                // cleared value back by the change's inverse.
+                FieldType::OptionalDelta => {
+                    if field.reversible {
+                        bound.extend(quote!(+ ::delta_struct::Invert));
+                    }
+                    quote!(<<#ty as ::std::iter::IntoIterator>::Item as Delta>::Output)
                 }
-            },
-            FieldType::Delta => quote! {
-                if let Some(v) = #ident {
-                    self.#og_ident.apply_delta(v);
+                // Plain values compose by the later one winning, so only the
+                // keys have anything to ask for.
+                FieldType::UnorderedScalar => {
+                    bound = quote!(::std::hash::Hash + ::std::cmp::Eq);
+                    quote!(<<#ty as ::std::iter::IntoIterator>::Item as ::delta_struct::MapEntry>::Key)
                 }
-            },
-        };
-        // Binds one local per field, in declaration order — see the matching
-        // note in `delta_compute_fields` about braces versus parens.
-        (quote!(#ident,), statements)
-    })
-    .unzip()
+                // Increments add up whatever the counter, once it is one.
+                FieldType::Counter => {
+                    bound = quote!(::delta_struct::counter::Counter);
+                    quote!(#ty)
+                }
+                _ => declared_type(field),
+            };
+            Some(deferred_predicate(
+                field,
+                type_params,
+                composed,
+                quote!(for<'compose>),
+                bound,
+            ))
+        })
+        .collect()
+}
+
+/// The predicates the `ChangedPaths` impl of a delta type needs: that the
+/// delta of each `delta` field lists its paths too. Like
+/// [`compose_predicates`], these are deferred for concrete field types, so a
+/// field whose type's delta cannot list its paths costs the impl rather than
+/// the derive.
+fn changed_paths_predicates(fields: &[Field], type_params: &[Ident]) -> Vec<WherePredicate> {
+    fields
+        .iter()
+        .filter(|field| !field.skip && field.field_type == FieldType::Delta)
+        .map(|field| {
+            let ty = &field.ty;
+            deferred_predicate(
+                field,
+                type_params,
+                quote!(<#ty as Delta>::Output),
+                quote!(for<'changed>),
+                quote!(::delta_struct::ChangedPaths),
+            )
+        })
+        .collect()
+}
+
+/// The predicate `bounded: bound`, put under `binder` unless `field`'s type
+/// mentions one of `type_params` — see [`compose_predicates`] for why.
+fn deferred_predicate(
+    field: &Field,
+    type_params: &[Ident],
+    bounded: proc_macro2::TokenStream,
+    binder: proc_macro2::TokenStream,
+    bound: proc_macro2::TokenStream,
+) -> WherePredicate {
+    if mentions_type_param(field, type_params) {
+        parse_quote!(#bounded: #bound)
+    } else {
+        parse_quote!(#binder #bounded: #bound)
+    }
+}
+
+/// Whether a field's declared type — or, for a field with a key, its key type —
+/// names any of `type_params`.
+///
+/// This is a plain scan for the identifiers, so a path segment that merely
+/// shares a parameter's name counts too. That errs towards adding a bound
+/// that holds anyway, never towards leaving a needed one out.
+fn mentions_type_param(field: &Field, type_params: &[Ident]) -> bool {
+    fn scan(tokens: proc_macro2::TokenStream, type_params: &[Ident]) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => type_params.contains(&ident),
+            TokenTree::Group(group) => scan(group.stream(), type_params),
+            _ => false,
+        })
+    }
+    let ty = &field.ty;
+    let key_type = field.key.as_ref().map(|(_, key_type)| key_type);
+    scan(quote!(#ty #key_type), type_params)
+}
+
+/// Parses a `bound = "..."` attribute into the predicates it lists, aborting
+/// with `owner` named if they do not parse. An empty string is no predicates
+/// at all, which is how to drop an inferred bound without replacing it.
+fn parse_bound(bound: &str, owner: &str) -> Vec<WherePredicate> {
+    match syn::parse_str::<WhereClause>(&format!("where {}", bound)) {
+        Ok(where_clause) => where_clause.predicates.into_iter().collect(),
+        Err(e) => abort_call_site!(
+            "delta_struct(bound = {:?}) on {} is not a list of where predicates: {}",
+            bound,
+            owner,
+            e
+        ),
+    }
+}
+
+/// The closure a `keyed` or keyed `ordered-delta` field hands to its runtime
+/// module, borrowing the key
+/// field out of one item of the collection.
+///
+/// The parameter's type is spelled out because a field access cannot be
+/// resolved on a type that has not been inferred yet.
+fn key_accessor(
+    ty: &Type,
+    key: &Option<(proc_macro2::TokenStream, Type)>,
+) -> proc_macro2::TokenStream {
+    let (key, _) = key.as_ref().unwrap();
+    quote!(|item: &<#ty as ::std::iter::IntoIterator>::Item| &item.#key)
 }
 
 /// The runtime module backing a collection field type.
 ///
-/// The three collection field types differ in what their delta looks like, but
-/// not in how the derive drives one: each module pairs a `diff` and an `apply`
-/// over a delta type that reports whether it is empty. Panics for the two
-/// non-collection field types, which the callers never pass.
+/// The collection field types, and `text`, differ in what their delta looks
+/// like, but not in how the derive drives one: each module pairs a `diff` and
+/// an `apply` over a delta type that reports whether it is empty. Where a
+/// diff takes more than the two sides — `text`'s a granularity, and
+/// `ordered-delta`'s and `keyed-ordered`'s a key — it is emitted on its own.
+/// Panics for every other field type, which the callers never pass — `keyed`
+/// included, since all of its calls take a key closure on top.
 fn collection_module(field_ty: FieldType) -> Ident {
     match field_ty {
         FieldType::Ordered => format_ident!("seq"),
         FieldType::Unordered => format_ident!("bag"),
+        FieldType::Multiset => format_ident!("multiset"),
         FieldType::UnorderedDelta => format_ident!("map"),
-        FieldType::Scalar | FieldType::Delta => {
+        FieldType::UnorderedScalar => format_ident!("scalar_map"),
+        FieldType::Text => format_ident!("text"),
+        FieldType::OrderedDelta => format_ident!("ordered_delta"),
+        FieldType::KeyedOrdered => format_ident!("keyed_ordered"),
+        FieldType::Indexed => format_ident!("indexed"),
+        FieldType::Scalar
+        | FieldType::Counter
+        | FieldType::Delta
+        | FieldType::OptionalDelta
+        | FieldType::Keyed
+        | FieldType::Strategy => {
             unreachable!("{:?} is not a collection field type", field_ty)
         }
     }
 }
 
+/// The function applying a collection field's delta: its module's `apply`,
+/// or `seq::apply_in_place` for an `ordered` field marked `in_place`.
+fn collection_apply(field: &Field) -> proc_macro2::TokenStream {
+    if field.in_place {
+        quote!(::delta_struct::seq::apply_in_place)
+    } else {
+        let module = collection_module(field.field_type);
+        quote!(::delta_struct::#module::apply)
+    }
+}
+
 /// Resolves each field's parsed attributes against the container default,
 /// collecting *every* bad field rather than stopping at the first, so one
 /// compile reports them all.
@@ -441,8 +2371,181 @@ fn collect_results(
     default_field_type: FieldType,
 ) -> Result<Vec<Field>, Vec<String>> {
     iter.fold(Ok(vec![]), |v, i| match (v, i) {
-        (Ok(mut v), (ident, b, Ok((c, d)))) => {
-            v.push((ident, b, c.unwrap_or(default_field_type), d));
+        (Ok(mut v), (name, ty, Ok(attrs))) => {
+            // A strategy stands in for the field type, so naming both is a
+            // contradiction rather than an override.
+            let (field_type, with) = match (attrs.field_type, attrs.with) {
+                (None, Some(with)) => match syn::parse_str::<syn::Path>(&with) {
+                    Ok(with) => (FieldType::Strategy, Some(with)),
+                    Err(_) => abort_call_site!(
+                        "delta_struct(with = {:?}) on field {} could not be parsed as a path to a type.",
+                        with,
+                        name
+                    ),
+                },
+                (Some(_), Some(_)) => abort_call_site!(
+                    "field {} has both delta_struct(field_type = ...) and delta_struct(with = ...). A strategy replaces the field type; keep one.",
+                    name
+                ),
+                (field_type, None) => (field_type.unwrap_or(default_field_type), None),
+            };
+            let key = match (field_type, attrs.key, attrs.key_type) {
+                (
+                    FieldType::Keyed | FieldType::KeyedOrdered | FieldType::OrderedDelta,
+                    Some(key),
+                    Some(key_type),
+                ) => {
+                    match (
+                        proc_macro2::TokenStream::from_str(&key),
+                        syn::parse_str::<Type>(&key_type),
+                    ) {
+                        (Ok(key), Ok(key_type)) => Some((key, key_type)),
+                        _ => abort_call_site!(
+                            "delta_struct(key = {:?}, key_type = {:?}) on field {} could not be parsed as a field name and a type.",
+                            key,
+                            key_type,
+                            name
+                        ),
+                    }
+                }
+                (FieldType::Keyed | FieldType::KeyedOrdered, _, _) => abort_call_site!(
+                    "field {} is \"keyed\" or \"keyed-ordered\", and needs both delta_struct(key = \"...\") naming the item field to pair records by and delta_struct(key_type = \"...\") giving that field's type.",
+                    name
+                ),
+                (_, None, None) => None,
+                (FieldType::OrderedDelta, _, _) => abort_call_site!(
+                    "field {} is \"ordered-delta\" with only one of delta_struct(key = \"...\") and delta_struct(key_type = \"...\"). Give both to align items by a key field, or neither to align them by the items themselves.",
+                    name
+                ),
+                (_, _, _) => abort_call_site!(
+                    "delta_struct(key = ...) and delta_struct(key_type = ...) on field {} only apply to the \"keyed\", \"keyed-ordered\" and \"ordered-delta\" field types.",
+                    name
+                ),
+            };
+            let eq_with = match (field_type, attrs.eq_with) {
+                (_, None) => None,
+                (FieldType::Scalar, Some(eq_with)) => match syn::parse_str::<syn::Path>(&eq_with) {
+                    Ok(eq_with) => Some(eq_with),
+                    Err(_) => abort_call_site!(
+                        "delta_struct(eq_with = {:?}) on field {} could not be parsed as a path to a function.",
+                        eq_with,
+                        name
+                    ),
+                },
+                (_, Some(_)) => abort_call_site!(
+                    "delta_struct(eq_with = ...) on field {} only applies to the \"scalar\" field type.",
+                    name
+                ),
+            };
+            let granularity = match (field_type, attrs.granularity.as_deref()) {
+                (FieldType::Text, None | Some("chars")) => Some(format_ident!("Chars")),
+                (FieldType::Text, Some("lines")) => Some(format_ident!("Lines")),
+                (FieldType::Text, Some(granularity)) => abort_call_site!(
+                    "delta_struct(granularity = {:?}) on field {} is not an accepted value, expected \"chars\" or \"lines\".",
+                    granularity,
+                    name
+                ),
+                (_, None) => None,
+                (_, Some(_)) => abort_call_site!(
+                    "delta_struct(granularity = ...) on field {} only applies to the \"text\" field type.",
+                    name
+                ),
+            };
+            let overflow = match (field_type, attrs.overflow.as_deref()) {
+                (FieldType::Counter, None | Some("checked")) => Some(format_ident!("Checked")),
+                (FieldType::Counter, Some("wrapping")) => Some(format_ident!("Wrapping")),
+                (FieldType::Counter, Some(overflow)) => abort_call_site!(
+                    "delta_struct(overflow = {:?}) on field {} is not an accepted value, expected \"checked\" or \"wrapping\".",
+                    overflow,
+                    name
+                ),
+                (_, None) => None,
+                (_, Some(_)) => abort_call_site!(
+                    "delta_struct(overflow = ...) on field {} only applies to the \"counter\" field type.",
+                    name
+                ),
+            };
+            let diff_options = match (field_type, attrs.algorithm, attrs.deadline) {
+                (_, None, None) => None,
+                (FieldType::Ordered, algorithm, deadline) => {
+                    let algorithm = match algorithm.as_deref() {
+                        None | Some("myers") => format_ident!("Myers"),
+                        Some("patience") => format_ident!("Patience"),
+                        Some("lcs") => format_ident!("Lcs"),
+                        Some(algorithm) => abort_call_site!(
+                            "delta_struct(algorithm = {:?}) on field {} is not an accepted value, expected \"myers\", \"patience\" or \"lcs\".",
+                            algorithm,
+                            name
+                        ),
+                    };
+                    let deadline = match deadline {
+                        Some(deadline) => match parse_duration(&deadline) {
+                            Some(duration) => quote!(::std::option::Option::Some(#duration)),
+                            None => abort_call_site!(
+                                "delta_struct(deadline = {:?}) on field {} is not a duration, expected a whole number followed by \"s\", \"ms\", \"us\" or \"ns\".",
+                                deadline,
+                                name
+                            ),
+                        },
+                        None => quote!(::std::option::Option::None),
+                    };
+                    Some(quote! {
+                        ::delta_struct::seq::DiffOptions {
+                            algorithm: ::delta_struct::seq::Algorithm::#algorithm,
+                            deadline: #deadline,
+                        }
+                    })
+                }
+                (_, _, _) => abort_call_site!(
+                    "delta_struct(algorithm = ...) and delta_struct(deadline = ...) on field {} only apply to the \"ordered\" field type.",
+                    name
+                ),
+            };
+            // A cost function says replacing is wanted as plainly as the flag.
+            let replace = match (field_type, attrs.replace_if_cheaper, attrs.replace_cost) {
+                (_, false, None) => None,
+                (FieldType::Ordered | FieldType::Unordered, _, None) => {
+                    Some(quote!(::delta_struct::replace::count))
+                }
+                (FieldType::Ordered | FieldType::Unordered, _, Some(cost)) => {
+                    match syn::parse_str::<syn::Path>(&cost) {
+                        Ok(cost) => Some(quote!(#cost)),
+                        Err(_) => abort_call_site!(
+                            "delta_struct(replace_cost = {:?}) on field {} could not be parsed as a path to a function.",
+                            cost,
+                            name
+                        ),
+                    }
+                }
+                (_, _, _) => abort_call_site!(
+                    "delta_struct(replace_if_cheaper) and delta_struct(replace_cost = ...) on field {} only apply to the \"ordered\" and \"unordered\" field types.",
+                    name
+                ),
+            };
+            if attrs.in_place && field_type != FieldType::Ordered {
+                abort_call_site!(
+                    "delta_struct(in_place) on field {} only applies to the \"ordered\" field type.",
+                    name
+                );
+            }
+            let bound = attrs.bound.map(|bound| parse_bound(&bound, &name));
+            v.push(Field {
+                name,
+                ty,
+                field_type,
+                delta_leader: attrs.delta_leader,
+                skip: attrs.skip,
+                key,
+                bound,
+                reversible: false,
+                eq_with,
+                with,
+                granularity,
+                overflow,
+                diff_options,
+                replace,
+                in_place: attrs.in_place,
+            });
             Ok(v)
         }
         (Ok(_), (ident, _, Err(_))) => Err(vec![ident]),
@@ -454,20 +2557,38 @@ fn collect_results(
     })
 }
 
+/// Parses a `deadline` attribute, a whole number and a unit like `50ms`,
+/// into the expression for that `Duration`, or `None` if it is not one.
+fn parse_duration(duration: &str) -> Option<proc_macro2::TokenStream> {
+    let digits = duration.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = duration.split_at(digits);
+    let amount = proc_macro2::Literal::u64_unsuffixed(amount.parse().ok()?);
+    let constructor = match unit {
+        "s" => format_ident!("from_secs"),
+        "ms" => format_ident!("from_millis"),
+        "us" => format_ident!("from_micros"),
+        "ns" => format_ident!("from_nanos"),
+        _ => return None,
+    };
+    Some(quote!(::std::time::Duration::#constructor(#amount)))
+}
+
 enum FieldTypeError {
-    /// The `delta_struct(...)` attribute contained entries that were not
-    /// `name = "value"` pairs.
+    /// The `delta_struct(...)` attribute contained entries that were neither
+    /// `name = "value"` pairs nor bare flags.
     UnrecognizedJunkFound,
 }
 
-/// Reads a `#[delta_struct(...)]` attribute, returning
-/// `(field type, delta_leader)`.
+/// Reads a `#[delta_struct(...)]` attribute into its [`Attrs`].
 ///
 /// `attr_name` is the key naming the field type in this position — `"default"`
 /// on a container, `"field_type"` on a field — because the two spellings mean
 /// the same thing at different scopes. The field type is `None` when the
 /// attribute is absent or names no field type, leaving the caller to fill in
-/// the default; `delta_leader` is empty when unspecified.
+/// the default; `delta_leader` is empty when unspecified. The `skip` flag,
+/// the `key` and `key_type` pair, `eq_with`, `with`, `granularity`,
+/// `overflow`, `algorithm`, `deadline`, `replace_if_cheaper`, `replace_cost` and
+/// `in_place` are only recognized on a field, and the `reversible` flag only on a container.
 #[allow(clippy::manual_try_fold)] // Collects errors too
 fn get_fieldtype_from_attrs(iter: impl Iterator<Item = Attribute>, attr_name: &str) -> ParsedAttrs {
     for attr in iter {
@@ -485,7 +2606,10 @@ fn get_fieldtype_from_attrs(iter: impl Iterator<Item = Attribute>, attr_name: &s
                             path,
                             lit: Lit::Str(s),
                             ..
-                        })) => Ok((path.get_ident().map(|i| i.to_string()), s.value())),
+                        })) => Ok((path.get_ident().map(|i| i.to_string()), Some(s.value()))),
+                        NestedMeta::Meta(Meta::Path(path)) => {
+                            Ok((path.get_ident().map(|i| i.to_string()), None))
+                        }
                         e => Err(e),
                     })
                     .fold(Ok(vec![]), |v, i| match (v, i) {
@@ -502,29 +2626,72 @@ fn get_fieldtype_from_attrs(iter: impl Iterator<Item = Attribute>, attr_name: &s
                     });
                 return match values {
                     Ok(v) => {
-                        let mut field_type = None;
-                        let mut delta_leader = String::new();
-                        for i in v {
-                            match i.0.as_deref() {
-                                Some("delta_leader") => {
-                                    delta_leader = i.1;
+                        let mut attrs = Attrs::default();
+                        for (name, value) in v {
+                            match (name.as_deref(), value) {
+                                (Some("delta_leader"), Some(value)) => {
+                                    attrs.delta_leader = value;
+                                }
+                                (Some("skip"), None) if attr_name == "field_type" => {
+                                    attrs.skip = true;
+                                }
+                                (Some("reversible"), None) if attr_name == "default" => {
+                                    attrs.reversible = true;
+                                }
+                                (Some("key"), Some(value)) if attr_name == "field_type" => {
+                                    attrs.key = Some(value);
+                                }
+                                (Some("key_type"), Some(value)) if attr_name == "field_type" => {
+                                    attrs.key_type = Some(value);
+                                }
+                                (Some("eq_with"), Some(value)) if attr_name == "field_type" => {
+                                    attrs.eq_with = Some(value);
+                                }
+                                (Some("with"), Some(value)) if attr_name == "field_type" => {
+                                    attrs.with = Some(value);
+                                }
+                                (Some("granularity"), Some(value)) if attr_name == "field_type" => {
+                                    attrs.granularity = Some(value);
+                                }
+                                (Some("overflow"), Some(value)) if attr_name == "field_type" => {
+                                    attrs.overflow = Some(value);
+                                }
+                                (Some("algorithm"), Some(value)) if attr_name == "field_type" => {
+                                    attrs.algorithm = Some(value);
                                 }
-                                a if Some(attr_name) == a => {
-                                    field_type = string_to_fieldtype(&i.1);
+                                (Some("deadline"), Some(value)) if attr_name == "field_type" => {
+                                    attrs.deadline = Some(value);
                                 }
-                                a => {
+                                (Some("replace_if_cheaper"), None) if attr_name == "field_type" => {
+                                    attrs.replace_if_cheaper = true;
+                                }
+                                (Some("replace_cost"), Some(value))
+                                    if attr_name == "field_type" =>
+                                {
+                                    attrs.replace_cost = Some(value);
+                                }
+                                (Some("in_place"), None) if attr_name == "field_type" => {
+                                    attrs.in_place = true;
+                                }
+                                (Some("bound"), Some(value)) => {
+                                    attrs.bound = Some(value);
+                                }
+                                (a, Some(value)) if Some(attr_name) == a => {
+                                    attrs.field_type = string_to_fieldtype(&value);
+                                }
+                                (a, _) => {
                                     abort_call_site!("Unrecognized value {:?}", a);
                                 }
                             }
                         }
-                        Ok((field_type, delta_leader))
+                        Ok(attrs)
                     }
                     Err(_) => Err(FieldTypeError::UnrecognizedJunkFound),
                 };
             }
         }
     }
-    Ok((None, String::new()))
+    Ok(Attrs::default())
 }
 
 /// Maps the attribute spelling of a field type to its variant, or `None` if it
@@ -532,10 +2699,19 @@ fn get_fieldtype_from_attrs(iter: impl Iterator<Item = Attribute>, attr_name: &s
 fn string_to_fieldtype(s: &str) -> Option<FieldType> {
     match s {
         "ordered" => Some(FieldType::Ordered),
+        "ordered-delta" => Some(FieldType::OrderedDelta),
         "unordered" => Some(FieldType::Unordered),
         "unordered-delta" => Some(FieldType::UnorderedDelta),
+        "unordered-scalar" => Some(FieldType::UnorderedScalar),
+        "multiset" => Some(FieldType::Multiset),
         "scalar" => Some(FieldType::Scalar),
+        "counter" => Some(FieldType::Counter),
         "delta" => Some(FieldType::Delta),
+        "optional-delta" => Some(FieldType::OptionalDelta),
+        "keyed" => Some(FieldType::Keyed),
+        "keyed-ordered" => Some(FieldType::KeyedOrdered),
+        "indexed" => Some(FieldType::Indexed),
+        "text" => Some(FieldType::Text),
         _ => None,
     }
 }
@@ -547,9 +2723,12 @@ fn string_to_fieldtype(s: &str) -> Option<FieldType> {
 /// followed by that variant's fields. Every field type has to implement
 /// `Fingerprint` too, and every type parameter picks up a `Fingerprint` bound.
 ///
+/// A field marked `#[delta_struct(skip)]` is left out, the same attribute that
+/// leaves it out of `Delta`: state that is never sent must not be able to make
+/// two sides look diverged.
+///
 /// Unlike the `Delta` derive this needs nothing in scope — the generated code
-/// names `::delta_struct::Fingerprint` in full — and it accepts enums, which
-/// have a perfectly good content hash even though they have no obvious delta.
+/// names `::delta_struct::Fingerprint` in full.
 ///
 /// ```ignore
 /// use delta_struct::Fingerprint;
@@ -560,7 +2739,7 @@ fn string_to_fieldtype(s: &str) -> Option<FieldType> {
 ///     online: bool,
 /// }
 /// ```
-#[proc_macro_derive(Fingerprint)]
+#[proc_macro_derive(Fingerprint, attributes(delta_struct))]
 #[proc_macro_error]
 pub fn derive_fingerprint(input: TokenStream) -> TokenStream {
     let DeriveInput {
@@ -574,15 +2753,20 @@ pub fn derive_fingerprint(input: TokenStream) -> TokenStream {
         Data::Struct(strukt) => {
             // A struct's fields are reached through `self`, by name or by
             // position.
-            fingerprint_calls(strukt.fields.iter().enumerate().map(|(i, field)| {
-                match &field.ident {
-                    Some(ident) => quote!(self.#ident),
-                    None => {
-                        let index = syn::Index::from(i);
-                        quote!(self.#index)
-                    }
-                }
-            }))
+            fingerprint_calls(
+                strukt
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, field)| !is_skipped(field))
+                    .map(|(i, field)| match &field.ident {
+                        Some(ident) => quote!(self.#ident),
+                        None => {
+                            let index = syn::Index::from(i);
+                            quote!(self.#index)
+                        }
+                    }),
+            )
         }
         Data::Enum(enom) => {
             // A variant's fields are reached through the locals its pattern
@@ -591,12 +2775,21 @@ pub fn derive_fingerprint(input: TokenStream) -> TokenStream {
             let arms = enom.variants.into_iter().enumerate().map(|(index, variant)| {
                 let variant_ident = variant.ident;
                 let bindings = binding_idents(&variant.fields);
+                // A skipped field is matched but not bound, so it can neither
+                // reach the hash nor trip the unused-variable lint.
+                let patterns = variant.fields.iter().zip(&bindings).map(|(field, binding)| {
+                    match (&field.ident, binding) {
+                        (_, Some(binding)) => quote!(#binding),
+                        (Some(ident), None) => quote!(#ident: _),
+                        (None, None) => quote!(_),
+                    }
+                });
                 let pattern = match &variant.fields {
-                    Fields::Named(_) => quote!(Self::#variant_ident { #(#bindings),* }),
-                    Fields::Unnamed(_) => quote!(Self::#variant_ident( #(#bindings),* )),
+                    Fields::Named(_) => quote!(Self::#variant_ident { #(#patterns),* }),
+                    Fields::Unnamed(_) => quote!(Self::#variant_ident( #(#patterns),* )),
                     Fields::Unit => quote!(Self::#variant_ident),
                 };
-                let fields = fingerprint_calls(bindings.iter().map(|b| quote!(#b)));
+                let fields = fingerprint_calls(bindings.iter().flatten().map(|b| quote!(#b)));
                 let index = index as u32;
                 quote! {
                     #pattern => {
@@ -640,17 +2833,29 @@ pub fn derive_fingerprint(input: TokenStream) -> TokenStream {
 
 /// The locals an enum variant's fields bind to in a match pattern: the field's
 /// own name where it has one, and `field_0`, `field_1`, … where it does not.
-fn binding_idents(fields: &Fields) -> Vec<Ident> {
+/// A field marked `skip` binds nothing, and gets [`None`].
+fn binding_idents(fields: &Fields) -> Vec<Option<Ident>> {
     fields
         .iter()
         .enumerate()
         .map(|(i, field)| match &field.ident {
-            Some(ident) => ident.clone(),
-            None => format_ident!("field_{}", i),
+            _ if is_skipped(field) => None,
+            Some(ident) => Some(ident.clone()),
+            None => Some(format_ident!("field_{}", i)),
         })
         .collect()
 }
 
+/// Whether a field is marked `#[delta_struct(skip)]`.
+///
+/// An attribute that cannot be read counts as not skipping; if the type also
+/// derives `Delta`, that derive is the one to report it.
+fn is_skipped(field: &syn::Field) -> bool {
+    get_fieldtype_from_attrs(field.attrs.iter().cloned(), "field_type")
+        .map(|attrs| attrs.skip)
+        .unwrap_or(false)
+}
+
 /// Emits one `Fingerprint::fingerprint` call per expression, in order.
 ///
 /// The expressions name the fields however the caller can reach them —
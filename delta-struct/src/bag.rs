@@ -5,9 +5,13 @@
 //! [`BagDelta`] — which elements came and which went, and nothing about where
 //! they sit.
 //!
-//! The derive emits calls to [`diff`] and [`apply`]; you only need this module
-//! directly to inspect or construct a delta by hand.
+//! The derive emits calls to [`diff`] and [`apply`], or to [`diff_ref`] for
+//! [`DeltaRef`](crate::DeltaRef), and to [`check`] for
+//! [`Delta::try_apply_delta`](crate::Delta::try_apply_delta); you only need
+//! this module directly to inspect or construct a delta by hand.
 
+use crate::check::{all_present, ApplyError, Problem};
+use crate::index::{merge_join, Joined};
 use crate::TryIndex;
 
 /// A membership diff between two collections: what arrived and what left.
@@ -44,8 +48,10 @@ impl<T> Default for BagDelta<T> {
 /// Returns an empty [`BagDelta`] when the two hold the same elements. Each
 /// element of `old` is looked up in `new` exactly once, through the
 /// collection's own [`TryIndex`] implementation, so the cost is that of n
-/// lookups: O(n) for a [`HashSet`](std::collections::HashSet), O(n log n) for
-/// a [`BTreeSet`](std::collections::BTreeSet).
+/// lookups: O(n) for a [`HashSet`](std::collections::HashSet). A collection
+/// with a [key order](TryIndex::key_order), such as a
+/// [`BTreeSet`](std::collections::BTreeSet), is merged with the other side in
+/// one pass instead, in O(n + m).
 ///
 /// ```
 /// use delta_struct::bag::diff;
@@ -62,6 +68,17 @@ pub fn diff<C, T>(old: C, mut new: C) -> BagDelta<T>
 where
     C: IntoIterator<Item = T> + TryIndex<T, Output = T>,
 {
+    if let Some(order) = new.key_order() {
+        let mut delta = BagDelta::default();
+        for joined in merge_join(old, new, |element| element, order) {
+            match joined {
+                Joined::Old(element) => delta.remove.push(element),
+                Joined::New(element) => delta.add.push(element),
+                Joined::Both(..) => {}
+            }
+        }
+        return delta;
+    }
     // Take each of `old`'s elements out of `new` as it is matched, so whatever
     // is still standing at the end is exactly what was added, and no second
     // pass is needed to work that out.
@@ -75,6 +92,54 @@ where
     }
 }
 
+/// Computes the same [`BagDelta`] as [`diff`] from borrowed collections,
+/// cloning only the elements that ended up in it.
+///
+/// Every element of each side is looked up in the other once, so this costs
+/// twice the lookups [`diff`] does — the price of not being able to take
+/// matched elements out of `new` as it goes. A collection with a key order is
+/// merged in one pass, as by [`diff`].
+///
+/// ```
+/// use delta_struct::bag::diff_ref;
+/// use std::collections::BTreeSet;
+///
+/// let old: BTreeSet<i32> = vec![1, 2, 3].into_iter().collect();
+/// let new: BTreeSet<i32> = vec![3, 4, 5].into_iter().collect();
+///
+/// let delta = diff_ref(&old, &new);
+/// assert_eq!(delta.add, vec![4, 5]);
+/// assert_eq!(delta.remove, vec![1, 2]);
+/// ```
+pub fn diff_ref<'a, C, T>(old: &'a C, new: &'a C) -> BagDelta<T>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    C: TryIndex<T, Output = T>,
+    T: Clone + 'a,
+{
+    if let Some(order) = new.key_order() {
+        let mut delta = BagDelta::default();
+        for joined in merge_join(old, new, |element| *element, order) {
+            match joined {
+                Joined::Old(element) => delta.remove.push(element.clone()),
+                Joined::New(element) => delta.add.push(element.clone()),
+                Joined::Both(..) => {}
+            }
+        }
+        return delta;
+    }
+    let missing_from = |side: &'a C, other: &'a C| {
+        side.into_iter()
+            .filter(|element| other.try_index(element).is_none())
+            .cloned()
+            .collect()
+    };
+    BagDelta {
+        add: missing_from(new, old),
+        remove: missing_from(old, new),
+    }
+}
+
 /// Applies a membership diff to `target` in place.
 ///
 /// Each removal is a single lookup rather than a scan, so this costs the same
@@ -83,7 +148,7 @@ where
 /// matters.
 ///
 /// A removal that `target` does not have is ignored, which makes applying the
-/// same delta twice harmless.
+/// same delta twice harmless. Use [`try_apply`] to have it refused instead.
 ///
 /// ```
 /// use delta_struct::bag::{apply, diff};
@@ -105,3 +170,39 @@ where
     }
     target.extend(delta.add);
 }
+
+/// Checks that every element `delta` removes is in `target`, reporting the
+/// first that is not as [`Problem::NotPresent`].
+pub fn check<C, T>(target: &C, delta: &BagDelta<T>) -> Result<(), ApplyError>
+where
+    C: TryIndex<T, Output = T>,
+{
+    all_present(
+        &delta.remove,
+        |element| target.try_index(element).is_some(),
+        |index| Problem::NotPresent { index },
+    )
+}
+
+/// Applies a membership diff like [`apply`], but only if it passes [`check`];
+/// otherwise `target` is left as it was.
+///
+/// ```
+/// use delta_struct::bag::{diff, try_apply};
+/// use std::collections::BTreeSet;
+///
+/// let set = |items: Vec<i32>| items.into_iter().collect::<BTreeSet<i32>>();
+///
+/// let delta = diff(set(vec![1, 2, 3]), set(vec![2, 3, 4]));
+/// let mut target = set(vec![2, 3]);
+/// assert!(try_apply(&mut target, delta).is_err());
+/// assert_eq!(target, set(vec![2, 3]));
+/// ```
+pub fn try_apply<C, T>(target: &mut C, delta: BagDelta<T>) -> Result<(), ApplyError>
+where
+    C: IntoIterator<Item = T> + Extend<T> + TryIndex<T, Output = T>,
+{
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
+}
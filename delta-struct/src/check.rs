@@ -0,0 +1,239 @@
+//! Refusing a delta that does not fit the value it is applied to.
+//!
+//! [`Delta::apply_delta`] is lenient: a removal of something that is not
+//! there is skipped, a change to a missing key is dropped, and a splice past
+//! the end is clamped. That keeps a bad delta from panicking, but it also lets
+//! one that was malformed or sent to the wrong receiver quietly produce
+//! garbage. [`Delta::try_apply_delta`] checks the whole delta against the
+//! value first, and applies nothing at all unless every part of it fits.
+//!
+//! ```
+//! use delta_struct::{Delta, Problem};
+//! use std::collections::BTreeSet;
+//!
+//! #[derive(Debug, Delta, PartialEq)]
+//! struct Team {
+//!     #[delta_struct(field_type = "unordered")]
+//!     members: BTreeSet<String>,
+//! }
+//!
+//! let team = |members: &[&str]| Team {
+//!     members: members.iter().map(|member| member.to_string()).collect(),
+//! };
+//!
+//! let delta = Delta::delta(team(&["ann", "bob"]), team(&["ann"])).unwrap();
+//!
+//! // Bob already left this copy, so removing him again does not fit.
+//! let mut elsewhere = team(&["ann", "cat"]);
+//! let error = elsewhere.try_apply_delta(delta).unwrap_err();
+//! assert_eq!(error.problem, Problem::NotPresent { index: 0 });
+//! assert_eq!(error.to_string(), "members: remove[0] is not present");
+//! assert_eq!(elsewhere, team(&["ann", "cat"]));
+//! ```
+//!
+//! The collection modules have the same check as free functions, as
+//! [`bag::try_apply`](crate::bag::try_apply) and its siblings.
+//!
+//! [`Delta::apply_delta`]: crate::Delta::apply_delta
+//! [`Delta::try_apply_delta`]: crate::Delta::try_apply_delta
+
+use std::fmt;
+
+/// A delta that does not fit the value it was applied to: where in the
+/// delta, and what was wrong there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApplyError {
+    /// The way from the top of the delta down to the part that did not fit,
+    /// outermost first. Empty when the problem is with the delta as a whole.
+    pub path: Vec<PathSegment>,
+    /// What was wrong.
+    pub problem: Problem,
+}
+
+impl ApplyError {
+    /// An error at the top of the delta being checked.
+    pub fn new(problem: Problem) -> Self {
+        ApplyError {
+            path: Vec::new(),
+            problem,
+        }
+    }
+
+    /// Places the error under `segment`, for when the delta it was found in
+    /// is part of a larger one.
+    pub fn within(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+}
+
+/// One step of an [`ApplyError`]'s path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// A field, by name or, in a tuple struct or variant, by position.
+    Field(&'static str),
+    /// The variant of an enum's delta.
+    Variant(&'static str),
+    /// The change at this index of a map, `keyed` or `ordered-delta` delta.
+    Change(usize),
+    /// An element of an array, by position.
+    Index(usize),
+    /// The op at this index of a `keyed-ordered` delta.
+    Op(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Field(name) | PathSegment::Variant(name) => f.write_str(name),
+            PathSegment::Change(index) => write!(f, "change[{}]", index),
+            PathSegment::Index(index) => write!(f, "{}", index),
+            PathSegment::Op(index) => write!(f, "ops[{}]", index),
+        }
+    }
+}
+
+/// What made a delta not fit. Indices count into the collection delta at the
+/// error's path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// `remove[index]` names an element or key the target does not have.
+    NotPresent {
+        /// Which removal.
+        index: usize,
+    },
+    /// `change[index]` is for a key the target does not have.
+    MissingKey {
+        /// Which change.
+        index: usize,
+    },
+    /// `splices[index]` reaches past the end of the target sequence.
+    SpliceOutOfRange {
+        /// Which splice.
+        index: usize,
+        /// How long the target sequence is.
+        len: usize,
+    },
+    /// `splices[index]` starts before the splice ahead of it ends, so the two
+    /// overlap or are out of order.
+    UnsortedSplices {
+        /// Which splice.
+        index: usize,
+    },
+    /// `change[index]` is for a position past the end of the target sequence.
+    ChangeOutOfRange {
+        /// Which change.
+        index: usize,
+        /// How long the target sequence is.
+        len: usize,
+    },
+    /// `remove[index]` of a multiset delta takes away more of an element
+    /// than the target holds.
+    TooFew {
+        /// Which removal.
+        index: usize,
+        /// How many of the element the target holds.
+        count: usize,
+    },
+    /// The tail of an `indexed` delta does not end where the target sequence
+    /// does, or the target cannot change length.
+    WrongLength {
+        /// How long the target sequence is.
+        len: usize,
+    },
+    /// A `counter` delta's increment would take the target past its bounds.
+    Overflow,
+    /// `ops[index]` is for a position past the end of the target sequence,
+    /// as the ops before it left it.
+    OpOutOfRange {
+        /// Which op.
+        index: usize,
+        /// How long the sequence was by then.
+        len: usize,
+    },
+    /// `splices[index]` of a text delta starts or ends inside a character.
+    SplitsChar {
+        /// Which splice.
+        index: usize,
+    },
+    /// The delta is for a different variant than the one the target holds.
+    WrongVariant,
+    /// A [`Strategy`](crate::Strategy) refused the delta, for the reason
+    /// given.
+    Refused(&'static str),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::NotPresent { index } => write!(f, "remove[{}] is not present", index),
+            Problem::MissingKey { index } => {
+                write!(f, "change[{}] is for a key that is not present", index)
+            }
+            Problem::SpliceOutOfRange { index, len } => write!(
+                f,
+                "splices[{}] reaches past the end of a sequence of {}",
+                index, len
+            ),
+            Problem::UnsortedSplices { index } => write!(
+                f,
+                "splices[{}] starts before the previous splice ends",
+                index
+            ),
+            Problem::ChangeOutOfRange { index, len } => write!(
+                f,
+                "change[{}] is for a position past the end of a sequence of {}",
+                index, len
+            ),
+            Problem::TooFew { index, count } => write!(
+                f,
+                "remove[{}] takes more than the {} the target holds",
+                index, count
+            ),
+            Problem::WrongLength { len } => {
+                write!(f, "tail does not fit a sequence of {}", len)
+            }
+            Problem::Overflow => write!(f, "increment takes the counter past its bounds"),
+            Problem::OpOutOfRange { index, len } => write!(
+                f,
+                "ops[{}] is for a position past the end of a sequence of {}",
+                index, len
+            ),
+            Problem::SplitsChar { index } => {
+                write!(f, "splices[{}] starts or ends inside a character", index)
+            }
+            Problem::WrongVariant => {
+                f.write_str("delta is for a different variant than the value holds")
+            }
+            Problem::Refused(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (position, segment) in self.path.iter().enumerate() {
+            let separator = if position == 0 { "" } else { "." };
+            write!(f, "{}{}", separator, segment)?;
+        }
+        if !self.path.is_empty() {
+            f.write_str(": ")?;
+        }
+        write!(f, "{}", self.problem)
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
+/// Checks that each of `keys` is present according to `present`, reporting
+/// the first that is not as `problem(index)`.
+pub(crate) fn all_present<'a, K: 'a>(
+    keys: impl IntoIterator<Item = &'a K>,
+    present: impl Fn(&K) -> bool,
+    problem: impl Fn(usize) -> Problem,
+) -> Result<(), ApplyError> {
+    match keys.into_iter().position(|key| !present(key)) {
+        Some(index) => Err(ApplyError::new(problem(index))),
+        None => Ok(()),
+    }
+}
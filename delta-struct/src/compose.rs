@@ -0,0 +1,133 @@
+//! Merging consecutive deltas into one.
+//!
+//! A receiver several versions behind can be caught up with the deltas it
+//! missed, one after another — or with a single delta that does the work of
+//! all of them. [`Compose`] builds that delta: `compose(first, second)` is
+//! the delta whose effect is that of applying `first` and then `second`.
+//!
+//! Composing cancels what the second delta undoes of the first. An element
+//! the first adds and the second removes appears in neither side of the
+//! result, and a changed map value that is then removed is only a removal.
+//! Splices of an `ordered` field are rebased: the second delta's positions
+//! index the first delta's output, and the result's index the original.
+//!
+//! Every generated delta type implements [`Compose`] whenever its fields'
+//! deltas do:
+//!
+//! ```
+//! use delta_struct::{Compose, Delta};
+//! use std::collections::BTreeSet;
+//!
+//! #[derive(Clone, Debug, Delta, PartialEq)]
+//! struct Room {
+//!     topic: String,
+//!     #[delta_struct(field_type = "unordered")]
+//!     members: BTreeSet<String>,
+//! }
+//!
+//! let room = |topic: &str, members: &[&str]| Room {
+//!     topic: topic.to_string(),
+//!     members: members.iter().map(|member| member.to_string()).collect(),
+//! };
+//!
+//! let first = Delta::delta(room("a", &["ann"]), room("b", &["ann", "bob"])).unwrap();
+//! let second = Delta::delta(room("b", &["ann", "bob"]), room("c", &["ann"])).unwrap();
+//! let both = Compose::compose(first, second);
+//!
+//! // Bob came and went, so the composed delta does not mention him.
+//! assert!(both.members.is_empty());
+//! assert_eq!(both.topic, Some("c".to_string()));
+//!
+//! let mut caught_up = room("a", &["ann"]);
+//! caught_up.apply_delta(both);
+//! assert_eq!(caught_up, room("c", &["ann"]));
+//! ```
+
+use crate::{BagDelta, Change};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A delta that can be merged with the one following it.
+///
+/// Applying `compose(first, second)` has the same effect as applying `first`
+/// and then `second`, provided `second` was computed against what `first`
+/// produces — as consecutive deltas from one stream always are.
+pub trait Compose {
+    /// Returns the delta doing the work of `first` followed by `second`.
+    fn compose(first: Self, second: Self) -> Self;
+}
+
+/// Composes the deltas of a `scalar` field: the later replacement wins.
+///
+/// The derive emits calls to this; `Option<T>` itself does not implement
+/// [`Compose`], since an optional `delta` field composes its contents instead
+/// — see [`option`].
+pub fn replace<T>(first: Option<T>, second: Option<T>) -> Option<T> {
+    second.or(first)
+}
+
+/// Composes two optional deltas, composing their contents when both are
+/// present. This is how a `delta` field, or a `scalar` field of a reversible
+/// type, composes.
+pub fn option<D: Compose>(first: Option<D>, second: Option<D>) -> Option<D> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(D::compose(first, second)),
+        (first, None) => first,
+        (None, second) => second,
+    }
+}
+
+/// The value before the first change and after the second.
+impl<T> Compose for Change<T> {
+    fn compose(first: Self, second: Self) -> Self {
+        Change {
+            old: first.old,
+            new: second.new,
+        }
+    }
+}
+
+/// Elements are matched with `Hash + Eq`, so composing costs O(n) in the size
+/// of the two deltas however the collections themselves look elements up.
+impl<T: Hash + Eq> Compose for BagDelta<T> {
+    fn compose(first: Self, second: Self) -> Self {
+        // An element added and then removed was never there, and one removed
+        // and then added back is still there; either way neither delta needs
+        // to mention it.
+        let (first_add, second_remove) = cancel(first.add, second.remove);
+        let (first_remove, second_add) = cancel(first.remove, second.add);
+        BagDelta {
+            add: first_add.into_iter().chain(second_add).collect(),
+            remove: first_remove.into_iter().chain(second_remove).collect(),
+        }
+    }
+}
+
+/// Drops each element of `left` that has an equal partner in `right`, along
+/// with that partner, and returns what is left of each. Duplicates pair off
+/// one for one.
+fn cancel<T: Hash + Eq>(left: Vec<T>, right: Vec<T>) -> (Vec<T>, Vec<T>) {
+    let mut keep_left = vec![true; left.len()];
+    let mut keep_right = vec![true; right.len()];
+    {
+        let mut unmatched: HashMap<&T, Vec<usize>> = HashMap::new();
+        for (index, element) in right.iter().enumerate().rev() {
+            unmatched.entry(element).or_default().push(index);
+        }
+        for (index, element) in left.iter().enumerate() {
+            if let Some(partner) = unmatched.get_mut(element).and_then(Vec::pop) {
+                keep_left[index] = false;
+                keep_right[partner] = false;
+            }
+        }
+    }
+    (retain(left, keep_left), retain(right, keep_right))
+}
+
+fn retain<T>(elements: Vec<T>, keep: Vec<bool>) -> Vec<T> {
+    elements
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(element, keep)| keep.then_some(element))
+        .collect()
+}
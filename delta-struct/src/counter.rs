@@ -0,0 +1,450 @@
+//! Increment diffing, behind the `counter` field type.
+//!
+//! A `scalar` field sends its new value, so two senders that each bump a
+//! shared counter overwrite each other's bump. A field marked
+//! `#[delta_struct(field_type = "counter")]` sends by how much it went up or
+//! down instead: its delta is an `Option` of the signed difference, which
+//! applying adds on. Increments commute, so deltas from several senders can
+//! be applied, or [composed](compose), in any order, as long as none of them
+//! overflows on the way.
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Delta)]
+//! struct Page {
+//!     #[delta_struct(field_type = "counter")]
+//!     views: u64,
+//! }
+//!
+//! // Two replicas each saw some views since the last sync.
+//! let first = Delta::delta(Page { views: 10 }, Page { views: 13 }).unwrap();
+//! let second = Delta::delta(Page { views: 10 }, Page { views: 11 }).unwrap();
+//! assert_eq!(first.views, Some(3));
+//!
+//! let mut page = Page { views: 10 };
+//! page.apply_delta(first);
+//! page.apply_delta(second);
+//! assert_eq!(page.views, 14);
+//! ```
+//!
+//! The field has to be a [`Counter`], which every primitive integer and
+//! float is. An integer's increment is the signed integer twice its width,
+//! so that any two values are an exact difference apart; a 64-bit counter's
+//! is an `i128`, and a 128-bit one's is a [`WideIncrement`].
+//!
+//! What happens when an increment would take an integer past its bounds is
+//! the field's `overflow` attribute's to say. With `overflow = "checked"`,
+//! the default, [`apply`] skips such an increment and [`check`] refuses it
+//! with [`Problem::Overflow`]. With `overflow = "wrapping"` it wraps around,
+//! as [`u64::wrapping_add`] does, and is never refused.
+//!
+//! A float counter is approximate. Its increment is a float subtraction and
+//! applying it a float addition, so `old + (new - old)` can round to
+//! something other than `new`, and deltas composed in a different order can
+//! land a rounding step apart. An infinite value makes it worse: the
+//! increment from infinity to anything is infinite, and adding that back
+//! gives NaN. With `overflow = "checked"` an increment whose sum is not
+//! finite is skipped and refused like an integer overflow; with
+//! `overflow = "wrapping"` it is added anyway, NaN and all. Floats are
+//! compared by their bits, so a NaN left as it was is no change.
+//!
+//! The derive emits calls to [`diff`], [`apply`] and [`check`], and to
+//! [`invert`] and [`compose`] for [`Invert`](crate::Invert) and
+//! [`Compose`](crate::Compose), which a primitive already implements as a
+//! replacement. An increment undoes by negation, so a `reversible` type's
+//! field has the same delta.
+
+use crate::check::{ApplyError, Problem};
+use std::convert::TryFrom;
+use std::fmt::Debug;
+
+/// What an increment that would take an integer counter past its bounds
+/// does, as a `counter` field's `overflow` attribute chooses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    /// The increment is skipped by [`apply`] and refused by [`check`]. The
+    /// default.
+    Checked,
+    /// The increment wraps around, and is never refused.
+    Wrapping,
+}
+
+/// A number whose changes can be sent as increments.
+pub trait Counter: PartialEq + Sized {
+    /// The signed difference between two values.
+    type Increment: Increment;
+
+    /// By how much `new` is above `old`, or below it when negative.
+    fn between(old: &Self, new: &Self) -> Self::Increment;
+
+    /// Adds `by`, wrapping around at the type's bounds.
+    fn wrapping_increment(&mut self, by: Self::Increment);
+
+    /// The sum of `self` and `by`, or `None` if it is out of the type's
+    /// bounds.
+    fn checked_increment(&self, by: Self::Increment) -> Option<Self>;
+
+    /// Whether `old` and `new` are the same value, so that there is no
+    /// increment to send. `==` by default; a float compares its bits
+    /// instead, since NaN is not `==` to itself.
+    fn unchanged(old: &Self, new: &Self) -> bool {
+        old == new
+    }
+}
+
+/// The difference between two values of a [`Counter`].
+pub trait Increment: Copy + PartialEq + Debug {
+    /// The increment that undoes this one.
+    fn negate(self) -> Self;
+
+    /// The increment of this one followed by `later`.
+    fn sum(self, later: Self) -> Self;
+
+    /// Whether the increment changes nothing.
+    fn is_zero(&self) -> bool;
+}
+
+macro_rules! integer_increment {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            /// Sums wrap, so that adding up a wrapped increment still lands
+            /// where its parts would.
+            impl Increment for $ty {
+                fn negate(self) -> Self {
+                    self.wrapping_neg()
+                }
+
+                fn sum(self, later: Self) -> Self {
+                    self.wrapping_add(later)
+                }
+
+                fn is_zero(&self) -> bool {
+                    *self == 0
+                }
+            }
+        )*
+    };
+}
+
+integer_increment!(i16, i32, i64, i128);
+
+macro_rules! float_increment {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Increment for $ty {
+                fn negate(self) -> Self {
+                    -self
+                }
+
+                fn sum(self, later: Self) -> Self {
+                    self + later
+                }
+
+                fn is_zero(&self) -> bool {
+                    *self == 0.0
+                }
+            }
+        )*
+    };
+}
+
+float_increment!(f32, f64);
+
+macro_rules! widened_counter {
+    ($($ty:ty => $wide:ty),* $(,)?) => {
+        $(
+            impl Counter for $ty {
+                type Increment = $wide;
+
+                fn between(old: &Self, new: &Self) -> $wide {
+                    *new as $wide - *old as $wide
+                }
+
+                fn wrapping_increment(&mut self, by: $wide) {
+                    // Truncating the wide sum keeps it modulo the narrow
+                    // type, which is what wrapping is.
+                    *self = (*self as $wide).wrapping_add(by) as $ty;
+                }
+
+                fn checked_increment(&self, by: $wide) -> Option<Self> {
+                    (*self as $wide)
+                        .checked_add(by)
+                        .and_then(|sum| <$ty>::try_from(sum).ok())
+                }
+            }
+        )*
+    };
+}
+
+widened_counter!(
+    i8 => i16,
+    i16 => i32,
+    i32 => i64,
+    i64 => i128,
+    isize => i128,
+    u8 => i16,
+    u16 => i32,
+    u32 => i64,
+    u64 => i128,
+    usize => i128,
+);
+
+/// The increment of a 128-bit counter: a 256-bit signed integer.
+///
+/// No primitive is wide enough for the difference between two `u128`s or
+/// two `i128`s, which takes 129 bits. This one has room for that and for
+/// sums of such differences besides, so an increment never wraps on the way
+/// and checked overflow round-trips at the extremes as it does for narrower
+/// counters.
+///
+/// ```
+/// use delta_struct::counter::{diff, WideIncrement};
+///
+/// assert_eq!(diff(&0u128, &u128::MAX), Some(WideIncrement::from(u128::MAX)));
+/// assert_eq!(diff(&3i128, &1i128), Some(WideIncrement::from(-2i128)));
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WideIncrement {
+    // The value is `high * 2^128 + low`.
+    high: i128,
+    low: u128,
+}
+
+impl WideIncrement {
+    fn wrapping_add(self, other: Self) -> Self {
+        let (low, carry) = self.low.overflowing_add(other.low);
+        let high = self
+            .high
+            .wrapping_add(other.high)
+            .wrapping_add(carry as i128);
+        WideIncrement { high, low }
+    }
+
+    fn wrapping_sub(self, other: Self) -> Self {
+        let (low, borrow) = self.low.overflowing_sub(other.low);
+        let high = self
+            .high
+            .wrapping_sub(other.high)
+            .wrapping_sub(borrow as i128);
+        WideIncrement { high, low }
+    }
+}
+
+impl From<i128> for WideIncrement {
+    fn from(value: i128) -> Self {
+        WideIncrement {
+            high: if value < 0 { -1 } else { 0 },
+            low: value as u128,
+        }
+    }
+}
+
+impl From<u128> for WideIncrement {
+    fn from(value: u128) -> Self {
+        WideIncrement {
+            high: 0,
+            low: value,
+        }
+    }
+}
+
+impl Increment for WideIncrement {
+    fn negate(self) -> Self {
+        WideIncrement::from(0u128).wrapping_sub(self)
+    }
+
+    fn sum(self, later: Self) -> Self {
+        self.wrapping_add(later)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.high == 0 && self.low == 0
+    }
+}
+
+impl Counter for i128 {
+    type Increment = WideIncrement;
+
+    fn between(old: &Self, new: &Self) -> WideIncrement {
+        WideIncrement::from(*new).wrapping_sub(WideIncrement::from(*old))
+    }
+
+    fn wrapping_increment(&mut self, by: WideIncrement) {
+        // The low half is the sum modulo 2^128, which is what wrapping is.
+        *self = (*self as u128).wrapping_add(by.low) as i128;
+    }
+
+    fn checked_increment(&self, by: WideIncrement) -> Option<Self> {
+        let sum = WideIncrement::from(*self).wrapping_add(by);
+        // In range exactly when the high half is the low half's sign bit
+        // extended.
+        let low = sum.low as i128;
+        if sum.high == if low < 0 { -1 } else { 0 } {
+            Some(low)
+        } else {
+            None
+        }
+    }
+}
+
+impl Counter for u128 {
+    type Increment = WideIncrement;
+
+    fn between(old: &Self, new: &Self) -> WideIncrement {
+        WideIncrement::from(*new).wrapping_sub(WideIncrement::from(*old))
+    }
+
+    fn wrapping_increment(&mut self, by: WideIncrement) {
+        *self = self.wrapping_add(by.low);
+    }
+
+    fn checked_increment(&self, by: WideIncrement) -> Option<Self> {
+        let sum = WideIncrement::from(*self).wrapping_add(by);
+        if sum.high == 0 {
+            Some(sum.low)
+        } else {
+            None
+        }
+    }
+}
+
+macro_rules! float_counter {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Counter for $ty {
+                type Increment = $ty;
+
+                fn between(old: &Self, new: &Self) -> $ty {
+                    new - old
+                }
+
+                fn wrapping_increment(&mut self, by: $ty) {
+                    *self += by;
+                }
+
+                fn checked_increment(&self, by: $ty) -> Option<Self> {
+                    Some(self + by).filter(|sum| sum.is_finite())
+                }
+
+                fn unchanged(old: &Self, new: &Self) -> bool {
+                    old.to_bits() == new.to_bits()
+                }
+            }
+        )*
+    };
+}
+
+float_counter!(f32, f64);
+
+/// Computes by how much `new` is above `old`, or `None` when the two are
+/// [unchanged](Counter::unchanged).
+///
+/// ```
+/// use delta_struct::counter::diff;
+///
+/// assert_eq!(diff(&5u8, &3u8), Some(-2i16));
+/// assert_eq!(diff(&0u32, &u32::MAX), Some(i64::from(u32::MAX)));
+/// assert_eq!(diff(&1.5f64, &1.5f64), None);
+/// assert_eq!(diff(&f64::NAN, &f64::NAN), None);
+/// ```
+pub fn diff<T: Counter>(old: &T, new: &T) -> Option<T::Increment> {
+    if !T::unchanged(old, new) {
+        Some(T::between(old, new))
+    } else {
+        None
+    }
+}
+
+/// Adds an increment to `target` in place.
+///
+/// With [`Overflow::Checked`], an increment that would take `target` past
+/// its bounds is skipped, leaving it as it was. Use [`try_apply`] to have
+/// such an increment refused instead.
+///
+/// ```
+/// use delta_struct::counter::{apply, Overflow};
+///
+/// let mut level = 250u8;
+/// apply(&mut level, Some(10), Overflow::Checked);
+/// assert_eq!(level, 250);
+/// apply(&mut level, Some(10), Overflow::Wrapping);
+/// assert_eq!(level, 4);
+/// ```
+pub fn apply<T: Counter>(target: &mut T, delta: Option<T::Increment>, overflow: Overflow) {
+    let by = match delta {
+        Some(by) => by,
+        None => return,
+    };
+    match overflow {
+        Overflow::Checked => {
+            if let Some(sum) = target.checked_increment(by) {
+                *target = sum;
+            }
+        }
+        Overflow::Wrapping => target.wrapping_increment(by),
+    }
+}
+
+/// Checks that, with [`Overflow::Checked`], `delta` keeps `target` within
+/// its bounds, reporting [`Problem::Overflow`] if it does not.
+pub fn check<T: Counter>(
+    target: &T,
+    delta: &Option<T::Increment>,
+    overflow: Overflow,
+) -> Result<(), ApplyError> {
+    match (delta, overflow) {
+        (Some(by), Overflow::Checked) if target.checked_increment(*by).is_none() => {
+            Err(ApplyError::new(Problem::Overflow))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Adds an increment like [`apply`], but only if it passes [`check`];
+/// otherwise `target` is left as it was.
+///
+/// ```
+/// use delta_struct::counter::{try_apply, Overflow};
+/// use delta_struct::Problem;
+///
+/// let mut stock = 2u32;
+/// let error = try_apply(&mut stock, Some(-3), Overflow::Checked).unwrap_err();
+/// assert_eq!(error.problem, Problem::Overflow);
+/// assert_eq!(stock, 2);
+/// ```
+pub fn try_apply<T: Counter>(
+    target: &mut T,
+    delta: Option<T::Increment>,
+    overflow: Overflow,
+) -> Result<(), ApplyError> {
+    check(target, &delta, overflow)?;
+    apply(target, delta, overflow);
+    Ok(())
+}
+
+/// The increment that undoes `delta`: its negation.
+pub fn invert<D: Increment>(delta: Option<D>) -> Option<D> {
+    delta.map(Increment::negate)
+}
+
+/// The increment of `first` followed by `second`: their sum, or `None` when
+/// they cancel out.
+///
+/// Since sums commute, so do the deltas this composes.
+///
+/// ```
+/// use delta_struct::counter::compose;
+///
+/// assert_eq!(compose(Some(3i64), Some(-1)), Some(2));
+/// assert_eq!(compose(Some(3i64), Some(-3)), None);
+/// assert_eq!(compose(None, Some(4i64)), Some(4));
+/// ```
+pub fn compose<D: Increment>(first: Option<D>, second: Option<D>) -> Option<D> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(first.sum(second)).filter(|sum| !sum.is_zero()),
+        (first, None) => first,
+        (None, second) => second,
+    }
+}
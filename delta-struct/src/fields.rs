@@ -0,0 +1,192 @@
+//! Asking a delta which fields it changes.
+//!
+//! For a struct `Config`, the derive also emits a `ConfigField` enum with one
+//! variant per field — less any marked `skip` — named after the field in
+//! `UpperCamelCase`, or `Field0`, `Field1`, … for a tuple struct. The delta
+//! struct gets a `changed_fields()` method returning the [`FieldSet`] of
+//! those it carries a change for, so routing on a change needs no matching on
+//! `Option`s and [`BagDelta`](crate::BagDelta)s:
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Delta)]
+//! struct Config {
+//!     host: String,
+//!     port: u16,
+//! }
+//!
+//! let config = |port| Config { host: "localhost".to_string(), port };
+//!
+//! let delta = Delta::delta(config(80), config(8080)).unwrap();
+//! let changed = delta.changed_fields();
+//! assert!(changed.contains(ConfigField::Port));
+//! assert!(!changed.contains(ConfigField::Host));
+//! ```
+//!
+//! A field set only covers its own struct. To see through `delta` fields to
+//! the fields that changed inside them, use [`ChangedPaths`], which every
+//! generated delta type implements as long as the deltas of its `delta`
+//! fields do:
+//!
+//! ```
+//! use delta_struct::{ChangedPaths, Delta, PathSegment};
+//!
+//! #[derive(Delta)]
+//! struct Server {
+//!     host: String,
+//!     port: u16,
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Config {
+//!     #[delta_struct(field_type = "delta")]
+//!     server: Server,
+//!     debug: bool,
+//! }
+//!
+//! let config = |port| Config {
+//!     server: Server { host: "localhost".to_string(), port },
+//!     debug: false,
+//! };
+//!
+//! let delta = Delta::delta(config(80), config(8080)).unwrap();
+//! assert_eq!(
+//!     delta.changed_paths(),
+//!     vec![vec![PathSegment::Field("server"), PathSegment::Field("port")]],
+//! );
+//! ```
+
+use crate::PathSegment;
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+
+/// A generated `{Type}Field` enum, naming the fields of a struct that a
+/// delta can change.
+pub trait Field: Copy + 'static {
+    /// Every field, in declaration order.
+    const ALL: &'static [Self];
+
+    /// This field's position in [`ALL`](Field::ALL).
+    fn index(self) -> usize;
+
+    /// The field's name as written in the struct, or its position in a tuple
+    /// struct.
+    fn name(self) -> &'static str;
+}
+
+/// A set of the fields of one struct, as a bitmask.
+///
+/// A struct of up to 64 fields fits in a single word, so building one costs
+/// no allocation and testing it is a mask.
+#[derive(Clone, PartialEq, Eq)]
+pub struct FieldSet<F> {
+    bits: u64,
+    /// The words past the first, for structs of more than 64 fields.
+    overflow: Vec<u64>,
+    fields: PhantomData<F>,
+}
+
+impl<F: Field> FieldSet<F> {
+    /// The empty set.
+    pub fn new() -> Self {
+        FieldSet {
+            bits: 0,
+            overflow: Vec::new(),
+            fields: PhantomData,
+        }
+    }
+
+    /// Adds `field` to the set.
+    pub fn insert(&mut self, field: F) {
+        let (word, bit) = (field.index() / 64, field.index() % 64);
+        match word {
+            0 => self.bits |= 1 << bit,
+            _ => {
+                if self.overflow.len() < word {
+                    self.overflow.resize(word, 0);
+                }
+                self.overflow[word - 1] |= 1 << bit;
+            }
+        }
+    }
+
+    /// Whether `field` is in the set.
+    pub fn contains(&self, field: F) -> bool {
+        let (word, bit) = (field.index() / 64, field.index() % 64);
+        let word = match word {
+            0 => self.bits,
+            _ => self.overflow.get(word - 1).copied().unwrap_or(0),
+        };
+        word & (1 << bit) != 0
+    }
+
+    /// Whether the set holds no fields at all.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0 && self.overflow.iter().all(|&word| word == 0)
+    }
+
+    /// How many fields the set holds.
+    pub fn len(&self) -> usize {
+        let overflow: u32 = self.overflow.iter().map(|word| word.count_ones()).sum();
+        (self.bits.count_ones() + overflow) as usize
+    }
+
+    /// The fields in the set, in declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = F> + '_ {
+        F::ALL
+            .iter()
+            .copied()
+            .filter(move |&field| self.contains(field))
+    }
+}
+
+impl<F: Field> Default for FieldSet<F> {
+    fn default() -> Self {
+        FieldSet::new()
+    }
+}
+
+impl<F: Field> FromIterator<F> for FieldSet<F> {
+    fn from_iter<I: IntoIterator<Item = F>>(fields: I) -> Self {
+        let mut set = FieldSet::new();
+        for field in fields {
+            set.insert(field);
+        }
+        set
+    }
+}
+
+impl<F: Field + fmt::Debug> fmt::Debug for FieldSet<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// A delta that can list the paths it changes, looking inside the deltas of
+/// `delta` fields.
+///
+/// Each path leads from the top of the value to one changed field, in the
+/// same [`PathSegment`]s an [`ApplyError`](crate::ApplyError) uses. A field
+/// is a path of its own unless it is a `delta` field whose delta says more; an
+/// enum's delta starts each path with its variant, and a replacement of the
+/// whole value is the empty path.
+pub trait ChangedPaths {
+    /// Every path this delta changes, in declaration order.
+    fn changed_paths(&self) -> Vec<Vec<PathSegment>>;
+}
+
+/// The paths a `delta` field changes, given the field's own segment: the
+/// nested delta's paths under it, or just the field if the nested delta has
+/// none to give. The derive emits calls to this.
+pub fn nested_paths<D: ChangedPaths>(segment: PathSegment, delta: &D) -> Vec<Vec<PathSegment>> {
+    let mut paths = delta.changed_paths();
+    if paths.is_empty() {
+        paths.push(Vec::new());
+    }
+    for path in &mut paths {
+        path.insert(0, segment.clone());
+    }
+    paths
+}
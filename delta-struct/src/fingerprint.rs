@@ -36,6 +36,7 @@
 //! assert_ne!(fingerprint_of(&device(true)), fingerprint_of(&device(false)));
 //! ```
 
+use crate::{SortedVec, SortedVecMap};
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 /// The FNV-1a 64-bit offset basis.
@@ -319,3 +320,20 @@ impl<K: Fingerprint, V: Fingerprint> Fingerprint for BTreeMap<K, V> {
         });
     }
 }
+
+impl<T: Fingerprint> Fingerprint for SortedVec<T> {
+    fn fingerprint(&self, hasher: &mut Hasher) {
+        fingerprint_unordered(hasher, self.len(), self.iter(), |h, item| {
+            item.fingerprint(h)
+        });
+    }
+}
+
+impl<K: Fingerprint, V: Fingerprint> Fingerprint for SortedVecMap<K, V> {
+    fn fingerprint(&self, hasher: &mut Hasher) {
+        fingerprint_unordered(hasher, self.len(), self.iter(), |h, (key, value)| {
+            key.fingerprint(h);
+            value.fingerprint(h);
+        });
+    }
+}
@@ -0,0 +1,285 @@
+//! [`Delta`] for standard library types, so that they can be `delta` fields
+//! and be diffed at the top level without a derive.
+//!
+//! Primitives and `String` are replaced whole, as a `scalar` field would be.
+//! Tuples and arrays are diffed element by element, into a tuple or array of
+//! one `Option` per element. `Box`, `Rc` and `Arc` delegate to what they
+//! point at; an `Rc` or `Arc` that both sides still share is skipped without
+//! being compared, and applying to one clones the value out only if it is
+//! shared with something else.
+
+use crate::check::{ApplyError, PathSegment};
+use crate::{compose, ChangedPaths, Compose, Delta, DeltaRef, Invert};
+use std::rc::Rc;
+use std::sync::Arc;
+
+macro_rules! replaced_whole {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Delta for $ty {
+                type Output = $ty;
+
+                fn delta(old: Self, new: Self) -> Option<Self::Output> {
+                    if old != new {
+                        Some(new)
+                    } else {
+                        None
+                    }
+                }
+
+                fn apply_delta(&mut self, delta: Self::Output) {
+                    *self = delta;
+                }
+            }
+
+            impl DeltaRef for $ty {
+                fn delta_ref(old: &Self, new: &Self) -> Option<Self::Output> {
+                    if old != new {
+                        Some(Clone::clone(new))
+                    } else {
+                        None
+                    }
+                }
+            }
+
+            /// The later replacement wins.
+            impl Compose for $ty {
+                fn compose(_first: Self, second: Self) -> Self {
+                    second
+                }
+            }
+
+            /// A replacement changes the whole value, which is the empty path.
+            impl ChangedPaths for $ty {
+                fn changed_paths(&self) -> Vec<Vec<PathSegment>> {
+                    vec![Vec::new()]
+                }
+            }
+        )*
+    };
+}
+
+replaced_whole! {
+    (), bool, char, f32, f64, String,
+    i8, i16, i32, i64, i128, isize,
+    u8, u16, u32, u64, u128, usize,
+}
+
+macro_rules! element_wise_tuples {
+    ($(($($index:tt $param:ident),+))+) => {
+        $(
+            impl<$($param: Delta),+> Delta for ($($param,)+) {
+                type Output = ($(Option<<$param as Delta>::Output>,)+);
+
+                fn delta(old: Self, new: Self) -> Option<Self::Output> {
+                    let delta = ($($param::delta(old.$index, new.$index),)+);
+                    if $(delta.$index.is_none())&&+ {
+                        None
+                    } else {
+                        Some(delta)
+                    }
+                }
+
+                fn apply_delta(&mut self, delta: Self::Output) {
+                    $(
+                        if let Some(delta) = delta.$index {
+                            self.$index.apply_delta(delta);
+                        }
+                    )+
+                }
+
+                fn check_delta(&self, delta: &Self::Output) -> Result<(), ApplyError> {
+                    $(
+                        if let Some(delta) = &delta.$index {
+                            self.$index.check_delta(delta).map_err(|error| {
+                                error.within(PathSegment::Field(stringify!($index)))
+                            })?;
+                        }
+                    )+
+                    Ok(())
+                }
+            }
+
+            impl<$($param: DeltaRef),+> DeltaRef for ($($param,)+) {
+                fn delta_ref(old: &Self, new: &Self) -> Option<Self::Output> {
+                    let delta = ($($param::delta_ref(&old.$index, &new.$index),)+);
+                    if $(delta.$index.is_none())&&+ {
+                        None
+                    } else {
+                        Some(delta)
+                    }
+                }
+            }
+
+            /// Element by element, as a tuple struct's delta composes.
+            impl<$($param: Compose),+> Compose for ($(Option<$param>,)+) {
+                fn compose(first: Self, second: Self) -> Self {
+                    ($(compose::option(first.$index, second.$index),)+)
+                }
+            }
+
+            impl<$($param: Invert),+> Invert for ($($param,)+) {
+                fn invert(self) -> Self {
+                    ($(self.$index.invert(),)+)
+                }
+            }
+
+            impl<$($param: ChangedPaths),+> ChangedPaths for ($(Option<$param>,)+) {
+                fn changed_paths(&self) -> Vec<Vec<PathSegment>> {
+                    let mut paths = Vec::new();
+                    $(
+                        if let Some(delta) = &self.$index {
+                            let segment = PathSegment::Field(stringify!($index));
+                            paths.extend(crate::fields::nested_paths(segment, delta));
+                        }
+                    )+
+                    paths
+                }
+            }
+        )+
+    };
+}
+
+element_wise_tuples! {
+    (0 A)
+    (0 A, 1 B)
+    (0 A, 1 B, 2 C)
+    (0 A, 1 B, 2 C, 3 D)
+    (0 A, 1 B, 2 C, 3 D, 4 E)
+    (0 A, 1 B, 2 C, 3 D, 4 E, 5 F)
+}
+
+impl<T: Delta, const N: usize> Delta for [T; N] {
+    type Output = [Option<T::Output>; N];
+
+    fn delta(old: Self, new: Self) -> Option<Self::Output> {
+        let mut new = IntoIterator::into_iter(new);
+        // Both arrays are `N` long, so `new` runs out exactly when `old` does.
+        let delta = old.map(|old| T::delta(old, new.next().unwrap()));
+        if delta.iter().all(Option::is_none) {
+            None
+        } else {
+            Some(delta)
+        }
+    }
+
+    fn apply_delta(&mut self, delta: Self::Output) {
+        for (value, delta) in self.iter_mut().zip(IntoIterator::into_iter(delta)) {
+            if let Some(delta) = delta {
+                value.apply_delta(delta);
+            }
+        }
+    }
+
+    fn check_delta(&self, delta: &Self::Output) -> Result<(), ApplyError> {
+        for (index, (value, delta)) in self.iter().zip(delta).enumerate() {
+            if let Some(delta) = delta {
+                value
+                    .check_delta(delta)
+                    .map_err(|error| error.within(PathSegment::Index(index)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: DeltaRef, const N: usize> DeltaRef for [T; N] {
+    fn delta_ref(old: &Self, new: &Self) -> Option<Self::Output> {
+        let delta: Self::Output =
+            std::array::from_fn(|index| T::delta_ref(&old[index], &new[index]));
+        if delta.iter().all(Option::is_none) {
+            None
+        } else {
+            Some(delta)
+        }
+    }
+}
+
+/// Element by element.
+impl<D: Compose, const N: usize> Compose for [Option<D>; N] {
+    fn compose(first: Self, second: Self) -> Self {
+        let mut second = IntoIterator::into_iter(second);
+        first.map(|first| compose::option(first, second.next().unwrap()))
+    }
+}
+
+impl<D: Invert, const N: usize> Invert for [D; N] {
+    fn invert(self) -> Self {
+        self.map(Invert::invert)
+    }
+}
+
+impl<D: ChangedPaths, const N: usize> ChangedPaths for [Option<D>; N] {
+    fn changed_paths(&self) -> Vec<Vec<PathSegment>> {
+        self.iter()
+            .enumerate()
+            .filter_map(|(index, delta)| Some((index, delta.as_ref()?)))
+            .flat_map(|(index, delta)| {
+                crate::fields::nested_paths(PathSegment::Index(index), delta)
+            })
+            .collect()
+    }
+}
+
+impl<T: Delta> Delta for Box<T> {
+    type Output = T::Output;
+
+    fn delta(old: Self, new: Self) -> Option<Self::Output> {
+        T::delta(*old, *new)
+    }
+
+    fn apply_delta(&mut self, delta: Self::Output) {
+        (**self).apply_delta(delta);
+    }
+
+    fn check_delta(&self, delta: &Self::Output) -> Result<(), ApplyError> {
+        (**self).check_delta(delta)
+    }
+}
+
+impl<T: DeltaRef> DeltaRef for Box<T> {
+    fn delta_ref(old: &Self, new: &Self) -> Option<Self::Output> {
+        T::delta_ref(old, new)
+    }
+}
+
+macro_rules! shared_pointers {
+    ($($pointer:ident),* $(,)?) => {
+        $(
+            /// Takes the value out of each side if nothing else holds it, and
+            /// clones it out otherwise.
+            impl<T: Delta + Clone> Delta for $pointer<T> {
+                type Output = T::Output;
+
+                fn delta(old: Self, new: Self) -> Option<Self::Output> {
+                    if $pointer::ptr_eq(&old, &new) {
+                        return None;
+                    }
+                    let take = |shared: Self| {
+                        $pointer::try_unwrap(shared).unwrap_or_else(|shared| T::clone(&shared))
+                    };
+                    T::delta(take(old), take(new))
+                }
+
+                fn apply_delta(&mut self, delta: Self::Output) {
+                    $pointer::make_mut(self).apply_delta(delta);
+                }
+
+                fn check_delta(&self, delta: &Self::Output) -> Result<(), ApplyError> {
+                    (**self).check_delta(delta)
+                }
+            }
+
+            impl<T: DeltaRef + Clone> DeltaRef for $pointer<T> {
+                fn delta_ref(old: &Self, new: &Self) -> Option<Self::Output> {
+                    if $pointer::ptr_eq(old, new) {
+                        return None;
+                    }
+                    T::delta_ref(old, new)
+                }
+            }
+        )*
+    };
+}
+
+shared_pointers!(Rc, Arc);
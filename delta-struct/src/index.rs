@@ -7,11 +7,18 @@
 //! push the lookup down into the collection instead of scanning it.
 //!
 //! The consequence is that a field's cost is the cost of the collection you
-//! picked: a diff over a [`HashSet`] or [`HashMap`] is O(n), and over a
-//! [`BTreeSet`] or [`BTreeMap`] it is O(n log n).
+//! picked: a diff over a [`HashSet`] or [`HashMap`] is O(n). A [`BTreeSet`]
+//! or [`BTreeMap`] would cost O(n log n) that way, but it also says, through
+//! [`TryIndex::key_order`], that it iterates in key order, so its diffs walk
+//! both sides side by side instead, in O(n + m).
+//!
+//! [`Multiset`] is the same idea for a collection that counts its elements,
+//! which is what the [`multiset`](crate::multiset) field type diffs through.
 
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::hash::{BuildHasher, Hash};
+use std::iter;
 
 /// A collection that can look an element up by key without panicking when it
 /// is not there.
@@ -29,6 +36,18 @@ pub trait TryIndex<Idx: ?Sized> {
     /// Removes the element stored under `index` and returns it, or [`None`] if
     /// there was none.
     fn try_remove(&mut self, index: &Idx) -> Option<Self::Output>;
+
+    /// The order the collection iterates its keys in, if it iterates them
+    /// in ascending order and each only once, or [`None`], the default, if
+    /// it does not.
+    ///
+    /// A collection that says so has its diffs merge the two sides in a
+    /// single pass, rather than look each element of one up in the other.
+    /// Both its owning and its borrowing iterators must then agree with the
+    /// order returned, or the diff will pair up the wrong elements.
+    fn key_order(&self) -> Option<fn(&Idx, &Idx) -> Ordering> {
+        None
+    }
 }
 
 /// A [`TryIndex`] whose elements can also be modified in place.
@@ -77,6 +96,10 @@ where
     fn try_remove(&mut self, index: &K) -> Option<V> {
         self.remove(index)
     }
+
+    fn key_order(&self) -> Option<fn(&K, &K) -> Ordering> {
+        Some(K::cmp)
+    }
 }
 
 impl<K, V> TryIndexMut<K> for BTreeMap<K, V>
@@ -117,4 +140,124 @@ where
     fn try_remove(&mut self, index: &T) -> Option<T> {
         self.take(index)
     }
+
+    fn key_order(&self) -> Option<fn(&T, &T) -> Ordering> {
+        Some(T::cmp)
+    }
+}
+
+/// Where a key of a merge-joined diff turned up: only in the old
+/// collection, only in the new one, or in both.
+pub(crate) enum Joined<A> {
+    Old(A),
+    New(A),
+    Both(A, A),
+}
+
+/// Walks `old` and `new`, both ascending by `order` of the key `key` reads
+/// off an item, side by side, pairing up the items with equal keys.
+pub(crate) fn merge_join<A, K: ?Sized>(
+    old: impl IntoIterator<Item = A>,
+    new: impl IntoIterator<Item = A>,
+    key: impl Fn(&A) -> &K,
+    order: fn(&K, &K) -> Ordering,
+) -> impl Iterator<Item = Joined<A>> {
+    let (mut old, mut new) = (old.into_iter().peekable(), new.into_iter().peekable());
+    iter::from_fn(move || {
+        let side = match (old.peek(), new.peek()) {
+            (Some(o), Some(n)) => order(key(o), key(n)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+        Some(match side {
+            Ordering::Less => Joined::Old(old.next()?),
+            Ordering::Greater => Joined::New(new.next()?),
+            Ordering::Equal => Joined::Both(old.next()?, new.next()?),
+        })
+    })
+}
+
+/// A collection that holds each element some number of times, and can look
+/// that number up by element.
+///
+/// A count map, such as a `HashMap<T, usize>`, is one: an element it has no
+/// entry for is there zero times, and one whose count drops to zero loses its
+/// entry, so that two maps holding the same elements compare equal.
+pub trait Multiset<T> {
+    /// How many times `element` is there, which is zero if it is not.
+    fn count(&self, element: &T) -> usize;
+
+    /// Adds `n` more of `element`.
+    fn insert_many(&mut self, element: T, n: usize);
+
+    /// Removes `n` of `element`, or every one there is if there are fewer,
+    /// and returns how many it removed. A count never goes below zero.
+    fn remove_many(&mut self, element: &T, n: usize) -> usize;
+
+    /// The order the multiset iterates its elements in, if it does so in
+    /// ascending order and each only once, or [`None`], the default, as for
+    /// [`TryIndex::key_order`].
+    fn key_order(&self) -> Option<fn(&T, &T) -> Ordering> {
+        None
+    }
+}
+
+impl<T, S> Multiset<T> for HashMap<T, usize, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    fn count(&self, element: &T) -> usize {
+        self.get(element).copied().unwrap_or(0)
+    }
+
+    fn insert_many(&mut self, element: T, n: usize) {
+        if n > 0 {
+            let count = self.entry(element).or_insert(0);
+            *count = count.saturating_add(n);
+        }
+    }
+
+    fn remove_many(&mut self, element: &T, n: usize) -> usize {
+        match self.get_mut(element) {
+            Some(count) if *count > n => {
+                *count -= n;
+                n
+            }
+            Some(_) => self.remove(element).unwrap_or(0),
+            None => 0,
+        }
+    }
+}
+
+impl<T> Multiset<T> for BTreeMap<T, usize>
+where
+    T: Ord,
+{
+    fn count(&self, element: &T) -> usize {
+        self.get(element).copied().unwrap_or(0)
+    }
+
+    fn insert_many(&mut self, element: T, n: usize) {
+        if n > 0 {
+            let count = self.entry(element).or_insert(0);
+            *count = count.saturating_add(n);
+        }
+    }
+
+    fn remove_many(&mut self, element: &T, n: usize) -> usize {
+        match self.get_mut(element) {
+            Some(count) if *count > n => {
+                *count -= n;
+                n
+            }
+            Some(_) => self.remove(element).unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    fn key_order(&self) -> Option<fn(&T, &T) -> Ordering> {
+        Some(T::cmp)
+    }
 }
@@ -0,0 +1,620 @@
+//! Pairing items by position, behind the `indexed` field type.
+//!
+//! For an array, or a `Vec` whose length rarely changes, such as the channels
+//! of a mixer or a bank of hardware registers, lining the two sides up with
+//! Myers is wasted work: item 3 is item 3. A field marked
+//! `#[delta_struct(field_type = "indexed")]` pairs its items by position and
+//! diffs each pair with the item's own [`Delta`], into a [`PositionalDelta`]:
+//! one [`IndexedDelta`] for each position that changed, and, for a `Vec` that
+//! grew or shrank, a `tail` splice that cuts or extends its end.
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Clone, Delta)]
+//! struct Channel {
+//!     gain: i8,
+//!     muted: bool,
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Mixer {
+//!     #[delta_struct(field_type = "indexed")]
+//!     channels: [Channel; 16],
+//! }
+//!
+//! let quiet = Channel { gain: 0, muted: false };
+//! let old = Mixer { channels: std::array::from_fn(|_| quiet.clone()) };
+//! let mut new = Mixer { channels: std::array::from_fn(|_| quiet.clone()) };
+//! new.channels[3].muted = true;
+//!
+//! let delta = Delta::delta(old, new).unwrap().channels;
+//! assert_eq!(delta.change.len(), 1);
+//! assert_eq!(delta.change[0].index, 3);
+//! assert_eq!(delta.change[0].delta.muted, Some(true));
+//! assert_eq!(delta.change[0].delta.gain, None);
+//! assert!(delta.tail.is_none());
+//! ```
+//!
+//! An item type with no fields to speak of, like a number or a `String`,
+//! implements [`Delta`] by replacement already, so its changes carry the new
+//! value.
+//!
+//! The field has to implement [`Slots`], which a `Vec` and an array do. The
+//! derive emits calls to [`diff`] and [`apply`], or to [`diff_ref`] for
+//! [`DeltaRef`], and to [`check`] for [`Delta::try_apply_delta`]. A
+//! `reversible` type's field gets a [`ReversiblePositionalDelta`] instead,
+//! from [`diff_reversible`] or [`diff_ref_reversible`].
+
+use crate::check::{ApplyError, PathSegment, Problem};
+use crate::seq::{IndexedDelta, ReversibleSplice, Splice};
+use crate::{Compose, Delta, DeltaRef, Invert};
+use std::cmp::Ordering;
+
+/// A diff between two sequences paired by position: a change per position
+/// that differs, and a splice at the end for a change in length.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionalDelta<T, D> {
+    /// The items that changed, in ascending order of
+    /// [`IndexedDelta::index`]. Every index is one both sequences have.
+    pub change: Vec<IndexedDelta<D>>,
+    /// For sequences of different lengths, the splice that drops the old
+    /// sequence's extra items from [`Splice::at`] on, or appends the new
+    /// one's.
+    pub tail: Option<Splice<T>>,
+}
+
+impl<T, D> PositionalDelta<T, D> {
+    /// Whether the two sequences were identical, and so nothing needs sending.
+    pub fn is_empty(&self) -> bool {
+        self.change.is_empty() && self.tail.is_none()
+    }
+}
+
+impl<T, D> Default for PositionalDelta<T, D> {
+    fn default() -> Self {
+        PositionalDelta {
+            change: Vec::new(),
+            tail: None,
+        }
+    }
+}
+
+/// A [`PositionalDelta`] whose tail also keeps the items it drops, so that it
+/// can be [inverted](Invert).
+///
+/// This is what an `indexed` field turns into on a `reversible` type.
+/// Inverting it needs the items' own deltas to be reversible too.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReversiblePositionalDelta<T, D> {
+    /// The items that changed, in ascending order of
+    /// [`IndexedDelta::index`].
+    pub change: Vec<IndexedDelta<D>>,
+    /// For sequences of different lengths, the splice at the end that drops
+    /// or appends the extra items.
+    pub tail: Option<ReversibleSplice<T>>,
+}
+
+impl<T, D> ReversiblePositionalDelta<T, D> {
+    /// Whether the two sequences were identical, and so nothing needs sending.
+    pub fn is_empty(&self) -> bool {
+        self.change.is_empty() && self.tail.is_none()
+    }
+
+    /// Drops the removed items, which only undoing needs, leaving the
+    /// [`PositionalDelta`] that [`apply`] takes.
+    pub fn into_forward(self) -> PositionalDelta<T, D> {
+        PositionalDelta {
+            change: self.change,
+            tail: self
+                .tail
+                .map(|ReversibleSplice { at, remove, insert }| Splice {
+                    at,
+                    remove: remove.len(),
+                    insert,
+                }),
+        }
+    }
+}
+
+impl<T, D> Default for ReversiblePositionalDelta<T, D> {
+    fn default() -> Self {
+        ReversiblePositionalDelta {
+            change: Vec::new(),
+            tail: None,
+        }
+    }
+}
+
+/// The tail sits past every change, so nothing moves: each change is
+/// inverted where it is, and the tail puts back what it dropped.
+impl<T, D: Invert> Invert for ReversiblePositionalDelta<T, D> {
+    fn invert(self) -> Self {
+        ReversiblePositionalDelta {
+            change: self.change.into_iter().map(Invert::invert).collect(),
+            tail: self
+                .tail
+                .map(|ReversibleSplice { at, remove, insert }| ReversibleSplice {
+                    at,
+                    remove: insert,
+                    insert: remove,
+                }),
+        }
+    }
+}
+
+/// Changes at the same position compose, and a change the second delta makes
+/// to an item the first appended is applied to that item. Changes to items
+/// the composed tail drops are dropped with them.
+///
+/// ```
+/// use delta_struct::indexed::{apply, diff};
+/// use delta_struct::Compose;
+///
+/// let first = diff(vec![1, 2, 3], vec![1, 5, 3, 4]);
+/// let second = diff(vec![1, 5, 3, 4], vec![1, 5, 6, 7]);
+/// let both = Compose::compose(first, second);
+/// assert_eq!(both.tail.as_ref().unwrap().insert, vec![7]);
+///
+/// let mut target = vec![1, 2, 3];
+/// apply(&mut target, both);
+/// assert_eq!(target, vec![1, 5, 6, 7]);
+/// ```
+impl<T, D> Compose for PositionalDelta<T, D>
+where
+    T: Delta<Output = D>,
+    D: Compose,
+{
+    fn compose(first: Self, second: Self) -> Self {
+        let mut first_tail = first.tail;
+        let change = merge_changes(
+            first.change,
+            second.change,
+            first_tail.as_mut().map(|tail| (tail.at, &mut tail.insert)),
+        );
+        let tail = match (first_tail, second.tail) {
+            (first, None) => first,
+            (None, second) => second,
+            (Some(first), Some(second)) => {
+                let (at, insert) = compose_tails(first.at, first.insert, second.at, second.insert);
+                let remove = match second.at.cmp(&first.at) {
+                    Ordering::Less => first.at - second.at + first.remove,
+                    _ => first.remove,
+                };
+                Some(Splice { at, remove, insert })
+            }
+        };
+        let tail = tail.filter(|tail| tail.remove > 0 || !tail.insert.is_empty());
+        let change = match &tail {
+            Some(tail) => change
+                .into_iter()
+                .take_while(|change| change.index < tail.at)
+                .collect(),
+            None => change,
+        };
+        PositionalDelta { change, tail }
+    }
+}
+
+/// As for [`PositionalDelta`]. Where the second delta drops items the first
+/// changed, the first's changes are undone on the dropped items, so the
+/// result still holds them as they were before either delta.
+impl<T, D> Compose for ReversiblePositionalDelta<T, D>
+where
+    T: Delta<Output = D>,
+    D: Compose + Invert,
+{
+    fn compose(first: Self, second: Self) -> Self {
+        let mut first_tail = first.tail;
+        let mut change = merge_changes(
+            first.change,
+            second.change,
+            first_tail.as_mut().map(|tail| (tail.at, &mut tail.insert)),
+        );
+        let tail = match (first_tail, second.tail) {
+            (first, None) => first,
+            (None, Some(mut second)) => {
+                restore(&mut second.remove, second.at, &mut change);
+                Some(second)
+            }
+            (Some(first), Some(mut second)) => {
+                let remove = if second.at < first.at {
+                    // What the second drops past the first's tail is what
+                    // the first appended, which neither side ever had.
+                    second.remove.truncate(first.at - second.at);
+                    restore(&mut second.remove, second.at, &mut change);
+                    second.remove.into_iter().chain(first.remove).collect()
+                } else {
+                    first.remove
+                };
+                let (at, insert) = compose_tails(first.at, first.insert, second.at, second.insert);
+                Some(ReversibleSplice { at, remove, insert })
+            }
+        };
+        let tail = tail.filter(|tail| !tail.remove.is_empty() || !tail.insert.is_empty());
+        ReversiblePositionalDelta { change, tail }
+    }
+}
+
+/// Merges two deltas' changes by index, composing those at the same one.
+///
+/// With `appended`, the first delta's tail start and the items it appends,
+/// the second's changes from that start on are applied to those items
+/// instead.
+fn merge_changes<T: Delta>(
+    first: Vec<IndexedDelta<T::Output>>,
+    second: Vec<IndexedDelta<T::Output>>,
+    appended: Option<(usize, &mut Vec<T>)>,
+) -> Vec<IndexedDelta<T::Output>>
+where
+    T::Output: Compose,
+{
+    let (boundary, appended) = match appended {
+        Some((at, items)) => (at, Some(items)),
+        None => (usize::MAX, None),
+    };
+    let (second, onto_appended): (Vec<_>, Vec<_>) = second
+        .into_iter()
+        .partition(|change| change.index < boundary);
+    if let Some(items) = appended {
+        for IndexedDelta { index, delta } in onto_appended {
+            if let Some(item) = items.get_mut(index - boundary) {
+                item.apply_delta(delta);
+            }
+        }
+    }
+    let mut merged = Vec::with_capacity(first.len() + second.len());
+    let (mut first, mut second) = (first.into_iter().peekable(), second.into_iter().peekable());
+    loop {
+        let next = match (first.peek(), second.peek()) {
+            (Some(a), Some(b)) => a.index.cmp(&b.index),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        merged.push(match next {
+            Ordering::Less => first.next().unwrap(),
+            Ordering::Greater => second.next().unwrap(),
+            Ordering::Equal => {
+                let (a, b) = (first.next().unwrap(), second.next().unwrap());
+                IndexedDelta {
+                    index: a.index,
+                    delta: Compose::compose(a.delta, b.delta),
+                }
+            }
+        });
+    }
+    merged
+}
+
+/// Where the tail of two composed deltas starts and what it appends, given
+/// each delta's tail start and appended items.
+fn compose_tails<T>(
+    first_at: usize,
+    mut first_insert: Vec<T>,
+    second_at: usize,
+    second_insert: Vec<T>,
+) -> (usize, Vec<T>) {
+    if second_at < first_at {
+        (second_at, second_insert)
+    } else {
+        // The second cuts into what the first appended, if anywhere.
+        first_insert.truncate(second_at - first_at);
+        first_insert.extend(second_insert);
+        (first_at, first_insert)
+    }
+}
+
+/// Undoes, on `removed`, the items a tail from `at` drops, every change in
+/// `change` to those items, taking the changes out of `change`.
+fn restore<T: Delta>(removed: &mut [T], at: usize, change: &mut Vec<IndexedDelta<T::Output>>)
+where
+    T::Output: Invert,
+{
+    let kept = change.iter().take_while(|change| change.index < at).count();
+    for IndexedDelta { index, delta } in change.drain(kept..) {
+        if let Some(item) = removed.get_mut(index - at) {
+            item.apply_delta(delta.invert());
+        }
+    }
+}
+
+/// A sequence an `indexed` field can be: one whose items can be reached by
+/// position, and that may or may not be able to change length.
+pub trait Slots<T>: AsRef<[T]> + AsMut<[T]> {
+    /// Whether the sequence can change length at all.
+    fn resizable(&self) -> bool;
+
+    /// Drops the items from `at` on and appends `items`. A sequence that
+    /// cannot change length is left as it is.
+    fn replace_tail(&mut self, at: usize, items: Vec<T>);
+}
+
+impl<T> Slots<T> for Vec<T> {
+    fn resizable(&self) -> bool {
+        true
+    }
+
+    fn replace_tail(&mut self, at: usize, items: Vec<T>) {
+        self.truncate(at);
+        self.extend(items);
+    }
+}
+
+impl<T, const N: usize> Slots<T> for [T; N] {
+    fn resizable(&self) -> bool {
+        false
+    }
+
+    fn replace_tail(&mut self, _: usize, _: Vec<T>) {}
+}
+
+/// Diffs the items of `old` and `new` pair by pair, handing the tail, as its
+/// start, the items only `old` has and the ones only `new` has, to `tail`.
+fn diff_with<T, G>(
+    old: impl IntoIterator<Item = T>,
+    new: impl IntoIterator<Item = T>,
+    pair: impl Fn(T, T) -> Option<T::Output>,
+    tail: impl FnOnce(usize, Vec<T>, Vec<T>) -> G,
+) -> (Vec<IndexedDelta<T::Output>>, Option<G>)
+where
+    T: Delta,
+{
+    let (mut old, mut new) = (old.into_iter(), new.into_iter());
+    let mut change = Vec::new();
+    let mut index = 0;
+    loop {
+        match (old.next(), new.next()) {
+            (Some(old), Some(new)) => {
+                if let Some(delta) = pair(old, new) {
+                    change.push(IndexedDelta { index, delta });
+                }
+                index += 1;
+            }
+            (first_old, first_new) => {
+                let removed: Vec<T> = first_old.into_iter().chain(old).collect();
+                let inserted: Vec<T> = first_new.into_iter().chain(new).collect();
+                let tail = if removed.is_empty() && inserted.is_empty() {
+                    None
+                } else {
+                    Some(tail(index, removed, inserted))
+                };
+                return (change, tail);
+            }
+        }
+    }
+}
+
+/// Computes a positional diff turning `old` into `new`.
+///
+/// Returns an empty [`PositionalDelta`] when the two are identical. Only
+/// items at the same position are ever compared, so a diff costs one
+/// [`Delta::delta`] per position the two share.
+///
+/// ```
+/// use delta_struct::indexed::diff;
+/// use delta_struct::Splice;
+///
+/// let delta = diff(vec![1, 2, 3, 4], vec![1, 9, 3]);
+/// assert_eq!(delta.change[0].index, 1);
+/// assert_eq!(delta.change[0].delta, 9);
+/// assert_eq!(delta.tail, Some(Splice { at: 3, remove: 1, insert: vec![] }));
+/// ```
+pub fn diff<C, T>(old: C, new: C) -> PositionalDelta<T, T::Output>
+where
+    C: IntoIterator<Item = T>,
+    T: Delta,
+{
+    diff_reversible(old, new).into_forward()
+}
+
+/// Computes the same delta as [`diff`], keeping the removed items so that it
+/// can be [inverted](Invert).
+pub fn diff_reversible<C, T>(old: C, new: C) -> ReversiblePositionalDelta<T, T::Output>
+where
+    C: IntoIterator<Item = T>,
+    T: Delta,
+{
+    let (change, tail) = diff_with(old, new, T::delta, |at, remove, insert| ReversibleSplice {
+        at,
+        remove,
+        insert,
+    });
+    ReversiblePositionalDelta { change, tail }
+}
+
+/// Computes the same [`PositionalDelta`] as [`diff`] from borrowed
+/// sequences, diffing each pair with [`DeltaRef::delta_ref`] and cloning
+/// only the appended items.
+pub fn diff_ref<'a, C, T>(old: &'a C, new: &'a C) -> PositionalDelta<T, T::Output>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: DeltaRef + Clone + 'a,
+{
+    let (change, tail) = diff_ref_with(old, new, |at, remove: Vec<&T>, insert: Vec<&T>| Splice {
+        at,
+        remove: remove.len(),
+        insert: insert.into_iter().cloned().collect(),
+    });
+    PositionalDelta { change, tail }
+}
+
+/// Computes the same delta as [`diff_ref`], cloning the removed items too so
+/// that it can be [inverted](Invert).
+pub fn diff_ref_reversible<'a, C, T>(
+    old: &'a C,
+    new: &'a C,
+) -> ReversiblePositionalDelta<T, T::Output>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: DeltaRef + Clone + 'a,
+{
+    let cloned = |items: Vec<&T>| items.into_iter().cloned().collect();
+    let (change, tail) = diff_ref_with(old, new, |at, remove, insert| ReversibleSplice {
+        at,
+        remove: cloned(remove),
+        insert: cloned(insert),
+    });
+    ReversiblePositionalDelta { change, tail }
+}
+
+/// [`diff_with`] over borrowed sequences.
+fn diff_ref_with<'a, C, T, G>(
+    old: &'a C,
+    new: &'a C,
+    tail: impl FnOnce(usize, Vec<&'a T>, Vec<&'a T>) -> G,
+) -> (Vec<IndexedDelta<T::Output>>, Option<G>)
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: DeltaRef + 'a,
+{
+    let (mut old, mut new) = (old.into_iter(), new.into_iter());
+    let mut change = Vec::new();
+    let mut index = 0;
+    loop {
+        match (old.next(), new.next()) {
+            (Some(old), Some(new)) => {
+                if let Some(delta) = T::delta_ref(old, new) {
+                    change.push(IndexedDelta { index, delta });
+                }
+                index += 1;
+            }
+            (first_old, first_new) => {
+                let removed: Vec<&T> = first_old.into_iter().chain(old).collect();
+                let inserted: Vec<&T> = first_new.into_iter().chain(new).collect();
+                let tail = if removed.is_empty() && inserted.is_empty() {
+                    None
+                } else {
+                    Some(tail(index, removed, inserted))
+                };
+                return (change, tail);
+            }
+        }
+    }
+}
+
+/// Applies a positional delta to `target` in place: each change to the item
+/// at its index, and then the tail.
+///
+/// A change to a position `target` does not have is skipped, and so is a
+/// tail on a sequence that cannot change length. Use [`try_apply`] to have
+/// such a delta refused instead.
+///
+/// ```
+/// use delta_struct::indexed::{apply, diff};
+///
+/// let delta = diff([1, 2, 3, 4], [1, 9, 3, 4]);
+/// let mut target = [1, 2, 3, 4];
+/// apply(&mut target, delta);
+/// assert_eq!(target, [1, 9, 3, 4]);
+/// ```
+pub fn apply<C, T>(target: &mut C, delta: PositionalDelta<T, T::Output>)
+where
+    C: Slots<T>,
+    T: Delta,
+{
+    let items = target.as_mut();
+    for IndexedDelta { index, delta } in delta.change {
+        if let Some(item) = items.get_mut(index) {
+            item.apply_delta(delta);
+        }
+    }
+    if let Some(Splice { at, insert, .. }) = delta.tail {
+        target.replace_tail(at, insert);
+    }
+}
+
+/// Checks that each change of `delta` is for a position `target` has and
+/// fits the item there, reporting one past the end as
+/// [`Problem::ChangeOutOfRange`], and that its tail ends where `target` does
+/// and `target` can change length, reporting one that does not as
+/// [`Problem::WrongLength`].
+pub fn check<C, T>(target: &C, delta: &PositionalDelta<T, T::Output>) -> Result<(), ApplyError>
+where
+    C: Slots<T>,
+    T: Delta,
+{
+    check_with(
+        target,
+        &delta.change,
+        delta.tail.as_ref().map(|tail| (tail.at, tail.remove)),
+    )
+}
+
+/// Checks a [`ReversiblePositionalDelta`] as [`check`] does a
+/// [`PositionalDelta`].
+pub fn check_reversible<C, T>(
+    target: &C,
+    delta: &ReversiblePositionalDelta<T, T::Output>,
+) -> Result<(), ApplyError>
+where
+    C: Slots<T>,
+    T: Delta,
+{
+    check_with(
+        target,
+        &delta.change,
+        delta.tail.as_ref().map(|tail| (tail.at, tail.remove.len())),
+    )
+}
+
+/// The check of either delta type, given the tail as `(at, remove)`.
+fn check_with<C, T>(
+    target: &C,
+    change: &[IndexedDelta<T::Output>],
+    tail: Option<(usize, usize)>,
+) -> Result<(), ApplyError>
+where
+    C: Slots<T>,
+    T: Delta,
+{
+    let items = target.as_ref();
+    for (position, IndexedDelta { index, delta }) in change.iter().enumerate() {
+        match items.get(*index) {
+            Some(item) => item
+                .check_delta(delta)
+                .map_err(|error| error.within(PathSegment::Change(position)))?,
+            None => {
+                return Err(ApplyError::new(Problem::ChangeOutOfRange {
+                    index: position,
+                    len: items.len(),
+                }))
+            }
+        }
+    }
+    match tail {
+        Some((at, remove))
+            if !target.resizable() || at.checked_add(remove) != Some(items.len()) =>
+        {
+            Err(ApplyError::new(Problem::WrongLength { len: items.len() }))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Applies a positional delta like [`apply`], but only if it passes
+/// [`check`]; otherwise `target` is left as it was.
+///
+/// ```
+/// use delta_struct::indexed::{diff, try_apply};
+/// use delta_struct::Problem;
+///
+/// let delta = diff(vec![1, 2], vec![1, 2, 3]);
+/// let mut target = [1, 2];
+/// let error = try_apply(&mut target, delta).unwrap_err();
+/// assert_eq!(error.problem, Problem::WrongLength { len: 2 });
+/// ```
+pub fn try_apply<C, T>(
+    target: &mut C,
+    delta: PositionalDelta<T, T::Output>,
+) -> Result<(), ApplyError>
+where
+    C: Slots<T>,
+    T: Delta,
+{
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! Deltas that can be run backwards, behind `#[delta_struct(reversible)]`.
+//!
+//! A plain delta only records the new side of each change: a `scalar` field's
+//! delta is the new value, and a removal from an `unordered-delta` map is just
+//! a key. That is all applying needs, and all a wire should carry. Undo needs
+//! the old side too, which is what the `reversible` container attribute adds:
+//!
+//! | Field type | Plain delta | Reversible delta |
+//! | --- | --- | --- |
+//! | `scalar` | `Option<T>` | `Option<Change<T>>` |
+//! | `counter` | `Option` of the increment | the same, which undoes by negation |
+//! | `unordered` | [`BagDelta`] | [`BagDelta`], which already records both sides |
+//! | `multiset` | [`MultisetDelta`](crate::MultisetDelta) | [`MultisetDelta`](crate::MultisetDelta), which already records both sides |
+//! | `unordered-delta`, `keyed` | [`MapDelta`](crate::MapDelta) | [`ReversibleMapDelta`](crate::ReversibleMapDelta), whose `remove` keeps the removed values |
+//! | `unordered-scalar` | [`MapDelta`](crate::MapDelta) whose changes are new values | [`ReversibleMapDelta`](crate::ReversibleMapDelta) whose changes are [`Change`]s |
+//! | `ordered` | [`SeqDelta`](crate::SeqDelta) | [`ReversibleSeqDelta`](crate::ReversibleSeqDelta), whose splices keep the items they drop |
+//! | `keyed-ordered` | [`KeyedOrderedDelta`](crate::KeyedOrderedDelta) | [`ReversibleKeyedOrderedDelta`](crate::ReversibleKeyedOrderedDelta), whose removals keep the items they drop |
+//! | `ordered-delta` | [`OrderedDelta`](crate::OrderedDelta) | [`ReversibleOrderedDelta`](crate::ReversibleOrderedDelta), whose splices keep the items they drop, and whose items' deltas are reversible in turn |
+//! | `indexed` | [`PositionalDelta`](crate::PositionalDelta) | [`ReversiblePositionalDelta`](crate::ReversiblePositionalDelta), whose tail keeps the items it drops, and whose items' deltas are reversible in turn |
+//! | `text` | [`TextDelta`](crate::TextDelta) | [`ReversibleTextDelta`](crate::ReversibleTextDelta), whose splices keep the text they drop |
+//! | `delta` | `Option<<T as Delta>::Output>` | the same, so `T` has to be reversible too |
+//! | `optional-delta` | `Option<`[`OptionDelta`](crate::OptionDelta)`>` | `Option<`[`ReversibleOptionDelta`](crate::ReversibleOptionDelta)`>`, whose `Clear` keeps the cleared value |
+//!
+//! With `replace_if_cheaper`, an `ordered` or `unordered` field's delta is an
+//! [`OrReplace`](crate::OrReplace) of the one above and a [`Change`] of the
+//! whole collection.
+//!
+//! Every one of those implements [`Invert`], and so does the generated delta
+//! type. Inverting the delta from `old` to `new` gives the delta from `new` to
+//! `old`, so an undo stack is a `Vec` of deltas rather than of snapshots.
+//!
+//! ```
+//! use delta_struct::{Delta, Invert};
+//!
+//! #[derive(Debug, Delta, PartialEq)]
+//! #[delta_struct(reversible, delta_leader = "#[derive(Clone)]")]
+//! struct Document {
+//!     title: String,
+//!     #[delta_struct(field_type = "ordered")]
+//!     lines: Vec<String>,
+//! }
+//!
+//! let document = |title: &str, lines: &[&str]| Document {
+//!     title: title.to_string(),
+//!     lines: lines.iter().map(|line| line.to_string()).collect(),
+//! };
+//!
+//! let mut current = document("Draft", &["one", "two"]);
+//! let edit = Delta::delta(document("Draft", &["one", "two"]), document("Final", &["one", "2"]))
+//!     .unwrap();
+//!
+//! // The old title travels alongside the new one.
+//! assert_eq!(edit.title.as_ref().unwrap().old, "Draft");
+//!
+//! let undo = edit.clone().invert();
+//! current.apply_delta(edit);
+//! assert_eq!(current, document("Final", &["one", "2"]));
+//! current.apply_delta(undo);
+//! assert_eq!(current, document("Draft", &["one", "two"]));
+//! ```
+
+use crate::BagDelta;
+
+/// A delta that can be turned around.
+///
+/// `invert` on the delta from `old` to `new` returns the delta from `new` to
+/// `old`: applying a delta and then its inverse leaves a value where it
+/// started. Inverting twice gives back the delta you began with.
+pub trait Invert {
+    /// Returns the delta that undoes this one.
+    fn invert(self) -> Self;
+}
+
+/// Both sides of a changed `scalar` field, as a reversible delta records it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change<T> {
+    /// The value before the change.
+    pub old: T,
+    /// The value after it — what applying the delta writes.
+    pub new: T,
+}
+
+impl<T> Invert for Change<T> {
+    fn invert(self) -> Self {
+        Change {
+            old: self.new,
+            new: self.old,
+        }
+    }
+}
+
+/// An absent delta is its own inverse: nothing changed either way.
+impl<D: Invert> Invert for Option<D> {
+    fn invert(self) -> Self {
+        self.map(Invert::invert)
+    }
+}
+
+impl<T> Invert for BagDelta<T> {
+    fn invert(self) -> Self {
+        BagDelta {
+            add: self.remove,
+            remove: self.add,
+        }
+    }
+}
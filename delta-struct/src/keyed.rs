@@ -0,0 +1,384 @@
+//! Keyed diffing over a sequence of records, behind the `keyed` field type.
+//!
+//! A field marked `#[delta_struct(field_type = "keyed", key = "id", key_type =
+//! "...")]` is a `Vec` of records that each carry their own identity in a
+//! field. Records are paired up by that field rather than by position, and
+//! the result is the same [`MapDelta`] an `unordered-delta` field produces: a
+//! record whose key is on both sides travels as its own [`Delta`], not as a
+//! removal plus a re-send.
+//!
+//! This is `unordered-delta` for collections that are not maps, typically
+//! because they mirror a wire format that sends arrays. Pairing goes through a
+//! [`HashMap`] built for the occasion, so it costs O(n) rather than the
+//! quadratic scan pairing a `Vec` would otherwise take.
+//!
+//! The derive emits calls to [`diff`] and [`apply`], or to [`diff_ref`] for
+//! [`DeltaRef`] and [`check`] for [`Delta::try_apply_delta`], handing each a
+//! closure that borrows the key out of a record; you only need this module
+//! directly to inspect or construct a delta by hand.
+//! A `reversible` type's field gets a [`ReversibleMapDelta`] instead, from
+//! [`diff_reversible`] or [`diff_ref_reversible`].
+
+use crate::check::{all_present, ApplyError, PathSegment, Problem};
+use crate::{Delta, DeltaRef, KeyedDelta, MapDelta, ReversibleMapDelta};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Pairs the records of `old` and `new` by the key `key` borrows out of each,
+/// and diffs the records that survived.
+///
+/// A key on both sides with an equal record produces nothing at all, so the
+/// [`MapDelta`] is empty when the two hold the same records, in any order.
+/// Records in `add` are paired with a copy of their key, as in any
+/// [`MapDelta`].
+///
+/// Keys are expected to be unique within each side. A key that is on one side
+/// more than once is paired once, its last old record with its first new one,
+/// as a `keyed-ordered` field pairs it; its other records are removed or
+/// added. [`apply`] resolves the key the same way, so the delta still lands.
+///
+/// ```
+/// use delta_struct::{keyed, Delta};
+///
+/// #[derive(Delta)]
+/// struct Device {
+///     id: u32,
+///     online: bool,
+/// }
+///
+/// let old = vec![Device { id: 1, online: true }, Device { id: 2, online: true }];
+/// let new = vec![Device { id: 2, online: false }, Device { id: 3, online: true }];
+///
+/// let delta = keyed::diff(old, new, |device: &Device| &device.id);
+/// assert_eq!(delta.add.len(), 1);
+/// assert_eq!(delta.remove, vec![1]);
+/// assert_eq!(delta.change[0].key, 2);
+/// assert_eq!(delta.change[0].delta.online, Some(false));
+/// ```
+pub fn diff<C, T, K, F>(old: C, new: C, key: F) -> MapDelta<K, T, T::Output>
+where
+    C: IntoIterator<Item = T>,
+    T: Delta,
+    K: Hash + Eq + Clone,
+    F: Fn(&T) -> &K,
+{
+    diff_reversible(old, new, key).into_forward()
+}
+
+/// Computes the same delta as [`diff`], keeping the removed records so that
+/// it can be [inverted](crate::Invert).
+pub fn diff_reversible<C, T, K, F>(old: C, new: C, key: F) -> ReversibleMapDelta<K, T, T::Output>
+where
+    C: IntoIterator<Item = T>,
+    T: Delta,
+    K: Hash + Eq + Clone,
+    F: Fn(&T) -> &K,
+{
+    let old: Vec<T> = old.into_iter().collect();
+    let new: Vec<T> = new.into_iter().collect();
+
+    // Find each new record's partner first, while both sides can still be
+    // borrowed, so that no key has to be cloned just to build the index.
+    let partners = partners(&old, &new, &key);
+
+    // Then take each partner out of `old` as it is matched, so whatever is
+    // still standing at the end is exactly what was removed.
+    let mut old: Vec<Option<T>> = old.into_iter().map(Some).collect();
+    let mut add = Vec::new();
+    let mut change = Vec::new();
+    for (record, partner) in new.into_iter().zip(partners) {
+        let record_key = key(&record).clone();
+        match partner.and_then(|index| old[index].take()) {
+            Some(old_record) => {
+                if let Some(delta) = Delta::delta(old_record, record) {
+                    change.push(KeyedDelta {
+                        key: record_key,
+                        delta,
+                    });
+                }
+            }
+            None => add.push((record_key, record)),
+        }
+    }
+    let remove = old
+        .into_iter()
+        .flatten()
+        .map(|record| (key(&record).clone(), record))
+        .collect();
+    ReversibleMapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// Computes the same [`MapDelta`] as [`diff`] from borrowed records, cloning
+/// only the keys and records that ended up in it.
+///
+/// Records whose key is on both sides are diffed with
+/// [`DeltaRef::delta_ref`], so a changed record is never cloned whole.
+///
+/// ```
+/// use delta_struct::{keyed, Delta, DeltaRef};
+///
+/// #[derive(Clone, Delta, DeltaRef)]
+/// struct Device {
+///     id: u32,
+///     online: bool,
+/// }
+///
+/// let old = vec![Device { id: 1, online: true }, Device { id: 2, online: true }];
+/// let new = vec![Device { id: 2, online: false }];
+///
+/// let delta = keyed::diff_ref(&old, &new, |device: &Device| &device.id);
+/// assert_eq!(delta.remove, vec![1]);
+/// assert_eq!(delta.change[0].delta.online, Some(false));
+/// ```
+pub fn diff_ref<'a, T, K, F>(old: &'a [T], new: &'a [T], key: F) -> MapDelta<K, T, T::Output>
+where
+    T: DeltaRef + Clone,
+    K: Hash + Eq + Clone + 'a,
+    F: Fn(&T) -> &K,
+{
+    let (add, remove, change) = diff_ref_with(old, new, key, |key, _| key.clone());
+    MapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// Computes the same delta as [`diff_ref`], cloning the removed records too
+/// so that it can be [inverted](crate::Invert).
+pub fn diff_ref_reversible<'a, T, K, F>(
+    old: &'a [T],
+    new: &'a [T],
+    key: F,
+) -> ReversibleMapDelta<K, T, T::Output>
+where
+    T: DeltaRef + Clone,
+    K: Hash + Eq + Clone + 'a,
+    F: Fn(&T) -> &K,
+{
+    let (add, remove, change) =
+        diff_ref_with(old, new, key, |key, record| (key.clone(), record.clone()));
+    ReversibleMapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// The parts of a borrowing diff, with each removal recorded by `removed`.
+#[allow(clippy::type_complexity)] // The three fields of either delta type
+fn diff_ref_with<'a, T, K, F, R>(
+    old: &'a [T],
+    new: &'a [T],
+    key: F,
+    removed: impl Fn(&K, &T) -> R,
+) -> (Vec<(K, T)>, Vec<R>, Vec<KeyedDelta<K, T::Output>>)
+where
+    T: DeltaRef + Clone,
+    K: Hash + Eq + Clone + 'a,
+    F: Fn(&T) -> &K,
+{
+    let mut paired = vec![false; old.len()];
+    let mut add = Vec::new();
+    let mut change = Vec::new();
+    for (record, partner) in new.iter().zip(partners(old, new, &key)) {
+        let record_key = key(record);
+        match partner {
+            Some(index) => {
+                paired[index] = true;
+                if let Some(delta) = DeltaRef::delta_ref(&old[index], record) {
+                    change.push(KeyedDelta {
+                        key: record_key.clone(),
+                        delta,
+                    });
+                }
+            }
+            None => add.push((record_key.clone(), record.clone())),
+        }
+    }
+    let remove = old
+        .iter()
+        .zip(paired)
+        .filter(|(_, paired)| !paired)
+        .map(|(record, _)| removed(key(record), record))
+        .collect();
+    (add, remove, change)
+}
+
+/// The position in `old` of each `new` record's partner, if it has one.
+///
+/// Each old record is paired at most once: a key's last record in `old` with
+/// its first in `new`, leaving any other records under the key unpaired.
+fn partners<T, K, F>(old: &[T], new: &[T], key: &F) -> Vec<Option<usize>>
+where
+    K: Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    let mut positions: HashMap<&K, usize> = old
+        .iter()
+        .enumerate()
+        .map(|(index, record)| (key(record), index))
+        .collect();
+    new.iter()
+        .map(|record| positions.remove(key(record)))
+        .collect()
+}
+
+/// Applies a keyed diff to `target` in place.
+///
+/// Removals and changes are found through one [`HashMap`] built over
+/// `target`, so this costs O(n) as [`diff`] does, and a changed record is
+/// updated where it sits. Surviving records keep their relative order and
+/// additions are appended at the end — as with an `unordered-delta` field,
+/// membership is preserved but position is not.
+///
+/// A key in `remove` or `change` that `target` does not have is ignored; use
+/// [`try_apply`] to have the delta refused instead. Where `target` holds a
+/// key more than once, each removal of it takes its first remaining record,
+/// and a change goes to its last, the one [`diff`] pairs up.
+///
+/// ```
+/// use delta_struct::{keyed, Delta};
+///
+/// #[derive(Debug, Delta, PartialEq)]
+/// struct Device {
+///     id: u32,
+///     online: bool,
+/// }
+///
+/// let devices = |online| vec![Device { id: 1, online }, Device { id: 2, online: true }];
+///
+/// let delta = keyed::diff(devices(true), devices(false), |device: &Device| &device.id);
+/// let mut target = devices(true);
+/// keyed::apply(&mut target, delta, |device: &Device| &device.id);
+/// assert_eq!(target, devices(false));
+/// ```
+pub fn apply<T, K, F>(target: &mut Vec<T>, delta: MapDelta<K, T, T::Output>, key: F)
+where
+    T: Delta,
+    K: Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    let MapDelta {
+        add,
+        remove,
+        change,
+    } = delta;
+    if !remove.is_empty() || !change.is_empty() {
+        // Resolve every key to a position up front, while `target` can still
+        // be borrowed for the index, and only then start mutating it.
+        let mut doomed = vec![false; target.len()];
+        let mut changes = Vec::with_capacity(change.len());
+        {
+            let mut positions: HashMap<&K, VecDeque<usize>> = HashMap::new();
+            for (index, record) in target.iter().enumerate() {
+                positions.entry(key(record)).or_default().push_back(index);
+            }
+            for KeyedDelta {
+                key: changed,
+                delta,
+            } in change
+            {
+                if let Some(&index) = positions.get(&changed).and_then(VecDeque::back) {
+                    changes.push((index, delta));
+                }
+            }
+            for removed in &remove {
+                if let Some(index) = positions.get_mut(removed).and_then(VecDeque::pop_front) {
+                    doomed[index] = true;
+                }
+            }
+        }
+        for (index, delta) in changes {
+            target[index].apply_delta(delta);
+        }
+        let mut doomed = doomed.into_iter();
+        target.retain(|_| !doomed.next().unwrap_or(false));
+    }
+    target.extend(add.into_iter().map(|(_, record)| record));
+}
+
+/// Checks that every key `delta` removes or changes belongs to a record of
+/// `target`, and that each change fits its record, as
+/// [`map::check`](crate::map::check) does for a map.
+pub fn check<T, K, F>(
+    target: &[T],
+    delta: &MapDelta<K, T, T::Output>,
+    key: F,
+) -> Result<(), ApplyError>
+where
+    T: Delta,
+    K: Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    check_with(target, &delta.remove, &delta.change, key)
+}
+
+/// Checks a [`ReversibleMapDelta`] as [`check`] does a [`MapDelta`].
+pub fn check_reversible<T, K, F>(
+    target: &[T],
+    delta: &ReversibleMapDelta<K, T, T::Output>,
+    key: F,
+) -> Result<(), ApplyError>
+where
+    T: Delta,
+    K: Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    check_with(
+        target,
+        delta.remove.iter().map(|(key, _)| key),
+        &delta.change,
+        key,
+    )
+}
+
+/// The check of either delta type, given the keys it removes.
+fn check_with<'a, T, K, F>(
+    target: &[T],
+    removed: impl IntoIterator<Item = &'a K>,
+    change: &[KeyedDelta<K, T::Output>],
+    key: F,
+) -> Result<(), ApplyError>
+where
+    T: Delta,
+    K: Hash + Eq + 'a,
+    F: Fn(&T) -> &K,
+{
+    let records: HashMap<&K, &T> = target.iter().map(|record| (key(record), record)).collect();
+    all_present(
+        removed,
+        |key| records.contains_key(key),
+        |index| Problem::NotPresent { index },
+    )?;
+    for (index, KeyedDelta { key, delta }) in change.iter().enumerate() {
+        match records.get(key) {
+            Some(record) => record
+                .check_delta(delta)
+                .map_err(|error| error.within(PathSegment::Change(index)))?,
+            None => return Err(ApplyError::new(Problem::MissingKey { index })),
+        }
+    }
+    Ok(())
+}
+
+/// Applies a keyed diff like [`apply`], but only if it passes [`check`];
+/// otherwise `target` is left as it was.
+pub fn try_apply<T, K, F>(
+    target: &mut Vec<T>,
+    delta: MapDelta<K, T, T::Output>,
+    key: F,
+) -> Result<(), ApplyError>
+where
+    T: Delta,
+    K: Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    check(target, &delta, &key)?;
+    apply(target, delta, key);
+    Ok(())
+}
@@ -0,0 +1,664 @@
+//! Keyed reconciliation of an ordered list, with moves, behind the
+//! `keyed-ordered` field type.
+//!
+//! An `ordered` field sees an item that moved as one removed and another
+//! inserted, so dragging a track to the top of a playlist resends it, and
+//! every item in between if they all shifted the other way. A field marked
+//! `#[delta_struct(field_type = "keyed-ordered", key = "id", key_type =
+//! "...")]` is a `Vec` of items that each carry their identity in a field, as
+//! for `keyed`, and its delta is a list of [`KeyedOp`]s: an item that stayed
+//! but changed travels as its own [`Delta`], an item that moved as a
+//! [`Move`](KeyedOp::Move) of its position, and only items that really came
+//! or went as an [`Insert`](KeyedOp::Insert) or a
+//! [`Remove`](KeyedOp::Remove).
+//!
+//! ```
+//! use delta_struct::{Delta, KeyedOp};
+//!
+//! #[derive(Clone, Delta)]
+//! struct Track {
+//!     id: u32,
+//!     title: String,
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Playlist {
+//!     #[delta_struct(field_type = "keyed-ordered", key = "id", key_type = "u32")]
+//!     tracks: Vec<Track>,
+//! }
+//!
+//! let track = |id, title: &str| Track { id, title: title.to_string() };
+//! let old = Playlist { tracks: vec![track(1, "a"), track(2, "b"), track(3, "c"), track(4, "d")] };
+//! let new = Playlist { tracks: vec![track(4, "d"), track(1, "a"), track(2, "b"), track(3, "c")] };
+//!
+//! // The last track was dragged to the top, and nothing else moved.
+//! let delta = Delta::delta(old, new).unwrap().tracks;
+//! assert!(matches!(delta.ops[..], [KeyedOp::Move { from: 3, to: 0 }]));
+//! ```
+//!
+//! Which items move is decided the way a UI framework reconciles a keyed
+//! list: the longest run of surviving items that kept their relative order
+//! stays put, and everything else moves around it, so the number of moves is
+//! the least that will do.
+//!
+//! The ops apply one after another, and each one's positions count items as a
+//! [`Splice`](crate::Splice)'s do, in the sequence as the ops before it left
+//! it. A diff lists the changes first, at their positions in the old
+//! sequence, then the removals from the back, and then the moves and
+//! insertions that put the new order together. Composing two deltas chains
+//! their ops.
+//!
+//! The derive emits calls to [`diff`] and [`apply`], or to [`diff_ref`] for
+//! [`DeltaRef`] and [`check`] for [`Delta::try_apply_delta`], handing the
+//! diff a closure that borrows the key out of an item. A `reversible` type's
+//! field gets a [`ReversibleKeyedOrderedDelta`] instead, from
+//! [`diff_reversible`] or [`diff_ref_reversible`].
+
+use crate::check::{ApplyError, PathSegment, Problem};
+use crate::{Compose, Delta, DeltaRef, Invert};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// One step of a [`KeyedOrderedDelta`], at positions in the sequence as the
+/// steps before it left it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyedOp<T, D> {
+    /// The item at `at` stayed, and changed by `delta`.
+    Change {
+        /// Where the item is.
+        at: usize,
+        /// How it changed.
+        delta: D,
+    },
+    /// The item at `at` went away.
+    Remove {
+        /// Where the item was.
+        at: usize,
+    },
+    /// `item` appeared at `at`, and travels whole since the receiver has
+    /// nothing to diff it against.
+    Insert {
+        /// Where the item goes, with what was there and after it moving up
+        /// one.
+        at: usize,
+        /// The item.
+        item: T,
+    },
+    /// The item at `from` moved to `to`.
+    Move {
+        /// Where the item was.
+        from: usize,
+        /// Where it goes, counted once it has been taken out of `from`.
+        to: usize,
+    },
+}
+
+/// A [`KeyedOp`] whose removals also keep the item they drop, so that it can
+/// be [inverted](Invert).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReversibleKeyedOp<T, D> {
+    /// The item at `at` stayed, and changed by `delta`.
+    Change {
+        /// Where the item is.
+        at: usize,
+        /// How it changed.
+        delta: D,
+    },
+    /// The item at `at` went away; this is what it was.
+    Remove {
+        /// Where the item was.
+        at: usize,
+        /// The item.
+        item: T,
+    },
+    /// `item` appeared at `at`.
+    Insert {
+        /// Where the item goes, with what was there and after it moving up
+        /// one.
+        at: usize,
+        /// The item.
+        item: T,
+    },
+    /// The item at `from` moved to `to`.
+    Move {
+        /// Where the item was.
+        from: usize,
+        /// Where it goes, counted once it has been taken out of `from`.
+        to: usize,
+    },
+}
+
+impl<T, D> ReversibleKeyedOp<T, D> {
+    /// Drops the removed item, which only undoing needs.
+    pub fn into_forward(self) -> KeyedOp<T, D> {
+        match self {
+            ReversibleKeyedOp::Change { at, delta } => KeyedOp::Change { at, delta },
+            ReversibleKeyedOp::Remove { at, .. } => KeyedOp::Remove { at },
+            ReversibleKeyedOp::Insert { at, item } => KeyedOp::Insert { at, item },
+            ReversibleKeyedOp::Move { from, to } => KeyedOp::Move { from, to },
+        }
+    }
+}
+
+/// The ops that turn one keyed list into another, in the order they apply.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyedOrderedDelta<T, D> {
+    /// The ops, in the order they apply.
+    pub ops: Vec<KeyedOp<T, D>>,
+}
+
+impl<T, D> KeyedOrderedDelta<T, D> {
+    /// Whether the two lists were identical, and so nothing needs sending.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+impl<T, D> Default for KeyedOrderedDelta<T, D> {
+    fn default() -> Self {
+        KeyedOrderedDelta { ops: Vec::new() }
+    }
+}
+
+/// A [`KeyedOrderedDelta`] whose removals also keep the items they drop, so
+/// that it can be [inverted](Invert).
+///
+/// This is what a `keyed-ordered` field turns into on a `reversible` type.
+/// Inverting it needs the items' own deltas to be reversible too.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReversibleKeyedOrderedDelta<T, D> {
+    /// The ops, in the order they apply.
+    pub ops: Vec<ReversibleKeyedOp<T, D>>,
+}
+
+impl<T, D> ReversibleKeyedOrderedDelta<T, D> {
+    /// Whether the two lists were identical, and so nothing needs sending.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Drops the removed items, which only undoing needs, leaving the
+    /// [`KeyedOrderedDelta`] that [`apply`] takes.
+    pub fn into_forward(self) -> KeyedOrderedDelta<T, D> {
+        KeyedOrderedDelta {
+            ops: self
+                .ops
+                .into_iter()
+                .map(ReversibleKeyedOp::into_forward)
+                .collect(),
+        }
+    }
+}
+
+impl<T, D> Default for ReversibleKeyedOrderedDelta<T, D> {
+    fn default() -> Self {
+        ReversibleKeyedOrderedDelta { ops: Vec::new() }
+    }
+}
+
+/// Undoes the ops from the last back: an insertion becomes the removal of
+/// what it inserted and the other way round, a move goes back where it came
+/// from, and a change is inverted.
+impl<T, D: Invert> Invert for ReversibleKeyedOrderedDelta<T, D> {
+    fn invert(self) -> Self {
+        let ops = self
+            .ops
+            .into_iter()
+            .rev()
+            .map(|op| match op {
+                ReversibleKeyedOp::Change { at, delta } => ReversibleKeyedOp::Change {
+                    at,
+                    delta: delta.invert(),
+                },
+                ReversibleKeyedOp::Remove { at, item } => ReversibleKeyedOp::Insert { at, item },
+                ReversibleKeyedOp::Insert { at, item } => ReversibleKeyedOp::Remove { at, item },
+                ReversibleKeyedOp::Move { from, to } => {
+                    ReversibleKeyedOp::Move { from: to, to: from }
+                }
+            })
+            .collect();
+        ReversibleKeyedOrderedDelta { ops }
+    }
+}
+
+/// The second delta's ops run after the first's, so composing chains them.
+/// The result does the right thing, but is not the fewest ops that would:
+/// an item inserted and then removed again is still inserted and removed.
+impl<T, D> Compose for KeyedOrderedDelta<T, D> {
+    fn compose(mut first: Self, second: Self) -> Self {
+        first.ops.extend(second.ops);
+        first
+    }
+}
+
+/// As for [`KeyedOrderedDelta`].
+impl<T, D> Compose for ReversibleKeyedOrderedDelta<T, D> {
+    fn compose(mut first: Self, second: Self) -> Self {
+        first.ops.extend(second.ops);
+        first
+    }
+}
+
+/// How to get from one list of keys to another, before any item is touched.
+struct Plan {
+    /// Each old item that survived, as its old and new position, in old
+    /// order.
+    kept: Vec<(usize, usize)>,
+    /// The old positions of the items that went away, in ascending order.
+    removed: Vec<usize>,
+    /// The moves and insertions, in the order they apply.
+    placements: Vec<Placement>,
+}
+
+/// A move or insertion of a [`Plan`]; an insertion names the new item it
+/// inserts by its position in the new list.
+enum Placement {
+    Insert { at: usize, new_index: usize },
+    Move { from: usize, to: usize },
+}
+
+/// Pairs `old` and `new` up by key and works out the fewest moves that put
+/// the survivors in their new order.
+///
+/// A key that is on one side more than once is paired once, and its other
+/// items are removed or inserted, as for `keyed`.
+fn plan<K: Hash + Eq>(old: &[&K], new: &[&K]) -> Plan {
+    let positions: HashMap<&K, usize> = old
+        .iter()
+        .enumerate()
+        .map(|(index, &key)| (key, index))
+        .collect();
+    let mut partners: Vec<Option<usize>> = vec![None; old.len()];
+    let mut origins: Vec<Option<usize>> = vec![None; new.len()];
+    for (new_index, key) in new.iter().enumerate() {
+        if let Some(&index) = positions.get(key) {
+            if partners[index].is_none() {
+                partners[index] = Some(new_index);
+                origins[new_index] = Some(index);
+            }
+        }
+    }
+    let kept: Vec<(usize, usize)> = partners
+        .iter()
+        .enumerate()
+        .filter_map(|(index, partner)| Some((index, (*partner)?)))
+        .collect();
+    let removed = partners
+        .iter()
+        .enumerate()
+        .filter(|(_, partner)| partner.is_none())
+        .map(|(index, _)| index)
+        .collect();
+
+    // Once the removals are done, the list holds the survivors in their old
+    // order. The longest run of them already in new order stays, and the
+    // rest, along with the insertions, go in front of their new successor
+    // from the back of the new list forwards, so that the successor is
+    // always in place to be found.
+    let mut current: Vec<usize> = kept.iter().map(|&(_, new_index)| new_index).collect();
+    let mut stays = vec![false; new.len()];
+    for (position, stayed) in longest_increasing(&current).into_iter().enumerate() {
+        stays[current[position]] = stayed;
+    }
+    let mut placements = Vec::new();
+    for new_index in (0..new.len()).rev() {
+        if stays[new_index] {
+            continue;
+        }
+        let successor = |current: &[usize]| {
+            current
+                .iter()
+                .position(|&other| other == new_index + 1)
+                .unwrap_or(current.len())
+        };
+        match origins[new_index] {
+            None => {
+                let at = successor(&current);
+                current.insert(at, new_index);
+                placements.push(Placement::Insert { at, new_index });
+            }
+            Some(_) => {
+                // Every survivor is in `current`.
+                let from = current
+                    .iter()
+                    .position(|&other| other == new_index)
+                    .unwrap();
+                current.remove(from);
+                let to = successor(&current);
+                current.insert(to, new_index);
+                if from != to {
+                    placements.push(Placement::Move { from, to });
+                }
+            }
+        }
+    }
+    Plan {
+        kept,
+        removed,
+        placements,
+    }
+}
+
+/// Marks the positions of one longest strictly increasing run in `values`,
+/// by patience sorting in O(n log n).
+fn longest_increasing(values: &[usize]) -> Vec<bool> {
+    // `tails[length]` is where the run of `length + 1` with the smallest last
+    // value found so far ends, and `previous` links each position back to
+    // the one before it in its run.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; values.len()];
+    for (position, &value) in values.iter().enumerate() {
+        let length = tails.partition_point(|&tail| values[tail] < value);
+        previous[position] = length.checked_sub(1).map(|before| tails[before]);
+        if length == tails.len() {
+            tails.push(position);
+        } else {
+            tails[length] = position;
+        }
+    }
+    let mut in_run = vec![false; values.len()];
+    let mut next = tails.last().copied();
+    while let Some(position) = next {
+        in_run[position] = true;
+        next = previous[position];
+    }
+    in_run
+}
+
+/// Pairs the items of `old` and `new` by the key `key` borrows out of each,
+/// and lists the ops that turn one into the other: a change for each item
+/// that stayed but changed, then the fewest removals, moves and insertions
+/// that reorder the rest.
+///
+/// Planning the moves finds each one's destination with a scan, so it costs
+/// O(n) per item moved or inserted on top of the O(n log n) reconciliation.
+/// Keys are expected to be unique within each side, as for
+/// [`keyed::diff`](crate::keyed::diff).
+///
+/// ```
+/// use delta_struct::{keyed_ordered, Delta, KeyedOp};
+///
+/// #[derive(Delta)]
+/// struct Track {
+///     id: u32,
+///     seconds: u32,
+/// }
+///
+/// let track = |id, seconds| Track { id, seconds };
+/// let old = vec![track(1, 200), track(2, 180), track(3, 240)];
+/// let new = vec![track(3, 240), track(2, 185), track(4, 90)];
+///
+/// let delta = keyed_ordered::diff(old, new, |track: &Track| &track.id);
+/// match &delta.ops[..] {
+///     [KeyedOp::Change { at: 1, delta }, KeyedOp::Remove { at: 0 }, KeyedOp::Insert { at: 2, item }, KeyedOp::Move { from: 0, to: 1 }] => {
+///         assert_eq!(delta.seconds, Some(185));
+///         assert_eq!(item.id, 4);
+///     }
+///     _ => panic!("unexpected ops"),
+/// }
+/// ```
+pub fn diff<T, K, F>(old: Vec<T>, new: Vec<T>, key: F) -> KeyedOrderedDelta<T, T::Output>
+where
+    T: Delta,
+    K: Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    diff_reversible(old, new, key).into_forward()
+}
+
+/// Computes the same delta as [`diff`], keeping the removed items so that it
+/// can be [inverted](Invert).
+pub fn diff_reversible<T, K, F>(
+    old: Vec<T>,
+    new: Vec<T>,
+    key: F,
+) -> ReversibleKeyedOrderedDelta<T, T::Output>
+where
+    T: Delta,
+    K: Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    let plan = {
+        let old_keys: Vec<&K> = old.iter().map(&key).collect();
+        let new_keys: Vec<&K> = new.iter().map(&key).collect();
+        plan(&old_keys, &new_keys)
+    };
+    // Items are taken out of both sides as the ops claim them.
+    let mut old: Vec<Option<T>> = old.into_iter().map(Some).collect();
+    let mut new: Vec<Option<T>> = new.into_iter().map(Some).collect();
+    let mut ops = Vec::new();
+    for (index, new_index) in plan.kept {
+        // The plan takes each position on either side once.
+        let (old_item, new_item) = (old[index].take().unwrap(), new[new_index].take().unwrap());
+        if let Some(delta) = T::delta(old_item, new_item) {
+            ops.push(ReversibleKeyedOp::Change { at: index, delta });
+        }
+    }
+    for at in plan.removed.into_iter().rev() {
+        let item = old[at].take().unwrap();
+        ops.push(ReversibleKeyedOp::Remove { at, item });
+    }
+    ops.extend(
+        plan.placements
+            .into_iter()
+            .map(|placement| match placement {
+                Placement::Insert { at, new_index } => ReversibleKeyedOp::Insert {
+                    at,
+                    item: new[new_index].take().unwrap(),
+                },
+                Placement::Move { from, to } => ReversibleKeyedOp::Move { from, to },
+            }),
+    );
+    ReversibleKeyedOrderedDelta { ops }
+}
+
+/// Computes the same [`KeyedOrderedDelta`] as [`diff`] from borrowed lists,
+/// cloning only the inserted items, and diffing the ones that stayed with
+/// [`DeltaRef::delta_ref`].
+pub fn diff_ref<'a, T, K, F>(old: &'a [T], new: &'a [T], key: F) -> KeyedOrderedDelta<T, T::Output>
+where
+    T: DeltaRef + Clone,
+    K: Hash + Eq + 'a,
+    F: Fn(&T) -> &K,
+{
+    let ops = diff_ref_with(old, new, key, |at, _| KeyedOp::Remove { at }, |op| op);
+    KeyedOrderedDelta { ops }
+}
+
+/// Computes the same delta as [`diff_ref`], cloning the removed items too so
+/// that it can be [inverted](Invert).
+pub fn diff_ref_reversible<'a, T, K, F>(
+    old: &'a [T],
+    new: &'a [T],
+    key: F,
+) -> ReversibleKeyedOrderedDelta<T, T::Output>
+where
+    T: DeltaRef + Clone,
+    K: Hash + Eq + 'a,
+    F: Fn(&T) -> &K,
+{
+    let removed = |at, item: &T| ReversibleKeyedOp::Remove {
+        at,
+        item: item.clone(),
+    };
+    let ops = diff_ref_with(old, new, key, removed, |op| match op {
+        KeyedOp::Change { at, delta } => ReversibleKeyedOp::Change { at, delta },
+        KeyedOp::Insert { at, item } => ReversibleKeyedOp::Insert { at, item },
+        KeyedOp::Move { from, to } => ReversibleKeyedOp::Move { from, to },
+        // Removals are built by `removed`.
+        KeyedOp::Remove { .. } => unreachable!(),
+    });
+    ReversibleKeyedOrderedDelta { ops }
+}
+
+/// The ops of a borrowing diff, with each removal built by `removed` and
+/// every other op converted by `other`.
+fn diff_ref_with<'a, T, K, F, O>(
+    old: &'a [T],
+    new: &'a [T],
+    key: F,
+    removed: impl Fn(usize, &T) -> O,
+    other: impl Fn(KeyedOp<T, T::Output>) -> O,
+) -> Vec<O>
+where
+    T: DeltaRef + Clone,
+    K: Hash + Eq + 'a,
+    F: Fn(&T) -> &K,
+{
+    let plan = {
+        let old_keys: Vec<&K> = old.iter().map(&key).collect();
+        let new_keys: Vec<&K> = new.iter().map(&key).collect();
+        plan(&old_keys, &new_keys)
+    };
+    let mut ops = Vec::new();
+    for (index, new_index) in plan.kept {
+        if let Some(delta) = T::delta_ref(&old[index], &new[new_index]) {
+            ops.push(other(KeyedOp::Change { at: index, delta }));
+        }
+    }
+    for at in plan.removed.into_iter().rev() {
+        ops.push(removed(at, &old[at]));
+    }
+    ops.extend(plan.placements.into_iter().map(|placement| {
+        other(match placement {
+            Placement::Insert { at, new_index } => KeyedOp::Insert {
+                at,
+                item: new[new_index].clone(),
+            },
+            Placement::Move { from, to } => KeyedOp::Move { from, to },
+        })
+    }));
+    ops
+}
+
+/// Applies a keyed-ordered delta to `target` in place, one op after
+/// another.
+///
+/// Each removal, insertion and move shifts the items after it, so this costs
+/// O(n) per op that is not a change. An op for a position `target` does not
+/// have is skipped, and an insertion or a move past the end lands at the end;
+/// use [`try_apply`] to have such a delta refused instead.
+///
+/// ```
+/// use delta_struct::keyed_ordered;
+///
+/// let old = vec![1, 2, 3, 4];
+/// let new = vec![4, 1, 3, 5];
+/// let delta = keyed_ordered::diff(old.clone(), new.clone(), |item: &i32| item);
+///
+/// let mut target = old;
+/// keyed_ordered::apply(&mut target, delta);
+/// assert_eq!(target, new);
+/// ```
+pub fn apply<T: Delta>(target: &mut Vec<T>, delta: KeyedOrderedDelta<T, T::Output>) {
+    for op in delta.ops {
+        match op {
+            KeyedOp::Change { at, delta } => {
+                if let Some(item) = target.get_mut(at) {
+                    item.apply_delta(delta);
+                }
+            }
+            KeyedOp::Remove { at } => {
+                if at < target.len() {
+                    target.remove(at);
+                }
+            }
+            KeyedOp::Insert { at, item } => target.insert(at.min(target.len()), item),
+            KeyedOp::Move { from, to } => {
+                if from < target.len() {
+                    let item = target.remove(from);
+                    target.insert(to.min(target.len()), item);
+                }
+            }
+        }
+    }
+}
+
+/// Checks that every op of `delta` is for a position the list has by the
+/// time it runs, reporting one that is not as [`Problem::OpOutOfRange`], and
+/// that each change fits its item.
+///
+/// A change is checked against the item as `target` holds it, or as an
+/// earlier op inserted it, rather than as earlier changes in the same delta
+/// left it.
+pub fn check<T: Delta>(
+    target: &[T],
+    delta: &KeyedOrderedDelta<T, T::Output>,
+) -> Result<(), ApplyError> {
+    check_with(
+        target,
+        delta.ops.iter().map(|op| match op {
+            KeyedOp::Change { at, delta } => KeyedOp::Change { at: *at, delta },
+            KeyedOp::Remove { at } => KeyedOp::Remove { at: *at },
+            KeyedOp::Insert { at, item } => KeyedOp::Insert { at: *at, item },
+            KeyedOp::Move { from, to } => KeyedOp::Move {
+                from: *from,
+                to: *to,
+            },
+        }),
+    )
+}
+
+/// Checks a [`ReversibleKeyedOrderedDelta`] as [`check`] does a
+/// [`KeyedOrderedDelta`].
+pub fn check_reversible<T: Delta>(
+    target: &[T],
+    delta: &ReversibleKeyedOrderedDelta<T, T::Output>,
+) -> Result<(), ApplyError> {
+    check_with(
+        target,
+        delta.ops.iter().map(|op| match op {
+            ReversibleKeyedOp::Change { at, delta } => KeyedOp::Change { at: *at, delta },
+            ReversibleKeyedOp::Remove { at, .. } => KeyedOp::Remove { at: *at },
+            ReversibleKeyedOp::Insert { at, item } => KeyedOp::Insert { at: *at, item },
+            ReversibleKeyedOp::Move { from, to } => KeyedOp::Move {
+                from: *from,
+                to: *to,
+            },
+        }),
+    )
+}
+
+/// The check of either delta type, over its ops as borrows.
+fn check_with<'a, T: Delta + 'a>(
+    target: &'a [T],
+    ops: impl Iterator<Item = KeyedOp<&'a T, &'a T::Output>>,
+) -> Result<(), ApplyError> {
+    let mut items: Vec<&T> = target.iter().collect();
+    for (index, op) in ops.enumerate() {
+        let len = items.len();
+        let out_of_range = || ApplyError::new(Problem::OpOutOfRange { index, len });
+        match op {
+            KeyedOp::Change { at, delta } => items
+                .get(at)
+                .ok_or_else(out_of_range)?
+                .check_delta(delta)
+                .map_err(|error| error.within(PathSegment::Op(index)))?,
+            KeyedOp::Remove { at } if at < len => {
+                items.remove(at);
+            }
+            KeyedOp::Insert { at, item } if at <= len => items.insert(at, item),
+            KeyedOp::Move { from, to } if from < len && to < len => {
+                let item = items.remove(from);
+                items.insert(to, item);
+            }
+            _ => return Err(out_of_range()),
+        }
+    }
+    Ok(())
+}
+
+/// Applies a keyed-ordered delta like [`apply`], but only if it passes
+/// [`check`]; otherwise `target` is left as it was.
+pub fn try_apply<T: Delta>(
+    target: &mut Vec<T>,
+    delta: KeyedOrderedDelta<T, T::Output>,
+) -> Result<(), ApplyError> {
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
+}
@@ -52,6 +52,45 @@
 //! it becomes `Option<T>`: `Some(new_value)` when the two differ, [`None`]
 //! when they don't. Requires `T: PartialEq`.
 //!
+//! `#[delta_struct(eq_with = "path::to::fn")]` swaps `!=` for a function of
+//! your own, `fn(&T, &T) -> bool`, answering whether the two values count as
+//! equal. That quiets a reading that jitters below any precision you care
+//! about, and lets a type with no `PartialEq`, or with one too strict for the
+//! purpose, still be a scalar field:
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! fn close(a: &f64, b: &f64) -> bool {
+//!     (a - b).abs() < 1e-6
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Sensor {
+//!     #[delta_struct(eq_with = "close")]
+//!     reading: f64,
+//! }
+//!
+//! let sensor = |reading| Sensor { reading };
+//! assert!(Delta::delta(sensor(20.0), sensor(20.000_000_001)).is_none());
+//! assert_eq!(Delta::delta(sensor(20.0), sensor(21.0)).unwrap().reading, Some(21.0));
+//! ```
+//!
+//! A change too small to send never reaches the other side, so the two can
+//! hold values that differ by as much as the function tolerates — which
+//! [`Versioned`] notices, since [`Fingerprint`] sees every bit of the value.
+//!
+//! ## `counter`
+//!
+//! For an integer or float that several senders bump, such as a view count:
+//! the delta is an `Option` of the signed difference rather than of the new
+//! value, and applying it adds that on. Bumps from two senders then both
+//! land, in either order, where two `scalar` deltas would overwrite each
+//! other. An increment that would overflow an integer, or take a float to
+//! infinity or NaN, is skipped, or refused by `try_apply_delta`, unless the
+//! field has `#[delta_struct(overflow = "wrapping")]`. Float counters are
+//! approximate, since float addition rounds. See the [`counter`] module.
+//!
 //! ## `unordered`
 //!
 //! The field is treated as a bag of elements whose order carries no meaning,
@@ -77,23 +116,33 @@
 //! assert_eq!(delta.services.remove, vec!["ssh".to_string()]);
 //! ```
 //!
-//! The field has to be a **set** — a [`HashSet`](std::collections::HashSet) or
-//! a [`BTreeSet`](std::collections::BTreeSet). Formally it needs [`Extend`]
-//! and [`TryIndex`], this crate's fallible answer to
-//! [`Index`](std::ops::Index); the two std sets implement it, and you can
-//! implement it for your own collection. A [`Vec`] deliberately does not
-//! qualify — see [Limitations](#limitations).
+//! The field has to be a **set** — a [`HashSet`](std::collections::HashSet), a
+//! [`BTreeSet`](std::collections::BTreeSet), or this crate's [`SortedVec`].
+//! Formally it needs [`Extend`] and [`TryIndex`], this crate's fallible
+//! answer to [`Index`](std::ops::Index); those three implement it, and you
+//! can implement it for your own collection. A plain [`Vec`] deliberately
+//! does not qualify — see [Limitations](#limitations).
 //!
 //! Every element of the old collection is looked up in the new one exactly
 //! once, so the cost of a diff is the cost of n lookups in whichever
-//! collection you picked: **O(n)** for a `HashSet`, O(n log n) for a
-//! `BTreeSet`. Applying one costs the same, since each removal is a lookup
-//! rather than a rebuild.
+//! collection you picked: **O(n)** for a `HashSet`. A `BTreeSet` already
+//! iterates in order, which it says through [`TryIndex::key_order`], so its
+//! two sides are merged in a single pass instead, in **O(n + m)**. Applying
+//! a delta costs n lookups either way, since each removal is a lookup rather
+//! than a rebuild.
 //!
 //! `apply_delta` preserves membership but not position — additions land
 //! wherever the collection decides to put them. Use `ordered` where that
 //! matters.
 //!
+//! ## `multiset`
+//!
+//! Like `unordered`, for a bag whose elements can repeat, kept as a count per
+//! element: a `HashMap<T, usize>` or `BTreeMap<T, usize>`, or your own
+//! [`Multiset`]. The delta is a [`MultisetDelta`], by how much each element's
+//! count went up or down, and applying it never takes a count below zero.
+//! See the [`multiset`] module.
+//!
 //! ## `unordered-delta`
 //!
 //! Like `unordered`, but for a collection of key/value entries whose values
@@ -133,8 +182,9 @@
 //! assert_eq!(delta.services.change[0].delta.healthy, None);
 //! ```
 //!
-//! The key is the collection's own — the `K` of a `HashMap<K, V>` — not
-//! something you nominate. The field has to be a **map**: a
+//! The key is the collection's own — the `K` of a `HashMap<K, V>`; see
+//! [`keyed`](#keyed) to nominate a field of the value instead. The field has
+//! to be a **map**: a
 //! [`HashMap`](std::collections::HashMap) or a
 //! [`BTreeMap`](std::collections::BTreeMap). Formally it needs [`Extend`] and
 //! [`TryIndexMut`], its entry type needs [`MapEntry`] (implemented for
@@ -146,10 +196,61 @@
 //! a set cannot allow without letting you invalidate the hash or ordering it
 //! filed the element under.
 //!
-//! Every key of the old collection is looked up in the new one exactly once,
-//! so as with `unordered` the cost is n lookups — **O(n)** for a `HashMap`,
-//! O(n log n) for a `BTreeMap`. Applying one preserves membership rather than
-//! position, also the same as `unordered`.
+//! The cost is the same as `unordered`'s: n lookups, **O(n)** for a
+//! `HashMap`, or a single merge pass, **O(n + m)** for a `BTreeMap`. Applying
+//! one preserves membership rather than position, also the same as
+//! `unordered`.
+//!
+//! ## `unordered-scalar`
+//!
+//! Like `unordered-delta`, for a map whose values are plain data with no
+//! [`Delta`] of their own, such as a `HashMap<String, String>` of labels.
+//! Values under a surviving key are compared with `PartialEq`, and one that
+//! differs travels whole, as the `delta` of its [`KeyedDelta`] in the same
+//! [`MapDelta`] layout. See the [`scalar_map`] module.
+//!
+//! ## `keyed`
+//!
+//! `unordered-delta` for a [`Vec`] of records that carry their own key in a
+//! field — the shape a JSON array of objects arrives in. `key` names the field
+//! records are paired by and `key_type` gives its type, which the derive has
+//! no way to look up on its own. The delta is the same [`MapDelta`] an
+//! `unordered-delta` field produces.
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Delta)]
+//! struct Device {
+//!     id: u32,
+//!     online: bool,
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Fleet {
+//!     #[delta_struct(field_type = "keyed", key = "id", key_type = "u32")]
+//!     devices: Vec<Device>,
+//! }
+//!
+//! let fleet = |online| Fleet {
+//!     devices: vec![Device { id: 7, online: true }, Device { id: 9, online }],
+//! };
+//!
+//! let delta = Delta::delta(fleet(true), fleet(false)).unwrap();
+//! assert!(delta.devices.add.is_empty() && delta.devices.remove.is_empty());
+//! assert_eq!(delta.devices.change[0].key, 9);
+//! assert_eq!(delta.devices.change[0].delta.online, Some(false));
+//! ```
+//!
+//! Records are paired through a [`HashMap`](std::collections::HashMap) built
+//! over the old side, so a diff is **O(n)** with no quadratic scan, and the key
+//! type needs `Hash + Eq + Clone`. The record type needs [`Delta`]. Keys are
+//! expected to be unique; a duplicated key is paired once, its last old record
+//! with its first new one, and its other records are removed or added.
+//!
+//! Applying one updates changed records where they sit, drops removed ones,
+//! and appends additions at the end — membership is preserved, not position,
+//! just as with `unordered-delta`.
 //!
 //! ## `ordered`
 //!
@@ -181,12 +282,75 @@
 //! real change here where `unordered` would see none, and applying a delta
 //! reproduces the new sequence exactly, position included.
 //!
-//! The collection needs `IntoIterator` and `FromIterator`, and its items need
-//! `Hash + Eq`, because that is what indexing the sequences for Myers
-//! requires. This is the one field type that takes a [`Vec`], and so the only
+//! The collection needs `IntoIterator` and `FromIterator`, and its items
+//! need `Hash + Eq`, because that is what indexing the sequences for Myers
+//! requires. Applying a delta collects the collection again;
+//! `#[delta_struct(in_place)]` applies it through [`seq::SpliceTarget`]
+//! instead, which `Vec`, `VecDeque` and `LinkedList` implement by splicing
+//! themselves from the first splice on, leaving everything ahead of it
+//! where it is. This is the one field type that takes a [`Vec`], and so the only
 //! one that will diff a sequence at all — but `f64` is neither `Hash` nor
 //! `Eq`, so a `Vec<f64>` still has nowhere to go but `scalar`.
 //!
+//! `#[delta_struct(algorithm = "patience")]` or `"lcs"` swaps Myers for
+//! another [`seq::Algorithm`], and `#[delta_struct(deadline = "50ms")]` caps
+//! how long a diff may run. A diff that runs out of time returns a coarser
+//! script, one big splice at worst, that is still correct:
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Delta)]
+//! struct Feed {
+//!     #[delta_struct(field_type = "ordered", algorithm = "patience", deadline = "20ms")]
+//!     posts: Vec<u64>,
+//! }
+//!
+//! let old = Feed { posts: (0..10_000).collect() };
+//! let new = Feed { posts: (0..10_000).rev().collect() };
+//! let mut applied = Feed { posts: (0..10_000).collect() };
+//! applied.apply_delta(Delta::delta(old, new).unwrap());
+//! assert!(applied.posts.iter().rev().copied().eq(0..10_000));
+//! ```
+//!
+//! ## `ordered-delta`
+//!
+//! Like `ordered`, for a sequence of items that implement [`Delta`]
+//! themselves: an item that is on both sides travels as its own delta rather
+//! than being replaced, and splices are left for the items that really came
+//! or went. Items line up by `Hash + Eq`, pairing off a replaced run by
+//! position, or with `key` and `key_type` as for `keyed`, by a field of each
+//! item. The delta is an [`OrderedDelta`]; see the [`ordered_delta`] module.
+//!
+//! ## `keyed-ordered`
+//!
+//! For a [`Vec`] of records paired by a key field, as for `keyed`, where
+//! their order matters too, such as a playlist the user reorders by drag and
+//! drop. The delta is a [`KeyedOrderedDelta`], a list of [`KeyedOp`]s:
+//! changes to the records that stayed, removals and insertions of the ones
+//! that came and went, and the fewest moves that put the rest in their new
+//! order, found by a longest-increasing-subsequence pass. See the
+//! [`keyed_ordered`] module.
+//!
+//! ## `indexed`
+//!
+//! For an array, or a `Vec` whose length rarely changes, whose items are
+//! paired by position rather than aligned, such as the channels of a mixer:
+//! each pair is diffed with the item's own [`Delta`], so a number or a
+//! `String` is replaced and a struct sends its own delta. The delta is a
+//! [`PositionalDelta`], a change per position that differs and, when a `Vec`
+//! grows or shrinks, a splice that extends or truncates its end. See the
+//! [`indexed`] module.
+//!
+//! ## `text`
+//!
+//! For a `String` that is edited rather than replaced, such as a document
+//! body: the same Myers diff as `ordered`, over the text's characters, or its
+//! lines with `#[delta_struct(granularity = "lines")]`. The delta is a
+//! [`TextDelta`] of splices at byte offsets, which always fall between two
+//! characters. With the `unified-diff` feature, a delta renders as a unified
+//! diff for logs. See the [`text`] module.
+//!
 //! ## `delta`
 //!
 //! The field is itself diffed recursively, which keeps a nested change from
@@ -218,6 +382,92 @@
 //! assert_eq!(inner_delta.b, Some(3));
 //! ```
 //!
+//! The standard library types implement [`Delta`] too, so they can be `delta`
+//! fields as they are. Primitives and `String` are replaced whole, as a
+//! `scalar` would be. Tuples and arrays are diffed element by element, into a
+//! tuple or array with an `Option` per element. `Box`, `Rc` and `Arc` diff
+//! what they point at. An `Rc` or `Arc` that both sides share is skipped
+//! without comparing the value, and applying calls `make_mut`, which clones the
+//! value only if something else still holds it.
+//!
+//! ```
+//! use delta_struct::Delta;
+//! use std::sync::Arc;
+//!
+//! #[derive(Clone, Delta)]
+//! struct Scene {
+//!     #[delta_struct(field_type = "delta")]
+//!     origin: (i32, i32),
+//!     #[delta_struct(field_type = "delta")]
+//!     palette: Arc<[u8; 3]>,
+//! }
+//!
+//! let old = Scene { origin: (0, 0), palette: Arc::new([255, 0, 0]) };
+//! let new = Scene { origin: (0, 5), ..old.clone() };
+//!
+//! let delta = Delta::delta(old, new).unwrap();
+//! assert_eq!(delta.origin, Some((None, Some(5))));
+//! assert_eq!(delta.palette, None);
+//! ```
+//!
+//! ## `optional-delta`
+//!
+//! For an `Option<T>` whose `T` implements [`Delta`]: while the value stays
+//! `Some`, it is diffed like a `delta` field rather than resent whole. The
+//! delta struct holds `Option<OptionDelta<T, T::Output>>`, an [`OptionDelta`]
+//! telling apart a value that appeared (`Set`), one that went away (`Clear`),
+//! and one changed in place (`Change`). See the [`option`] module.
+//!
+//! ## Your own: `with`
+//!
+//! `#[delta_struct(with = "path::to::Type")]` hands the field to a type of
+//! your own implementing [`Strategy`] for it, in place of a field type: the
+//! delta field is the strategy's `Delta`, and diffing, applying, and checking
+//! all go through the strategy. See the [`strategy`] module.
+//!
+//! # Enums
+//!
+//! Deriving [`Delta`] on an enum generates a delta *enum*. When the old and
+//! the new value are the same variant, their fields are diffed one by one,
+//! each according to its field type, and the delta is that variant's
+//! counterpart holding the field deltas. When the variant changed there is
+//! nothing to diff against, and the whole new value travels in `Replace`.
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Debug, Delta, PartialEq)]
+//! #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+//! enum Connection {
+//!     Offline,
+//!     Online { address: String, latency_ms: u32 },
+//! }
+//!
+//! let online = |latency_ms| Connection::Online { address: "10.0.0.1".to_string(), latency_ms };
+//!
+//! // Same variant: only the field that moved.
+//! assert_eq!(
+//!     Delta::delta(online(20), online(35)),
+//!     Some(ConnectionDelta::Online { address: None, latency_ms: Some(35) }),
+//! );
+//! // Different variant: the new value, whole.
+//! assert_eq!(
+//!     Delta::delta(online(20), Connection::Offline),
+//!     Some(ConnectionDelta::Replace(Connection::Offline)),
+//! );
+//! ```
+//!
+//! Since `Replace` holds a whole `Connection`, deriving anything on the delta
+//! enum needs the same derive on the enum itself.
+//!
+//! Field attributes go on the fields of each variant, and the container's
+//! `default` applies to all of them. A unit variant has no counterpart, since
+//! two equal ones produce no delta at all. Applying a field delta to a value
+//! holding some other variant does nothing.
+//!
+//! An enum deriving [`Delta`] is a natural `delta` field: a payload that
+//! changed in place is sent as its field deltas rather than resent whole.
+//!
 //! # Container attributes
 //!
 //! `#[delta_struct(...)]` on the struct itself accepts:
@@ -227,6 +477,10 @@
 //! - `delta_leader = "..."` — tokens to emit immediately above the generated
 //!   struct. This is how you attach derives, doc comments, or any other
 //!   attribute to a type you never get to write by hand.
+//! - `bound = "..."` — where predicates for the generated impl, in place of
+//!   the ones inferred from the fields. See [Trait bounds](#trait-bounds).
+//! - `reversible` — makes the generated delta record what each change
+//!   replaced, so it can be [inverted](Invert). See [Undo](#undo).
 //!
 //! ```
 //! use delta_struct::Delta;
@@ -266,6 +520,238 @@
 //! }
 //! ```
 //!
+//! # Sending a collection whole
+//!
+//! A collection delta can end up bigger than the collection it produces: a
+//! reversed `ordered` field, or an `unordered` one that swapped most of its
+//! elements. `#[delta_struct(replace_if_cheaper)]` on either makes the
+//! field's delta an [`OrReplace`], holding the usual delta or, when that
+//! touches more elements than the new collection has, the new collection
+//! whole. `#[delta_struct(replace_cost = "path::to::fn")]` weighs each
+//! element with a `fn(&Item) -> usize` instead of counting it.
+//!
+//! ```
+//! use delta_struct::{Delta, OrReplace};
+//! use std::collections::BTreeSet;
+//!
+//! #[derive(Delta)]
+//! struct Tags {
+//!     #[delta_struct(field_type = "unordered", replace_cost = "String::len")]
+//!     tags: BTreeSet<String>,
+//! }
+//!
+//! let tags = |tags: &[&str]| Tags {
+//!     tags: tags.iter().map(|tag| tag.to_string()).collect(),
+//! };
+//!
+//! // Dropping one long tag for another costs more than sending "a" and the
+//! // new one.
+//! let delta = Delta::delta(tags(&["a", "ancient"]), tags(&["a", "current"])).unwrap();
+//! assert!(matches!(delta.tags, OrReplace::Replace(_)));
+//!
+//! let mut applied = tags(&["a", "ancient"]);
+//! applied.apply_delta(delta);
+//! assert_eq!(applied.tags, tags(&["a", "current"]).tags);
+//! ```
+//!
+//! Applying, checking, inverting and composing all handle either form; see
+//! the [`replace`] module.
+//!
+//! # Skipping fields
+//!
+//! `#[delta_struct(skip)]` on a field leaves it out of the generated struct
+//! altogether. It is never compared, so its type needs no `PartialEq`; it is
+//! never sent; and `apply_delta` leaves it exactly as it was. This is where
+//! caches, [`PhantomData`](std::marker::PhantomData), locks, and other
+//! local-only bookkeeping belong.
+//!
+//! ```
+//! use delta_struct::Delta;
+//! use std::sync::Mutex;
+//!
+//! #[derive(Delta)]
+//! struct Sensor {
+//!     reading: u32,
+//!     #[delta_struct(skip)]
+//!     last_polled: Mutex<Option<std::time::Instant>>,
+//! }
+//!
+//! let sensor = |reading| Sensor { reading, last_polled: Mutex::new(None) };
+//!
+//! // `SensorDelta` has a `reading` and nothing else.
+//! let delta = Delta::delta(sensor(1), sensor(2)).unwrap();
+//! assert_eq!(delta.reading, Some(2));
+//! ```
+//!
+//! The [`Fingerprint`] derive reads the same attribute and leaves the field
+//! out of the fingerprint, so state that never syncs cannot make a
+//! [`Versioned`] receiver report a [`Mismatch::Base`]. A skipped field of a
+//! tuple struct leaves no slot behind, so the delta's later fields move up one
+//! position.
+//!
+//! An enum's skipped fields need a [`Default`] as well. A change of variant
+//! sends the new value whole, with its skipped fields reset to their defaults,
+//! and the receiver starts the new variant from those: neither side's local
+//! state reaches the other.
+//!
+//! # Trait bounds
+//!
+//! The generated impl asks of each type parameter only what the fields using
+//! it need: a `scalar` field of type `T` adds `T: PartialEq`, a `delta` field
+//! adds `T: Delta`, an `ordered` `Vec<T>` adds `T: Hash + Eq`, and so on down
+//! the "Requires" column of the [derive's field type table](derive@Delta). A
+//! field whose type names no parameter adds nothing, and neither does a
+//! skipped one.
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Delta)]
+//! struct Wrapper<T: Delta> {
+//!     #[delta_struct(field_type = "delta")]
+//!     inner: T,
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Counter(u32);
+//!
+//! // `Counter` is not `PartialEq`, and `Wrapper<Counter>` does not need it to be.
+//! let delta = Delta::delta(Wrapper { inner: Counter(1) }, Wrapper { inner: Counter(2) });
+//! assert_eq!(delta.unwrap().inner.unwrap().0, Some(2));
+//! ```
+//!
+//! Those predicates are spelled on the field's whole type, `Vec<T>: PartialEq`
+//! rather than `T: PartialEq`, which is always right but not always what you
+//! want to read in documentation or error messages. `bound = "..."` spells the
+//! predicates out instead. On a field it replaces what that field would have
+//! inferred; on the container it replaces everything, and `bound = ""` leaves
+//! the impl with no predicates beyond the type's own.
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Delta)]
+//! struct History<T> {
+//!     #[delta_struct(bound = "T: PartialEq")]
+//!     values: Vec<T>,
+//! }
+//!
+//! let delta = Delta::delta(History { values: vec![1] }, History { values: vec![1, 2] });
+//! assert_eq!(delta.unwrap().values, Some(vec![1, 2]));
+//! ```
+//!
+//! # Borrowing diffs
+//!
+//! [`Delta::delta`] consumes both values, so a caller who needs to keep the
+//! new one — to diff against next time, say — has to clone all of it first.
+//! Deriving [`DeltaRef`] as well adds `delta_ref(&old, &new)`, which produces
+//! the same delta from two borrows and clones only what ends up in it: a
+//! changed `scalar`, the added and removed elements of a collection, the
+//! inserted run of an `ordered` splice.
+//!
+//! ```
+//! use delta_struct::{Delta, DeltaRef};
+//! use std::collections::BTreeSet;
+//!
+//! #[derive(Delta, DeltaRef)]
+//! struct Document {
+//!     title: String,
+//!     #[delta_struct(field_type = "unordered")]
+//!     tags: BTreeSet<String>,
+//!     #[delta_struct(field_type = "ordered")]
+//!     lines: Vec<String>,
+//! }
+//!
+//! let document = |lines: &[&str]| Document {
+//!     title: "Notes".to_string(),
+//!     tags: BTreeSet::new(),
+//!     lines: lines.iter().map(|line| line.to_string()).collect(),
+//! };
+//!
+//! let (old, new) = (document(&["one", "two"]), document(&["one", "two", "three"]));
+//! let delta = DeltaRef::delta_ref(&old, &new).unwrap();
+//! assert_eq!(delta.title, None);
+//! assert_eq!(delta.lines.splices[0].insert, vec!["three".to_string()]);
+//! // Both are still here to use.
+//! assert_eq!(old.lines.len() + 1, new.lines.len());
+//! ```
+//!
+//! It reads the same attributes and yields the same delta type, so the two
+//! derives always sit together. Borrowing asks a little more of each field —
+//! `Clone` on whatever may be copied into the delta, and collections that
+//! iterate by reference, as every std collection does; the [derive's
+//! documentation](derive@DeltaRef) has the table. An enum also needs `Clone`
+//! itself, since a change of variant sends the new value whole.
+//!
+//! [`Versioned::commit_ref`] diffs through [`DeltaRef`], so a type that
+//! derives it can be versioned without being `Clone`.
+//!
+//! # Undo
+//!
+//! A delta records only what applying it needs, which is the new side of each
+//! change. `#[delta_struct(reversible)]` on the container makes it record the
+//! old side too — a `scalar` field becomes `Option<`[`Change<T>`]`>`, holding
+//! both values, and a map or sequence delta keeps what it removes — and
+//! implements [`Invert`] for the generated type. Inverting the delta from
+//! `old` to `new` gives the delta from `new` to `old`, so an undo stack can
+//! hold deltas rather than snapshots.
+//!
+//! ```
+//! use delta_struct::{Delta, Invert};
+//!
+//! #[derive(Debug, Delta, PartialEq)]
+//! #[delta_struct(reversible, delta_leader = "#[derive(Clone)]")]
+//! struct Cursor {
+//!     line: u32,
+//!     column: u32,
+//! }
+//!
+//! let cursor = |line| Cursor { line, column: 1 };
+//!
+//! let moved = Delta::delta(cursor(1), cursor(4)).unwrap();
+//! let mut current = cursor(1);
+//! current.apply_delta(moved.clone());
+//! assert_eq!(current, cursor(4));
+//!
+//! current.apply_delta(moved.invert());
+//! assert_eq!(current, cursor(1));
+//! ```
+//!
+//! A `delta` field of a reversible type has to be reversible itself. The
+//! [`invert`] module has the full mapping from field types to their
+//! reversible deltas.
+//!
+//! # Composing deltas
+//!
+//! Every generated delta type implements [`Compose`], as long as its fields'
+//! deltas do: `Compose::compose(first, second)` is a single delta with the
+//! effect of applying `first` and then `second`. A receiver that fell several
+//! versions behind can be sent one delta instead of the whole backlog.
+//!
+//! ```
+//! use delta_struct::{Compose, Delta};
+//!
+//! #[derive(Debug, Delta, PartialEq)]
+//! struct Cursor {
+//!     line: u32,
+//!     column: u32,
+//! }
+//!
+//! let first = Delta::delta(Cursor { line: 1, column: 1 }, Cursor { line: 2, column: 1 });
+//! let second = Delta::delta(Cursor { line: 2, column: 1 }, Cursor { line: 2, column: 7 });
+//! let both = Compose::compose(first.unwrap(), second.unwrap());
+//!
+//! let mut current = Cursor { line: 1, column: 1 };
+//! current.apply_delta(both);
+//! assert_eq!(current, Cursor { line: 2, column: 7 });
+//! ```
+//!
+//! Composing cancels what the second delta undoes of the first, so an element
+//! added and then removed is not mentioned at all. The collection deltas match
+//! elements and keys up with `Hash + Eq`; a type with an `unordered` field of
+//! elements that are only `Ord` still derives [`Delta`], just without
+//! [`Compose`]. The [`compose`] module has the details.
+//!
 //! # Working with serde
 //!
 //! For `scalar` and `delta` fields there is no serde integration to enable;
@@ -391,12 +877,84 @@
 //! So sets and maps fold commutatively, iteration order cannot reach the
 //! result, and the hash is pinned to FNV-1a constants written down in the
 //! source. The same value fingerprints identically on any platform and any
-//! Rust version. Unlike [`Delta`], it derives on enums too.
+//! Rust version. Like [`Delta`], it derives on enums too.
 //!
 //! Checking costs a full traversal of the state on each `commit` and each
 //! `apply` — cheaper than serializing it, but not free, which is the price of
 //! the `base` and `result` guarantees.
 //!
+//! # Refusing a delta that does not fit
+//!
+//! [`Versioned`] notices a delta meant for another state after the fact.
+//! [`Delta::try_apply_delta`] looks at the delta itself, before anything is
+//! applied: every element or key it removes has to be present, every key it
+//! changes too, splices have to be sorted and within the sequence, and a
+//! variant's delta has to meet a value of that variant. If any part does not
+//! fit, nothing is applied and the [`ApplyError`] says where:
+//!
+//! ```
+//! use delta_struct::{Delta, PathSegment, Problem};
+//!
+//! #[derive(Debug, Delta, PartialEq)]
+//! struct Playlist {
+//!     #[delta_struct(field_type = "ordered")]
+//!     tracks: Vec<u32>,
+//! }
+//!
+//! let delta = Delta::delta(
+//!     Playlist { tracks: vec![1, 2, 3] },
+//!     Playlist { tracks: vec![1, 2] },
+//! )
+//! .unwrap();
+//!
+//! let mut other = Playlist { tracks: vec![7] };
+//! let error = other.try_apply_delta(delta).unwrap_err();
+//! assert_eq!(error.path, vec![PathSegment::Field("tracks")]);
+//! assert_eq!(error.problem, Problem::SpliceOutOfRange { index: 0, len: 1 });
+//! assert_eq!(other, Playlist { tracks: vec![7] });
+//! ```
+//!
+//! `apply_delta` stays as lenient as ever. The [`check`] module has the
+//! details, and each collection module has a matching `try_apply`.
+//!
+//! # Which fields changed
+//!
+//! A delta struct's `changed_fields()` says which of its fields carry a
+//! change, as a bitmask over the generated `{Type}Field` enum — enough to
+//! skip work a change cannot affect without matching on every field's delta.
+//! [`ChangedPaths`] goes further, following `delta` fields down to the fields
+//! that changed inside them:
+//!
+//! ```
+//! use delta_struct::{ChangedPaths, Delta, PathSegment};
+//!
+//! #[derive(Delta)]
+//! struct Window {
+//!     title: String,
+//!     width: u32,
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Screen {
+//!     #[delta_struct(field_type = "delta")]
+//!     window: Window,
+//!     brightness: u8,
+//! }
+//!
+//! let old = Screen { window: Window { title: "a".into(), width: 80 }, brightness: 5 };
+//! let new = Screen { window: Window { title: "a".into(), width: 120 }, brightness: 7 };
+//!
+//! let delta = Delta::delta(old, new).unwrap();
+//! let changed = delta.changed_fields();
+//! assert!(changed.contains(ScreenField::Window) && changed.contains(ScreenField::Brightness));
+//!
+//! let paths = delta.changed_paths();
+//! assert_eq!(paths[0], [PathSegment::Field("window"), PathSegment::Field("width")]);
+//! assert_eq!(paths[1], [PathSegment::Field("brightness")]);
+//! ```
+//!
+//! See the [`fields`] module for the details.
+//!
 //! # What gets generated
 //!
 //! For `struct Foo`, deriving [`Delta`] emits `struct FooDelta` with the same
@@ -409,7 +967,7 @@
 //!
 //! Every field type maps one source field onto exactly one delta field, so a
 //! delta struct always has the same fields in the same order as the struct it
-//! came from — only their types differ. A tuple struct's delta is a tuple
+//! came from, less any marked `skip` — only their types differ. A tuple struct's delta is a tuple
 //! struct in turn, so its fields keep their positions:
 //!
 //! ```
@@ -422,12 +980,24 @@
 //! assert_eq!(delta.0, Some(4));
 //! ```
 //!
+//! For `enum Foo`, it emits `enum FooDelta`, again with the same visibility
+//! and generic parameters: a `Replace(Foo)` variant, plus one variant of the
+//! same name and shape for each variant of `Foo` that has fields, each field
+//! swapped for its delta. See [Enums](#enums).
+//!
+//! Either way the delta type implements [`Compose`] and [`ChangedPaths`], and
+//! [`Invert`] too when the container is `reversible`. A struct also gets a
+//! `FooField` enum with a variant per delta field, which `FooDelta`'s
+//! `changed_fields()` returns a [`FieldSet`] of. See [Which fields
+//! changed](#which-fields-changed).
+//!
 //! # Limitations
 //!
-//! - **Structs only.** Enums and unions are rejected; there is no obvious
-//!   delta for a value that changed variant.
-//! - **Every type parameter gets a `PartialEq` bound** on the generated impl,
-//!   whether or not the field that uses it needs one.
+//! - **Unions are rejected.** Structs and enums both derive, but an enum
+//!   cannot have a variant named `Replace`, which its delta enum already uses.
+//! - **Bounds are inferred from field types by name.** A field whose type
+//!   mentions a type parameter gets the bounds its field type requires, spelled
+//!   on the whole field type; anything subtler needs `bound = "..."`.
 //! - **A unit struct's delta is always [`None`]**, as is that of a struct with
 //!   no fields — there is nothing that could differ.
 //! - **`ordered` items need `Hash + Eq`**, so float sequences are out. See
@@ -435,12 +1005,13 @@
 //! - **A [`Vec`] cannot be an `unordered` field.** Membership diffing goes
 //!   through [`TryIndex`], and a `Vec` has no sub-linear lookup to offer —
 //!   implementing it would only hide a quadratic scan behind an O(1)-looking
-//!   call. Use a [`HashSet`](std::collections::HashSet) or a
+//!   call. Use a [`SortedVec`], which keeps a `Vec` sorted to search it, a
+//!   [`HashSet`](std::collections::HashSet) or a
 //!   [`BTreeSet`](std::collections::BTreeSet), or `ordered` if position
 //!   matters.
-//! - **`unordered-delta` keys are the collection's own.** There is no way to
-//!   nominate a field of the value as the key, so a `Vec<Record>` has to
-//!   become a `HashMap<Id, Record>` to use it.
+//! - **`unordered-delta` keys are the collection's own.** To pair the records
+//!   of a `Vec<Record>` by one of their fields, use `keyed` instead — which in
+//!   turn needs the key's type spelled out in `key_type`.
 //! - **[`Versioned`] assumes one writer per stream.** Two senders committing
 //!   against the same base both produce `from: 0`, and the second is rejected
 //!   rather than merged. Divergence is detected, not reconciled — reach for a
@@ -454,18 +1025,50 @@
 extern crate self as delta_struct;
 
 pub mod bag;
+pub mod check;
+pub mod compose;
+pub mod counter;
+pub mod fields;
 pub mod fingerprint;
+mod impls;
 pub mod index;
+pub mod indexed;
+pub mod invert;
+pub mod keyed;
+pub mod keyed_ordered;
 pub mod map;
+pub mod multiset;
+pub mod option;
+pub mod ordered_delta;
+pub mod replace;
+pub mod scalar_map;
 pub mod seq;
+pub mod sorted_vec;
+pub mod strategy;
+pub mod text;
 pub mod version;
 
 pub use bag::BagDelta;
-pub use delta_struct_macros::{Delta, Fingerprint};
+pub use check::{ApplyError, PathSegment, Problem};
+pub use compose::Compose;
+pub use delta_struct_macros::{Delta, DeltaRef, Fingerprint};
+pub use fields::{ChangedPaths, Field, FieldSet};
 pub use fingerprint::{fingerprint_of, Fingerprint};
-pub use index::{TryIndex, TryIndexMut};
-pub use map::{KeyedDelta, MapDelta, MapEntry};
-pub use seq::{SeqDelta, Splice};
+pub use index::{Multiset, TryIndex, TryIndexMut};
+pub use indexed::{PositionalDelta, ReversiblePositionalDelta};
+pub use invert::{Change, Invert};
+pub use keyed_ordered::{
+    KeyedOp, KeyedOrderedDelta, ReversibleKeyedOp, ReversibleKeyedOrderedDelta,
+};
+pub use map::{KeyedDelta, MapDelta, MapEntry, ReversibleMapDelta};
+pub use multiset::MultisetDelta;
+pub use option::{OptionDelta, ReversibleOptionDelta};
+pub use ordered_delta::{OrderedDelta, ReversibleOrderedDelta};
+pub use replace::OrReplace;
+pub use seq::{IndexedDelta, ReversibleSeqDelta, ReversibleSplice, SeqDelta, Splice};
+pub use sorted_vec::{SortedVec, SortedVecMap};
+pub use strategy::Strategy;
+pub use text::{ReversibleTextDelta, TextDelta};
 pub use version::{Applied, Mismatch, Versioned, VersionedDelta};
 
 /// Computing the difference between two values, and applying it to a third.
@@ -478,7 +1081,7 @@ pub use version::{Applied, Mismatch, Versioned, VersionedDelta};
 pub trait Delta {
     /// The type describing a difference between two `Self` values.
     ///
-    /// The derive sets this to the generated `{Self}Delta` struct.
+    /// The derive sets this to the generated `{Self}Delta` struct or enum.
     type Output;
 
     /// Computes what it would take to turn `old` into `new`.
@@ -493,12 +1096,53 @@ pub trait Delta {
     /// Applying the delta from `delta(old, new)` to a value equal to `old`
     /// yields a value equal to `new` — with the caveat that `unordered` fields
     /// preserve membership rather than order.
+    ///
+    /// A delta that does not fit is applied as far as it goes: removals of
+    /// things that are not there are skipped, and splices are clamped. Use
+    /// [`try_apply_delta`](Delta::try_apply_delta) to have it refused instead.
     fn apply_delta(&mut self, delta: Self::Output);
+
+    /// Checks that `delta` fits this value — that everything it removes or
+    /// changes is there, and that its splices are in range and in order —
+    /// without applying it.
+    ///
+    /// The derive checks every field. The provided implementation accepts
+    /// anything, which is right for a delta that is a whole replacement value.
+    fn check_delta(&self, delta: &Self::Output) -> Result<(), ApplyError> {
+        let _ = delta;
+        Ok(())
+    }
+
+    /// Applies a delta in place if it fits, and otherwise leaves the value
+    /// untouched and says what did not fit. See the [`check`] module.
+    fn try_apply_delta(&mut self, delta: Self::Output) -> Result<(), ApplyError> {
+        self.check_delta(&delta)?;
+        self.apply_delta(delta);
+        Ok(())
+    }
+}
+
+/// Computing a delta from borrowed values, cloning only the parts that
+/// changed.
+///
+/// [`Delta::delta`] consumes both sides, which costs a clone of the whole
+/// value whenever the caller still needs one of them afterwards. `delta_ref`
+/// produces the same delta from two borrows instead. Derive it alongside
+/// [`Delta`] with `#[derive(Delta, DeltaRef)]`; it reads the same
+/// `#[delta_struct(...)]` attributes. See [Borrowing
+/// diffs](crate#borrowing-diffs).
+pub trait DeltaRef: Delta {
+    /// Computes what it would take to turn `old` into `new`, exactly as
+    /// [`Delta::delta`] would, cloning whatever the delta carries out of `new`.
+    fn delta_ref(old: &Self, new: &Self) -> Option<Self::Output>;
 }
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use text::TextSplice;
 
     #[derive(Delta)]
     #[allow(dead_code)] // The derive is itself the test
@@ -1096,7 +1740,7 @@ mod tests {
         );
     }
 
-    #[derive(Clone, Debug, Delta, Fingerprint, PartialEq)]
+    #[derive(Clone, Debug, Delta, DeltaRef, Fingerprint, PartialEq)]
     #[delta_struct(delta_leader = "#[derive(Clone, Debug)]")]
     struct Tracked {
         name: String,
@@ -1273,7 +1917,7 @@ mod tests {
     #[cfg(feature = "serde")]
     #[test]
     fn versioned_delta_serializes() {
-        #[derive(Clone, Delta, Fingerprint)]
+        #[derive(Delta, DeltaRef, Fingerprint)]
         #[delta_struct(delta_leader = "#[derive(serde::Serialize, serde::Deserialize)]")]
         struct Config {
             port: u16,
@@ -1283,7 +1927,7 @@ mod tests {
         let mut receiver = Versioned::new(Config { port: 80 });
 
         let payload =
-            serde_json::to_string(&sender.commit(Config { port: 8080 }).unwrap()).unwrap();
+            serde_json::to_string(&sender.commit_ref(Config { port: 8080 }).unwrap()).unwrap();
         let message: VersionedDelta<ConfigDelta> = serde_json::from_str(&payload).unwrap();
         assert_eq!(receiver.apply(message), Ok(Applied::Updated));
         assert_eq!(receiver.get().port, 8080);
@@ -1345,4 +1989,2824 @@ mod tests {
         old_delta_applied.apply_delta(delta.unwrap());
         assert_eq!(new_clone, old_delta_applied);
     }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    enum Job {
+        Queued,
+        Running {
+            progress: u8,
+            #[delta_struct(field_type = "unordered")]
+            workers: BTreeSet<u8>,
+        },
+        Failed(String),
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Worker {
+        #[delta_struct(field_type = "delta")]
+        job: Job,
+        name: String,
+    }
+
+    fn running(progress: u8, workers: &[u8]) -> Job {
+        Job::Running {
+            progress,
+            workers: workers.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn enum_same_variant_diffs_fields() {
+        let delta = Delta::delta(running(10, &[1, 2]), running(20, &[2, 3])).unwrap();
+        match delta {
+            JobDelta::Running { progress, workers } => {
+                assert_eq!(progress, Some(20));
+                assert_eq!(workers.add, vec![3]);
+                assert_eq!(workers.remove, vec![1]);
+            }
+            other => panic!("expected a field delta, got {:?}", other),
+        }
+        assert_eq!(
+            Delta::delta(Job::Failed("a".to_string()), Job::Failed("b".to_string())),
+            Some(JobDelta::Failed(Some("b".to_string())))
+        );
+    }
+
+    #[test]
+    fn enum_variant_change_replaces() {
+        assert_eq!(
+            Delta::delta(Job::Queued, running(0, &[])),
+            Some(JobDelta::Replace(running(0, &[])))
+        );
+        assert_eq!(
+            Delta::delta(running(5, &[1]), Job::Queued),
+            Some(JobDelta::Replace(Job::Queued))
+        );
+    }
+
+    #[test]
+    fn enum_false_positive_check() {
+        assert!(Delta::delta(Job::Queued, Job::Queued).is_none());
+        assert!(Delta::delta(running(5, &[1]), running(5, &[1])).is_none());
+    }
+
+    #[test]
+    fn enum_apply_round_trips() {
+        let cases = vec![
+            (Job::Queued, running(0, &[1])),
+            (running(0, &[1]), running(50, &[1, 2])),
+            (running(50, &[1, 2]), Job::Failed("oom".to_string())),
+            (
+                Job::Failed("oom".to_string()),
+                Job::Failed("disk".to_string()),
+            ),
+            (Job::Failed("oom".to_string()), Job::Queued),
+        ];
+        for (old, new) in cases {
+            let mut applied = old.clone();
+            applied.apply_delta(Delta::delta(old.clone(), new.clone()).unwrap());
+            assert_eq!(applied, new, "{:?} -> {:?}", old, new);
+        }
+    }
+
+    #[test]
+    fn enum_field_delta_sends_only_the_payload_change() {
+        let worker = |job| Worker {
+            job,
+            name: "w".to_string(),
+        };
+        let delta = Delta::delta(worker(running(10, &[1])), worker(running(20, &[1]))).unwrap();
+        assert_eq!(
+            delta.job,
+            Some(JobDelta::Running {
+                progress: Some(20),
+                workers: BagDelta::default(),
+            })
+        );
+        assert_eq!(delta.name, None);
+
+        let mut applied = worker(running(10, &[1]));
+        applied.apply_delta(delta);
+        assert_eq!(applied, worker(running(20, &[1])));
+    }
+
+    #[test]
+    fn enum_delta_for_another_variant_is_ignored() {
+        let delta = Delta::delta(running(10, &[1]), running(20, &[1])).unwrap();
+        let mut target = Job::Queued;
+        target.apply_delta(delta);
+        assert_eq!(target, Job::Queued);
+    }
+
+    #[derive(Delta)]
+    #[allow(dead_code)] // The derive is itself the test
+    enum SingleVariant<T> {
+        Only(T),
+    }
+
+    #[derive(Delta)]
+    #[allow(dead_code)] // The derive is itself the test
+    enum NoVariants {}
+
+    /// Deliberately neither `PartialEq` nor `Fingerprint`, so only a skipped
+    /// field can hold one.
+    #[derive(Clone, Debug, Default)]
+    struct Cache(Vec<u8>);
+
+    #[derive(Clone, Debug, Delta, DeltaRef, Fingerprint)]
+    struct Cached {
+        value: u32,
+        #[delta_struct(skip)]
+        cache: Cache,
+    }
+
+    #[test]
+    fn skipped_field_is_never_diffed_or_applied() {
+        let cached = |value, cache: &[u8]| Cached {
+            value,
+            cache: Cache(cache.to_vec()),
+        };
+        assert!(Delta::delta(cached(1, &[1]), cached(1, &[2])).is_none());
+
+        // Exhaustive, so a stray `cache` field on the delta would not compile.
+        let CachedDelta { value } = Delta::delta(cached(1, &[1]), cached(2, &[2])).unwrap();
+        assert_eq!(value, Some(2));
+
+        let mut applied = cached(1, &[7]);
+        applied.apply_delta(CachedDelta { value: Some(2) });
+        assert_eq!(applied.value, 2);
+        assert_eq!(applied.cache.0, vec![7]);
+    }
+
+    #[test]
+    fn skipped_field_does_not_reach_the_fingerprint() {
+        let cached = |cache: &[u8]| Cached {
+            value: 1,
+            cache: Cache(cache.to_vec()),
+        };
+        assert_eq!(fingerprint_of(&cached(&[1])), fingerprint_of(&cached(&[2])));
+
+        // Two sides whose local-only state differs still agree.
+        let mut sender = Versioned::new(cached(&[1]));
+        let mut receiver = Versioned::new(cached(&[2]));
+        let mut next = cached(&[3]);
+        next.value = 2;
+        let message = sender.commit(next).unwrap();
+        assert_eq!(receiver.apply(message), Ok(Applied::Updated));
+        assert_eq!(receiver.get().value, 2);
+    }
+
+    #[derive(Debug, Delta, Fingerprint)]
+    #[delta_struct(delta_leader = "#[derive(Debug)]")]
+    #[allow(dead_code)] // The skipped caches are never read back
+    struct Positional(u8, #[delta_struct(skip)] Cache, bool);
+
+    #[derive(Debug, Delta, Fingerprint)]
+    #[delta_struct(delta_leader = "#[derive(Debug)]")]
+    #[allow(dead_code)] // The skipped caches are never read back
+    enum Slot {
+        Empty,
+        Held {
+            id: u8,
+            #[delta_struct(skip)]
+            cache: Cache,
+        },
+        Pinned(#[delta_struct(skip)] Cache, u8),
+    }
+
+    #[test]
+    fn skipped_fields_leave_no_slot() {
+        let PositionalDelta(first, last) = Delta::delta(
+            Positional(1, Cache::default(), false),
+            Positional(2, Cache::default(), true),
+        )
+        .unwrap();
+        assert_eq!((first, last), (Some(2), Some(true)));
+
+        let held = |id| Slot::Held {
+            id,
+            cache: Cache::default(),
+        };
+        match Delta::delta(held(1), held(2)) {
+            Some(SlotDelta::Held { id }) => assert_eq!(id, Some(2)),
+            other => panic!("expected a field delta, got {:?}", other),
+        }
+        match Delta::delta(
+            Slot::Pinned(Cache(vec![1]), 1),
+            Slot::Pinned(Cache(vec![2]), 1),
+        ) {
+            None => {}
+            other => panic!("expected no delta, got {:?}", other),
+        }
+        assert_eq!(
+            fingerprint_of(&Slot::Pinned(Cache(vec![1]), 1)),
+            fingerprint_of(&Slot::Pinned(Cache(vec![2]), 1))
+        );
+        assert_ne!(fingerprint_of(&held(1)), fingerprint_of(&Slot::Empty));
+
+        // A change of variant sends the new one without its cache, and the
+        // receiver starts from an empty one.
+        let delta = Delta::delta(held(1), Slot::Pinned(Cache(vec![9]), 2)).unwrap();
+        match &delta {
+            SlotDelta::Replace(Slot::Pinned(cache, 2)) => assert!(cache.0.is_empty()),
+            other => panic!("expected a replacement, got {:?}", other),
+        }
+        let mut local = Slot::Held {
+            id: 1,
+            cache: Cache(vec![5]),
+        };
+        local.apply_delta(delta);
+        match local {
+            Slot::Pinned(cache, 2) => assert!(cache.0.is_empty()),
+            other => panic!("expected the new variant, got {:?}", other),
+        }
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Record {
+        id: String,
+        port: u16,
+        healthy: bool,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Inventory {
+        #[delta_struct(field_type = "keyed", key = "id", key_type = "String")]
+        records: Vec<Record>,
+        #[delta_struct(field_type = "keyed", key = "0", key_type = "u8")]
+        pairs: Vec<Pair>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Pair(u8, i32);
+
+    fn inventory(records: Services) -> Inventory {
+        Inventory {
+            records: records
+                .iter()
+                .map(|(id, port, healthy)| Record {
+                    id: id.to_string(),
+                    port: *port,
+                    healthy: *healthy,
+                })
+                .collect(),
+            pairs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn keyed_pairs_records_by_field() {
+        let delta = Delta::delta(
+            inventory(&[("web", 80, true), ("db", 5432, true), ("gone", 1, true)]),
+            inventory(&[("db", 5432, false), ("web", 80, true), ("new", 2, true)]),
+        )
+        .unwrap();
+        // `web` only moved position, which is no change at all here.
+        assert_eq!(delta.records.remove, vec!["gone".to_string()]);
+        assert_eq!(delta.records.add.len(), 1);
+        assert_eq!(delta.records.add[0].0, "new");
+        assert_eq!(delta.records.change.len(), 1);
+        assert_eq!(delta.records.change[0].key, "db");
+        assert_eq!(
+            delta.records.change[0].delta,
+            RecordDelta {
+                id: None,
+                port: None,
+                healthy: Some(false),
+            }
+        );
+        assert!(delta.pairs.is_empty());
+    }
+
+    #[test]
+    fn keyed_false_positive_check() {
+        let delta = Delta::delta(
+            inventory(&[("web", 80, true), ("db", 5432, true)]),
+            inventory(&[("db", 5432, true), ("web", 80, true)]),
+        );
+        assert!(delta.is_none());
+    }
+
+    #[test]
+    fn keyed_apply_round_trips() {
+        let by_id = |mut inventory: Inventory| {
+            inventory.records.sort_by(|a, b| a.id.cmp(&b.id));
+            inventory
+        };
+        let cases: &[(Services, Services)] = &[
+            (&[("web", 80, true)], &[("web", 8080, true)]),
+            (&[("web", 80, true)], &[("web", 80, true), ("db", 1, false)]),
+            (&[("web", 80, true), ("db", 1, false)], &[("web", 80, true)]),
+            (
+                &[("a", 1, true), ("b", 2, true), ("c", 3, true)],
+                &[("c", 3, false), ("d", 4, true), ("a", 1, true)],
+            ),
+            (&[], &[("web", 80, true)]),
+            (&[("web", 80, true)], &[]),
+        ];
+        for (old, new) in cases {
+            let mut applied = inventory(old);
+            applied.apply_delta(Delta::delta(inventory(old), inventory(new)).unwrap());
+            // Membership, not position, is what `keyed` preserves.
+            assert_eq!(
+                by_id(applied),
+                by_id(inventory(new)),
+                "{:?} -> {:?}",
+                old,
+                new
+            );
+        }
+    }
+
+    #[test]
+    fn keyed_apply_keeps_surviving_order() {
+        let mut applied = inventory(&[("a", 1, true), ("b", 2, true), ("c", 3, true)]);
+        let delta = Delta::delta(
+            inventory(&[("a", 1, true), ("b", 2, true), ("c", 3, true)]),
+            inventory(&[("c", 3, true), ("a", 9, true), ("d", 4, true)]),
+        )
+        .unwrap();
+        applied.apply_delta(delta);
+        assert_eq!(
+            applied,
+            inventory(&[("a", 9, true), ("c", 3, true), ("d", 4, true)])
+        );
+    }
+
+    #[test]
+    fn keyed_by_tuple_position() {
+        let pairs = |pairs: &[(u8, i32)]| Inventory {
+            records: Vec::new(),
+            pairs: pairs.iter().map(|(k, v)| Pair(*k, *v)).collect(),
+        };
+        let mut applied = pairs(&[(1, 10), (2, 20)]);
+        let delta = Delta::delta(pairs(&[(1, 10), (2, 20)]), pairs(&[(2, 21), (3, 30)])).unwrap();
+        assert_eq!(delta.pairs.remove, vec![1]);
+        assert_eq!(delta.pairs.change[0].key, 2);
+        applied.apply_delta(delta);
+        assert_eq!(applied, pairs(&[(2, 21), (3, 30)]));
+    }
+
+    #[test]
+    fn keyed_pairs_a_duplicate_key_once() {
+        fn id(record: &Record) -> &String {
+            &record.id
+        }
+        let old = inventory(&[("a", 1, true), ("a", 2, true), ("b", 3, true)]).records;
+        let new = inventory(&[("a", 5, true), ("a", 2, true), ("b", 3, true)]).records;
+
+        // The last old `a` pairs with the first new one; the others come
+        // and go whole.
+        let delta = keyed::diff(old.clone(), new.clone(), id);
+        assert_eq!(delta, keyed::diff_ref(&old, &new, id));
+        assert_eq!(delta.remove, vec!["a".to_string()]);
+        assert_eq!(delta.add, vec![("a".to_string(), new[1].clone())]);
+        assert_eq!(delta.change.len(), 1);
+        assert_eq!(delta.change[0].delta.port, Some(5));
+        let reversible = keyed::diff_reversible(old.clone(), new.clone(), id);
+        assert_eq!(reversible, keyed::diff_ref_reversible(&old, &new, id));
+        assert_eq!(reversible.remove, vec![("a".to_string(), old[0].clone())]);
+
+        let mut applied = old;
+        keyed::try_apply(&mut applied, delta, id).unwrap();
+        let sorted = |mut records: Vec<Record>| {
+            records.sort_by(|a, b| (&a.id, a.port).cmp(&(&b.id, b.port)));
+            records
+        };
+        assert_eq!(sorted(applied), sorted(new));
+    }
+
+    // Only the `Delta` bound the delta type itself needs: the derive adds
+    // `U: PartialEq` for `label`, and nothing asks for `T: PartialEq`.
+    #[derive(Delta)]
+    struct InferredBounds<T: Delta, U> {
+        #[delta_struct(field_type = "delta")]
+        inner: T,
+        label: U,
+    }
+
+    #[derive(Delta)]
+    struct Unequal(u32);
+
+    #[derive(Delta)]
+    #[delta_struct(bound = "T: Clone + PartialEq")]
+    struct ContainerBound<T> {
+        values: Vec<T>,
+    }
+
+    #[derive(Delta)]
+    struct FieldBound<T> {
+        #[delta_struct(bound = "T: PartialEq")]
+        values: Vec<T>,
+        #[delta_struct(field_type = "ordered")]
+        ordered: Vec<T>,
+    }
+
+    #[test]
+    fn inferred_bounds() {
+        let old = InferredBounds {
+            inner: Unequal(1),
+            label: "a",
+        };
+        let new = InferredBounds {
+            inner: Unequal(2),
+            label: "a",
+        };
+        let delta = Delta::delta(old, new).unwrap();
+        assert_eq!(delta.inner.unwrap().0, Some(2));
+        assert_eq!(delta.label, None);
+    }
+
+    #[test]
+    fn bound_attribute() {
+        let delta = Delta::delta(
+            ContainerBound { values: vec![1] },
+            ContainerBound { values: vec![2] },
+        )
+        .unwrap();
+        assert_eq!(delta.values, Some(vec![2]));
+
+        // The field bound replaces only its own field's predicates; `ordered`
+        // still brings in its `Hash + Eq`.
+        let delta = Delta::delta(
+            FieldBound {
+                values: vec![1],
+                ordered: vec![1],
+            },
+            FieldBound {
+                values: vec![1],
+                ordered: vec![1, 2],
+            },
+        )
+        .unwrap();
+        assert_eq!(delta.values, None);
+        assert_eq!(delta.ordered.splices.len(), 1);
+    }
+
+    #[derive(Clone, Delta, DeltaRef)]
+    #[allow(dead_code)] // `skipped` is only there to be left out
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Everything {
+        scalar: String,
+        #[delta_struct(field_type = "delta")]
+        delta: Record,
+        #[delta_struct(field_type = "unordered")]
+        unordered: BTreeSet<u8>,
+        #[delta_struct(field_type = "unordered-delta")]
+        unordered_delta: BTreeMap<String, Record>,
+        #[delta_struct(field_type = "keyed", key = "id", key_type = "String")]
+        keyed: Vec<Record>,
+        #[delta_struct(field_type = "ordered")]
+        ordered: Vec<String>,
+        #[delta_struct(skip)]
+        skipped: u8,
+    }
+
+    // Every field mentions a parameter, so each of them has to infer the
+    // predicates borrowing needs — the higher-ranked `&T: IntoIterator` ones
+    // included. `T: PartialEq` is for the delta type itself, whose `keyed`
+    // field names `<Keyed<K, T> as Delta>::Output`.
+    #[derive(Delta, DeltaRef)]
+    #[allow(dead_code)] // The derive is itself the test
+    struct GenericEverything<T: Delta + PartialEq, K: Ord> {
+        scalar: T,
+        #[delta_struct(field_type = "delta")]
+        delta: T,
+        #[delta_struct(field_type = "unordered")]
+        unordered: BTreeSet<K>,
+        #[delta_struct(field_type = "unordered-delta")]
+        unordered_delta: BTreeMap<K, T>,
+        #[delta_struct(field_type = "keyed", key = "0", key_type = "K")]
+        keyed: Vec<Keyed<K, T>>,
+        #[delta_struct(field_type = "ordered")]
+        ordered: Vec<T>,
+        #[delta_struct(field_type = "optional-delta")]
+        optional_delta: Option<T>,
+    }
+
+    #[derive(Clone, Delta, DeltaRef)]
+    #[allow(dead_code)] // The derive is itself the test
+    struct Keyed<K, T>(K, T);
+
+    fn record(id: &str, port: u16) -> Record {
+        Record {
+            id: id.to_string(),
+            port,
+            healthy: true,
+        }
+    }
+
+    fn everything(version: u16) -> Everything {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        Everything {
+            scalar: format!("v{}", version.min(1)),
+            delta: record("self", 80 + version),
+            unordered: (version..version + 3).map(|v| v as u8).collect(),
+            unordered_delta: vec![
+                ("a".to_string(), record("a", 1)),
+                (format!("b{}", version), record("b", 2)),
+                ("c".to_string(), record("c", version)),
+            ]
+            .into_iter()
+            .collect(),
+            keyed: vec![
+                record("x", 1),
+                record("y", version),
+                record(&version.to_string(), 0),
+            ],
+            ordered: if version == 0 {
+                strings(&["a", "b", "c"])
+            } else {
+                strings(&["a", "z", "c", "d"])
+            },
+            skipped: version as u8,
+        }
+    }
+
+    #[test]
+    fn delta_ref_matches_delta() {
+        for (old, new) in [(0, 0), (0, 1), (1, 2), (2, 2)] {
+            assert_eq!(
+                DeltaRef::delta_ref(&everything(old), &everything(new)),
+                Delta::delta(everything(old), everything(new)),
+                "{} -> {}",
+                old,
+                new
+            );
+        }
+
+        let mut applied = everything(0);
+        applied.apply_delta(DeltaRef::delta_ref(&everything(0), &everything(1)).unwrap());
+        assert_eq!(
+            Delta::delta(applied, everything(1)).map(|delta| delta.unordered_delta),
+            None
+        );
+    }
+
+    #[test]
+    fn delta_ref_of_an_enum() {
+        let jobs = [
+            Job::Queued,
+            Job::Running {
+                progress: 10,
+                workers: vec![1, 2].into_iter().collect(),
+            },
+            Job::Running {
+                progress: 20,
+                workers: vec![2, 3].into_iter().collect(),
+            },
+            Job::Failed("disk".to_string()),
+        ];
+        for old in &jobs {
+            for new in &jobs {
+                assert_eq!(
+                    DeltaRef::delta_ref(old, new),
+                    Delta::delta(old.clone(), new.clone())
+                );
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Entry {
+        id: String,
+        size: u32,
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Undoable {
+        scalar: String,
+        #[delta_struct(field_type = "delta")]
+        delta: Entry,
+        #[delta_struct(field_type = "unordered")]
+        unordered: BTreeSet<u8>,
+        #[delta_struct(field_type = "unordered-delta")]
+        unordered_delta: BTreeMap<String, Entry>,
+        #[delta_struct(field_type = "keyed", key = "id", key_type = "String")]
+        keyed: Vec<Entry>,
+        #[delta_struct(field_type = "ordered")]
+        ordered: Vec<u8>,
+        #[delta_struct(skip)]
+        skipped: u8,
+    }
+
+    fn entry(id: &str, size: u32) -> Entry {
+        Entry {
+            id: id.to_string(),
+            size,
+        }
+    }
+
+    fn undoable(version: u32) -> Undoable {
+        Undoable {
+            scalar: format!("v{}", version),
+            delta: entry("self", version),
+            unordered: (version..version + 3).map(|v| v as u8).collect(),
+            unordered_delta: vec![
+                ("a".to_string(), entry("a", 1)),
+                (format!("b{}", version), entry("b", 2)),
+                ("c".to_string(), entry("c", version)),
+            ]
+            .into_iter()
+            .collect(),
+            keyed: vec![
+                entry("x", 1),
+                entry("y", version),
+                entry(&version.to_string(), 0),
+            ],
+            ordered: (0..version as u8 + 3)
+                .filter(|v| v % (version as u8 + 2) != 0)
+                .collect(),
+            skipped: 0,
+        }
+    }
+
+    #[test]
+    fn reversible_delta_inverts() {
+        for (old, new) in [(0, 1), (1, 0), (1, 3), (2, 2)] {
+            let delta = Delta::delta(undoable(old), undoable(new));
+            let mut applied = undoable(old);
+            if let Some(delta) = delta.clone() {
+                applied.apply_delta(delta);
+            }
+            assert_eq!(applied, undoable(new), "{} -> {}", old, new);
+            if let Some(delta) = delta.clone() {
+                applied.apply_delta(delta.invert());
+            }
+            assert_eq!(applied, undoable(old), "{} -> {} -> {}", old, new, old);
+            assert_eq!(delta.clone().invert().invert(), delta);
+        }
+    }
+
+    #[test]
+    fn reversible_delta_records_both_sides() {
+        let delta = Delta::delta(undoable(0), undoable(1)).unwrap();
+        assert_eq!(
+            delta.scalar,
+            Some(Change {
+                old: "v0".to_string(),
+                new: "v1".to_string(),
+            })
+        );
+        assert_eq!(
+            delta.unordered_delta.remove,
+            vec![("b0".to_string(), entry("b", 2))]
+        );
+        assert_eq!(delta.keyed.remove, vec![("0".to_string(), entry("0", 0))]);
+        assert_eq!(delta.ordered.splices[0].insert, vec![2]);
+    }
+
+    #[test]
+    fn reversible_delta_ref_matches_delta() {
+        for (old, new) in [(0, 1), (1, 3), (2, 2)] {
+            assert_eq!(
+                DeltaRef::delta_ref(&undoable(old), &undoable(new)),
+                Delta::delta(undoable(old), undoable(new))
+            );
+        }
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    enum Shape {
+        Empty,
+        Circle(u32),
+        Rectangle {
+            width: u32,
+            #[delta_struct(field_type = "ordered")]
+            corners: Vec<u8>,
+        },
+    }
+
+    #[test]
+    fn reversible_enum_inverts() {
+        let shapes = [
+            Shape::Empty,
+            Shape::Circle(1),
+            Shape::Circle(2),
+            Shape::Rectangle {
+                width: 3,
+                corners: vec![1, 2],
+            },
+            Shape::Rectangle {
+                width: 4,
+                corners: vec![2, 3],
+            },
+        ];
+        for old in &shapes {
+            for new in &shapes {
+                let delta = Delta::delta(old.clone(), new.clone());
+                assert_eq!(DeltaRef::delta_ref(old, new), delta);
+                let mut applied = new.clone();
+                if let Some(delta) = delta {
+                    applied.apply_delta(delta.invert());
+                }
+                assert_eq!(&applied, old);
+            }
+        }
+    }
+
+    // The `Invert` impl has to infer that each field's delta inverts.
+    #[derive(Delta)]
+    #[delta_struct(reversible)]
+    #[allow(dead_code)] // The derive is itself the test
+    struct ReversibleGeneric<T: Delta, K: Ord> {
+        scalar: K,
+        #[delta_struct(field_type = "delta")]
+        delta: T,
+        #[delta_struct(field_type = "unordered-delta")]
+        unordered_delta: BTreeMap<K, T>,
+    }
+
+    #[test]
+    fn inverted_splices_land_in_new_coordinates() {
+        let delta = seq::diff_reversible(vec![1, 2, 3, 4, 5], vec![9, 9, 2, 3, 5, 6]);
+        let mut undone = vec![9, 9, 2, 3, 5, 6];
+        seq::apply(&mut undone, delta.invert().into_forward());
+        assert_eq!(undone, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn composed_delta_applies_both() {
+        for (a, b, c) in [(0, 1, 2), (0, 1, 0), (1, 1, 2), (2, 0, 1), (0, 2, 2)] {
+            let composed = compose::option(
+                Delta::delta(everything(a), everything(b)),
+                Delta::delta(everything(b), everything(c)),
+            );
+            let mut applied = everything(a);
+            if let Some(delta) = composed {
+                applied.apply_delta(delta);
+            }
+            assert_eq!(
+                Delta::delta(applied, everything(c)),
+                None,
+                "{} -> {} -> {}",
+                a,
+                b,
+                c
+            );
+        }
+    }
+
+    #[test]
+    fn composed_reversible_delta_inverts() {
+        for a in 0..4 {
+            for b in 0..4 {
+                for c in 0..4 {
+                    let composed = compose::option(
+                        Delta::delta(undoable(a), undoable(b)),
+                        Delta::delta(undoable(b), undoable(c)),
+                    );
+                    let mut applied = undoable(a);
+                    if let Some(delta) = composed.clone() {
+                        applied.apply_delta(delta);
+                    }
+                    assert_eq!(applied, undoable(c), "{} -> {} -> {}", a, b, c);
+                    if let Some(delta) = composed {
+                        applied.apply_delta(delta.invert());
+                    }
+                    assert_eq!(applied, undoable(a), "{} -> {} -> {} -> {}", a, b, c, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn composed_enum_delta_applies_both() {
+        let jobs = [
+            Job::Queued,
+            Job::Running {
+                progress: 10,
+                workers: vec![1, 2].into_iter().collect(),
+            },
+            Job::Running {
+                progress: 20,
+                workers: vec![2, 3].into_iter().collect(),
+            },
+            Job::Failed("disk".to_string()),
+        ];
+        let shapes = [
+            Shape::Empty,
+            Shape::Circle(1),
+            Shape::Circle(2),
+            Shape::Rectangle {
+                width: 3,
+                corners: vec![1, 2],
+            },
+            Shape::Rectangle {
+                width: 4,
+                corners: vec![2, 3],
+            },
+        ];
+        for a in &jobs {
+            for b in &jobs {
+                for c in &jobs {
+                    let mut applied = a.clone();
+                    let composed = compose::option(
+                        Delta::delta(a.clone(), b.clone()),
+                        Delta::delta(b.clone(), c.clone()),
+                    );
+                    if let Some(delta) = composed {
+                        applied.apply_delta(delta);
+                    }
+                    assert_eq!(&applied, c);
+                }
+            }
+        }
+        for a in &shapes {
+            for b in &shapes {
+                for c in &shapes {
+                    let mut applied = a.clone();
+                    let composed = compose::option(
+                        Delta::delta(a.clone(), b.clone()),
+                        Delta::delta(b.clone(), c.clone()),
+                    );
+                    if let Some(delta) = composed.clone() {
+                        applied.apply_delta(delta);
+                    }
+                    assert_eq!(&applied, c);
+                    if let Some(delta) = composed {
+                        applied.apply_delta(delta.invert());
+                    }
+                    assert_eq!(&applied, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn composed_splices_are_rebased() {
+        let sequences: [&[u8]; 6] = [
+            &[],
+            &[1, 2, 3],
+            &[1, 3],
+            &[4, 1, 2, 3, 5],
+            &[3, 2, 1],
+            &[2, 2, 4, 2],
+        ];
+        for a in sequences.iter() {
+            for b in sequences.iter() {
+                for c in sequences.iter() {
+                    let composed = Compose::compose(
+                        seq::diff_reversible(a.to_vec(), b.to_vec()),
+                        seq::diff_reversible(b.to_vec(), c.to_vec()),
+                    );
+                    let mut applied = a.to_vec();
+                    seq::apply(&mut applied, composed.clone().into_forward());
+                    assert_eq!(&applied, c, "{:?} -> {:?} -> {:?}", a, b, c);
+                    seq::apply(&mut applied, composed.invert().into_forward());
+                    assert_eq!(&applied, a, "{:?} -> {:?} -> {:?} -> {:?}", a, b, c, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn composing_cancels_what_the_second_delta_undoes() {
+        let set = |items: &[u8]| items.iter().copied().collect::<BTreeSet<_>>();
+        let composed = Compose::compose(
+            bag::diff(set(&[1, 2]), set(&[2, 3])),
+            bag::diff(set(&[2, 3]), set(&[1, 2])),
+        );
+        assert!(composed.add.is_empty() && composed.remove.is_empty());
+
+        let composed = Compose::compose(
+            seq::diff(vec![1, 2], vec![1, 5, 2]),
+            seq::diff(vec![1, 5, 2], vec![1, 2]),
+        );
+        assert!(composed.is_empty());
+
+        let entries = |size| {
+            vec![("x".to_string(), entry("x", size))]
+                .into_iter()
+                .collect::<BTreeMap<_, _>>()
+        };
+        let composed = Compose::compose(
+            map::diff_reversible(entries(1), entries(2)),
+            map::diff_reversible(entries(2), BTreeMap::new()),
+        );
+        // A change followed by a removal removes what was there before.
+        assert!(composed.change.is_empty());
+        assert_eq!(composed.remove, vec![("x".to_string(), entry("x", 1))]);
+    }
+
+    #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+    struct Unhashable(u8);
+
+    // A bag of elements without `Hash` cannot compose, since that is how its
+    // elements are matched up. The type still gets its `Delta`, just not a
+    // composable one.
+    #[derive(Delta)]
+    #[allow(dead_code)] // The derive is itself the test
+    struct Unhashables {
+        #[delta_struct(field_type = "unordered")]
+        values: BTreeSet<Unhashable>,
+    }
+
+    #[test]
+    fn try_apply_delta_applies_a_delta_that_fits() {
+        let mut applied = everything(0);
+        let delta = Delta::delta(everything(0), everything(1)).unwrap();
+        assert_eq!(applied.try_apply_delta(delta), Ok(()));
+        assert_eq!(Delta::delta(applied, everything(1)), None);
+    }
+
+    #[test]
+    fn try_apply_delta_refuses_a_delta_that_does_not_fit() {
+        let mut target = everything(2);
+        let delta = Delta::delta(everything(0), everything(1)).unwrap();
+        let error = target.try_apply_delta(delta).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("unordered")]);
+        assert_eq!(error.problem, Problem::NotPresent { index: 0 });
+        assert_eq!(Delta::delta(target, everything(2)), None);
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Tracklist {
+        #[delta_struct(field_type = "ordered")]
+        tracks: Vec<u8>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    struct Library {
+        #[delta_struct(field_type = "unordered-delta")]
+        playlists: BTreeMap<String, Tracklist>,
+    }
+
+    #[test]
+    fn try_apply_delta_names_the_path_to_the_problem() {
+        let library = |tracks: &[u8]| Library {
+            playlists: vec![(
+                "mix".to_string(),
+                Tracklist {
+                    tracks: tracks.to_vec(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        let delta = Delta::delta(library(&[1, 2, 3]), library(&[1, 2])).unwrap();
+
+        let mut shorter = library(&[1]);
+        let error = shorter.try_apply_delta(delta).unwrap_err();
+        assert_eq!(
+            error.path,
+            vec![
+                PathSegment::Field("playlists"),
+                PathSegment::Change(0),
+                PathSegment::Field("tracks"),
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "playlists.change[0].tracks: splices[0] reaches past the end of a sequence of 1"
+        );
+        assert_eq!(shorter, library(&[1]));
+
+        let delta = Delta::delta(library(&[1, 2, 3]), library(&[1, 2])).unwrap();
+        let mut empty = Library {
+            playlists: BTreeMap::new(),
+        };
+        assert_eq!(
+            empty.try_apply_delta(delta).unwrap_err().problem,
+            Problem::MissingKey { index: 0 }
+        );
+    }
+
+    #[test]
+    fn try_apply_delta_refuses_another_variant() {
+        let running = |workers: Vec<u8>| Job::Running {
+            progress: 10,
+            workers: workers.into_iter().collect(),
+        };
+        let delta = Delta::delta(running(vec![1, 2]), running(vec![2])).unwrap();
+        let mut queued = Job::Queued;
+        let error = queued.try_apply_delta(delta).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Variant("Running")]);
+        assert_eq!(error.problem, Problem::WrongVariant);
+        assert_eq!(queued, Job::Queued);
+
+        let delta = Delta::delta(running(vec![1, 2]), running(vec![2])).unwrap();
+        let mut without = running(vec![2, 3]);
+        let error = without.try_apply_delta(delta).unwrap_err();
+        assert_eq!(
+            error.path,
+            vec![
+                PathSegment::Variant("Running"),
+                PathSegment::Field("workers")
+            ]
+        );
+        assert_eq!(without, running(vec![2, 3]));
+
+        // A replacement fits any value.
+        let mut replaced = Job::Queued;
+        let delta = Delta::delta(Job::Queued, running(vec![1])).unwrap();
+        assert_eq!(replaced.try_apply_delta(delta), Ok(()));
+        assert_eq!(replaced, running(vec![1]));
+    }
+
+    #[test]
+    fn strict_apply_refuses_unsorted_splices_and_missing_records() {
+        let splice = |at, remove| seq::Splice {
+            at,
+            remove,
+            insert: vec![0],
+        };
+        let delta = SeqDelta {
+            splices: vec![splice(2, 1), splice(1, 0)],
+        };
+        let mut target = vec![1, 2, 3];
+        assert_eq!(
+            seq::try_apply(&mut target, delta).unwrap_err().problem,
+            Problem::UnsortedSplices { index: 1 }
+        );
+        assert_eq!(target, vec![1, 2, 3]);
+
+        let delta = keyed::diff(
+            vec![record("a", 1), record("b", 2)],
+            vec![record("a", 2)],
+            |record: &Record| &record.id,
+        );
+        let mut target = vec![record("a", 1)];
+        assert_eq!(
+            keyed::try_apply(&mut target, delta, |record: &Record| &record.id)
+                .unwrap_err()
+                .problem,
+            Problem::NotPresent { index: 0 }
+        );
+        assert_eq!(target, vec![record("a", 1)]);
+    }
+
+    #[test]
+    fn changed_fields_names_the_fields_a_delta_touches() {
+        use EverythingField as E;
+        assert_eq!(
+            E::ALL,
+            [
+                E::Scalar,
+                E::Delta,
+                E::Unordered,
+                E::UnorderedDelta,
+                E::Keyed,
+                E::Ordered
+            ]
+        );
+        assert_eq!(
+            (E::UnorderedDelta.index(), E::UnorderedDelta.name()),
+            (3, "unordered_delta")
+        );
+
+        let delta = Delta::delta(everything(1), everything(2)).unwrap();
+        let changed = delta.changed_fields();
+        assert_eq!(
+            changed.iter().collect::<Vec<_>>(),
+            [E::Delta, E::Unordered, E::UnorderedDelta, E::Keyed]
+        );
+        assert!(!changed.contains(E::Scalar) && !changed.contains(E::Ordered));
+        assert_eq!(changed.len(), 4);
+        assert_eq!(
+            format!("{:?}", changed),
+            "{Delta, Unordered, UnorderedDelta, Keyed}"
+        );
+
+        // Positions are the source struct's, skipped slot and all.
+        let delta = Delta::delta(
+            Positional(1, Cache::default(), false),
+            Positional(1, Cache::default(), true),
+        )
+        .unwrap();
+        assert_eq!(
+            delta.changed_fields().iter().collect::<Vec<_>>(),
+            [PositionalField::Field2]
+        );
+        assert_eq!(PositionalField::Field2.name(), "2");
+    }
+
+    #[test]
+    fn field_set_spills_past_one_word() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Wide(usize);
+        const WIDE: [Wide; 130] = {
+            let mut fields = [Wide(0); 130];
+            let mut index = 0;
+            while index < fields.len() {
+                fields[index] = Wide(index);
+                index += 1;
+            }
+            fields
+        };
+        impl Field for Wide {
+            const ALL: &'static [Self] = &WIDE;
+
+            fn index(self) -> usize {
+                self.0
+            }
+
+            fn name(self) -> &'static str {
+                "wide"
+            }
+        }
+
+        let mut set = FieldSet::new();
+        assert!(set.is_empty());
+        set.insert(Wide(129));
+        set.insert(Wide(3));
+        set.insert(Wide(64));
+        assert!(!set.is_empty());
+        assert!(set.contains(Wide(64)) && !set.contains(Wide(65)));
+        assert_eq!(set.len(), 3);
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            [Wide(3), Wide(64), Wide(129)]
+        );
+        assert_eq!(
+            set,
+            [Wide(64), Wide(129), Wide(3)].iter().copied().collect()
+        );
+        assert_ne!(set, FieldSet::new());
+    }
+
+    #[test]
+    fn changed_paths_follow_delta_fields_and_variants() {
+        let field = PathSegment::Field;
+        let delta = Delta::delta(everything(1), everything(2)).unwrap();
+        assert_eq!(
+            delta.changed_paths(),
+            [
+                vec![field("delta"), field("port")],
+                vec![field("unordered")],
+                vec![field("unordered_delta")],
+                vec![field("keyed")],
+            ]
+        );
+
+        let worker = |job, name: &str| Worker {
+            job,
+            name: name.to_string(),
+        };
+        let delta = Delta::delta(
+            worker(running(10, &[1]), "ann"),
+            worker(running(20, &[1]), "bob"),
+        )
+        .unwrap();
+        assert_eq!(
+            delta.changed_paths(),
+            [
+                vec![
+                    field("job"),
+                    PathSegment::Variant("Running"),
+                    field("progress")
+                ],
+                vec![field("name")],
+            ]
+        );
+
+        // A change of variant replaces the job whole.
+        let delta =
+            Delta::delta(worker(Job::Queued, "ann"), worker(running(10, &[1]), "ann")).unwrap();
+        assert_eq!(delta.changed_paths(), [vec![field("job")]]);
+    }
+
+    /// Deliberately not `PartialEq`, so only `eq_with` can compare it.
+    #[derive(Clone, Debug)]
+    struct Stamp {
+        nanos: u64,
+    }
+
+    fn same_second(a: &Stamp, b: &Stamp) -> bool {
+        a.nanos / 1_000_000_000 == b.nanos / 1_000_000_000
+    }
+
+    fn never_equal<T>(_: &T, _: &T) -> bool {
+        false
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef)]
+    #[delta_struct(delta_leader = "#[derive(Debug)]")]
+    struct Stamped<T> {
+        #[delta_struct(eq_with = "same_second")]
+        at: Stamp,
+        #[delta_struct(eq_with = "never_equal")]
+        value: T,
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef)]
+    #[delta_struct(delta_leader = "#[derive(Debug)]")]
+    enum Event {
+        Seen(#[delta_struct(eq_with = "self::same_second")] Stamp),
+    }
+
+    #[test]
+    fn eq_with_decides_whether_a_scalar_changed() {
+        let stamped = |nanos, value| Stamped {
+            at: Stamp { nanos },
+            value,
+        };
+        let delta = Delta::delta(stamped(1_000, 'a'), stamped(2_000, 'a')).unwrap();
+        assert!(delta.at.is_none());
+        assert_eq!(delta.value, Some('a'));
+
+        let delta =
+            DeltaRef::delta_ref(&stamped(1_000, 'a'), &stamped(1_000_000_000, 'a')).unwrap();
+        assert_eq!(delta.at.map(|at| at.nanos), Some(1_000_000_000));
+
+        let seen = |nanos| Event::Seen(Stamp { nanos });
+        assert!(Delta::delta(seen(1), seen(2)).is_none());
+        assert!(DeltaRef::delta_ref(&seen(1), &seen(2)).is_none());
+        match Delta::delta(seen(1), seen(3_000_000_000)) {
+            Some(EventDelta::Seen(Some(at))) => assert_eq!(at.nanos, 3_000_000_000),
+            other => panic!("expected a field delta, got {:?}", other),
+        }
+    }
+
+    /// Diffs a count as how far it moved, and refuses to take it below zero.
+    struct Moved;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Moves(i64);
+
+    impl Strategy<u32> for Moved {
+        type Delta = Moves;
+
+        fn diff(old: u32, new: u32) -> Moves {
+            Moves(i64::from(new) - i64::from(old))
+        }
+
+        fn apply(target: &mut u32, delta: Moves) {
+            *target = (i64::from(*target) + delta.0) as u32;
+        }
+
+        fn is_empty(delta: &Moves) -> bool {
+            delta.0 == 0
+        }
+
+        fn check(target: &u32, delta: &Moves) -> Result<(), ApplyError> {
+            if i64::from(*target) + delta.0 < 0 {
+                return Err(ApplyError::new(Problem::Refused(
+                    "count would go below zero",
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    impl Compose for Moves {
+        fn compose(first: Self, second: Self) -> Self {
+            Moves(first.0 + second.0)
+        }
+    }
+
+    impl Invert for Moves {
+        fn invert(self) -> Self {
+            Moves(-self.0)
+        }
+    }
+
+    /// Diffs anything as the whole new value, to exercise a strategy that is
+    /// generic over the field.
+    struct Resent;
+
+    impl<T: PartialEq> Strategy<T> for Resent {
+        type Delta = Vec<T>;
+
+        fn diff(old: T, new: T) -> Vec<T> {
+            if old == new {
+                Vec::new()
+            } else {
+                vec![new]
+            }
+        }
+
+        fn apply(target: &mut T, mut delta: Vec<T>) {
+            if let Some(value) = delta.pop() {
+                *target = value;
+            }
+        }
+
+        fn is_empty(delta: &Vec<T>) -> bool {
+            delta.is_empty()
+        }
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Debug)]")]
+    struct Tally {
+        #[delta_struct(with = "Moved")]
+        count: u32,
+        label: String,
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    enum Resendable<T: PartialEq> {
+        Empty,
+        Full(#[delta_struct(with = "Resent")] T),
+    }
+
+    #[test]
+    fn with_diffs_through_the_strategy() {
+        let tally = |count| Tally {
+            count,
+            label: "a".to_string(),
+        };
+        assert!(Delta::delta(tally(3), tally(3)).is_none());
+        let delta = Delta::delta(tally(3), tally(1)).unwrap();
+        assert_eq!(delta.count, Moves(-2));
+        assert_eq!(delta.label, None);
+        assert_eq!(
+            delta.changed_fields().iter().collect::<Vec<_>>(),
+            [TallyField::Count]
+        );
+        let mut applied = tally(3);
+        applied.apply_delta(delta);
+        assert_eq!(applied, tally(1));
+
+        let delta = DeltaRef::delta_ref(&tally(3), &tally(1)).unwrap();
+        let mut undone = tally(1);
+        undone.apply_delta(delta.invert());
+        assert_eq!(undone, tally(3));
+
+        let first = Delta::delta(tally(3), tally(1)).unwrap();
+        let second = Delta::delta(tally(1), tally(7)).unwrap();
+        assert_eq!(Compose::compose(first, second).count, Moves(4));
+
+        let delta = Delta::delta(Resendable::Full(1), Resendable::Full(2)).unwrap();
+        let mut applied = Resendable::Full(1);
+        applied.apply_delta(delta);
+        assert_eq!(applied, Resendable::Full(2));
+        assert!(DeltaRef::delta_ref(&Resendable::Full(2), &Resendable::Full(2)).is_none());
+        assert!(Delta::delta(Resendable::<u8>::Empty, Resendable::Empty).is_none());
+    }
+
+    #[test]
+    fn with_checks_through_the_strategy() {
+        let tally = |count| Tally {
+            count,
+            label: "a".to_string(),
+        };
+        let delta = Delta::delta(tally(3), tally(1)).unwrap();
+        let mut short = tally(1);
+        let error = short.try_apply_delta(delta).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("count")]);
+        assert_eq!(error.to_string(), "count: count would go below zero");
+        assert_eq!(short, tally(1));
+    }
+
+    /// Checks that the delta between each two neighbouring `states` is undone
+    /// by its inverse, and that all of them composed into one get from the
+    /// first state to the last and back again. Returns the composed delta.
+    fn assert_round_trips<T>(states: &[T]) -> Option<T::Output>
+    where
+        T: Delta + Clone + std::fmt::Debug + PartialEq,
+        T::Output: Clone + Compose + Invert,
+    {
+        let mut composed = None;
+        for pair in states.windows(2) {
+            let delta = Delta::delta(pair[0].clone(), pair[1].clone());
+            let mut undone = pair[1].clone();
+            if let Some(inverse) = delta.clone().invert() {
+                undone.apply_delta(inverse);
+            }
+            assert_eq!(undone, pair[0]);
+            composed = compose::option(composed, delta);
+        }
+        let (first, last) = (&states[0], &states[states.len() - 1]);
+        let mut applied = first.clone();
+        if let Some(delta) = composed.clone() {
+            applied.apply_delta(delta);
+        }
+        assert_eq!(&applied, last);
+        if let Some(inverse) = composed.clone().invert() {
+            applied.apply_delta(inverse);
+        }
+        assert_eq!(&applied, first);
+        composed
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug)]")]
+    struct Listener {
+        #[delta_struct(field_type = "optional-delta")]
+        upstream: Option<Record>,
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Attachment {
+        #[delta_struct(field_type = "optional-delta")]
+        entry: Option<Entry>,
+    }
+
+    #[test]
+    fn optional_delta_diffs_the_value_in_place() {
+        let listener = |upstream| Listener { upstream };
+        let some = |port| listener(Some(record("up", port)));
+        assert!(Delta::delta(some(80), some(80)).is_none());
+        assert!(Delta::delta(listener(None), listener(None)).is_none());
+
+        match Delta::delta(some(80), some(81)).unwrap().upstream {
+            Some(OptionDelta::Change(change)) => {
+                assert_eq!(change.port, Some(81));
+                assert_eq!(change.id, None);
+            }
+            other => panic!("expected a change in place, got {:?}", other),
+        }
+        for (old, new) in [
+            (some(80), some(81)),
+            (listener(None), some(80)),
+            (some(80), listener(None)),
+        ] {
+            let delta = DeltaRef::delta_ref(&old, &new).unwrap();
+            assert_eq!(delta.changed_fields().len(), 1);
+            let mut applied = old.clone();
+            applied.apply_delta(delta);
+            assert_eq!(applied, new);
+            assert_eq!(
+                Delta::delta(old.clone(), new.clone()).map(|delta| format!("{:?}", delta)),
+                DeltaRef::delta_ref(&old, &new).map(|delta| format!("{:?}", delta)),
+            );
+        }
+
+        // A change has nothing to land on once the value is gone.
+        let delta = Delta::delta(some(80), some(81)).unwrap();
+        let mut cleared = listener(None);
+        let error = cleared.try_apply_delta(delta).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("upstream")]);
+        assert_eq!(error.problem, Problem::WrongVariant);
+        assert_eq!(cleared, listener(None));
+
+        let set = Delta::delta(listener(None), some(80)).unwrap();
+        let changed = Delta::delta(some(80), some(81)).unwrap();
+        let mut applied = listener(None);
+        applied.apply_delta(Compose::compose(set, changed));
+        assert_eq!(applied, some(81));
+    }
+
+    #[test]
+    fn optional_delta_inverts_and_composes() {
+        let attachment = |entry| Attachment { entry };
+        let some = |size| attachment(Some(entry("a", size)));
+        let none = || attachment(None);
+        assert_round_trips(&[some(1), some(2), some(2), none(), none(), some(1)]);
+        // A value with nothing in common with the old one is still diffed in
+        // place, since the field holds at most one.
+        let swapped = assert_round_trips(&[some(1), attachment(Some(entry("b", 1)))]);
+        assert!(matches!(
+            swapped.unwrap().entry,
+            Some(ReversibleOptionDelta::Change(_))
+        ));
+
+        let compose = |states: &[Attachment]| assert_round_trips(states).unwrap().entry;
+        // Set and then cleared, the entry was never there.
+        assert_eq!(compose(&[none(), some(1), none()]), None);
+        // Cleared and then set again, it changed in place.
+        match compose(&[some(1), none(), some(2)]) {
+            Some(ReversibleOptionDelta::Change(change)) => {
+                assert_eq!(change.size, Some(Change { old: 1, new: 2 }))
+            }
+            other => panic!("expected a change in place, got {:?}", other),
+        }
+        assert_eq!(compose(&[some(1), none(), some(1)]), None);
+        // Changed and then cleared, it clears what was there to begin with.
+        assert_eq!(
+            compose(&[some(1), some(2), none()]),
+            Some(ReversibleOptionDelta::Clear(entry("a", 1)))
+        );
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug)]")]
+    struct Layout {
+        #[delta_struct(field_type = "delta")]
+        origin: (u16, String),
+        #[delta_struct(field_type = "delta")]
+        links: [Listener; 2],
+        #[delta_struct(field_type = "delta")]
+        fallback: Box<Listener>,
+    }
+
+    /// Fails the test if it is ever diffed.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Untouchable(u8);
+
+    impl Delta for Untouchable {
+        type Output = u8;
+
+        fn delta(_old: Self, _new: Self) -> Option<u8> {
+            panic!("a shared value was compared")
+        }
+
+        fn apply_delta(&mut self, delta: u8) {
+            self.0 = delta;
+        }
+    }
+
+    impl DeltaRef for Untouchable {
+        fn delta_ref(_old: &Self, _new: &Self) -> Option<u8> {
+            panic!("a shared value was compared")
+        }
+    }
+
+    #[test]
+    fn std_types_diff_as_delta_fields() {
+        assert_eq!(Delta::delta(1u8, 1u8), None);
+        assert_eq!(
+            Delta::delta("a".to_string(), "b".to_string()),
+            Some("b".to_string())
+        );
+        assert_eq!(Delta::delta((1, 'a'), (1, 'b')), Some((None, Some('b'))));
+        assert_eq!(
+            Delta::delta([1, 2, 3], [1, 2, 4]),
+            Some([None, None, Some(4)])
+        );
+        assert_eq!(DeltaRef::delta_ref(&[1, 2], &[1, 2]), None);
+
+        let listener = |port| Listener {
+            upstream: Some(record("b", port)),
+        };
+        let layout = |x, port| Layout {
+            origin: (x, "top".to_string()),
+            links: [
+                Listener {
+                    upstream: Some(record("a", 80)),
+                },
+                listener(port),
+            ],
+            fallback: Box::new(listener(port)),
+        };
+        let old = layout(0, 80);
+        let new = layout(5, 81);
+        let delta = DeltaRef::delta_ref(&old, &new).unwrap();
+        assert_eq!(delta.origin, Some((Some(5), None)));
+        assert_eq!(
+            delta.changed_paths(),
+            vec![
+                vec![PathSegment::Field("origin"), PathSegment::Field("0")],
+                vec![
+                    PathSegment::Field("links"),
+                    PathSegment::Index(1),
+                    PathSegment::Field("upstream"),
+                ],
+                vec![
+                    PathSegment::Field("fallback"),
+                    PathSegment::Field("upstream")
+                ],
+            ]
+        );
+        let mut applied = old.clone();
+        applied.apply_delta(Delta::delta(old.clone(), new.clone()).unwrap());
+        assert_eq!(applied, new);
+
+        let mut unlinked = old.clone();
+        unlinked.links[1].upstream = None;
+        let error = unlinked.try_apply_delta(delta).unwrap_err();
+        assert_eq!(
+            error.path,
+            vec![
+                PathSegment::Field("links"),
+                PathSegment::Index(1),
+                PathSegment::Field("upstream"),
+            ]
+        );
+        assert_eq!(error.problem, Problem::WrongVariant);
+    }
+
+    #[test]
+    fn shared_pointers_skip_what_both_sides_share() {
+        let shared = Arc::new(Untouchable(1));
+        assert_eq!(Delta::delta(shared.clone(), shared.clone()), None);
+        assert_eq!(DeltaRef::delta_ref(&shared, &shared), None);
+
+        let old = Rc::new((1, 'a'));
+        let kept = old.clone();
+        let mut applied = old.clone();
+        applied.apply_delta(Delta::delta(old, Rc::new((2, 'a'))).unwrap());
+        assert_eq!(*applied, (2, 'a'));
+        // The other holder still sees the value from before.
+        assert_eq!(*kept, (1, 'a'));
+
+        let boxed = |value| Box::new([value, 0]);
+        assert_eq!(Delta::delta(boxed(1), boxed(2)), Some([Some(2), None]));
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Manuscript {
+        #[delta_struct(field_type = "text")]
+        body: String,
+        #[delta_struct(field_type = "text", granularity = "lines")]
+        script: String,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug)]")]
+    struct Draft {
+        #[delta_struct(field_type = "text")]
+        body: String,
+    }
+
+    #[test]
+    fn text_diffs_characters_and_lines() {
+        let manuscript = |body: &str, script: &str| Manuscript {
+            body: body.to_string(),
+            script: script.to_string(),
+        };
+        let old = manuscript("Zoë ate the crème brûlée", "fn a() {}\nfn b() {}\n");
+        let new = manuscript("Zoë ate a crème brûlée 🍮", "fn a() {}\nfn c() {}\n");
+        assert!(Delta::delta(old.clone(), old.clone()).is_none());
+
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta, DeltaRef::delta_ref(&old, &new).unwrap());
+        for splice in &delta.body.splices {
+            assert!(old.body.is_char_boundary(splice.at));
+            assert!(old.body.is_char_boundary(splice.at + splice.remove));
+        }
+        // A changed line is resent whole.
+        assert_eq!(
+            delta.script.splices,
+            vec![TextSplice {
+                at: 10,
+                remove: 10,
+                insert: "fn c() {}\n".to_string(),
+            }]
+        );
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        let delta = Delta::delta(old.clone(), new).unwrap();
+        let mut short = manuscript("Zoë", "");
+        let error = short.try_apply_delta(delta).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("body")]);
+        assert!(matches!(
+            error.problem,
+            Problem::SpliceOutOfRange { len: 4, .. }
+        ));
+
+        let delta = TextDelta {
+            splices: vec![TextSplice {
+                at: 3,
+                remove: 0,
+                insert: "e".to_string(),
+            }],
+        };
+        let error = text::check("Zoë", &delta).unwrap_err();
+        assert_eq!(error.problem, Problem::SplitsChar { index: 0 });
+        // Applied anyway, the splice lands before the whole character.
+        let mut target = "Zoë".to_string();
+        text::apply(&mut target, delta);
+        assert_eq!(target, "Zoeë");
+    }
+
+    #[test]
+    fn text_inverts_and_composes() {
+        let draft = |body: &str| Draft {
+            body: body.to_string(),
+        };
+        assert_round_trips(&[draft("naïve"), draft("naïve café"), draft("na café!")]);
+        // Emptied and refilled, and edits at either end of multi-byte
+        // characters.
+        assert_round_trips(&[
+            draft("ü"),
+            draft(""),
+            draft("日本"),
+            draft("本日本"),
+            draft("本日"),
+        ]);
+
+        let plain = |old, new| text::diff(old, new, text::Granularity::Lines);
+        let both = Compose::compose(plain("a\nb\n", "a\nβ\n"), plain("a\nβ\n", "α\nβ\n"));
+        let mut target = "a\nb\n".to_string();
+        text::apply(&mut target, both);
+        assert_eq!(target, "α\nβ\n");
+    }
+
+    #[cfg(feature = "unified-diff")]
+    #[test]
+    fn text_renders_a_unified_diff() {
+        let old = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+        let new = "one\n2\nthree\nfour\nfive\nsix\nseven\neight\n";
+        let delta = text::diff(old, new, text::Granularity::Lines);
+        assert_eq!(
+            delta.unified_diff(old),
+            "--- old\n+++ new\n@@ -1,7 +1,8 @@\n one\n-two\n+2\n three\n four\n five\n six\n seven\n+eight\n"
+        );
+        assert_eq!(TextDelta::default().unified_diff(old), "");
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, Hash, PartialEq, Eq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Track {
+        title: String,
+        seconds: u32,
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Credit {
+        id: u32,
+        name: String,
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Album {
+        #[delta_struct(field_type = "ordered-delta")]
+        tracks: Vec<Track>,
+        #[delta_struct(field_type = "ordered-delta", key = "id", key_type = "u32")]
+        credits: Vec<Credit>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug)]")]
+    struct Take {
+        id: u32,
+        seconds: u32,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug)]")]
+    struct Session {
+        #[delta_struct(field_type = "ordered-delta", key = "id", key_type = "u32")]
+        takes: Vec<Take>,
+    }
+
+    #[test]
+    fn ordered_delta_changes_kept_items_in_place() {
+        let track = |title: &str, seconds| Track {
+            title: title.to_string(),
+            seconds,
+        };
+        let credit = |id, name: &str| Credit {
+            id,
+            name: name.to_string(),
+        };
+        let old = Album {
+            tracks: vec![track("a", 200), track("b", 180), track("c", 240)],
+            credits: vec![credit(1, "ann"), credit(2, "bo"), credit(3, "cy")],
+        };
+        let new = Album {
+            tracks: vec![
+                track("a", 200),
+                track("b (live)", 185),
+                track("c", 240),
+                track("d", 100),
+            ],
+            credits: vec![credit(2, "bob"), credit(3, "cy"), credit(4, "di")],
+        };
+        assert!(Delta::delta(old.clone(), old.clone()).is_none());
+
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta, DeltaRef::delta_ref(&old, &new).unwrap());
+        // A replaced run pairs off by position, and the pair is diffed.
+        assert_eq!(
+            delta.tracks.change,
+            vec![IndexedDelta {
+                index: 1,
+                delta: TrackDelta {
+                    title: Some("b (live)".to_string()),
+                    seconds: Some(185),
+                },
+            }]
+        );
+        assert_eq!(
+            delta.tracks.splices,
+            vec![Splice {
+                at: 3,
+                remove: 0,
+                insert: vec![track("d", 100)],
+            }]
+        );
+        // By key, only the same id pairs up, wherever it moved to.
+        assert_eq!(delta.credits.change.len(), 1);
+        assert_eq!(delta.credits.change[0].index, 1);
+        assert_eq!(delta.credits.change[0].delta.name, Some("bob".to_string()));
+        assert_eq!(delta.credits.change[0].delta.id, None);
+        assert_eq!(
+            delta.credits.splices,
+            vec![
+                Splice {
+                    at: 0,
+                    remove: 1,
+                    insert: vec![],
+                },
+                Splice {
+                    at: 3,
+                    remove: 0,
+                    insert: vec![credit(4, "di")],
+                },
+            ]
+        );
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        let third = Album {
+            tracks: vec![track("b (live)", 190), track("d", 100)],
+            credits: vec![credit(4, "dee"), credit(5, "eve")],
+        };
+        let first = Delta::delta(old.clone(), new.clone()).unwrap();
+        let second = Delta::delta(new.clone(), third.clone()).unwrap();
+        let mut applied = old.clone();
+        applied.apply_delta(Compose::compose(first, second));
+        assert_eq!(applied, third);
+
+        let delta = Delta::delta(old, new).unwrap();
+        let mut short = Album {
+            tracks: vec![track("a", 200)],
+            credits: Vec::new(),
+        };
+        let error = short.try_apply_delta(delta).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("tracks")]);
+        assert!(matches!(
+            error.problem,
+            Problem::SpliceOutOfRange { len: 1, .. }
+        ));
+
+        let delta = OrderedDelta {
+            splices: Vec::new(),
+            change: vec![IndexedDelta {
+                index: 2,
+                delta: TrackDelta {
+                    title: None,
+                    seconds: Some(1),
+                },
+            }],
+        };
+        let error = ordered_delta::check(&vec![track("a", 200)], &delta).unwrap_err();
+        assert_eq!(
+            error.problem,
+            Problem::ChangeOutOfRange { index: 0, len: 1 }
+        );
+        // Applied anyway, the change is skipped.
+        let mut target = vec![track("a", 200)];
+        ordered_delta::apply(&mut target, delta);
+        assert_eq!(target, vec![track("a", 200)]);
+    }
+
+    #[test]
+    fn ordered_delta_inverts_and_composes() {
+        let session = |takes: &[(u32, u32)]| Session {
+            takes: takes
+                .iter()
+                .map(|&(id, seconds)| Take { id, seconds })
+                .collect(),
+        };
+        // The second step removes a take the first changed, and changes one
+        // the first inserted.
+        assert_round_trips(&[
+            session(&[(1, 10), (2, 20)]),
+            session(&[(0, 5), (1, 11), (2, 20)]),
+            session(&[(0, 6), (2, 21), (3, 30)]),
+        ]);
+        // Emptied, refilled, and changed in every take at once.
+        assert_round_trips(&[
+            session(&[(1, 10), (2, 20)]),
+            session(&[]),
+            session(&[(1, 10), (2, 20)]),
+            session(&[(1, 11), (2, 21)]),
+        ]);
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Queue {
+        #[delta_struct(field_type = "keyed-ordered", key = "id", key_type = "u32")]
+        credits: Vec<Credit>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug)]")]
+    struct Reel {
+        #[delta_struct(field_type = "keyed-ordered", key = "id", key_type = "u32")]
+        takes: Vec<Take>,
+    }
+
+    #[test]
+    fn keyed_ordered_moves_the_fewest_items() {
+        let queue = |credits: &[(u32, &str)]| Queue {
+            credits: credits
+                .iter()
+                .map(|&(id, name)| Credit {
+                    id,
+                    name: name.to_string(),
+                })
+                .collect(),
+        };
+        let old = queue(&[(1, "a"), (2, "b"), (3, "c"), (4, "d"), (5, "e")]);
+        assert!(Delta::delta(old.clone(), old.clone()).is_none());
+
+        // Two items swapped ends: one stays behind the three that kept their
+        // order, and the other moves.
+        let new = queue(&[(5, "e"), (2, "b"), (3, "c"), (4, "d"), (1, "a")]);
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta, DeltaRef::delta_ref(&old, &new).unwrap());
+        let moves = |delta: &QueueDelta| {
+            delta
+                .credits
+                .ops
+                .iter()
+                .filter(|op| matches!(op, KeyedOp::Move { .. }))
+                .count()
+        };
+        assert_eq!(moves(&delta), 2);
+        assert_eq!(delta.credits.ops.len(), 2);
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        // Reversing five items keeps one and moves the other four.
+        let new = queue(&[(5, "e"), (4, "d"), (3, "c"), (2, "b"), (1, "a")]);
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(moves(&delta), 4);
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        let new = queue(&[(6, "f"), (3, "c"), (1, "alpha"), (2, "b"), (7, "g")]);
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta, DeltaRef::delta_ref(&old, &new).unwrap());
+        assert_eq!(moves(&delta), 1);
+        match &delta.credits.ops[..2] {
+            [KeyedOp::Change { at: 0, delta }, KeyedOp::Remove { at: 4 }] => {
+                assert_eq!(delta.name, Some("alpha".to_string()));
+            }
+            other => panic!("expected the change and then a removal, got {:?}", other),
+        }
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        let third = queue(&[(7, "g"), (2, "beta")]);
+        let first = Delta::delta(old.clone(), new.clone()).unwrap();
+        let second = Delta::delta(new.clone(), third.clone()).unwrap();
+        let mut applied = old.clone();
+        applied.apply_delta(Compose::compose(first, second));
+        assert_eq!(applied, third);
+
+        let delta = Delta::delta(old, new).unwrap();
+        let mut short = queue(&[(1, "a")]);
+        let error = short.try_apply_delta(delta).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("credits")]);
+        assert!(matches!(
+            error.problem,
+            Problem::OpOutOfRange { len: 1, .. }
+        ));
+        assert_eq!(short, queue(&[(1, "a")]));
+    }
+
+    #[test]
+    fn keyed_ordered_inverts() {
+        let reel = |takes: &[(u32, u32)]| Reel {
+            takes: takes
+                .iter()
+                .map(|&(id, seconds)| Take { id, seconds })
+                .collect(),
+        };
+        let states = [
+            reel(&[(1, 10), (2, 20), (3, 30)]),
+            reel(&[(3, 31), (0, 5), (1, 10)]),
+            reel(&[(1, 11), (4, 40), (3, 31)]),
+        ];
+        for pair in states.windows(2) {
+            let delta = Delta::delta(pair[0].clone(), pair[1].clone()).unwrap();
+            let mut undone = pair[1].clone();
+            undone.apply_delta(delta.invert());
+            assert_eq!(undone, pair[0]);
+        }
+
+        let first = Delta::delta(states[0].clone(), states[1].clone()).unwrap();
+        let second = Delta::delta(states[1].clone(), states[2].clone()).unwrap();
+        let both = Compose::compose(first, second);
+        let mut applied = states[0].clone();
+        applied.apply_delta(both.clone());
+        assert_eq!(applied, states[2]);
+        applied.apply_delta(both.invert());
+        assert_eq!(applied, states[0]);
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Timeline {
+        #[delta_struct(field_type = "ordered", algorithm = "patience")]
+        patience: Vec<u32>,
+        #[delta_struct(field_type = "ordered", algorithm = "lcs")]
+        lcs: Vec<u32>,
+        #[delta_struct(field_type = "ordered", deadline = "0ns")]
+        hurried: Vec<u32>,
+    }
+
+    #[test]
+    fn ordered_algorithm_and_deadline() {
+        let timeline = |items: &[u32]| Timeline {
+            patience: items.to_vec(),
+            lcs: items.to_vec(),
+            hurried: items.to_vec(),
+        };
+        let old: Vec<u32> = (0..200).map(|i| i * 7 % 13).collect();
+        let new: Vec<u32> = (0..200).map(|i| i * 5 % 11).collect();
+        let (old, new) = (timeline(&old), timeline(&new));
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta, DeltaRef::delta_ref(&old, &new).unwrap());
+
+        let options = |algorithm| seq::DiffOptions {
+            algorithm,
+            deadline: None,
+        };
+        let expected =
+            |algorithm| seq::diff_with(old.lcs.clone(), new.lcs.clone(), options(algorithm));
+        assert_eq!(delta.patience, expected(seq::Algorithm::Patience));
+        assert_eq!(delta.lcs, expected(seq::Algorithm::Lcs));
+        // With no time at all, everything between the common prefix and
+        // suffix is replaced at once.
+        assert_eq!(delta.hurried.splices.len(), 1);
+        assert!(delta.patience.splices.len() > 1);
+
+        let mut applied = old;
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+    }
+
+    fn weight(item: &u32) -> usize {
+        *item as usize
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Shelf {
+        #[delta_struct(field_type = "ordered", replace_if_cheaper)]
+        books: Vec<u32>,
+        #[delta_struct(field_type = "unordered", replace_cost = "weight")]
+        crates: BTreeSet<u32>,
+    }
+
+    #[test]
+    fn replace_if_cheaper_picks_the_smaller_form() {
+        let shelf = |books: &[u32], crates: &[u32]| Shelf {
+            books: books.to_vec(),
+            crates: crates.iter().copied().collect(),
+        };
+        let check = |old: &Shelf, new: &Shelf| {
+            let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+            assert_eq!(delta, DeltaRef::delta_ref(old, new).unwrap());
+            let mut applied = old.clone();
+            applied.apply_delta(delta);
+            assert_eq!(&applied, new);
+            Delta::delta(old.clone(), new.clone()).unwrap()
+        };
+
+        // One book moved and one light crate swapped: both stay deltas.
+        let delta = check(
+            &shelf(&[1, 2, 3, 4], &[1, 50]),
+            &shelf(&[2, 3, 4, 1], &[2, 50]),
+        );
+        assert!(matches!(delta.books, OrReplace::Delta(_)));
+        assert_eq!(
+            delta.crates,
+            OrReplace::Delta(BagDelta {
+                add: vec![2],
+                remove: vec![1],
+            })
+        );
+
+        // Reversed books, and a heavy crate swapped for another, are cheaper
+        // sent whole. Ties go to the delta.
+        let delta = check(
+            &shelf(&[1, 2, 3, 4], &[1, 50]),
+            &shelf(&[4, 3, 2, 1], &[1, 60]),
+        );
+        assert_eq!(delta.books, OrReplace::Replace(vec![4, 3, 2, 1]));
+        assert_eq!(delta.crates, OrReplace::Replace(vec![1, 60]));
+
+        // A replacement fits whatever it is applied to.
+        let mut elsewhere = shelf(&[9], &[7]);
+        elsewhere.try_apply_delta(delta).unwrap();
+        assert_eq!(elsewhere, shelf(&[4, 3, 2, 1], &[1, 60]));
+
+        let delta = check(&shelf(&[1, 2], &[]), &shelf(&[2, 1], &[]));
+        assert!(matches!(delta.books, OrReplace::Delta(_)));
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Ledger {
+        #[delta_struct(field_type = "ordered", replace_if_cheaper)]
+        entries: Vec<u32>,
+        #[delta_struct(field_type = "unordered", replace_if_cheaper)]
+        accounts: BTreeSet<u32>,
+    }
+
+    #[test]
+    fn replace_if_cheaper_inverts_and_composes() {
+        let ledger = |entries: &[u32], accounts: &[u32]| Ledger {
+            entries: entries.to_vec(),
+            accounts: accounts.iter().copied().collect(),
+        };
+        let states = [
+            ledger(&[1, 2, 3], &[1, 2, 3]),
+            ledger(&[1, 2, 3, 4], &[1, 2, 4]),
+            ledger(&[6, 5, 4, 3, 2, 1], &[7, 8, 9]),
+            ledger(&[6, 5, 4, 3], &[7, 8]),
+        ];
+        let deltas = states
+            .windows(2)
+            .map(|pair| Delta::delta(pair[0].clone(), pair[1].clone()).unwrap())
+            .collect::<Vec<_>>();
+        assert!(matches!(deltas[0].entries, OrReplace::Delta(_)));
+        assert!(matches!(deltas[1].entries, OrReplace::Replace(_)));
+        assert!(matches!(deltas[1].accounts, OrReplace::Replace(_)));
+        assert!(matches!(deltas[2].accounts, OrReplace::Delta(_)));
+
+        // Every way of composing a delta with a replacement, either side.
+        for (first, last) in [(0, 2), (1, 3), (0, 3)] {
+            assert_round_trips(&states[first..=last]);
+        }
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Desk {
+        #[delta_struct(field_type = "indexed")]
+        channels: [Credit; 3],
+        #[delta_struct(field_type = "indexed")]
+        levels: Vec<u8>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug)]")]
+    struct Rack {
+        #[delta_struct(field_type = "indexed")]
+        slots: Vec<Take>,
+    }
+
+    #[test]
+    fn indexed_pairs_items_by_position() {
+        let credit = |id, name: &str| Credit {
+            id,
+            name: name.to_string(),
+        };
+        let old = Desk {
+            channels: [credit(1, "a"), credit(2, "b"), credit(3, "c")],
+            levels: vec![1, 2, 3, 4],
+        };
+        assert!(Delta::delta(old.clone(), old.clone()).is_none());
+
+        let new = Desk {
+            channels: [credit(1, "a"), credit(2, "beta"), credit(3, "c")],
+            levels: vec![1, 9, 3],
+        };
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta, DeltaRef::delta_ref(&old, &new).unwrap());
+        match &delta.channels.change[..] {
+            [IndexedDelta { index: 1, delta }] => {
+                assert_eq!(delta.name, Some("beta".to_string()));
+                assert_eq!(delta.id, None);
+            }
+            other => panic!("expected one change at 1, got {:?}", other),
+        }
+        assert!(delta.channels.tail.is_none());
+        assert_eq!(delta.levels.change.len(), 1);
+        assert_eq!(
+            delta.levels.tail,
+            Some(Splice {
+                at: 3,
+                remove: 1,
+                insert: vec![]
+            })
+        );
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        let grown = Desk {
+            levels: vec![1, 9, 3, 7, 8],
+            ..new.clone()
+        };
+        let delta = Delta::delta(new.clone(), grown.clone()).unwrap();
+        assert!(delta.channels.is_empty());
+        assert!(delta.levels.change.is_empty());
+        let mut applied = new.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, grown);
+
+        // A tail has to end where the target does.
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        let mut short = Desk {
+            levels: vec![1, 2],
+            ..old.clone()
+        };
+        let error = short.try_apply_delta(delta).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("levels")]);
+        assert_eq!(error.problem, Problem::WrongLength { len: 2 });
+        assert_eq!(short.levels, vec![1, 2]);
+
+        let delta = Delta::delta(old.clone(), grown).unwrap();
+        let mut shorter = Desk {
+            levels: vec![1],
+            ..old
+        };
+        let error = shorter.try_apply_delta(delta).unwrap_err();
+        assert_eq!(
+            error.problem,
+            Problem::ChangeOutOfRange { index: 0, len: 1 }
+        );
+    }
+
+    #[test]
+    fn indexed_inverts_and_composes() {
+        let rack = |slots: &[(u32, u32)]| Rack {
+            slots: slots
+                .iter()
+                .map(|&(id, seconds)| Take { id, seconds })
+                .collect(),
+        };
+        let states = [
+            rack(&[(1, 10), (2, 20), (3, 30)]),
+            rack(&[(1, 11), (2, 20), (3, 31), (4, 40), (5, 50)]),
+            rack(&[(1, 11), (2, 22)]),
+            rack(&[(1, 12), (2, 22), (6, 60)]),
+            rack(&[(1, 12), (2, 22), (6, 60)]),
+        ];
+        assert_round_trips(&states);
+        for (i, j, k) in [(0, 1, 2), (1, 2, 3), (0, 2, 3), (0, 1, 3), (2, 1, 0)] {
+            assert_round_trips(&[states[i].clone(), states[j].clone(), states[k].clone()]);
+        }
+    }
+
+    #[test]
+    fn indexed_array_skips_a_tail() {
+        let credit = |id, name: &str| Credit {
+            id,
+            name: name.to_string(),
+        };
+        let old = Desk {
+            channels: [credit(1, "a"), credit(2, "b"), credit(3, "c")],
+            levels: vec![],
+        };
+        // An array never diffs to a tail, but can be handed a delta from a
+        // sequence that grew.
+        let grown = || DeskDelta {
+            channels: indexed::diff(
+                old.channels.to_vec(),
+                vec![
+                    credit(1, "a"),
+                    credit(2, "beta"),
+                    credit(3, "c"),
+                    credit(4, "d"),
+                ],
+            ),
+            levels: indexed::PositionalDelta::default(),
+        };
+        assert!(grown().channels.tail.is_some());
+
+        // Its changes land and its tail does not; applied strictly, it is
+        // refused whole.
+        let mut applied = old.clone();
+        applied.apply_delta(grown());
+        assert_eq!(applied.channels[1], credit(2, "beta"));
+        assert_eq!(applied.channels.len(), 3);
+        let mut strict = old.clone();
+        let error = strict.try_apply_delta(grown()).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("channels")]);
+        assert_eq!(error.problem, Problem::WrongLength { len: 3 });
+        assert_eq!(strict, old);
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug)]")]
+    struct History {
+        #[delta_struct(field_type = "ordered", in_place)]
+        recent: std::collections::VecDeque<u32>,
+        #[delta_struct(field_type = "ordered", in_place)]
+        older: std::collections::LinkedList<u32>,
+        // Not a `SpliceTarget`, so rebuilt.
+        #[delta_struct(field_type = "ordered")]
+        frozen: Box<[u32]>,
+    }
+
+    #[test]
+    fn ordered_applies_splices_in_place() {
+        let splice = |at, remove, insert: &str| seq::Splice {
+            at,
+            remove,
+            insert: insert.chars().collect::<Vec<_>>(),
+        };
+        let deltas = vec![
+            vec![splice(2, 1, "xy")],
+            vec![splice(0, 1, ""), splice(3, 0, "z"), splice(5, 1, "!")],
+            vec![splice(6, 0, "end")],
+            // Out of order and past the end: clamped alike everywhere.
+            vec![splice(4, 1, "a"), splice(1, 9, "b"), splice(40, 2, "c")],
+        ];
+        for splices in deltas {
+            let mut rebuilt: Vec<char> = "abcdef".chars().collect();
+            seq::rebuild(&mut rebuilt, splices.clone());
+
+            let mut vec: Vec<char> = "abcdef".chars().collect();
+            seq::apply_in_place(
+                &mut vec,
+                SeqDelta {
+                    splices: splices.clone(),
+                },
+            );
+            assert_eq!(vec, rebuilt);
+            let mut deque: std::collections::VecDeque<char> = "abcdef".chars().collect();
+            seq::apply_in_place(
+                &mut deque,
+                SeqDelta {
+                    splices: splices.clone(),
+                },
+            );
+            assert!(deque.iter().eq(rebuilt.iter()));
+            let mut string = "abcdef".to_string();
+            seq::apply_in_place(&mut string, SeqDelta { splices });
+            assert!(string.chars().eq(rebuilt.iter().copied()));
+        }
+
+        // A `String` counts characters, not bytes.
+        let mut string = "naïve café".to_string();
+        let delta = SeqDelta {
+            splices: vec![splice(3, 2, "f"), splice(9, 1, "e")],
+        };
+        seq::apply_in_place(&mut string, delta);
+        assert_eq!(string, "naïf cafe");
+
+        // A splice at the end leaves the items ahead of it where they are.
+        let mut long: Vec<u32> = Vec::with_capacity(1_001);
+        long.extend(0..1_000);
+        let buffer = long.as_ptr();
+        let delta = SeqDelta {
+            splices: vec![seq::Splice {
+                at: 1_000,
+                remove: 0,
+                insert: vec![1_000],
+            }],
+        };
+        seq::apply_in_place(&mut long, delta);
+        assert_eq!(long.as_ptr(), buffer);
+        assert!(long.iter().copied().eq(0..1_001));
+
+        let old = History {
+            recent: (0..5).collect(),
+            older: (0..5).collect(),
+            frozen: (0..5).collect(),
+        };
+        let new = History {
+            recent: vec![0, 9, 2, 3].into_iter().collect(),
+            older: vec![1, 2, 3, 4, 5].into_iter().collect(),
+            frozen: vec![4, 0, 1].into_iter().collect(),
+        };
+        let mut applied = old.clone();
+        applied.apply_delta(Delta::delta(old, new.clone()).unwrap());
+        assert_eq!(applied, new);
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Pantry {
+        #[delta_struct(field_type = "multiset")]
+        stock: BTreeMap<String, usize>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug)]")]
+    struct Ballot {
+        #[delta_struct(field_type = "multiset")]
+        votes: HashMap<char, usize>,
+    }
+
+    #[test]
+    fn multiset_counts_duplicates() {
+        let pantry = |stock: &[(&str, usize)]| Pantry {
+            stock: stock
+                .iter()
+                .map(|&(item, n)| (item.to_string(), n))
+                .collect(),
+        };
+        let old = pantry(&[("egg", 6), ("flour", 1), ("salt", 1)]);
+        assert!(Delta::delta(old.clone(), old.clone()).is_none());
+
+        let new = pantry(&[("egg", 2), ("flour", 3), ("milk", 1), ("salt", 1)]);
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta, DeltaRef::delta_ref(&old, &new).unwrap());
+        assert_eq!(
+            delta.stock.add,
+            vec![("flour".to_string(), 2), ("milk".to_string(), 1)]
+        );
+        assert_eq!(delta.stock.remove, vec![("egg".to_string(), 4)]);
+        let mut applied = old.clone();
+        applied.apply_delta(delta.clone());
+        assert_eq!(applied, new);
+
+        // Taking away more than is there leaves none rather than wrapping,
+        // and a strict apply refuses it.
+        let mut short = pantry(&[("egg", 3), ("flour", 1)]);
+        let error = short.clone().try_apply_delta(delta.clone()).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("stock")]);
+        assert_eq!(error.problem, Problem::TooFew { index: 0, count: 3 });
+        short.apply_delta(delta);
+        assert_eq!(short, pantry(&[("flour", 3), ("milk", 1)]));
+
+        // Count changes commute, so two deltas land the same in either order.
+        let restocked = pantry(&[("egg", 12), ("flour", 1), ("salt", 1)]);
+        let first = Delta::delta(old.clone(), new.clone()).unwrap();
+        let second = Delta::delta(old.clone(), restocked).unwrap();
+        let mut one_way = old.clone();
+        one_way.apply_delta(first.clone());
+        one_way.apply_delta(second.clone());
+        let mut other_way = old;
+        other_way.apply_delta(second);
+        other_way.apply_delta(first);
+        assert_eq!(one_way, other_way);
+        assert_eq!(
+            one_way,
+            pantry(&[("egg", 8), ("flour", 3), ("milk", 1), ("salt", 1)])
+        );
+    }
+
+    #[test]
+    fn multiset_inverts_and_composes() {
+        let ballot = |votes: &[(char, usize)]| Ballot {
+            votes: votes.iter().copied().collect(),
+        };
+        let both = assert_round_trips(&[
+            ballot(&[('a', 3), ('b', 1)]),
+            ballot(&[('a', 1), ('b', 4), ('c', 2)]),
+            ballot(&[('b', 4), ('c', 5)]),
+        ]);
+        assert_eq!(both.unwrap().votes.remove, vec![('a', 3)]);
+        // Counts as far apart as they get.
+        assert_round_trips(&[
+            ballot(&[]),
+            ballot(&[('a', usize::MAX)]),
+            ballot(&[('a', 1)]),
+        ]);
+
+        // Two senders' full counts sum past the largest there is. Composed,
+        // the sum saturates as applying them one after the other does.
+        let full = Delta::delta(ballot(&[]), ballot(&[('a', usize::MAX)])).unwrap();
+        let both = Compose::compose(full.clone(), full.clone());
+        assert_eq!(both.votes.add, vec![('a', usize::MAX)]);
+        let mut composed = ballot(&[('a', 1)]);
+        composed.apply_delta(both);
+        let mut in_turn = ballot(&[('a', 1)]);
+        in_turn.apply_delta(full.clone());
+        in_turn.apply_delta(full);
+        assert_eq!(composed, in_turn);
+        assert_eq!(composed, ballot(&[('a', usize::MAX)]));
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Pod {
+        #[delta_struct(field_type = "unordered-scalar")]
+        labels: BTreeMap<String, String>,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug)]")]
+    struct Manifest {
+        #[delta_struct(field_type = "unordered-scalar")]
+        limits: HashMap<&'static str, f64>,
+    }
+
+    #[test]
+    fn unordered_scalar_sends_changed_values_whole() {
+        let pod = |labels: &[(&str, &str)]| Pod {
+            labels: labels
+                .iter()
+                .map(|&(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+        let old = pod(&[("app", "web"), ("env", "dev"), ("team", "core")]);
+        assert!(Delta::delta(old.clone(), old.clone()).is_none());
+
+        let new = pod(&[("app", "web"), ("env", "prod"), ("zone", "b")]);
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta, DeltaRef::delta_ref(&old, &new).unwrap());
+        assert_eq!(
+            delta.labels.add,
+            vec![("zone".to_string(), "b".to_string())]
+        );
+        assert_eq!(delta.labels.remove, vec!["team".to_string()]);
+        assert_eq!(delta.labels.change.len(), 1);
+        assert_eq!(delta.labels.change[0].key, "env");
+        assert_eq!(delta.labels.change[0].delta, "prod");
+        let mut applied = old.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, new);
+
+        let third = pod(&[("app", "api"), ("zone", "c")]);
+        let first = Delta::delta(old.clone(), new.clone()).unwrap();
+        let second = Delta::delta(new.clone(), third.clone()).unwrap();
+        let mut applied = old.clone();
+        applied.apply_delta(Compose::compose(first, second));
+        assert_eq!(applied, third);
+
+        let delta = Delta::delta(old, new).unwrap();
+        let mut target = pod(&[("app", "web"), ("team", "core")]);
+        let error = target.try_apply_delta(delta).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("labels")]);
+        assert_eq!(error.problem, Problem::MissingKey { index: 0 });
+        assert_eq!(target, pod(&[("app", "web"), ("team", "core")]));
+    }
+
+    #[test]
+    fn unordered_scalar_inverts_and_composes() {
+        let manifest = |limits: &[(&'static str, f64)]| Manifest {
+            limits: limits.iter().copied().collect(),
+        };
+        let both = assert_round_trips(&[
+            manifest(&[("cpu", 0.5), ("memory", 256.0)]),
+            manifest(&[("cpu", 1.0), ("memory", 256.0), ("disk", 10.0)]),
+            manifest(&[("cpu", 2.0), ("disk", 20.0)]),
+        ])
+        .unwrap();
+        assert_eq!(both.limits.remove, vec![("memory", 256.0)]);
+        assert_eq!(both.limits.change[0].delta, Change { old: 0.5, new: 2.0 });
+
+        // Emptied, and refilled with another value.
+        let both = assert_round_trips(&[
+            manifest(&[("cpu", 0.5)]),
+            manifest(&[]),
+            manifest(&[("cpu", 4.0)]),
+        ])
+        .unwrap();
+        // Both sides stay: applying removes before it adds.
+        assert_eq!(both.limits.remove, vec![("cpu", 0.5)]);
+        assert_eq!(both.limits.add, vec![("cpu", 4.0)]);
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Stats {
+        #[delta_struct(field_type = "counter")]
+        hits: u32,
+        #[delta_struct(field_type = "counter", overflow = "wrapping")]
+        tick: u8,
+        #[delta_struct(field_type = "counter")]
+        load: f64,
+    }
+
+    #[derive(Clone, Debug, Delta, PartialEq)]
+    #[delta_struct(reversible, delta_leader = "#[derive(Clone, Debug, PartialEq)]")]
+    struct Meter<T: counter::Counter> {
+        #[delta_struct(field_type = "counter")]
+        reading: T,
+    }
+
+    #[test]
+    fn counter_increments_commute() {
+        let stats = |hits, tick, load| Stats { hits, tick, load };
+        let base = stats(10, 250, 1.5);
+        assert!(Delta::delta(base.clone(), base.clone()).is_none());
+
+        let first = Delta::delta(base.clone(), stats(13, 4, 1.5)).unwrap();
+        let second = Delta::delta(base.clone(), stats(8, 251, 2.0)).unwrap();
+        assert_eq!(first.hits, Some(3));
+        assert_eq!(first.tick, Some(-246));
+        assert_eq!(first.load, None);
+        assert_eq!(
+            first,
+            DeltaRef::delta_ref(&base, &stats(13, 4, 1.5)).unwrap()
+        );
+
+        // Both senders' changes land, whichever arrives first.
+        let mut one = base.clone();
+        one.apply_delta(first.clone());
+        one.apply_delta(second.clone());
+        let mut other = base.clone();
+        other.apply_delta(second.clone());
+        other.apply_delta(first.clone());
+        assert_eq!(one, stats(11, 5, 2.0));
+        assert_eq!(other, one);
+
+        // A wrapping counter wraps; a checked one skips the increment, and
+        // refuses it when applied strictly.
+        let bump = StatsDelta {
+            hits: Some(-20),
+            tick: Some(10),
+            load: None,
+        };
+        let mut applied = base.clone();
+        applied.apply_delta(bump.clone());
+        assert_eq!(applied, stats(10, 4, 1.5));
+        let error = base.clone().try_apply_delta(bump).unwrap_err();
+        assert_eq!(error.path, vec![PathSegment::Field("hits")]);
+        assert_eq!(error.problem, Problem::Overflow);
+    }
+
+    #[test]
+    fn counter_inverts_and_composes() {
+        let both = assert_round_trips(&[-3i64, 7, 7, 2].map(|reading| Meter { reading }));
+        assert_eq!(both.unwrap().reading, Some(5));
+        // An increment and its undo cancel out.
+        let both = assert_round_trips(&[-3i64, 7, -3].map(|reading| Meter { reading }));
+        assert_eq!(both.unwrap().reading, None);
+
+        // Each step spans the whole range, one way or the other.
+        let unsigned = [0, u128::MAX, 0, u128::MAX, 1];
+        assert_round_trips(&unsigned.map(|reading| Meter { reading }));
+        let signed = [i128::MIN, i128::MAX, 0, i128::MIN];
+        assert_round_trips(&signed.map(|reading| Meter { reading }));
+    }
+
+    /// Applies `delta` to a copy of `meter` with `try_apply_delta`.
+    fn strictly<T: counter::Counter + Clone>(
+        meter: &Meter<T>,
+        delta: &MeterDelta<T>,
+    ) -> Result<Meter<T>, ApplyError> {
+        let mut applied = meter.clone();
+        applied.try_apply_delta(delta.clone())?;
+        Ok(applied)
+    }
+
+    #[test]
+    fn counter_round_trips_at_128_bit_extremes() {
+        // The increment between the two ends of a 128-bit range does not fit
+        // in any primitive, and has to get there and back unchecked.
+        let (low, high) = (Meter { reading: 0u128 }, Meter { reading: u128::MAX });
+        let up = Delta::delta(low.clone(), high.clone()).unwrap();
+        assert_eq!(up.reading, Some(counter::WideIncrement::from(u128::MAX)));
+        assert_eq!(strictly(&low, &up), Ok(high.clone()));
+        assert_eq!(strictly(&high, &up.clone().invert()), Ok(low.clone()));
+        let error = strictly(&high, &up).unwrap_err();
+        assert_eq!(error.problem, Problem::Overflow);
+
+        let (low, high) = (Meter { reading: i128::MIN }, Meter { reading: i128::MAX });
+        let up = Delta::delta(low.clone(), high.clone()).unwrap();
+        assert_eq!(strictly(&low, &up), Ok(high.clone()));
+        assert_eq!(strictly(&high, &up.clone().invert()), Ok(low.clone()));
+
+        // Composing runs past either end on the way and still lands.
+        let twice = Compose::compose(up.clone(), up.clone());
+        assert_eq!(Compose::compose(twice.clone(), up.clone().invert()), up);
+        assert!(strictly(&low, &twice).is_err());
+    }
+
+    #[test]
+    fn float_counter_refuses_a_non_finite_sum() {
+        let infinite = Meter {
+            reading: f64::INFINITY,
+        };
+        let delta = Delta::delta(infinite.clone(), Meter { reading: 1.0 }).unwrap();
+        assert_eq!(delta.reading, Some(f64::NEG_INFINITY));
+
+        // Adding the increment back gives NaN rather than 1.0, so a checked
+        // counter skips it, and refuses it when applied strictly.
+        let error = strictly(&infinite, &delta).unwrap_err();
+        assert_eq!(error.problem, Problem::Overflow);
+        let mut applied = infinite.clone();
+        applied.apply_delta(delta);
+        assert_eq!(applied, infinite);
+    }
+
+    #[test]
+    fn float_counter_does_not_diff_nan_against_itself() {
+        let nan = || Meter { reading: f64::NAN };
+        assert!(Delta::delta(nan(), nan()).is_none());
+        let delta = Delta::delta(Meter { reading: 1.0 }, nan()).unwrap();
+        assert!(delta.reading.unwrap().is_nan());
+    }
+
+    #[test]
+    fn sorted_collections_diff_by_merging() {
+        let old: Vec<u32> = (0..200).filter(|n| n % 3 != 0).collect();
+        let new: Vec<u32> = (0..200).filter(|n| n % 5 != 0).collect();
+
+        // A tree merges its two sides; a hash looks each element up. The
+        // two have to agree, up to the order the hash iterates in.
+        let tree_set = |items: &[u32]| items.iter().copied().collect::<BTreeSet<_>>();
+        let hash_set = |items: &[u32]| items.iter().copied().collect::<HashSet<_>>();
+        let merged = bag::diff(tree_set(&old), tree_set(&new));
+        assert_eq!(merged, bag::diff_ref(&tree_set(&old), &tree_set(&new)));
+        let mut looked_up = bag::diff(hash_set(&old), hash_set(&new));
+        looked_up.add.sort_unstable();
+        looked_up.remove.sort_unstable();
+        assert_eq!(merged, looked_up);
+        let added: Vec<u32> = (0..200).filter(|n| n % 3 == 0 && n % 5 != 0).collect();
+        assert_eq!(merged.add, added);
+
+        let tree_map = |items: &[u32], m| items.iter().map(|&n| (n, n % m)).collect();
+        let hash_map =
+            |items: &[u32], m| -> HashMap<u32, u32> { items.iter().map(|&n| (n, n % m)).collect() };
+        let (old_tree, new_tree): (BTreeMap<u32, u32>, BTreeMap<u32, u32>) =
+            (tree_map(&old, 7), tree_map(&new, 4));
+        let merged = map::diff_reversible(old_tree.clone(), new_tree.clone());
+        assert_eq!(merged, map::diff_ref_reversible(&old_tree, &new_tree));
+        let mut looked_up = map::diff_reversible(hash_map(&old, 7), hash_map(&new, 4));
+        looked_up.add.sort_unstable();
+        looked_up.remove.sort_unstable();
+        looked_up.change.sort_unstable_by_key(|change| change.key);
+        assert_eq!(merged, looked_up);
+
+        let merged = scalar_map::diff(old_tree.clone(), new_tree.clone());
+        assert_eq!(merged, scalar_map::diff_ref(&old_tree, &new_tree));
+        let mut looked_up = scalar_map::diff(hash_map(&old, 7), hash_map(&new, 4));
+        looked_up.add.sort_unstable();
+        looked_up.remove.sort_unstable();
+        looked_up.change.sort_unstable_by_key(|change| change.key);
+        assert_eq!(merged, looked_up);
+        let mut applied = old_tree;
+        scalar_map::apply(&mut applied, merged);
+        assert_eq!(applied, new_tree);
+
+        // Some counts are zero, which neither way of diffing may report.
+        let tree_counts = |items: &[u32], m| -> BTreeMap<u32, usize> {
+            items.iter().map(|&n| (n, n as usize % m)).collect()
+        };
+        let hash_counts = |items: &[u32], m| -> HashMap<u32, usize> {
+            items.iter().map(|&n| (n, n as usize % m)).collect()
+        };
+        let merged = multiset::diff(tree_counts(&old, 4), tree_counts(&new, 3));
+        assert_eq!(
+            merged,
+            multiset::diff_ref(&tree_counts(&old, 4), &tree_counts(&new, 3))
+        );
+        let mut looked_up = multiset::diff(hash_counts(&old, 4), hash_counts(&new, 3));
+        looked_up.add.sort_unstable();
+        looked_up.remove.sort_unstable();
+        assert_eq!(merged, looked_up);
+
+        // A replacement lists the new set in its own order, whether it was
+        // diffed owned or borrowed.
+        let (old, new) = (tree_set(&[3, 4, 5, 9]), tree_set(&[1, 2, 5]));
+        let owned = replace::diff_bag(old.clone(), new.clone(), replace::count);
+        assert_eq!(owned, OrReplace::Replace(vec![1, 2, 5]));
+        assert_eq!(owned, replace::diff_bag_ref(&old, &new, replace::count));
+        let owned = replace::diff_bag_reversible(old.clone(), new.clone(), replace::count);
+        assert_eq!(
+            owned,
+            OrReplace::Replace(Change {
+                old: vec![3, 4, 5, 9],
+                new: vec![1, 2, 5],
+            })
+        );
+        assert_eq!(
+            owned,
+            replace::diff_bag_ref_reversible(&old, &new, replace::count)
+        );
+    }
+
+    #[derive(Clone, Debug, Delta, DeltaRef, Fingerprint, PartialEq)]
+    #[delta_struct(delta_leader = "#[derive(Debug, PartialEq)]")]
+    struct Stockroom {
+        #[delta_struct(field_type = "unordered")]
+        skus: SortedVec<u32>,
+        #[delta_struct(field_type = "unordered-delta")]
+        stock: SortedVecMap<String, u32>,
+    }
+
+    #[test]
+    fn sorted_vecs_diff_as_unordered_fields() {
+        let stockroom = |skus: &[u32], stock: &[(&str, u32)]| Stockroom {
+            skus: skus.iter().copied().collect(),
+            stock: stock
+                .iter()
+                .map(|&(name, count)| (name.to_string(), count))
+                .collect(),
+        };
+        let old = stockroom(&[7, 3, 5, 3], &[("bolt", 10), ("nut", 4)]);
+        assert_eq!(old.skus.as_slice(), &[3, 5, 7]);
+        let new = stockroom(&[9, 5, 3], &[("nut", 6), ("washer", 1)]);
+
+        let delta = Delta::delta(old.clone(), new.clone()).unwrap();
+        assert_eq!(delta, DeltaRef::delta_ref(&old, &new).unwrap());
+        assert_eq!(delta.skus.add, vec![9]);
+        assert_eq!(delta.skus.remove, vec![7]);
+        assert_eq!(delta.stock.add, vec![("washer".to_string(), 1)]);
+        assert_eq!(delta.stock.remove, vec!["bolt".to_string()]);
+        assert_eq!(delta.stock.change[0].delta, 6);
+        let mut applied = old.clone();
+        applied.try_apply_delta(delta).unwrap();
+        assert_eq!(applied, new);
+
+        // Extending keeps a set's element and replaces a map's value, as
+        // the tree collections do.
+        let mut stock = new.stock.clone();
+        stock.extend(vec![("nut".to_string(), 8), ("axle".to_string(), 2)]);
+        assert_eq!(stock.get(&"nut".to_string()), Some(&8));
+        assert_eq!(stock.iter().next(), Some((&"axle".to_string(), &2)));
+
+        // A set fingerprints as the tree would.
+        let skus: SortedVec<u32> = vec![4, 1, 4, 2].into();
+        let tree: BTreeSet<u32> = skus.iter().copied().collect();
+        assert_eq!(fingerprint_of(&skus), fingerprint_of(&tree));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sorted_vecs_sort_when_deserialized() {
+        let skus: SortedVec<u32> = serde_json::from_str("[4, 1, 4, 2]").unwrap();
+        assert_eq!(serde_json::to_string(&skus).unwrap(), "[1,2,4]");
+        let stock: SortedVecMap<String, u32> =
+            serde_json::from_str(r#"{"nut": 6, "bolt": 1}"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&stock).unwrap(),
+            r#"{"bolt":1,"nut":6}"#
+        );
+    }
 }
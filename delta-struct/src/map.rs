@@ -7,10 +7,15 @@
 //! a re-send of the whole thing. Reach for it when a map's values are
 //! themselves large structs that tend to change a field at a time.
 //!
-//! The derive emits calls to [`diff`] and [`apply`]; you only need this module
-//! directly to inspect or construct a delta by hand.
+//! The derive emits calls to [`diff`] and [`apply`], or to [`diff_ref`] for
+//! [`DeltaRef`], and to [`check`] for [`Delta::try_apply_delta`]; you only
+//! need this module directly to inspect or construct a delta by hand. A `reversible` type's field gets a [`ReversibleMapDelta`]
+//! instead, from [`diff_reversible`] or [`diff_ref_reversible`].
 
-use crate::{Delta, TryIndex, TryIndexMut};
+use crate::check::{all_present, ApplyError, PathSegment, Problem};
+use crate::index::{merge_join, Joined};
+use crate::{Compose, Delta, DeltaRef, Invert, TryIndex, TryIndexMut};
+use std::hash::Hash;
 
 /// An entry that splits into a key and a value.
 ///
@@ -105,6 +110,71 @@ pub struct KeyedDelta<K, D> {
     pub delta: D,
 }
 
+impl<K, D: Invert> Invert for KeyedDelta<K, D> {
+    fn invert(self) -> Self {
+        KeyedDelta {
+            key: self.key,
+            delta: self.delta.invert(),
+        }
+    }
+}
+
+/// A [`MapDelta`] that also keeps the values it removes, so that it can be
+/// [inverted](Invert).
+///
+/// This is what an `unordered-delta` or `keyed` field turns into on a
+/// `reversible` type. Inverting swaps `add` and `remove` and inverts each
+/// change, which needs the values' own deltas to be reversible too.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReversibleMapDelta<K, V, D> {
+    /// Entries whose keys are in the new collection but not the old one.
+    pub add: Vec<(K, V)>,
+    /// Entries whose keys were in the old collection but are not in the new
+    /// one, values and all.
+    pub remove: Vec<(K, V)>,
+    /// Keys in both collections whose values differ, and how.
+    pub change: Vec<KeyedDelta<K, D>>,
+}
+
+impl<K, V, D> ReversibleMapDelta<K, V, D> {
+    /// Whether the two collections held the same entries, and so nothing needs
+    /// sending.
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.remove.is_empty() && self.change.is_empty()
+    }
+
+    /// Drops the removed values, which only undoing needs, leaving the
+    /// [`MapDelta`] that [`apply`] takes.
+    pub fn into_forward(self) -> MapDelta<K, V, D> {
+        MapDelta {
+            add: self.add,
+            remove: self.remove.into_iter().map(|(key, _)| key).collect(),
+            change: self.change,
+        }
+    }
+}
+
+impl<K, V, D> Default for ReversibleMapDelta<K, V, D> {
+    fn default() -> Self {
+        ReversibleMapDelta {
+            add: Vec::new(),
+            remove: Vec::new(),
+            change: Vec::new(),
+        }
+    }
+}
+
+impl<K, V, D: Invert> Invert for ReversibleMapDelta<K, V, D> {
+    fn invert(self) -> Self {
+        ReversibleMapDelta {
+            add: self.remove,
+            remove: self.add,
+            change: self.change.into_iter().map(Invert::invert).collect(),
+        }
+    }
+}
+
 /// Pairs the entries of `old` and `new` by key and diffs the values that
 /// survived.
 ///
@@ -113,8 +183,10 @@ pub struct KeyedDelta<K, D> {
 ///
 /// Each key of `old` is looked up in `new` exactly once, through the
 /// collection's own [`TryIndex`] implementation, so the cost is that of n
-/// lookups: O(n) for a [`HashMap`](std::collections::HashMap), O(n log n) for
-/// a [`BTreeMap`](std::collections::BTreeMap).
+/// lookups: O(n) for a [`HashMap`](std::collections::HashMap). A collection
+/// with a [key order](TryIndex::key_order), such as a
+/// [`BTreeMap`](std::collections::BTreeMap), is merged with the other side in
+/// one pass instead, in O(n + m).
 ///
 /// ```
 /// use delta_struct::{map, Delta};
@@ -139,12 +211,42 @@ pub struct KeyedDelta<K, D> {
 /// assert_eq!(delta.change[0].delta.port, Some(8080));
 /// assert_eq!(delta.change[0].delta.healthy, None);
 /// ```
-pub fn diff<C, E>(old: C, mut new: C) -> MapDelta<E::Key, E::Value, <E::Value as Delta>::Output>
+pub fn diff<C, E>(old: C, new: C) -> MapDelta<E::Key, E::Value, <E::Value as Delta>::Output>
 where
     C: IntoIterator<Item = E> + TryIndex<E::Key, Output = E::Value>,
     E: MapEntry,
     E::Value: Delta,
 {
+    diff_reversible(old, new).into_forward()
+}
+
+/// Computes the same delta as [`diff`], keeping the removed values so that it
+/// can be [inverted](Invert).
+pub fn diff_reversible<C, E>(
+    old: C,
+    mut new: C,
+) -> ReversibleMapDelta<E::Key, E::Value, <E::Value as Delta>::Output>
+where
+    C: IntoIterator<Item = E> + TryIndex<E::Key, Output = E::Value>,
+    E: MapEntry,
+    E::Value: Delta,
+{
+    if let Some(order) = new.key_order() {
+        let parts = |side: C| side.into_iter().map(MapEntry::into_parts);
+        let mut delta = ReversibleMapDelta::default();
+        for joined in merge_join(parts(old), parts(new), |(key, _)| key, order) {
+            match joined {
+                Joined::Old(entry) => delta.remove.push(entry),
+                Joined::New(entry) => delta.add.push(entry),
+                Joined::Both((key, old_value), (_, new_value)) => {
+                    if let Some(change) = Delta::delta(old_value, new_value) {
+                        delta.change.push(KeyedDelta { key, delta: change });
+                    }
+                }
+            }
+        }
+        return delta;
+    }
     // Take each of `old`'s entries out of `new` as it is matched, so whatever
     // is still standing at the end is exactly what was added. Taking rather
     // than borrowing is also what makes the values below owned, which is what
@@ -159,16 +261,133 @@ where
                     change.push(KeyedDelta { key, delta });
                 }
             }
-            None => remove.push(key),
+            None => remove.push((key, old_value)),
         }
     }
-    MapDelta {
+    ReversibleMapDelta {
         add: new.into_iter().map(MapEntry::into_parts).collect(),
         remove,
         change,
     }
 }
 
+/// Computes the same [`MapDelta`] as [`diff`] from borrowed collections,
+/// cloning only the keys and values that ended up in it.
+///
+/// Values present on both sides are diffed with [`DeltaRef::delta_ref`], so a
+/// changed value is never cloned whole. The collection has to iterate by
+/// reference as `(&K, &V)`, which every std map does; [`MapEntry`] plays no
+/// part here.
+///
+/// ```
+/// use delta_struct::{map, Delta, DeltaRef};
+/// use std::collections::BTreeMap;
+///
+/// #[derive(Clone, Delta, DeltaRef)]
+/// struct Service {
+///     port: u16,
+/// }
+///
+/// let services = |port| {
+///     vec![("web", Service { port })]
+///         .into_iter()
+///         .collect::<BTreeMap<&str, Service>>()
+/// };
+///
+/// let (old, new) = (services(80), services(8080));
+/// let delta = map::diff_ref(&old, &new);
+/// assert_eq!(delta.change[0].key, "web");
+/// assert_eq!(delta.change[0].delta.port, Some(8080));
+/// ```
+pub fn diff_ref<'a, C, K, V>(old: &'a C, new: &'a C) -> MapDelta<K, V, <V as Delta>::Output>
+where
+    &'a C: IntoIterator<Item = (&'a K, &'a V)>,
+    C: TryIndex<K, Output = V>,
+    K: Clone + 'a,
+    V: DeltaRef + Clone + 'a,
+{
+    let (add, remove, change) = diff_ref_with(old, new, |key, _| key.clone());
+    MapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// Computes the same delta as [`diff_ref`], cloning the removed values too so
+/// that it can be [inverted](Invert).
+pub fn diff_ref_reversible<'a, C, K, V>(
+    old: &'a C,
+    new: &'a C,
+) -> ReversibleMapDelta<K, V, <V as Delta>::Output>
+where
+    &'a C: IntoIterator<Item = (&'a K, &'a V)>,
+    C: TryIndex<K, Output = V>,
+    K: Clone + 'a,
+    V: DeltaRef + Clone + 'a,
+{
+    let (add, remove, change) = diff_ref_with(old, new, |key, value| (key.clone(), value.clone()));
+    ReversibleMapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// The parts of a borrowing diff, with each removal recorded by `removed`.
+#[allow(clippy::type_complexity)] // The three fields of either delta type
+fn diff_ref_with<'a, C, K, V, R>(
+    old: &'a C,
+    new: &'a C,
+    removed: impl Fn(&K, &V) -> R,
+) -> (Vec<(K, V)>, Vec<R>, Vec<KeyedDelta<K, V::Output>>)
+where
+    &'a C: IntoIterator<Item = (&'a K, &'a V)>,
+    C: TryIndex<K, Output = V>,
+    K: Clone + 'a,
+    V: DeltaRef + Clone + 'a,
+{
+    let mut remove = Vec::new();
+    let mut change = Vec::new();
+    if let Some(order) = new.key_order() {
+        let mut add = Vec::new();
+        for joined in merge_join(old, new, |(key, _)| *key, order) {
+            match joined {
+                Joined::Old((key, value)) => remove.push(removed(key, value)),
+                Joined::New((key, value)) => add.push((key.clone(), value.clone())),
+                Joined::Both((key, old_value), (_, new_value)) => {
+                    if let Some(delta) = DeltaRef::delta_ref(old_value, new_value) {
+                        change.push(KeyedDelta {
+                            key: key.clone(),
+                            delta,
+                        });
+                    }
+                }
+            }
+        }
+        return (add, remove, change);
+    }
+    for (key, old_value) in old {
+        match new.try_index(key) {
+            Some(new_value) => {
+                if let Some(delta) = DeltaRef::delta_ref(old_value, new_value) {
+                    change.push(KeyedDelta {
+                        key: key.clone(),
+                        delta,
+                    });
+                }
+            }
+            None => remove.push(removed(key, old_value)),
+        }
+    }
+    let add = new
+        .into_iter()
+        .filter(|(key, _)| old.try_index(key).is_none())
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    (add, remove, change)
+}
+
 /// Applies a keyed diff to `target` in place.
 ///
 /// Removals and changes are single lookups rather than scans, so this costs
@@ -176,7 +395,8 @@ where
 /// rather than taken out and put back. As with an `unordered` field,
 /// membership is preserved but position is not.
 ///
-/// A key in `remove` or `change` that `target` does not have is ignored.
+/// A key in `remove` or `change` that `target` does not have is ignored; use
+/// [`try_apply`] to have the delta refused instead.
 ///
 /// ```
 /// use delta_struct::{map, Delta};
@@ -222,3 +442,233 @@ where
             .map(|(key, value)| E::from_parts(key, value)),
     );
 }
+
+/// Checks that every key `delta` removes or changes is in `target`, and that
+/// each change fits the value under its key.
+///
+/// The first key that is missing is reported as [`Problem::NotPresent`] for
+/// a removal and [`Problem::MissingKey`] for a change; a change that does not
+/// fit its value is reported from inside it, under [`PathSegment::Change`].
+pub fn check<C, K, V>(target: &C, delta: &MapDelta<K, V, V::Output>) -> Result<(), ApplyError>
+where
+    C: TryIndex<K, Output = V>,
+    V: Delta,
+{
+    check_with(target, &delta.remove, &delta.change)
+}
+
+/// Checks a [`ReversibleMapDelta`] as [`check`] does a [`MapDelta`].
+pub fn check_reversible<C, K, V>(
+    target: &C,
+    delta: &ReversibleMapDelta<K, V, V::Output>,
+) -> Result<(), ApplyError>
+where
+    C: TryIndex<K, Output = V>,
+    V: Delta,
+{
+    check_with(
+        target,
+        delta.remove.iter().map(|(key, _)| key),
+        &delta.change,
+    )
+}
+
+/// The check of either delta type, given the keys it removes.
+fn check_with<'a, C, K, V>(
+    target: &C,
+    removed: impl IntoIterator<Item = &'a K>,
+    change: &[KeyedDelta<K, V::Output>],
+) -> Result<(), ApplyError>
+where
+    C: TryIndex<K, Output = V>,
+    K: 'a,
+    V: Delta,
+{
+    all_present(
+        removed,
+        |key| target.try_index(key).is_some(),
+        |index| Problem::NotPresent { index },
+    )?;
+    for (index, KeyedDelta { key, delta }) in change.iter().enumerate() {
+        match target.try_index(key) {
+            Some(value) => value
+                .check_delta(delta)
+                .map_err(|error| error.within(PathSegment::Change(index)))?,
+            None => return Err(ApplyError::new(Problem::MissingKey { index })),
+        }
+    }
+    Ok(())
+}
+
+/// Applies a keyed diff like [`apply`], but only if it passes [`check`];
+/// otherwise `target` is left as it was.
+pub fn try_apply<C, E>(
+    target: &mut C,
+    delta: MapDelta<E::Key, E::Value, <E::Value as Delta>::Output>,
+) -> Result<(), ApplyError>
+where
+    C: IntoIterator<Item = E> + Extend<E> + TryIndexMut<E::Key, Output = E::Value>,
+    E: MapEntry,
+    E::Value: Delta,
+{
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
+}
+
+/// Keys are matched with `Hash + Eq`. An entry the first delta adds and the
+/// second changes is added with the change already made, and a change
+/// followed by a removal is just the removal.
+impl<K, V, D> Compose for MapDelta<K, V, D>
+where
+    K: Hash + Eq,
+    V: Delta<Output = D>,
+    D: Compose,
+{
+    fn compose(first: Self, second: Self) -> Self {
+        let (add, remove, change) = compose_with(
+            (first.add, first.remove, first.change),
+            (second.add, second.remove, second.change),
+            |key| key,
+            |key, _| key,
+            V::apply_delta,
+            D::compose,
+        );
+        MapDelta {
+            add,
+            remove,
+            change,
+        }
+    }
+}
+
+/// As for [`MapDelta`], except that a value changed and then removed has to be
+/// changed back before it is recorded as removed, so the values' deltas have
+/// to be [invertible](Invert) too.
+impl<K, V, D> Compose for ReversibleMapDelta<K, V, D>
+where
+    K: Hash + Eq,
+    V: Delta<Output = D>,
+    D: Compose + Invert,
+{
+    fn compose(first: Self, second: Self) -> Self {
+        let (add, remove, change) = compose_with(
+            (first.add, first.remove, first.change),
+            (second.add, second.remove, second.change),
+            |(key, _)| key,
+            |(key, mut value), delta: D| {
+                value.apply_delta(delta.invert());
+                (key, value)
+            },
+            V::apply_delta,
+            D::compose,
+        );
+        ReversibleMapDelta {
+            add,
+            remove,
+            change,
+        }
+    }
+}
+
+/// The parts of a composed delta, for either delta type. `removed_key` finds
+/// the key of a removal, and `rewind` turns a removal of a changed value into
+/// a removal of the value from before the change. `apply` makes a change to
+/// a value the first delta added, and `compose` merges two changes to one
+/// value, which is all the composition needs to know about the values.
+///
+/// The [`scalar_map`](crate::scalar_map) module composes through this too.
+#[allow(clippy::type_complexity)] // The three fields of either delta type
+pub(crate) fn compose_with<K, V, D, R>(
+    first: (Vec<(K, V)>, Vec<R>, Vec<KeyedDelta<K, D>>),
+    second: (Vec<(K, V)>, Vec<R>, Vec<KeyedDelta<K, D>>),
+    removed_key: impl Fn(&R) -> &K,
+    rewind: impl Fn(R, D) -> R,
+    apply: impl Fn(&mut V, D),
+    compose: impl Fn(D, D) -> D,
+) -> (Vec<(K, V)>, Vec<R>, Vec<KeyedDelta<K, D>>)
+where
+    K: Hash + Eq,
+{
+    let (first_add, first_remove, first_change) = first;
+    let (second_add, second_remove, second_change) = second;
+
+    // Work out what meets what while the keys can still be borrowed, then
+    // take the deltas apart once that is settled.
+    let mut add_dropped = vec![false; first_add.len()];
+    let mut add_changed_by = vec![None; first_add.len()];
+    let mut remove_dropped = vec![false; second_remove.len()];
+    let mut remove_rewound_by = vec![None; second_remove.len()];
+    let mut change_composed_with = vec![None; first_change.len()];
+    {
+        let removed: std::collections::HashMap<&K, usize> = second_remove
+            .iter()
+            .enumerate()
+            .map(|(index, removal)| (removed_key(removal), index))
+            .collect();
+        let changed: std::collections::HashMap<&K, usize> = second_change
+            .iter()
+            .enumerate()
+            .map(|(index, change)| (&change.key, index))
+            .collect();
+        for (index, (key, _)) in first_add.iter().enumerate() {
+            if let Some(&removal) = removed.get(key) {
+                add_dropped[index] = true;
+                remove_dropped[removal] = true;
+            } else if let Some(&change) = changed.get(key) {
+                add_changed_by[index] = Some(change);
+            }
+        }
+        for (index, KeyedDelta { key, .. }) in first_change.iter().enumerate() {
+            if let Some(&removal) = removed.get(key) {
+                remove_rewound_by[removal] = Some(index);
+            } else if let Some(&change) = changed.get(key) {
+                change_composed_with[index] = Some(change);
+            }
+        }
+    }
+
+    let mut first_change: Vec<_> = first_change.into_iter().map(Some).collect();
+    let mut second_change: Vec<_> = second_change.into_iter().map(Some).collect();
+    let take_delta = |changes: &mut Vec<Option<KeyedDelta<K, D>>>, index: usize| {
+        changes[index].take().map(|change| change.delta)
+    };
+
+    let mut add = Vec::new();
+    for (index, (key, mut value)) in first_add.into_iter().enumerate() {
+        if add_dropped[index] {
+            continue;
+        }
+        if let Some(delta) = add_changed_by[index].and_then(|j| take_delta(&mut second_change, j)) {
+            apply(&mut value, delta);
+        }
+        add.push((key, value));
+    }
+    add.extend(second_add);
+
+    let mut remove = first_remove;
+    for (index, removal) in second_remove.into_iter().enumerate() {
+        if remove_dropped[index] {
+            continue;
+        }
+        match remove_rewound_by[index].and_then(|i| take_delta(&mut first_change, i)) {
+            Some(delta) => remove.push(rewind(removal, delta)),
+            None => remove.push(removal),
+        }
+    }
+
+    let mut change = Vec::new();
+    for (index, first) in first_change.iter_mut().enumerate() {
+        if let Some(KeyedDelta { key, delta }) = first.take() {
+            let delta =
+                match change_composed_with[index].and_then(|j| take_delta(&mut second_change, j)) {
+                    Some(second) => compose(delta, second),
+                    None => delta,
+                };
+            change.push(KeyedDelta { key, delta });
+        }
+    }
+    change.extend(second_change.into_iter().flatten());
+
+    (add, remove, change)
+}
@@ -0,0 +1,297 @@
+//! Count diffing, behind the `multiset` field type.
+//!
+//! A field marked `#[delta_struct(field_type = "multiset")]` is a bag whose
+//! elements can repeat, kept as a count per element, such as a
+//! `HashMap<Tag, usize>`. Its delta is a [`MultisetDelta`]: by how much each
+//! element's count went up, and by how much each went down, rather than the
+//! counts themselves.
+//!
+//! ```
+//! use delta_struct::Delta;
+//! use std::collections::HashMap;
+//!
+//! #[derive(Delta)]
+//! struct Cart {
+//!     #[delta_struct(field_type = "multiset")]
+//!     items: HashMap<String, usize>,
+//! }
+//!
+//! let cart = |items: &[(&str, usize)]| Cart {
+//!     items: items.iter().map(|&(item, n)| (item.to_string(), n)).collect(),
+//! };
+//!
+//! let delta = Delta::delta(cart(&[("apple", 3), ("pear", 1)]), cart(&[("apple", 5)])).unwrap();
+//! assert_eq!(delta.items.add, vec![("apple".to_string(), 2)]);
+//! assert_eq!(delta.items.remove, vec![("pear".to_string(), 1)]);
+//! ```
+//!
+//! Applying a delta adds and takes away counts, so two deltas applied in
+//! either order leave the same counts, as long as neither takes away more
+//! than is there. A count never goes below zero: a removal of more than the
+//! target holds leaves none. Use [`try_apply`] to
+//! have such a delta refused instead.
+//!
+//! The field has to be a [`Multiset`] that iterates as `(element, count)`
+//! pairs, which a `HashMap` or `BTreeMap` of `usize` counts is. The derive
+//! emits calls to [`diff`] and [`apply`], or to [`diff_ref`] for
+//! [`DeltaRef`](crate::DeltaRef), and to [`check`] for
+//! [`Delta::try_apply_delta`](crate::Delta::try_apply_delta). Like a
+//! [`BagDelta`](crate::BagDelta), a [`MultisetDelta`] records both sides
+//! already, so a `reversible` type's field has the same delta.
+
+use crate::check::{ApplyError, Problem};
+use crate::index::{merge_join, Joined, Multiset};
+use crate::{Compose, Invert, MapEntry};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+/// A count diff between two multisets: how many more of some elements there
+/// are, and how many fewer of others.
+///
+/// An element is in at most one of the two lists, with a count above zero.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultisetDelta<T> {
+    /// Elements the new multiset has more of, and how many more.
+    pub add: Vec<(T, usize)>,
+    /// Elements the new multiset has fewer of, and how many fewer.
+    pub remove: Vec<(T, usize)>,
+}
+
+impl<T> MultisetDelta<T> {
+    /// Whether the two multisets held the same counts, and so nothing needs
+    /// sending.
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.remove.is_empty()
+    }
+}
+
+impl<T> Default for MultisetDelta<T> {
+    fn default() -> Self {
+        MultisetDelta {
+            add: Vec::new(),
+            remove: Vec::new(),
+        }
+    }
+}
+
+impl<T> Invert for MultisetDelta<T> {
+    fn invert(self) -> Self {
+        MultisetDelta {
+            add: self.remove,
+            remove: self.add,
+        }
+    }
+}
+
+/// Counts add up, so composing nets each element's changes out: three added
+/// and then one removed is two added. Elements are matched with
+/// `Hash + Eq`, so this costs O(n) in the size of the two deltas.
+impl<T: Hash + Eq> Compose for MultisetDelta<T> {
+    fn compose(first: Self, second: Self) -> Self {
+        let signed = |changes: Vec<(T, usize)>, sign: i128| {
+            changes
+                .into_iter()
+                .map(move |(element, n)| (element, sign * n as i128))
+        };
+        let changes: Vec<(T, i128)> = signed(first.add, 1)
+            .chain(signed(first.remove, -1))
+            .chain(signed(second.add, 1))
+            .chain(signed(second.remove, -1))
+            .collect();
+        // Sum each element's changes into the slot of its first mention.
+        let mut net = vec![None; changes.len()];
+        {
+            let mut first_mention: HashMap<&T, usize> = HashMap::new();
+            for (index, (element, n)) in changes.iter().enumerate() {
+                let slot = *first_mention.entry(element).or_insert(index);
+                *net[slot].get_or_insert(0) += n;
+            }
+        }
+        let mut composed = MultisetDelta::default();
+        for ((element, _), net) in changes.into_iter().zip(net) {
+            match net {
+                Some(n) if n > 0 => composed.add.push((element, saturate(n))),
+                Some(n) if n < 0 => composed.remove.push((element, saturate(-n))),
+                _ => {}
+            }
+        }
+        composed
+    }
+}
+
+/// A net count as a `usize`, clamped to the largest one there is.
+fn saturate(n: i128) -> usize {
+    usize::try_from(n).unwrap_or(usize::MAX)
+}
+
+/// Records in `delta` how an element's count went from `was` to `now`, if
+/// it changed at all.
+fn tally<T>(delta: &mut MultisetDelta<T>, element: T, was: usize, now: usize) {
+    if now > was {
+        delta.add.push((element, now - was));
+    } else if now < was {
+        delta.remove.push((element, was - now));
+    }
+}
+
+/// Computes by how much each element's count went up or down between `old`
+/// and `new`.
+///
+/// Returns an empty [`MultisetDelta`] when the two hold the same counts. Each
+/// element of `old` is looked up in `new` exactly once, so the cost is that
+/// of n lookups, as for [`bag::diff`](crate::bag::diff). A multiset with a
+/// [key order](Multiset::key_order) has its two sides merged in one pass
+/// instead.
+///
+/// ```
+/// use delta_struct::multiset::diff;
+/// use std::collections::BTreeMap;
+///
+/// let old: BTreeMap<char, usize> = vec![('a', 2), ('b', 1)].into_iter().collect();
+/// let new: BTreeMap<char, usize> = vec![('a', 1), ('c', 4)].into_iter().collect();
+///
+/// let delta = diff(old, new);
+/// assert_eq!(delta.add, vec![('c', 4)]);
+/// assert_eq!(delta.remove, vec![('a', 1), ('b', 1)]);
+/// ```
+pub fn diff<C, E>(old: C, mut new: C) -> MultisetDelta<E::Key>
+where
+    C: IntoIterator<Item = E> + Multiset<E::Key>,
+    E: MapEntry<Value = usize>,
+{
+    let mut delta = MultisetDelta::default();
+    if let Some(order) = new.key_order() {
+        let parts = |side: C| side.into_iter().map(MapEntry::into_parts);
+        for joined in merge_join(parts(old), parts(new), |(element, _)| element, order) {
+            match joined {
+                Joined::Old((element, was)) => tally(&mut delta, element, was, 0),
+                Joined::New((element, now)) => tally(&mut delta, element, 0, now),
+                Joined::Both((element, was), (_, now)) => tally(&mut delta, element, was, now),
+            }
+        }
+        return delta;
+    }
+    // As in `bag::diff`, take each of `old`'s elements out of `new` as it is
+    // matched, so that what is left of `new` is what was added.
+    for (element, was) in old.into_iter().map(MapEntry::into_parts) {
+        let now = new.remove_many(&element, usize::MAX);
+        tally(&mut delta, element, was, now);
+    }
+    for (element, now) in new.into_iter().map(MapEntry::into_parts) {
+        tally(&mut delta, element, 0, now);
+    }
+    delta
+}
+
+/// Computes the same [`MultisetDelta`] as [`diff`] from borrowed multisets,
+/// cloning only the elements that ended up in it.
+///
+/// Every element of each side is looked up in the other once, so this costs
+/// twice the lookups [`diff`] does. A multiset with a key order is merged in
+/// one pass, as by [`diff`].
+pub fn diff_ref<'a, C, T>(old: &'a C, new: &'a C) -> MultisetDelta<T>
+where
+    &'a C: IntoIterator<Item = (&'a T, &'a usize)>,
+    C: Multiset<T>,
+    T: Clone + 'a,
+{
+    let mut delta = MultisetDelta::default();
+    if let Some(order) = new.key_order() {
+        for joined in merge_join(old, new, |(element, _)| *element, order) {
+            match joined {
+                Joined::Old((element, &was)) => tally(&mut delta, element.clone(), was, 0),
+                Joined::New((element, &now)) => tally(&mut delta, element.clone(), 0, now),
+                Joined::Both((element, &was), (_, &now)) => {
+                    if was != now {
+                        tally(&mut delta, element.clone(), was, now);
+                    }
+                }
+            }
+        }
+        return delta;
+    }
+    for (element, &was) in old {
+        let now = new.count(element);
+        if was != now {
+            tally(&mut delta, element.clone(), was, now);
+        }
+    }
+    for (element, &now) in new {
+        if now > 0 && old.count(element) == 0 {
+            tally(&mut delta, element.clone(), 0, now);
+        }
+    }
+    delta
+}
+
+/// Applies a count diff to `target` in place: the removals first, and then
+/// the additions.
+///
+/// A removal of more than `target` holds takes away all there is, so no
+/// count ever goes below zero. Use [`try_apply`] to have such a removal
+/// refused instead.
+///
+/// ```
+/// use delta_struct::multiset::{apply, diff};
+/// use std::collections::HashMap;
+///
+/// let counts = |items: &[(char, usize)]| items.iter().copied().collect::<HashMap<_, _>>();
+///
+/// let delta = diff(counts(&[('a', 2), ('b', 1)]), counts(&[('a', 1)]));
+/// let mut target = counts(&[('a', 5)]);
+/// apply(&mut target, delta);
+/// assert_eq!(target, counts(&[('a', 4)]));
+/// ```
+pub fn apply<C, T>(target: &mut C, delta: MultisetDelta<T>)
+where
+    C: Multiset<T>,
+{
+    for (element, n) in delta.remove {
+        target.remove_many(&element, n);
+    }
+    for (element, n) in delta.add {
+        target.insert_many(element, n);
+    }
+}
+
+/// Checks that `target` holds at least as many of each element as `delta`
+/// removes, reporting the first it does not as [`Problem::TooFew`].
+pub fn check<C, T>(target: &C, delta: &MultisetDelta<T>) -> Result<(), ApplyError>
+where
+    C: Multiset<T>,
+{
+    for (index, (element, n)) in delta.remove.iter().enumerate() {
+        let count = target.count(element);
+        if count < *n {
+            return Err(ApplyError::new(Problem::TooFew { index, count }));
+        }
+    }
+    Ok(())
+}
+
+/// Applies a count diff like [`apply`], but only if it passes [`check`];
+/// otherwise `target` is left as it was.
+///
+/// ```
+/// use delta_struct::multiset::{diff, try_apply};
+/// use delta_struct::Problem;
+/// use std::collections::HashMap;
+///
+/// let counts = |items: &[(char, usize)]| items.iter().copied().collect::<HashMap<_, _>>();
+///
+/// let delta = diff(counts(&[('a', 3)]), counts(&[('a', 1)]));
+/// let mut target = counts(&[('a', 1)]);
+/// let error = try_apply(&mut target, delta).unwrap_err();
+/// assert_eq!(error.problem, Problem::TooFew { index: 0, count: 1 });
+/// assert_eq!(target, counts(&[('a', 1)]));
+/// ```
+pub fn try_apply<C, T>(target: &mut C, delta: MultisetDelta<T>) -> Result<(), ApplyError>
+where
+    C: Multiset<T>,
+{
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
+}
@@ -0,0 +1,293 @@
+//! Diffing inside an `Option`, behind the `optional-delta` field type.
+//!
+//! As a `scalar`, an `Option<Settings>` field is resent whole whenever
+//! anything inside it changes. Marked
+//! `#[delta_struct(field_type = "optional-delta")]`, it is diffed with the
+//! inner type's own [`Delta`] while it stays `Some`, and only sent whole when
+//! it appears. The delta field is an `Option<OptionDelta<T, T::Output>>` —
+//! [`None`] when nothing changed, and otherwise one of the three outcomes an
+//! [`OptionDelta`] tells apart:
+//!
+//! ```
+//! use delta_struct::{Delta, OptionDelta};
+//!
+//! #[derive(Clone, Debug, Delta, PartialEq)]
+//! #[delta_struct(delta_leader = "#[derive(Debug)]")]
+//! struct Settings {
+//!     verbose: bool,
+//!     retries: u8,
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Profile {
+//!     #[delta_struct(field_type = "optional-delta")]
+//!     settings: Option<Settings>,
+//! }
+//!
+//! let profile = |settings| Profile { settings };
+//! let settings = |verbose| Settings { verbose, retries: 3 };
+//!
+//! let delta = Delta::delta(profile(Some(settings(false))), profile(Some(settings(true))));
+//! match delta.unwrap().settings {
+//!     Some(OptionDelta::Change(change)) => {
+//!         assert_eq!(change.verbose, Some(true));
+//!         assert_eq!(change.retries, None);
+//!     }
+//!     other => panic!("expected a change in place, got {:?}", other),
+//! }
+//!
+//! let delta = Delta::delta(profile(Some(settings(false))), profile(None));
+//! assert!(matches!(delta.unwrap().settings, Some(OptionDelta::Clear)));
+//! ```
+//!
+//! The derive emits calls to [`diff`] and [`apply`], or to [`diff_ref`] for
+//! [`DeltaRef`], to [`check`] for [`Delta::try_apply_delta`], and to
+//! [`compose`]. A `reversible` type's field gets a [`ReversibleOptionDelta`]
+//! instead, from [`diff_reversible`] or [`diff_ref_reversible`].
+
+use crate::check::{ApplyError, Problem};
+use crate::{Compose, Delta, DeltaRef, Invert};
+
+/// How an `Option` changed: what an `optional-delta` field's delta holds when
+/// there is a change at all.
+///
+/// Turn on the `serde` feature to get `Serialize` and `Deserialize` on this,
+/// as a delta struct with an `optional-delta` field cannot derive them
+/// otherwise.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionDelta<T, D> {
+    /// The value appeared, and travels whole since the receiver has nothing to
+    /// diff it against.
+    Set(T),
+    /// The value went away.
+    Clear,
+    /// The value was there before and after, and changed by this delta.
+    Change(D),
+}
+
+/// An [`OptionDelta`] that also keeps the value it clears, so that it can be
+/// [inverted](Invert).
+///
+/// This is what an `optional-delta` field turns into on a `reversible` type.
+/// Inverting swaps [`Set`](ReversibleOptionDelta::Set) and
+/// [`Clear`](ReversibleOptionDelta::Clear) and inverts a change, which needs
+/// the inner type's delta to be reversible too.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReversibleOptionDelta<T, D> {
+    /// The value appeared where there was none.
+    Set(T),
+    /// The value went away, leaving none; this is what it was.
+    Clear(T),
+    /// The value was there before and after, and changed by this delta.
+    Change(D),
+}
+
+impl<T, D> ReversibleOptionDelta<T, D> {
+    /// Drops the cleared value, which only undoing needs, leaving the
+    /// [`OptionDelta`] that [`apply`] takes.
+    pub fn into_forward(self) -> OptionDelta<T, D> {
+        match self {
+            ReversibleOptionDelta::Set(value) => OptionDelta::Set(value),
+            ReversibleOptionDelta::Clear(_) => OptionDelta::Clear,
+            ReversibleOptionDelta::Change(delta) => OptionDelta::Change(delta),
+        }
+    }
+}
+
+impl<T, D: Invert> Invert for ReversibleOptionDelta<T, D> {
+    fn invert(self) -> Self {
+        match self {
+            ReversibleOptionDelta::Set(value) => ReversibleOptionDelta::Clear(value),
+            ReversibleOptionDelta::Clear(value) => ReversibleOptionDelta::Set(value),
+            ReversibleOptionDelta::Change(delta) => ReversibleOptionDelta::Change(delta.invert()),
+        }
+    }
+}
+
+/// Computes what it would take to turn `old` into `new`, diffing the inner
+/// values when both are there. Returns [`None`] when there is nothing to do.
+pub fn diff<T: Delta>(old: Option<T>, new: Option<T>) -> Option<OptionDelta<T, T::Output>> {
+    match (old, new) {
+        (Some(old), Some(new)) => T::delta(old, new).map(OptionDelta::Change),
+        (None, Some(new)) => Some(OptionDelta::Set(new)),
+        (Some(_), None) => Some(OptionDelta::Clear),
+        (None, None) => None,
+    }
+}
+
+/// Like [`diff`], but keeps a cleared value so the delta can be inverted.
+pub fn diff_reversible<T: Delta>(
+    old: Option<T>,
+    new: Option<T>,
+) -> Option<ReversibleOptionDelta<T, T::Output>> {
+    match (old, new) {
+        (Some(old), Some(new)) => T::delta(old, new).map(ReversibleOptionDelta::Change),
+        (None, Some(new)) => Some(ReversibleOptionDelta::Set(new)),
+        (Some(old), None) => Some(ReversibleOptionDelta::Clear(old)),
+        (None, None) => None,
+    }
+}
+
+/// Like [`diff`], from borrows: inner values are diffed with
+/// [`DeltaRef::delta_ref`], and one that appears is cloned.
+pub fn diff_ref<T>(old: &Option<T>, new: &Option<T>) -> Option<OptionDelta<T, T::Output>>
+where
+    T: DeltaRef + Clone,
+{
+    match (old, new) {
+        (Some(old), Some(new)) => T::delta_ref(old, new).map(OptionDelta::Change),
+        (None, Some(new)) => Some(OptionDelta::Set(new.clone())),
+        (Some(_), None) => Some(OptionDelta::Clear),
+        (None, None) => None,
+    }
+}
+
+/// Like [`diff_reversible`], from borrows, cloning a cleared value too.
+pub fn diff_ref_reversible<T>(
+    old: &Option<T>,
+    new: &Option<T>,
+) -> Option<ReversibleOptionDelta<T, T::Output>>
+where
+    T: DeltaRef + Clone,
+{
+    match (old, new) {
+        (Some(old), Some(new)) => T::delta_ref(old, new).map(ReversibleOptionDelta::Change),
+        (None, Some(new)) => Some(ReversibleOptionDelta::Set(new.clone())),
+        (Some(old), None) => Some(ReversibleOptionDelta::Clear(old.clone())),
+        (None, None) => None,
+    }
+}
+
+/// Applies a delta from [`diff`] in place.
+///
+/// A change to a `target` that is [`None`] has nothing to land on, and is
+/// skipped. Use [`try_apply`] to have it refused instead.
+pub fn apply<T: Delta>(target: &mut Option<T>, delta: OptionDelta<T, T::Output>) {
+    match delta {
+        OptionDelta::Set(value) => *target = Some(value),
+        OptionDelta::Clear => *target = None,
+        OptionDelta::Change(delta) => {
+            if let Some(value) = target {
+                value.apply_delta(delta);
+            }
+        }
+    }
+}
+
+/// Checks that a change is to a `target` that is there, and that it fits the
+/// value. A change to [`None`] is reported as [`Problem::WrongVariant`].
+pub fn check<T: Delta>(
+    target: &Option<T>,
+    delta: &OptionDelta<T, T::Output>,
+) -> Result<(), ApplyError> {
+    match delta {
+        OptionDelta::Change(delta) => check_change(target, delta),
+        OptionDelta::Set(_) | OptionDelta::Clear => Ok(()),
+    }
+}
+
+/// Checks a [`ReversibleOptionDelta`] as [`check`] does an [`OptionDelta`].
+pub fn check_reversible<T: Delta>(
+    target: &Option<T>,
+    delta: &ReversibleOptionDelta<T, T::Output>,
+) -> Result<(), ApplyError> {
+    match delta {
+        ReversibleOptionDelta::Change(delta) => check_change(target, delta),
+        ReversibleOptionDelta::Set(_) | ReversibleOptionDelta::Clear(_) => Ok(()),
+    }
+}
+
+/// The check of a change in place, for either delta type.
+fn check_change<T: Delta>(target: &Option<T>, delta: &T::Output) -> Result<(), ApplyError> {
+    match target {
+        Some(value) => value.check_delta(delta),
+        None => Err(ApplyError::new(Problem::WrongVariant)),
+    }
+}
+
+/// Applies a delta like [`apply`], but only if it passes [`check`];
+/// otherwise `target` is left as it was.
+pub fn try_apply<T: Delta>(
+    target: &mut Option<T>,
+    delta: OptionDelta<T, T::Output>,
+) -> Result<(), ApplyError> {
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
+}
+
+/// Composes two consecutive optional deltas into one.
+///
+/// A value set and then changed is set with the change already made, and
+/// anything followed by a set or a clear is just that set or clear.
+pub fn compose<T>(
+    first: Option<OptionDelta<T, T::Output>>,
+    second: Option<OptionDelta<T, T::Output>>,
+) -> Option<OptionDelta<T, T::Output>>
+where
+    T: Delta,
+    T::Output: Compose,
+{
+    let (first, second) = match (first, second) {
+        (first, None) => return first,
+        (None, second) => return second,
+        (Some(first), Some(second)) => (first, second),
+    };
+    Some(match (first, second) {
+        (OptionDelta::Set(mut value), OptionDelta::Change(delta)) => {
+            value.apply_delta(delta);
+            OptionDelta::Set(value)
+        }
+        (OptionDelta::Change(first), OptionDelta::Change(second)) => {
+            OptionDelta::Change(Compose::compose(first, second))
+        }
+        // A change to a cleared value would be skipped.
+        (OptionDelta::Clear, OptionDelta::Change(_)) => OptionDelta::Clear,
+        (_, second) => second,
+    })
+}
+
+/// Composes two consecutive reversible optional deltas into one, as
+/// [`compose`] does, keeping what undoing needs.
+///
+/// A value set and then cleared was never there, and composes to [`None`].
+/// One cleared and then set again becomes a change from the old value to the
+/// new, or nothing if the two are the same; and a change followed by a clear
+/// clears the value from before the change.
+pub fn compose_reversible<T>(
+    first: Option<ReversibleOptionDelta<T, T::Output>>,
+    second: Option<ReversibleOptionDelta<T, T::Output>>,
+) -> Option<ReversibleOptionDelta<T, T::Output>>
+where
+    T: Delta,
+    T::Output: Compose + Invert,
+{
+    let (first, second) = match (first, second) {
+        (first, None) => return first,
+        (None, second) => return second,
+        (Some(first), Some(second)) => (first, second),
+    };
+    match (first, second) {
+        (ReversibleOptionDelta::Set(_), ReversibleOptionDelta::Clear(_)) => None,
+        (ReversibleOptionDelta::Set(mut value), ReversibleOptionDelta::Change(delta)) => {
+            value.apply_delta(delta);
+            Some(ReversibleOptionDelta::Set(value))
+        }
+        (ReversibleOptionDelta::Clear(old), ReversibleOptionDelta::Set(new)) => {
+            T::delta(old, new).map(ReversibleOptionDelta::Change)
+        }
+        (ReversibleOptionDelta::Change(first), ReversibleOptionDelta::Change(second)) => Some(
+            ReversibleOptionDelta::Change(Compose::compose(first, second)),
+        ),
+        (ReversibleOptionDelta::Change(first), ReversibleOptionDelta::Clear(mut value)) => {
+            value.apply_delta(first.invert());
+            Some(ReversibleOptionDelta::Clear(value))
+        }
+        // Consecutive deltas never meet in the remaining ways. A cleared value
+        // stays cleared, and otherwise the second delta wins.
+        (first @ ReversibleOptionDelta::Clear(_), _) => Some(first),
+        (_, second) => Some(second),
+    }
+}
@@ -0,0 +1,854 @@
+//! Positional diffing that diffs the items it keeps, behind the
+//! `ordered-delta` field type.
+//!
+//! An `ordered` field's [`SeqDelta`] replaces an item that changed at all, so
+//! a `Vec<Track>` in which one track's title changed resends the whole track.
+//! Marked `#[delta_struct(field_type = "ordered-delta")]`, the field is
+//! aligned the same way, and then every item that is on both sides travels as
+//! its own [`Delta`], leaving splices for the items that really came or went:
+//!
+//! ```
+//! use delta_struct::Delta;
+//!
+//! #[derive(Clone, Delta, Hash, PartialEq, Eq)]
+//! struct Track {
+//!     title: String,
+//!     seconds: u32,
+//! }
+//!
+//! #[derive(Delta)]
+//! struct Playlist {
+//!     #[delta_struct(field_type = "ordered-delta")]
+//!     tracks: Vec<Track>,
+//! }
+//!
+//! let track = |title: &str| Track { title: title.to_string(), seconds: 200 };
+//! let old = Playlist { tracks: vec![track("intro"), track("b"), track("outro")] };
+//! let new = Playlist { tracks: vec![track("intro"), track("b (live)"), track("outro")] };
+//!
+//! let delta = Delta::delta(old, new).unwrap().tracks;
+//! assert!(delta.splices.is_empty());
+//! assert_eq!(delta.change[0].index, 1);
+//! assert_eq!(delta.change[0].delta.title, Some("b (live)".to_string()));
+//! assert_eq!(delta.change[0].delta.seconds, None);
+//! ```
+//!
+//! Items are aligned in one of two ways:
+//!
+//! - By default, by their own `Hash + Eq`, as `ordered` does. Equal items
+//!   line up, and where a run of items was replaced, they are paired up by
+//!   position and diffed: the first removed item with the first inserted one,
+//!   and so on, with whatever is left over removed or inserted.
+//! - With `key = "..."` and `key_type = "..."`, as for a `keyed` field, by a
+//!   field of each item. Items with the same key line up and are diffed, and
+//!   items with different keys never are, so the items themselves need no
+//!   `Hash` or `Eq`.
+//!
+//! Positions, of splices and of changes alike, index the *old* sequence. The
+//! derive emits calls to [`diff`] or [`diff_by_key`] and to [`apply`], or to
+//! [`diff_ref`] or [`diff_ref_by_key`] for [`DeltaRef`], and to [`check`] for
+//! [`Delta::try_apply_delta`]. A `reversible` type's field gets a
+//! [`ReversibleOrderedDelta`] instead, from the `_reversible` variant of each.
+
+use crate::check::{ApplyError, PathSegment, Problem};
+use crate::seq::{
+    self, check_splices, edit_script, DiffOptions, Edit, IndexedDelta, ReversibleSplice, Splice,
+};
+use crate::{Compose, Delta, DeltaRef, Invert, ReversibleSeqDelta, SeqDelta};
+use std::hash::Hash;
+use std::iter::{FromIterator, Peekable};
+use std::mem;
+use std::ops::Range;
+
+/// A positional diff between two sequences that changes the items they share
+/// in place.
+///
+/// Splices are sorted by [`Splice::at`] and never overlap, changes are sorted
+/// by [`IndexedDelta::index`] and never fall inside a splice, and every
+/// position indexes the old sequence.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderedDelta<T, D> {
+    /// The items that came and went, in ascending order of [`Splice::at`].
+    pub splices: Vec<Splice<T>>,
+    /// The items that stayed but changed, in ascending order of
+    /// [`IndexedDelta::index`].
+    pub change: Vec<IndexedDelta<D>>,
+}
+
+impl<T, D> OrderedDelta<T, D> {
+    /// Whether the two sequences were identical, and so nothing needs sending.
+    pub fn is_empty(&self) -> bool {
+        self.splices.is_empty() && self.change.is_empty()
+    }
+}
+
+impl<T, D> Default for OrderedDelta<T, D> {
+    fn default() -> Self {
+        OrderedDelta {
+            splices: Vec::new(),
+            change: Vec::new(),
+        }
+    }
+}
+
+/// An [`OrderedDelta`] whose splices also keep the items they drop, so that
+/// it can be [inverted](Invert).
+///
+/// This is what an `ordered-delta` field turns into on a `reversible` type.
+/// Inverting it needs the items' own deltas to be reversible too.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReversibleOrderedDelta<T, D> {
+    /// The items that came and went, in ascending order of
+    /// [`ReversibleSplice::at`].
+    pub splices: Vec<ReversibleSplice<T>>,
+    /// The items that stayed but changed, in ascending order of
+    /// [`IndexedDelta::index`].
+    pub change: Vec<IndexedDelta<D>>,
+}
+
+impl<T, D> ReversibleOrderedDelta<T, D> {
+    /// Whether the two sequences were identical, and so nothing needs sending.
+    pub fn is_empty(&self) -> bool {
+        self.splices.is_empty() && self.change.is_empty()
+    }
+
+    /// Drops the removed items, which only undoing needs, leaving the
+    /// [`OrderedDelta`] that [`apply`] takes.
+    pub fn into_forward(self) -> OrderedDelta<T, D> {
+        OrderedDelta {
+            splices: ReversibleSeqDelta {
+                splices: self.splices,
+            }
+            .into_forward()
+            .splices,
+            change: self.change,
+        }
+    }
+}
+
+impl<T, D> Default for ReversibleOrderedDelta<T, D> {
+    fn default() -> Self {
+        ReversibleOrderedDelta {
+            splices: Vec::new(),
+            change: Vec::new(),
+        }
+    }
+}
+
+impl<T, D: Invert> Invert for ReversibleOrderedDelta<T, D> {
+    fn invert(self) -> Self {
+        // A change moves to where its item landed in the new sequence: past
+        // everything the splices at or before it added or dropped.
+        let mut shift = 0isize;
+        let mut splices = self.splices.iter().peekable();
+        let change = self
+            .change
+            .into_iter()
+            .map(|IndexedDelta { index, delta }| {
+                while let Some(splice) = splices.next_if(|splice| splice.at <= index) {
+                    shift += splice.insert.len() as isize - splice.remove.len() as isize;
+                }
+                IndexedDelta {
+                    index: (index as isize + shift) as usize,
+                    delta: delta.invert(),
+                }
+            })
+            .collect();
+        let splices = ReversibleSeqDelta {
+            splices: self.splices,
+        }
+        .invert()
+        .splices;
+        ReversibleOrderedDelta { splices, change }
+    }
+}
+
+/// The second delta's positions index what the first one produced, and are
+/// rebased onto the sequence the first one started from, as for a
+/// [`SeqDelta`]. An item both deltas change gets the two changes
+/// [composed](Compose), and an item the first inserts and the second changes
+/// is inserted changed.
+///
+/// ```
+/// use delta_struct::ordered_delta::{apply, diff};
+/// use delta_struct::Compose;
+///
+/// let first = diff(vec![1, 2, 3], vec![1, 20, 3, 4]);
+/// let second = diff(vec![1, 20, 3, 4], vec![1, 21, 4, 40]);
+/// let both = Compose::compose(first, second);
+///
+/// let mut target = vec![1, 2, 3];
+/// apply(&mut target, both);
+/// assert_eq!(target, vec![1, 21, 4, 40]);
+/// ```
+impl<T, D> Compose for OrderedDelta<T, D>
+where
+    T: Delta<Output = D>,
+    D: Compose,
+{
+    fn compose(first: Self, second: Self) -> Self {
+        let parts = |delta: OrderedDelta<T, D>| Parts {
+            splices: delta
+                .splices
+                .into_iter()
+                .map(|Splice { at, remove, insert }| Edit {
+                    at,
+                    remove: vec![(); remove],
+                    insert,
+                })
+                .collect(),
+            change: delta.change,
+        };
+        let Parts { splices, change } = compose_parts(parts(first), parts(second), |(), _| ());
+        OrderedDelta {
+            splices: splices
+                .into_iter()
+                .map(|Edit { at, remove, insert }| Splice {
+                    at,
+                    remove: remove.len(),
+                    insert,
+                })
+                .collect(),
+            change,
+        }
+    }
+}
+
+/// As for [`OrderedDelta`]. An item the first delta changes and the second
+/// removes is kept as it was before the change, which takes undoing the
+/// change.
+impl<T, D> Compose for ReversibleOrderedDelta<T, D>
+where
+    T: Delta<Output = D>,
+    D: Compose + Invert,
+{
+    fn compose(first: Self, second: Self) -> Self {
+        let parts = |delta: ReversibleOrderedDelta<T, D>| Parts {
+            splices: delta
+                .splices
+                .into_iter()
+                .map(|ReversibleSplice { at, remove, insert }| Edit { at, remove, insert })
+                .collect(),
+            change: delta.change,
+        };
+        let unchange = |mut item: T, delta: D| {
+            item.apply_delta(delta.invert());
+            item
+        };
+        let Parts { splices, change } = compose_parts(parts(first), parts(second), unchange);
+        ReversibleOrderedDelta {
+            splices: splices
+                .into_iter()
+                .map(|Edit { at, remove, insert }| ReversibleSplice { at, remove, insert })
+                .collect(),
+            change,
+        }
+    }
+}
+
+/// Either delta type, with each removed item recorded as a `G`.
+struct Parts<T, G, D> {
+    splices: Vec<Edit<T, G>>,
+    change: Vec<IndexedDelta<D>>,
+}
+
+/// A stretch of the sequence between two deltas: old items the first delta
+/// kept as they were, an old item it changed, or an item it inserted.
+enum Piece<T, D> {
+    Old(Range<usize>),
+    Changed(usize, D),
+    New(T),
+}
+
+/// What the first delta produced, as pieces, for the second to walk over.
+struct Layout<T, D> {
+    pieces: std::vec::IntoIter<Piece<T, D>>,
+    current: Option<Piece<T, D>>,
+}
+
+impl<T, D> Layout<T, D> {
+    /// Takes the next `n` items off the front, splitting a stretch of old
+    /// items if it runs on past them.
+    fn take(&mut self, mut n: usize) -> Vec<Piece<T, D>> {
+        let mut taken = Vec::new();
+        while n > 0 {
+            match self.current.take() {
+                Some(Piece::Old(range)) if range.end - range.start > n => {
+                    taken.push(Piece::Old(range.start..range.start + n));
+                    self.current = Some(Piece::Old(range.start + n..range.end));
+                    n = 0;
+                }
+                Some(piece) => {
+                    n -= match &piece {
+                        Piece::Old(range) => range.end - range.start,
+                        _ => 1,
+                    };
+                    taken.push(piece);
+                    self.current = self.pieces.next();
+                }
+                None => break,
+            }
+        }
+        taken
+    }
+}
+
+/// Lays out old items from `cursor` up to `until`, with the changes among
+/// them. A change behind `cursor` was inside a removal, and is dropped.
+fn lay_out<T, D>(
+    pieces: &mut Vec<Piece<T, D>>,
+    cursor: &mut usize,
+    until: usize,
+    change: &mut Peekable<std::vec::IntoIter<IndexedDelta<D>>>,
+) {
+    while let Some(IndexedDelta { index, delta }) = change.next_if(|change| change.index < until) {
+        if index < *cursor {
+            continue;
+        }
+        if index > *cursor {
+            pieces.push(Piece::Old(*cursor..index));
+        }
+        pieces.push(Piece::Changed(index, delta));
+        *cursor = index + 1;
+    }
+    if until > *cursor {
+        pieces.push(Piece::Old(*cursor..until));
+        *cursor = until;
+    }
+}
+
+/// Composes either delta type. `unchange` turns an item the second delta
+/// removes back into the item the first delta changed, as the removal has to
+/// record it.
+fn compose_parts<T, G, D>(
+    first: Parts<T, G, D>,
+    second: Parts<T, G, D>,
+    unchange: impl Fn(G, D) -> G,
+) -> Parts<T, G, D>
+where
+    T: Delta<Output = D>,
+    D: Compose,
+{
+    // Lay out what the first delta produced in terms of where each item came
+    // from. The last stretch of old items runs on past any real length, so
+    // the second delta never walks off the end.
+    let mut pieces = Vec::new();
+    let mut removed = Vec::new();
+    let mut cursor = 0;
+    let mut change = first.change.into_iter().peekable();
+    for Edit { at, remove, insert } in first.splices {
+        lay_out(&mut pieces, &mut cursor, at, &mut change);
+        let count = remove.len();
+        removed.extend((cursor..).zip(remove));
+        cursor += count;
+        pieces.extend(insert.into_iter().map(Piece::New));
+    }
+    lay_out(&mut pieces, &mut cursor, usize::MAX, &mut change);
+    let mut pieces = pieces.into_iter();
+    let current = pieces.next();
+    let mut layout = Layout { pieces, current };
+
+    // Walk the second delta over that layout, in order of position; an
+    // insertion at a changed item's position comes before the item.
+    let mut kept = Vec::new();
+    let mut position = 0;
+    let mut change = second.change.into_iter().peekable();
+    let mut splices = second.splices.into_iter().peekable();
+    loop {
+        let splice_first = match (splices.peek(), change.peek()) {
+            (Some(splice), Some(change)) => splice.at <= change.index,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break,
+        };
+        if splice_first {
+            let Edit { at, remove, insert } = splices.next().unwrap();
+            let at = at.max(position);
+            kept.extend(layout.take(at - position));
+            position = at + remove.len();
+            let count = remove.len();
+            let mut gone = remove.into_iter();
+            for piece in layout.take(count) {
+                match piece {
+                    Piece::Old(range) => removed.extend(range.zip(gone.by_ref())),
+                    Piece::Changed(index, delta) => {
+                        removed.extend(gone.next().map(|item| (index, unchange(item, delta))))
+                    }
+                    Piece::New(_) => gone.by_ref().take(1).for_each(drop),
+                }
+            }
+            kept.extend(insert.into_iter().map(Piece::New));
+        } else {
+            let IndexedDelta { index, delta } = change.next().unwrap();
+            if index < position {
+                continue;
+            }
+            kept.extend(layout.take(index - position));
+            position = index + 1;
+            match layout.take(1).pop() {
+                Some(Piece::Old(range)) => kept.push(Piece::Changed(range.start, delta)),
+                Some(Piece::Changed(index, first)) => {
+                    kept.push(Piece::Changed(index, Compose::compose(first, delta)))
+                }
+                Some(Piece::New(mut item)) => {
+                    item.apply_delta(delta);
+                    kept.push(Piece::New(item));
+                }
+                None => {}
+            }
+        }
+    }
+    kept.extend(layout.current);
+    kept.extend(layout.pieces);
+    removed.sort_by_key(|&(index, _)| index);
+
+    // Everything between two old items that survived was removed, by one
+    // delta or the other, and everything inserted in between replaces it.
+    let mut removed = removed.into_iter().peekable();
+    let mut splices = Vec::new();
+    let mut change = Vec::new();
+    let mut insert = Vec::new();
+    let mut cursor = 0;
+    for piece in kept {
+        let (start, end, delta) = match piece {
+            Piece::New(item) => {
+                insert.push(item);
+                continue;
+            }
+            Piece::Old(range) => (range.start, range.end, None),
+            Piece::Changed(index, delta) => (index, index + 1, Some(delta)),
+        };
+        if start > cursor || !insert.is_empty() {
+            let mut remove = Vec::new();
+            while let Some((_, item)) = removed.next_if(|&(index, _)| index < start) {
+                remove.push(item);
+            }
+            splices.push(Edit {
+                at: cursor,
+                remove,
+                insert: mem::take(&mut insert),
+            });
+        }
+        change.extend(delta.map(|delta| IndexedDelta {
+            index: start,
+            delta,
+        }));
+        cursor = end;
+    }
+    Parts { splices, change }
+}
+
+/// One step of an alignment of two sequences. Steps come in ascending order
+/// on both sides.
+enum Step {
+    /// The old item at `.0` and the new item at `.1` are the same item, to be
+    /// diffed.
+    Pair(usize, usize),
+    /// The old items from `.0`, `.1` of them, give way to the new items in
+    /// `.2`.
+    Splice(usize, usize, Range<usize>),
+}
+
+/// Aligns two sequences of keys with Myers.
+///
+/// Keys that are equal are taken to be the same item; when they are the
+/// items themselves that means there is nothing to diff, so the pairs are
+/// left out unless `pair_equal`. Without `pair_equal`, each run of replaced
+/// items is paired up by position instead.
+fn align<K: Hash + Eq>(old: &[K], new: &[K], pair_equal: bool) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let (mut old_cursor, mut new_cursor) = (0, 0);
+    for (at, remove, insert) in edit_script(old, new, DiffOptions::default()) {
+        if pair_equal {
+            steps.extend(
+                (old_cursor..at)
+                    .zip(new_cursor..)
+                    .map(|(o, n)| Step::Pair(o, n)),
+            );
+        }
+        let paired = if pair_equal {
+            0
+        } else {
+            remove.min(insert.len())
+        };
+        steps.extend(
+            (at..at + paired)
+                .zip(insert.start..)
+                .map(|(o, n)| Step::Pair(o, n)),
+        );
+        if remove > paired || insert.len() > paired {
+            steps.push(Step::Splice(
+                at + paired,
+                remove - paired,
+                insert.start + paired..insert.end,
+            ));
+        }
+        old_cursor = at + remove;
+        new_cursor = insert.end;
+    }
+    if pair_equal {
+        steps.extend(
+            (old_cursor..old.len())
+                .zip(new_cursor..)
+                .map(|(o, n)| Step::Pair(o, n)),
+        );
+    }
+    steps
+}
+
+/// Takes `old` and `new` apart along `steps`, diffing each pair.
+fn diff_steps<T: Delta>(
+    old: Vec<T>,
+    new: Vec<T>,
+    steps: Vec<Step>,
+) -> ReversibleOrderedDelta<T, T::Output> {
+    // The steps are ascending on both sides, so the items can be pulled out
+    // of `old` and `new` in one pass each, as in `seq::diff_reversible`.
+    let (mut old, mut new) = (old.into_iter(), new.into_iter());
+    let (mut old_cursor, mut new_cursor) = (0, 0);
+    let mut splices = Vec::new();
+    let mut change = Vec::new();
+    for step in steps {
+        match step {
+            Step::Pair(index, new_index) => {
+                // Every step lies within both sequences.
+                let old_item = old.nth(index - old_cursor).unwrap();
+                let new_item = new.nth(new_index - new_cursor).unwrap();
+                old_cursor = index + 1;
+                new_cursor = new_index + 1;
+                if let Some(delta) = T::delta(old_item, new_item) {
+                    change.push(IndexedDelta { index, delta });
+                }
+            }
+            Step::Splice(at, remove, range) => {
+                old.by_ref().take(at - old_cursor).for_each(drop);
+                let remove = old.by_ref().take(remove).collect::<Vec<_>>();
+                old_cursor = at + remove.len();
+                new.by_ref().take(range.start - new_cursor).for_each(drop);
+                let insert = new.by_ref().take(range.end - range.start).collect();
+                new_cursor = range.end;
+                splices.push(ReversibleSplice { at, remove, insert });
+            }
+        }
+    }
+    ReversibleOrderedDelta { splices, change }
+}
+
+/// Takes borrowed `old` and `new` apart along `steps`, diffing each pair
+/// with [`DeltaRef::delta_ref`] and building each splice with `splice` from
+/// its position, the old items it drops, and clones of the new items it
+/// inserts.
+fn diff_ref_steps<'a, T, S>(
+    old: &[&'a T],
+    new: &[&'a T],
+    steps: Vec<Step>,
+    splice: impl Fn(usize, &[&'a T], Vec<T>) -> S,
+) -> (Vec<S>, Vec<IndexedDelta<T::Output>>)
+where
+    T: DeltaRef + Clone,
+{
+    let mut splices = Vec::new();
+    let mut change = Vec::new();
+    for step in steps {
+        match step {
+            Step::Pair(index, new_index) => {
+                if let Some(delta) = T::delta_ref(old[index], new[new_index]) {
+                    change.push(IndexedDelta { index, delta });
+                }
+            }
+            Step::Splice(at, remove, range) => {
+                let insert = new[range].iter().map(|&item| item.clone()).collect();
+                splices.push(splice(at, &old[at..at + remove], insert));
+            }
+        }
+    }
+    (splices, change)
+}
+
+/// Aligns `old` and `new` by the items themselves and diffs the items that
+/// changed in place.
+///
+/// Returns an empty [`OrderedDelta`] when the two are identical.
+///
+/// ```
+/// use delta_struct::ordered_delta::diff;
+/// use delta_struct::Splice;
+///
+/// let delta = diff(vec![1, 2, 3], vec![1, 5, 3, 4]);
+/// assert_eq!(delta.change[0].index, 1);
+/// assert_eq!(delta.change[0].delta, 5);
+/// assert_eq!(delta.splices, vec![Splice { at: 3, remove: 0, insert: vec![4] }]);
+/// ```
+pub fn diff<C, T>(old: C, new: C) -> OrderedDelta<T, T::Output>
+where
+    C: IntoIterator<Item = T>,
+    T: Delta + Hash + Eq,
+{
+    diff_reversible(old, new).into_forward()
+}
+
+/// Computes the same delta as [`diff`], keeping the removed items so that it
+/// can be [inverted](Invert).
+pub fn diff_reversible<C, T>(old: C, new: C) -> ReversibleOrderedDelta<T, T::Output>
+where
+    C: IntoIterator<Item = T>,
+    T: Delta + Hash + Eq,
+{
+    let old: Vec<T> = old.into_iter().collect();
+    let new: Vec<T> = new.into_iter().collect();
+    let steps = align(&old, &new, false);
+    diff_steps(old, new, steps)
+}
+
+/// Aligns `old` and `new` by the key `key` borrows out of each item, and
+/// diffs the items that are on both sides.
+///
+/// ```
+/// use delta_struct::{ordered_delta, Delta};
+///
+/// #[derive(Delta)]
+/// struct Track {
+///     id: u32,
+///     title: String,
+/// }
+///
+/// let track = |id, title: &str| Track { id, title: title.to_string() };
+/// let old = vec![track(1, "intro"), track(2, "b")];
+/// let new = vec![track(1, "intro"), track(2, "b (live)"), track(3, "outro")];
+///
+/// let delta = ordered_delta::diff_by_key(old, new, |track: &Track| &track.id);
+/// assert_eq!(delta.change[0].index, 1);
+/// assert_eq!(delta.change[0].delta.title, Some("b (live)".to_string()));
+/// assert_eq!(delta.splices[0].insert[0].id, 3);
+/// ```
+pub fn diff_by_key<C, T, K, F>(old: C, new: C, key: F) -> OrderedDelta<T, T::Output>
+where
+    C: IntoIterator<Item = T>,
+    T: Delta,
+    K: Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    diff_by_key_reversible(old, new, key).into_forward()
+}
+
+/// Computes the same delta as [`diff_by_key`], keeping the removed items so
+/// that it can be [inverted](Invert).
+pub fn diff_by_key_reversible<C, T, K, F>(
+    old: C,
+    new: C,
+    key: F,
+) -> ReversibleOrderedDelta<T, T::Output>
+where
+    C: IntoIterator<Item = T>,
+    T: Delta,
+    K: Hash + Eq,
+    F: Fn(&T) -> &K,
+{
+    let old: Vec<T> = old.into_iter().collect();
+    let new: Vec<T> = new.into_iter().collect();
+    let steps = {
+        let old_keys: Vec<&K> = old.iter().map(&key).collect();
+        let new_keys: Vec<&K> = new.iter().map(&key).collect();
+        align(&old_keys, &new_keys, true)
+    };
+    diff_steps(old, new, steps)
+}
+
+/// Computes the same [`OrderedDelta`] as [`diff`] from borrowed sequences,
+/// cloning only the inserted items, and diffing the changed ones with
+/// [`DeltaRef::delta_ref`].
+pub fn diff_ref<'a, C, T>(old: &'a C, new: &'a C) -> OrderedDelta<T, T::Output>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: DeltaRef + Hash + Eq + Clone + 'a,
+{
+    let old: Vec<&T> = old.into_iter().collect();
+    let new: Vec<&T> = new.into_iter().collect();
+    let steps = align(&old, &new, false);
+    let (splices, change) = diff_ref_steps(&old, &new, steps, forward_splice);
+    OrderedDelta { splices, change }
+}
+
+/// Computes the same delta as [`diff_ref`], cloning the removed items too so
+/// that it can be [inverted](Invert).
+pub fn diff_ref_reversible<'a, C, T>(old: &'a C, new: &'a C) -> ReversibleOrderedDelta<T, T::Output>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: DeltaRef + Hash + Eq + Clone + 'a,
+{
+    let old: Vec<&T> = old.into_iter().collect();
+    let new: Vec<&T> = new.into_iter().collect();
+    let steps = align(&old, &new, false);
+    let (splices, change) = diff_ref_steps(&old, &new, steps, reversible_splice);
+    ReversibleOrderedDelta { splices, change }
+}
+
+/// Computes the same [`OrderedDelta`] as [`diff_by_key`] from borrowed
+/// sequences, as [`diff_ref`] does for [`diff`].
+pub fn diff_ref_by_key<'a, C, T, K, F>(old: &'a C, new: &'a C, key: F) -> OrderedDelta<T, T::Output>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: DeltaRef + Clone + 'a,
+    K: Hash + Eq + 'a,
+    F: Fn(&T) -> &K,
+{
+    let old: Vec<&T> = old.into_iter().collect();
+    let new: Vec<&T> = new.into_iter().collect();
+    let keys = |items: &[&'a T]| items.iter().map(|&item| key(item)).collect::<Vec<_>>();
+    let steps = align(&keys(&old), &keys(&new), true);
+    let (splices, change) = diff_ref_steps(&old, &new, steps, forward_splice);
+    OrderedDelta { splices, change }
+}
+
+/// Computes the same delta as [`diff_ref_by_key`], cloning the removed items
+/// too so that it can be [inverted](Invert).
+pub fn diff_ref_by_key_reversible<'a, C, T, K, F>(
+    old: &'a C,
+    new: &'a C,
+    key: F,
+) -> ReversibleOrderedDelta<T, T::Output>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: DeltaRef + Clone + 'a,
+    K: Hash + Eq + 'a,
+    F: Fn(&T) -> &K,
+{
+    let old: Vec<&T> = old.into_iter().collect();
+    let new: Vec<&T> = new.into_iter().collect();
+    let keys = |items: &[&'a T]| items.iter().map(|&item| key(item)).collect::<Vec<_>>();
+    let steps = align(&keys(&old), &keys(&new), true);
+    let (splices, change) = diff_ref_steps(&old, &new, steps, reversible_splice);
+    ReversibleOrderedDelta { splices, change }
+}
+
+/// A [`Splice`] for [`diff_ref_steps`], counting the items it drops.
+fn forward_splice<T>(at: usize, removed: &[&T], insert: Vec<T>) -> Splice<T> {
+    Splice {
+        at,
+        remove: removed.len(),
+        insert,
+    }
+}
+
+/// A [`ReversibleSplice`] for [`diff_ref_steps`], cloning the items it drops.
+fn reversible_splice<T: Clone>(at: usize, removed: &[&T], insert: Vec<T>) -> ReversibleSplice<T> {
+    ReversibleSplice {
+        at,
+        remove: removed.iter().map(|&item| item.clone()).collect(),
+        insert,
+    }
+}
+
+/// Applies an ordered delta to `target` in place: each change to the item it
+/// names, and then the splices as [`seq::apply`] does.
+///
+/// A change to a position `target` does not have is skipped, and the splices
+/// are clamped as [`seq::apply`] clamps them. Use [`try_apply`] to have such
+/// a delta refused instead.
+///
+/// ```
+/// use delta_struct::ordered_delta::{apply, diff};
+///
+/// let delta = diff(vec![1, 2, 3], vec![1, 5, 3, 4]);
+/// let mut target = vec![1, 2, 3];
+/// apply(&mut target, delta);
+/// assert_eq!(target, vec![1, 5, 3, 4]);
+/// ```
+pub fn apply<C, T>(target: &mut C, delta: OrderedDelta<T, T::Output>)
+where
+    C: IntoIterator<Item = T> + FromIterator<T>,
+    T: Delta,
+{
+    let OrderedDelta { splices, change } = delta;
+    let mut items: Vec<T> = mem::replace(target, std::iter::empty().collect())
+        .into_iter()
+        .collect();
+    for IndexedDelta { index, delta } in change {
+        if let Some(item) = items.get_mut(index) {
+            item.apply_delta(delta);
+        }
+    }
+    seq::apply(&mut items, SeqDelta { splices });
+    *target = items.into_iter().collect();
+}
+
+/// Checks the splices of `delta` as [`seq::check`] does, and that each change
+/// is for a position `target` has and fits the item there, reporting a
+/// change past the end as [`Problem::ChangeOutOfRange`].
+pub fn check<'a, C, T>(target: &'a C, delta: &OrderedDelta<T, T::Output>) -> Result<(), ApplyError>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: Delta + 'a,
+{
+    check_with(
+        target,
+        delta
+            .splices
+            .iter()
+            .map(|splice| (splice.at, splice.remove)),
+        &delta.change,
+    )
+}
+
+/// Checks a [`ReversibleOrderedDelta`] as [`check`] does an
+/// [`OrderedDelta`].
+pub fn check_reversible<'a, C, T>(
+    target: &'a C,
+    delta: &ReversibleOrderedDelta<T, T::Output>,
+) -> Result<(), ApplyError>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: Delta + 'a,
+{
+    check_with(
+        target,
+        delta
+            .splices
+            .iter()
+            .map(|splice| (splice.at, splice.remove.len())),
+        &delta.change,
+    )
+}
+
+/// The check of either delta type, given each splice as `(at, remove)`.
+fn check_with<'a, C, T>(
+    target: &'a C,
+    splices: impl IntoIterator<Item = (usize, usize)>,
+    change: &[IndexedDelta<T::Output>],
+) -> Result<(), ApplyError>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: Delta + 'a,
+{
+    let items: Vec<&T> = target.into_iter().collect();
+    check_splices(items.len(), splices)?;
+    for (position, IndexedDelta { index, delta }) in change.iter().enumerate() {
+        match items.get(*index) {
+            Some(item) => item
+                .check_delta(delta)
+                .map_err(|error| error.within(PathSegment::Change(position)))?,
+            None => {
+                return Err(ApplyError::new(Problem::ChangeOutOfRange {
+                    index: position,
+                    len: items.len(),
+                }))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies an ordered delta like [`apply`], but only if it passes
+/// [`check`]; otherwise `target` is left as it was.
+pub fn try_apply<C, T>(target: &mut C, delta: OrderedDelta<T, T::Output>) -> Result<(), ApplyError>
+where
+    C: IntoIterator<Item = T> + FromIterator<T>,
+    for<'a> &'a C: IntoIterator<Item = &'a T>,
+    T: Delta,
+{
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
+}
@@ -0,0 +1,595 @@
+//! Sending a collection whole when its delta would be bigger, behind
+//! `#[delta_struct(replace_if_cheaper)]`.
+//!
+//! A collection delta is usually far smaller than the collection, but it need
+//! not be. Reversing an `ordered` field reinserts nearly every item, and a
+//! [`BagDelta`] that swaps 900 of 1000 elements for others carries 1800 of
+//! them. An `ordered` or `unordered` field with `replace_if_cheaper` gets an
+//! [`OrReplace`] instead of its usual delta: that delta, or the new
+//! collection itself whenever that has fewer elements than the delta
+//! touches. A delta is weighed by the elements it removes as well as the ones
+//! it inserts, even where, as in a [`SeqDelta`], it only sends a count of the
+//! former. `replace_cost = "<path>"` weighs each element with a
+//! `fn(&Item) -> usize` rather than counting it as one.
+//!
+//! A reversible type's replacement is a [`Change`] holding the old collection
+//! as well as the new, for undoing. It is chosen exactly where the plain one
+//! would be, so that [`OrReplace::into_forward`] gives what the plain diff
+//! does: weighed as both collections, it would never win, since everything a
+//! reversible delta holds is on one side or the other.
+//!
+//! ```
+//! use delta_struct::{Delta, OrReplace};
+//!
+//! #[derive(Delta)]
+//! struct Deck {
+//!     #[delta_struct(field_type = "ordered", replace_if_cheaper)]
+//!     cards: Vec<u32>,
+//! }
+//!
+//! // One card moved to the top is a splice or two…
+//! let old = Deck { cards: (0..52).collect() };
+//! let new = Deck { cards: (51..52).chain(0..51).collect() };
+//! assert!(matches!(Delta::delta(old, new).unwrap().cards, OrReplace::Delta(_)));
+//!
+//! // …but a full reversal is cheaper to send as the deck itself.
+//! let old = Deck { cards: (0..52).collect() };
+//! let new = Deck { cards: (0..52).rev().collect() };
+//! let delta = Delta::delta(old, new).unwrap();
+//! assert_eq!(delta.cards, OrReplace::Replace((0..52).rev().collect()));
+//!
+//! let mut deck = Deck { cards: (0..52).collect() };
+//! deck.apply_delta(delta);
+//! assert!(deck.cards.into_iter().eq((0..52).rev()));
+//! ```
+//!
+//! The derive emits calls to the `diff_` functions here, and to [`apply`] and
+//! [`check`] with the collection module's own as the way to apply or check
+//! the delta form.
+
+use crate::check::ApplyError;
+use crate::index::{merge_join, Joined};
+use crate::seq::{
+    self, cloned_reversible_splices, cloned_splices, edit_script, owned_splices, DiffOptions,
+};
+use crate::{BagDelta, Change, Compose, Invert, ReversibleSeqDelta, SeqDelta, TryIndex};
+use std::hash::Hash;
+use std::iter::FromIterator;
+use std::ops::Range;
+
+/// A collection delta, or the collection it produces, whichever was cheaper
+/// to send.
+///
+/// `R` is the replacement: the new elements as a `Vec`, or a [`Change`] of
+/// them on a reversible type.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OrReplace<D, R> {
+    /// The changes, as the field type would send them anyway.
+    Delta(D),
+    /// The whole new collection.
+    Replace(R),
+}
+
+impl<D: Replaceable, R> OrReplace<D, R> {
+    /// Whether the two collections were the same, and so nothing needs
+    /// sending. A replacement is only ever chosen for a real change.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            OrReplace::Delta(delta) => delta.is_empty(),
+            OrReplace::Replace(_) => false,
+        }
+    }
+}
+
+impl<D: Replaceable> OrReplace<D, Change<Vec<D::Item>>> {
+    /// Drops what only undoing needs, on either side, leaving what [`apply`]
+    /// takes.
+    pub fn into_forward(self) -> OrReplace<D::Forward, Vec<D::Item>> {
+        match self {
+            OrReplace::Delta(delta) => OrReplace::Delta(delta.into_forward()),
+            OrReplace::Replace(change) => OrReplace::Replace(change.new),
+        }
+    }
+}
+
+/// A collection delta that an [`OrReplace`] can stand in for.
+pub trait Replaceable {
+    /// The element the collection holds.
+    type Item;
+    /// The delta [`apply`] takes, with whatever only undoing needs dropped.
+    type Forward;
+
+    /// Whether the delta changes nothing.
+    fn is_empty(&self) -> bool;
+
+    /// Drops what only undoing needs.
+    fn into_forward(self) -> Self::Forward;
+
+    /// Applies the delta to the elements of a replacement, which is how
+    /// composing a replacement with a later delta folds the two into one.
+    fn apply_to(self, items: &mut Vec<Self::Item>);
+}
+
+impl<T> Replaceable for SeqDelta<T> {
+    type Item = T;
+    type Forward = Self;
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn into_forward(self) -> Self {
+        self
+    }
+
+    fn apply_to(self, items: &mut Vec<T>) {
+        seq::apply(items, self);
+    }
+}
+
+impl<T> Replaceable for ReversibleSeqDelta<T> {
+    type Item = T;
+    type Forward = SeqDelta<T>;
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn into_forward(self) -> SeqDelta<T> {
+        self.into_forward()
+    }
+
+    fn apply_to(self, items: &mut Vec<T>) {
+        seq::apply(items, self.into_forward());
+    }
+}
+
+/// A bag delta records both sides either way, so it is its own forward form.
+impl<T: PartialEq> Replaceable for BagDelta<T> {
+    type Item = T;
+    type Forward = Self;
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn into_forward(self) -> Self {
+        self
+    }
+
+    fn apply_to(self, items: &mut Vec<T>) {
+        // A replacement is a plain `Vec`, with no lookup to offer, but it is
+        // only ever composed with the deltas that follow it, so the scan
+        // is paid once per composition rather than per apply.
+        for element in self.remove {
+            if let Some(position) = items.iter().position(|item| *item == element) {
+                items.swap_remove(position);
+            }
+        }
+        items.extend(self.add);
+    }
+}
+
+/// A later replacement wins outright, and a later delta is folded into an
+/// earlier replacement.
+impl<D> Compose for OrReplace<D, Vec<D::Item>>
+where
+    D: Replaceable + Compose,
+{
+    fn compose(first: Self, second: Self) -> Self {
+        match (first, second) {
+            (OrReplace::Delta(first), OrReplace::Delta(second)) => {
+                OrReplace::Delta(D::compose(first, second))
+            }
+            (_, OrReplace::Replace(items)) => OrReplace::Replace(items),
+            (OrReplace::Replace(mut items), OrReplace::Delta(second)) => {
+                second.apply_to(&mut items);
+                OrReplace::Replace(items)
+            }
+        }
+    }
+}
+
+/// As for the forward form, except that a delta followed by a replacement
+/// still has to say what came before the delta: its inverse, applied to the
+/// old side of the replacement, works that out.
+impl<D> Compose for OrReplace<D, Change<Vec<D::Item>>>
+where
+    D: Replaceable + Compose + Invert,
+{
+    fn compose(first: Self, second: Self) -> Self {
+        match (first, second) {
+            (OrReplace::Delta(first), OrReplace::Delta(second)) => {
+                OrReplace::Delta(D::compose(first, second))
+            }
+            (OrReplace::Replace(first), OrReplace::Replace(second)) => {
+                OrReplace::Replace(Change::compose(first, second))
+            }
+            (OrReplace::Replace(Change { old, mut new }), OrReplace::Delta(second)) => {
+                second.apply_to(&mut new);
+                OrReplace::Replace(Change { old, new })
+            }
+            (OrReplace::Delta(first), OrReplace::Replace(Change { mut old, new })) => {
+                first.invert().apply_to(&mut old);
+                OrReplace::Replace(Change { old, new })
+            }
+        }
+    }
+}
+
+impl<D: Invert, R: Invert> Invert for OrReplace<D, R> {
+    fn invert(self) -> Self {
+        match self {
+            OrReplace::Delta(delta) => OrReplace::Delta(delta.invert()),
+            OrReplace::Replace(replacement) => OrReplace::Replace(replacement.invert()),
+        }
+    }
+}
+
+/// Counts every element as one, which is what `replace_if_cheaper` weighs
+/// elements by without a `replace_cost`.
+pub fn count<T>(_: &T) -> usize {
+    1
+}
+
+/// What `items` cost in all.
+fn total<'a, T: 'a>(items: impl IntoIterator<Item = &'a T>, cost: &impl Fn(&T) -> usize) -> usize {
+    items.into_iter().map(cost).sum()
+}
+
+/// Computes the [`SeqDelta`] that [`seq::diff_with`] would, or the new
+/// sequence if its items cost less than the ones the delta removes and
+/// inserts.
+///
+/// ```
+/// use delta_struct::replace::{count, diff_seq};
+/// use delta_struct::seq::DiffOptions;
+/// use delta_struct::OrReplace;
+///
+/// let (old, new) = (vec![1, 2, 3, 4], vec![4, 1, 2, 3]);
+///
+/// // Moving the 4 touches two items, fewer than the four of the sequence…
+/// let delta = diff_seq(old.clone(), new.clone(), DiffOptions::default(), count);
+/// assert!(matches!(delta, OrReplace::Delta(_)));
+///
+/// // …but weighed by their squares, the two 4s outweigh 1, 2, 3 and 4.
+/// let delta = diff_seq(old, new, DiffOptions::default(), |&item| item * item);
+/// assert_eq!(delta, OrReplace::Replace(vec![4, 1, 2, 3]));
+/// ```
+pub fn diff_seq<C, I>(
+    old: C,
+    new: C,
+    options: DiffOptions,
+    cost: impl Fn(&I) -> usize,
+) -> OrReplace<SeqDelta<I>, Vec<I>>
+where
+    C: IntoIterator<Item = I>,
+    I: Hash + Eq,
+{
+    let old: Vec<I> = old.into_iter().collect();
+    let new: Vec<I> = new.into_iter().collect();
+    let ops = edit_script(&old, &new, options);
+    if total(&new, &cost) < touched(&old, &new, &ops, &cost) {
+        return OrReplace::Replace(new);
+    }
+    OrReplace::Delta(
+        ReversibleSeqDelta {
+            splices: owned_splices(old, new, ops),
+        }
+        .into_forward(),
+    )
+}
+
+/// Computes the [`ReversibleSeqDelta`] that [`seq::diff_reversible_with`]
+/// would, or both sequences wherever [`diff_seq`] would replace.
+pub fn diff_seq_reversible<C, I>(
+    old: C,
+    new: C,
+    options: DiffOptions,
+    cost: impl Fn(&I) -> usize,
+) -> OrReplace<ReversibleSeqDelta<I>, Change<Vec<I>>>
+where
+    C: IntoIterator<Item = I>,
+    I: Hash + Eq,
+{
+    let old: Vec<I> = old.into_iter().collect();
+    let new: Vec<I> = new.into_iter().collect();
+    let ops = edit_script(&old, &new, options);
+    if total(&new, &cost) < touched(&old, &new, &ops, &cost) {
+        return OrReplace::Replace(Change { old, new });
+    }
+    OrReplace::Delta(ReversibleSeqDelta {
+        splices: owned_splices(old, new, ops),
+    })
+}
+
+/// Computes the same delta as [`diff_seq`] from borrowed sequences, cloning
+/// only what it ends up holding.
+pub fn diff_seq_ref<'a, C, I>(
+    old: &'a C,
+    new: &'a C,
+    options: DiffOptions,
+    cost: impl Fn(&I) -> usize,
+) -> OrReplace<SeqDelta<I>, Vec<I>>
+where
+    &'a C: IntoIterator<Item = &'a I>,
+    I: Hash + Eq + Clone + 'a,
+{
+    let old: Vec<&I> = old.into_iter().collect();
+    let new: Vec<&I> = new.into_iter().collect();
+    let ops = edit_script(&old, &new, options);
+    let cost = |item: &&I| cost(item);
+    if total(&new, &cost) < touched(&old, &new, &ops, &cost) {
+        return OrReplace::Replace(new.into_iter().cloned().collect());
+    }
+    OrReplace::Delta(SeqDelta {
+        splices: cloned_splices(&new, ops),
+    })
+}
+
+/// Computes the same delta as [`diff_seq_reversible`] from borrowed
+/// sequences, cloning only what it ends up holding.
+pub fn diff_seq_ref_reversible<'a, C, I>(
+    old: &'a C,
+    new: &'a C,
+    options: DiffOptions,
+    cost: impl Fn(&I) -> usize,
+) -> OrReplace<ReversibleSeqDelta<I>, Change<Vec<I>>>
+where
+    &'a C: IntoIterator<Item = &'a I>,
+    I: Hash + Eq + Clone + 'a,
+{
+    let old: Vec<&I> = old.into_iter().collect();
+    let new: Vec<&I> = new.into_iter().collect();
+    let ops = edit_script(&old, &new, options);
+    let cost = |item: &&I| cost(item);
+    if total(&new, &cost) < touched(&old, &new, &ops, &cost) {
+        let cloned = |items: Vec<&I>| items.into_iter().cloned().collect();
+        return OrReplace::Replace(Change {
+            old: cloned(old),
+            new: cloned(new),
+        });
+    }
+    OrReplace::Delta(ReversibleSeqDelta {
+        splices: cloned_reversible_splices(&old, &new, ops),
+    })
+}
+
+/// What the items an edit script removes from `old` and inserts from `new`
+/// cost in all.
+fn touched<I>(
+    old: &[I],
+    new: &[I],
+    ops: &[(usize, usize, Range<usize>)],
+    cost: &impl Fn(&I) -> usize,
+) -> usize {
+    ops.iter()
+        .map(|(at, remove, range)| {
+            total(&old[*at..at + remove], cost) + total(&new[range.clone()], cost)
+        })
+        .sum()
+}
+
+/// Takes `old` and `new` apart against each other as
+/// [`bag::diff`](crate::bag::diff) does: merged in one pass if the collection
+/// has a key order, and otherwise each element `old` shares with `new` paired
+/// in `old`'s order, followed by what `new` added.
+///
+/// Either way, the elements of each side come out in that side's own
+/// iteration order.
+fn partition<C, T>(old: C, mut new: C) -> Vec<Joined<T>>
+where
+    C: IntoIterator<Item = T> + TryIndex<T, Output = T>,
+{
+    if let Some(order) = new.key_order() {
+        return merge_join(old, new, |element| element, order).collect();
+    }
+    let mut joined: Vec<_> = old
+        .into_iter()
+        .map(|element| match new.try_remove(&element) {
+            Some(partner) => Joined::Both(element, partner),
+            None => Joined::Old(element),
+        })
+        .collect();
+    joined.extend(new.into_iter().map(Joined::New));
+    joined
+}
+
+/// Whether sending `joined`'s new side whole would cost less than the
+/// elements a [`BagDelta`] would add and remove.
+fn bag_replaces<T>(joined: &[Joined<T>], cost: impl Fn(&T) -> usize) -> bool {
+    let (mut whole, mut carried) = (0, 0);
+    for joined in joined {
+        match joined {
+            Joined::Old(element) => carried += cost(element),
+            Joined::New(element) => {
+                whole += cost(element);
+                carried += cost(element);
+            }
+            Joined::Both(_, element) => whole += cost(element),
+        }
+    }
+    whole < carried
+}
+
+/// The [`BagDelta`] `joined` makes up.
+fn bag_delta<T>(joined: Vec<Joined<T>>) -> BagDelta<T> {
+    let mut delta = BagDelta::default();
+    for joined in joined {
+        match joined {
+            Joined::Old(element) => delta.remove.push(element),
+            Joined::New(element) => delta.add.push(element),
+            Joined::Both(..) => {}
+        }
+    }
+    delta
+}
+
+/// `joined`'s old side and its new side, each in its own order.
+fn bag_sides<T>(joined: Vec<Joined<T>>) -> (Vec<T>, Vec<T>) {
+    let (mut old, mut new) = (Vec::new(), Vec::new());
+    for joined in joined {
+        match joined {
+            Joined::Old(element) => old.push(element),
+            Joined::New(element) => new.push(element),
+            Joined::Both(was, now) => {
+                old.push(was);
+                new.push(now);
+            }
+        }
+    }
+    (old, new)
+}
+
+/// Computes the [`BagDelta`] that [`bag::diff`](crate::bag::diff) would, or
+/// the new collection's elements if they cost less than the ones the delta
+/// adds and removes.
+///
+/// ```
+/// use delta_struct::replace::{count, diff_bag};
+/// use delta_struct::OrReplace;
+/// use std::collections::BTreeSet;
+///
+/// let old: BTreeSet<u32> = (0..1000).collect();
+/// let new: BTreeSet<u32> = (900..1900).collect();
+///
+/// // 900 removals and 900 additions cost more than 1000 elements.
+/// let delta = diff_bag(old, new, count);
+/// assert!(matches!(delta, OrReplace::Replace(ref elements) if elements.len() == 1000));
+/// ```
+pub fn diff_bag<C, T>(old: C, new: C, cost: impl Fn(&T) -> usize) -> OrReplace<BagDelta<T>, Vec<T>>
+where
+    C: IntoIterator<Item = T> + TryIndex<T, Output = T>,
+{
+    let joined = partition(old, new);
+    if bag_replaces(&joined, cost) {
+        return OrReplace::Replace(bag_sides(joined).1);
+    }
+    OrReplace::Delta(bag_delta(joined))
+}
+
+/// Computes the same [`BagDelta`] as [`diff_bag`], or both collections'
+/// elements wherever that would replace.
+pub fn diff_bag_reversible<C, T>(
+    old: C,
+    new: C,
+    cost: impl Fn(&T) -> usize,
+) -> OrReplace<BagDelta<T>, Change<Vec<T>>>
+where
+    C: IntoIterator<Item = T> + TryIndex<T, Output = T>,
+{
+    let joined = partition(old, new);
+    if bag_replaces(&joined, cost) {
+        let (old, new) = bag_sides(joined);
+        return OrReplace::Replace(Change { old, new });
+    }
+    OrReplace::Delta(bag_delta(joined))
+}
+
+/// Computes the same delta as [`diff_bag`] from borrowed collections,
+/// cloning only what it ends up holding.
+pub fn diff_bag_ref<'a, C, T>(
+    old: &'a C,
+    new: &'a C,
+    cost: impl Fn(&T) -> usize,
+) -> OrReplace<BagDelta<T>, Vec<T>>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    C: TryIndex<T, Output = T>,
+    T: Clone + 'a,
+{
+    let (add, remove) = added_and_removed(old, new);
+    if total(new, &cost) < total(add.iter().copied(), &cost) + total(remove.iter().copied(), &cost)
+    {
+        return OrReplace::Replace(new.into_iter().cloned().collect());
+    }
+    OrReplace::Delta(BagDelta {
+        add: add.into_iter().cloned().collect(),
+        remove: remove.into_iter().cloned().collect(),
+    })
+}
+
+/// Computes the same delta as [`diff_bag_reversible`] from borrowed
+/// collections, cloning only what it ends up holding.
+pub fn diff_bag_ref_reversible<'a, C, T>(
+    old: &'a C,
+    new: &'a C,
+    cost: impl Fn(&T) -> usize,
+) -> OrReplace<BagDelta<T>, Change<Vec<T>>>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    C: TryIndex<T, Output = T>,
+    T: Clone + 'a,
+{
+    let (add, remove) = added_and_removed(old, new);
+    let carried = total(add.iter().copied(), &cost) + total(remove.iter().copied(), &cost);
+    if total(new, &cost) < carried {
+        return OrReplace::Replace(Change {
+            old: old.into_iter().cloned().collect(),
+            new: new.into_iter().cloned().collect(),
+        });
+    }
+    OrReplace::Delta(BagDelta {
+        add: add.into_iter().cloned().collect(),
+        remove: remove.into_iter().cloned().collect(),
+    })
+}
+
+/// The elements only `new` has and those only `old` has: merged in one pass
+/// if the collection has a key order, and otherwise each looked up in the
+/// other side.
+fn added_and_removed<'a, C, T>(old: &'a C, new: &'a C) -> (Vec<&'a T>, Vec<&'a T>)
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    C: TryIndex<T, Output = T>,
+{
+    if let Some(order) = new.key_order() {
+        let BagDelta { add, remove } =
+            bag_delta(merge_join(old, new, |element| *element, order).collect());
+        return (add, remove);
+    }
+    let missing_from = |side: &'a C, other: &'a C| {
+        side.into_iter()
+            .filter(|element| other.try_index(element).is_none())
+            .collect()
+    };
+    (missing_from(new, old), missing_from(old, new))
+}
+
+/// Applies either form to `target`: the delta through `apply`, the
+/// collection module's own, or the replacement by collecting it.
+///
+/// ```
+/// use delta_struct::replace::{apply, count, diff_bag};
+/// use std::collections::BTreeSet;
+///
+/// let old: BTreeSet<u32> = (0..10).collect();
+/// let new: BTreeSet<u32> = (8..18).collect();
+///
+/// let mut target = old.clone();
+/// apply(&mut target, diff_bag(old, new.clone(), count), delta_struct::bag::apply);
+/// assert_eq!(target, new);
+/// ```
+pub fn apply<C, D>(target: &mut C, delta: OrReplace<D, Vec<C::Item>>, apply: impl FnOnce(&mut C, D))
+where
+    C: IntoIterator + FromIterator<C::Item>,
+{
+    match delta {
+        OrReplace::Delta(delta) => apply(target, delta),
+        OrReplace::Replace(items) => *target = items.into_iter().collect(),
+    }
+}
+
+/// Checks the delta form with `check`, the collection module's own. A
+/// replacement always fits.
+pub fn check<'a, C, D, R>(
+    target: &'a C,
+    delta: &OrReplace<D, R>,
+    check: impl FnOnce(&'a C, &D) -> Result<(), ApplyError>,
+) -> Result<(), ApplyError> {
+    match delta {
+        OrReplace::Delta(delta) => check(target, delta),
+        OrReplace::Replace(_) => Ok(()),
+    }
+}
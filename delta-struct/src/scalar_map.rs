@@ -0,0 +1,436 @@
+//! Keyed diffing of plain values, behind the `unordered-scalar` field type.
+//!
+//! `unordered-delta` diffs the values under a surviving key with their own
+//! [`Delta`](crate::Delta), which a map of labels, `HashMap<String, String>`,
+//! does not have. A field marked
+//! `#[delta_struct(field_type = "unordered-scalar")]` pairs entries by key
+//! the same way, into the same [`MapDelta`], but compares the values with
+//! `PartialEq` and sends a changed one whole: each [`KeyedDelta`] in `change`
+//! carries the new value.
+//!
+//! ```
+//! use delta_struct::Delta;
+//! use std::collections::HashMap;
+//!
+//! #[derive(Delta)]
+//! struct Pod {
+//!     #[delta_struct(field_type = "unordered-scalar")]
+//!     labels: HashMap<String, String>,
+//! }
+//!
+//! let pod = |labels: &[(&str, &str)]| Pod {
+//!     labels: labels.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect(),
+//! };
+//!
+//! let delta = Delta::delta(pod(&[("app", "web"), ("tier", "front")]), pod(&[("app", "api")])).unwrap();
+//! assert_eq!(delta.labels.change[0].key, "app");
+//! assert_eq!(delta.labels.change[0].delta, "api");
+//! assert_eq!(delta.labels.remove, vec!["tier".to_string()]);
+//! ```
+//!
+//! The derive emits calls to [`diff`] and [`apply`], or to [`diff_ref`] for
+//! [`DeltaRef`](crate::DeltaRef), to [`check`] for
+//! [`Delta::try_apply_delta`](crate::Delta::try_apply_delta), and to
+//! [`compose`] for [`Compose`](crate::Compose), which the delta type cannot
+//! implement for values that are not deltas themselves. A `reversible` type's
+//! field gets a [`ReversibleMapDelta`] whose changes are [`Change`]s, from
+//! [`diff_reversible`] or [`diff_ref_reversible`].
+
+use crate::check::{all_present, ApplyError, Problem};
+use crate::index::{merge_join, Joined};
+use crate::map::{compose_with, KeyedDelta, MapDelta, MapEntry, ReversibleMapDelta};
+use crate::{Change, TryIndex, TryIndexMut};
+use std::hash::Hash;
+
+/// Pairs the entries of `old` and `new` by key, and records each value that
+/// is not equal to its counterpart as a change carrying the new one.
+///
+/// As with [`map::diff`](crate::map::diff), each key of `old` is looked up in
+/// `new` exactly once, so the cost is that of n lookups, unless the map has a
+/// [key order](TryIndex::key_order) to merge the two sides by in one pass.
+///
+/// ```
+/// use delta_struct::scalar_map::diff;
+/// use std::collections::BTreeMap;
+///
+/// let old: BTreeMap<&str, u16> = vec![("http", 80), ("ssh", 22)].into_iter().collect();
+/// let new: BTreeMap<&str, u16> = vec![("http", 8080), ("ssh", 22)].into_iter().collect();
+///
+/// let delta = diff(old, new);
+/// assert_eq!(delta.change.len(), 1);
+/// assert_eq!((delta.change[0].key, delta.change[0].delta), ("http", 8080));
+/// ```
+pub fn diff<C, E>(old: C, new: C) -> MapDelta<E::Key, E::Value, E::Value>
+where
+    C: IntoIterator<Item = E> + TryIndex<E::Key, Output = E::Value>,
+    E: MapEntry,
+    E::Value: PartialEq,
+{
+    let (add, remove, change) = diff_with(old, new, |_, new| new, |key, _| key);
+    MapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// Computes the same delta as [`diff`], keeping the removed values and the
+/// old side of each change so that it can be [inverted](crate::Invert).
+pub fn diff_reversible<C, E>(
+    old: C,
+    new: C,
+) -> ReversibleMapDelta<E::Key, E::Value, Change<E::Value>>
+where
+    C: IntoIterator<Item = E> + TryIndex<E::Key, Output = E::Value>,
+    E: MapEntry,
+    E::Value: PartialEq,
+{
+    let (add, remove, change) = diff_with(
+        old,
+        new,
+        |old, new| Change { old, new },
+        |key, value| (key, value),
+    );
+    ReversibleMapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// The parts of an owning diff, with each change recorded by `changed` and
+/// each removal by `removed`.
+#[allow(clippy::type_complexity)] // The three fields of either delta type
+fn diff_with<C, E, D, R>(
+    old: C,
+    mut new: C,
+    changed: impl Fn(E::Value, E::Value) -> D,
+    removed: impl Fn(E::Key, E::Value) -> R,
+) -> (Vec<(E::Key, E::Value)>, Vec<R>, Vec<KeyedDelta<E::Key, D>>)
+where
+    C: IntoIterator<Item = E> + TryIndex<E::Key, Output = E::Value>,
+    E: MapEntry,
+    E::Value: PartialEq,
+{
+    let mut remove = Vec::new();
+    let mut change = Vec::new();
+    if let Some(order) = new.key_order() {
+        let parts = |side: C| side.into_iter().map(MapEntry::into_parts);
+        let mut add = Vec::new();
+        for joined in merge_join(parts(old), parts(new), |(key, _)| key, order) {
+            match joined {
+                Joined::Old((key, value)) => remove.push(removed(key, value)),
+                Joined::New(entry) => add.push(entry),
+                Joined::Both((key, old_value), (_, new_value)) => {
+                    if old_value != new_value {
+                        let delta = changed(old_value, new_value);
+                        change.push(KeyedDelta { key, delta });
+                    }
+                }
+            }
+        }
+        return (add, remove, change);
+    }
+    // As in `map::diff`, take each of `old`'s entries out of `new` as it is
+    // matched, so that what is left of `new` is what was added.
+    for entry in old {
+        let (key, old_value) = entry.into_parts();
+        match new.try_remove(&key) {
+            Some(new_value) => {
+                if old_value != new_value {
+                    let delta = changed(old_value, new_value);
+                    change.push(KeyedDelta { key, delta });
+                }
+            }
+            None => remove.push(removed(key, old_value)),
+        }
+    }
+    let add = new.into_iter().map(MapEntry::into_parts).collect();
+    (add, remove, change)
+}
+
+/// Computes the same [`MapDelta`] as [`diff`] from borrowed collections,
+/// cloning only the keys and values that ended up in it.
+pub fn diff_ref<'a, C, K, V>(old: &'a C, new: &'a C) -> MapDelta<K, V, V>
+where
+    &'a C: IntoIterator<Item = (&'a K, &'a V)>,
+    C: TryIndex<K, Output = V>,
+    K: Clone + 'a,
+    V: PartialEq + Clone + 'a,
+{
+    let (add, remove, change) =
+        diff_ref_with(old, new, |_, new: &V| new.clone(), |key, _| key.clone());
+    MapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// Computes the same delta as [`diff_ref`], cloning the removed values and
+/// the old side of each change too so that it can be
+/// [inverted](crate::Invert).
+pub fn diff_ref_reversible<'a, C, K, V>(
+    old: &'a C,
+    new: &'a C,
+) -> ReversibleMapDelta<K, V, Change<V>>
+where
+    &'a C: IntoIterator<Item = (&'a K, &'a V)>,
+    C: TryIndex<K, Output = V>,
+    K: Clone + 'a,
+    V: PartialEq + Clone + 'a,
+{
+    let (add, remove, change) = diff_ref_with(
+        old,
+        new,
+        |old: &V, new: &V| Change {
+            old: old.clone(),
+            new: new.clone(),
+        },
+        |key, value| (key.clone(), value.clone()),
+    );
+    ReversibleMapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// The parts of a borrowing diff, with each change recorded by `changed` and
+/// each removal by `removed`.
+#[allow(clippy::type_complexity)] // The three fields of either delta type
+fn diff_ref_with<'a, C, K, V, D, R>(
+    old: &'a C,
+    new: &'a C,
+    changed: impl Fn(&V, &V) -> D,
+    removed: impl Fn(&K, &V) -> R,
+) -> (Vec<(K, V)>, Vec<R>, Vec<KeyedDelta<K, D>>)
+where
+    &'a C: IntoIterator<Item = (&'a K, &'a V)>,
+    C: TryIndex<K, Output = V>,
+    K: Clone + 'a,
+    V: PartialEq + Clone + 'a,
+{
+    let mut remove = Vec::new();
+    let mut change = Vec::new();
+    if let Some(order) = new.key_order() {
+        let mut add = Vec::new();
+        for joined in merge_join(old, new, |(key, _)| *key, order) {
+            match joined {
+                Joined::Old((key, value)) => remove.push(removed(key, value)),
+                Joined::New((key, value)) => add.push((key.clone(), value.clone())),
+                Joined::Both((key, old_value), (_, new_value)) => {
+                    if old_value != new_value {
+                        change.push(KeyedDelta {
+                            key: key.clone(),
+                            delta: changed(old_value, new_value),
+                        });
+                    }
+                }
+            }
+        }
+        return (add, remove, change);
+    }
+    for (key, old_value) in old {
+        match new.try_index(key) {
+            Some(new_value) => {
+                if old_value != new_value {
+                    change.push(KeyedDelta {
+                        key: key.clone(),
+                        delta: changed(old_value, new_value),
+                    });
+                }
+            }
+            None => remove.push(removed(key, old_value)),
+        }
+    }
+    let add = new
+        .into_iter()
+        .filter(|(key, _)| old.try_index(key).is_none())
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    (add, remove, change)
+}
+
+/// Applies a keyed diff of plain values to `target` in place, overwriting
+/// each changed value where it sits.
+///
+/// A key in `remove` or `change` that `target` does not have is ignored; use
+/// [`try_apply`] to have the delta refused instead.
+///
+/// ```
+/// use delta_struct::scalar_map::{apply, diff};
+/// use std::collections::HashMap;
+///
+/// let ports = |http| vec![("http", http)].into_iter().collect::<HashMap<&str, u16>>();
+///
+/// let mut target = ports(80);
+/// apply(&mut target, diff(ports(80), ports(8080)));
+/// assert_eq!(target, ports(8080));
+/// ```
+pub fn apply<C, E>(target: &mut C, delta: MapDelta<E::Key, E::Value, E::Value>)
+where
+    C: IntoIterator<Item = E> + Extend<E> + TryIndexMut<E::Key, Output = E::Value>,
+    E: MapEntry,
+{
+    let MapDelta {
+        add,
+        remove,
+        change,
+    } = delta;
+    for key in remove {
+        target.try_remove(&key);
+    }
+    for KeyedDelta { key, delta } in change {
+        if let Some(value) = target.try_index_mut(&key) {
+            *value = delta;
+        }
+    }
+    target.extend(
+        add.into_iter()
+            .map(|(key, value)| E::from_parts(key, value)),
+    );
+}
+
+/// Checks that every key `delta` removes or changes is in `target`,
+/// reporting the first that is not as [`Problem::NotPresent`] for a removal
+/// and [`Problem::MissingKey`] for a change. A new value fits any old one.
+pub fn check<C, K, V>(target: &C, delta: &MapDelta<K, V, V>) -> Result<(), ApplyError>
+where
+    C: TryIndex<K, Output = V>,
+{
+    check_with(target, &delta.remove, &delta.change)
+}
+
+/// Checks a [`ReversibleMapDelta`] as [`check`] does a [`MapDelta`].
+pub fn check_reversible<C, K, V>(
+    target: &C,
+    delta: &ReversibleMapDelta<K, V, Change<V>>,
+) -> Result<(), ApplyError>
+where
+    C: TryIndex<K, Output = V>,
+{
+    check_with(
+        target,
+        delta.remove.iter().map(|(key, _)| key),
+        &delta.change,
+    )
+}
+
+/// The check of either delta type, given the keys it removes.
+fn check_with<'a, C, K, V, D>(
+    target: &C,
+    removed: impl IntoIterator<Item = &'a K>,
+    change: &[KeyedDelta<K, D>],
+) -> Result<(), ApplyError>
+where
+    C: TryIndex<K, Output = V>,
+    K: 'a,
+{
+    let present = |key: &K| target.try_index(key).is_some();
+    all_present(removed, present, |index| Problem::NotPresent { index })?;
+    all_present(change.iter().map(|change| &change.key), present, |index| {
+        Problem::MissingKey { index }
+    })
+}
+
+/// Applies a keyed diff like [`apply`], but only if it passes [`check`];
+/// otherwise `target` is left as it was.
+pub fn try_apply<C, E>(
+    target: &mut C,
+    delta: MapDelta<E::Key, E::Value, E::Value>,
+) -> Result<(), ApplyError>
+where
+    C: IntoIterator<Item = E> + Extend<E> + TryIndexMut<E::Key, Output = E::Value>,
+    E: MapEntry,
+{
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
+}
+
+/// Composes two deltas of an `unordered-scalar` field, as
+/// [`Compose`](crate::Compose) does those of an `unordered-delta` one: the
+/// later of two changes to a value wins, and an entry added and then changed
+/// is added with its new value.
+///
+/// ```
+/// use delta_struct::scalar_map::{apply, compose, diff};
+/// use std::collections::HashMap;
+///
+/// let labels = |pairs: &[(&'static str, &'static str)]| {
+///     pairs.iter().copied().collect::<HashMap<_, _>>()
+/// };
+///
+/// let first = diff(labels(&[("app", "web")]), labels(&[("app", "api"), ("env", "dev")]));
+/// let second = diff(labels(&[("app", "api"), ("env", "dev")]), labels(&[("app", "db"), ("env", "prod")]));
+/// let both = compose(first, second);
+/// assert_eq!(both.add, vec![("env", "prod")]);
+///
+/// let mut target = labels(&[("app", "web")]);
+/// apply(&mut target, both);
+/// assert_eq!(target, labels(&[("app", "db"), ("env", "prod")]));
+/// ```
+pub fn compose<K, V>(first: MapDelta<K, V, V>, second: MapDelta<K, V, V>) -> MapDelta<K, V, V>
+where
+    K: Hash + Eq,
+{
+    let (add, remove, change) = compose_with(
+        (first.add, first.remove, first.change),
+        (second.add, second.remove, second.change),
+        |key| key,
+        |key, _| key,
+        |value, new| *value = new,
+        |_, second| second,
+    );
+    MapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// Composes two reversible deltas of an `unordered-scalar` field, as
+/// [`compose`] does plain ones. A value changed and then removed is recorded
+/// as removed with the value it had before the change.
+pub fn compose_reversible<K, V>(
+    first: ReversibleMapDelta<K, V, Change<V>>,
+    second: ReversibleMapDelta<K, V, Change<V>>,
+) -> ReversibleMapDelta<K, V, Change<V>>
+where
+    K: Hash + Eq,
+{
+    let (add, remove, change) = compose_with(
+        (first.add, first.remove, first.change),
+        (second.add, second.remove, second.change),
+        |(key, _)| key,
+        |(key, _), change: Change<V>| (key, change.old),
+        |value, change: Change<V>| *value = change.new,
+        |first, second| Change {
+            old: first.old,
+            new: second.new,
+        },
+    );
+    ReversibleMapDelta {
+        add,
+        remove,
+        change,
+    }
+}
+
+/// Drops the removed values and the old side of each change, which only
+/// undoing needs, leaving the [`MapDelta`] that [`apply`] takes.
+pub fn into_forward<K, V>(delta: ReversibleMapDelta<K, V, Change<V>>) -> MapDelta<K, V, V> {
+    MapDelta {
+        add: delta.add,
+        remove: delta.remove.into_iter().map(|(key, _)| key).collect(),
+        change: delta
+            .change
+            .into_iter()
+            .map(|KeyedDelta { key, delta }| KeyedDelta {
+                key,
+                delta: delta.new,
+            })
+            .collect(),
+    }
+}
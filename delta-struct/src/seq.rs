@@ -6,13 +6,32 @@
 //! Reach for it when a field's order carries meaning and you would rather send
 //! two splices than the whole sequence.
 //!
-//! The derive emits calls to [`diff`] and [`apply`]; you only need this module
-//! directly to inspect or construct a delta by hand.
+//! `#[delta_struct(algorithm = "patience")]`, or `"lcs"`, diffs the field with
+//! another [`Algorithm`], and `#[delta_struct(deadline = "50ms")]` bounds how
+//! long a diff may take: one that runs out of time settles for a coarser
+//! script, with bigger splices than it needed, that still turns the old
+//! sequence into the new one. Both go through [`DiffOptions`] and
+//! [`diff_with`].
+//!
+//! The derive emits calls to [`diff`] and [`apply`], or to [`diff_ref`] for
+//! [`DeltaRef`](crate::DeltaRef) and [`check`] for
+//! [`Delta::try_apply_delta`](crate::Delta::try_apply_delta); you only need
+//! this module directly to inspect or construct a delta by hand, or to
+//! implement [`SpliceTarget`] for a collection of your own. A field marked
+//! `#[delta_struct(in_place)]` is applied with [`apply_in_place`], which
+//! splices the collection rather than rebuilding it. A `reversible`
+//! type's field gets a [`ReversibleSeqDelta`] instead, from
+//! [`diff_reversible`] or [`diff_ref_reversible`].
 
-use similar::algorithms::{myers, DiffHook, Replace};
+use crate::check::{ApplyError, Problem};
+use crate::{Compose, Invert};
+use similar::algorithms::{diff_slices_deadline, DiffHook, Replace};
+use std::collections::{LinkedList, VecDeque};
 use std::hash::Hash;
 use std::iter::FromIterator;
+use std::mem;
 use std::ops::Range;
+use std::time::{Duration, Instant};
 
 /// A positional diff between two sequences: an ordered edit script.
 ///
@@ -58,8 +77,336 @@ pub struct Splice<T> {
     pub insert: Vec<T>,
 }
 
+/// A change to the item at one position of a sequence, made in place rather
+/// than by a splice.
+///
+/// Turn on the `serde` feature to get `Serialize` and `Deserialize` on this,
+/// as a delta struct with an `ordered-delta` field cannot derive them
+/// otherwise.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedDelta<D> {
+    /// Where the item is, as an index into the old sequence.
+    pub index: usize,
+    /// What changed about the item, as produced by
+    /// [`Delta::delta`](crate::Delta::delta).
+    pub delta: D,
+}
+
+impl<D: Invert> Invert for IndexedDelta<D> {
+    fn invert(self) -> Self {
+        IndexedDelta {
+            index: self.index,
+            delta: self.delta.invert(),
+        }
+    }
+}
+
+/// A [`SeqDelta`] whose splices also keep the items they drop, so that it can
+/// be [inverted](Invert).
+///
+/// This is what an `ordered` field turns into on a `reversible` type. The
+/// same invariants hold: splices are sorted by [`ReversibleSplice::at`], never
+/// overlap, and index the old sequence.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReversibleSeqDelta<T> {
+    /// The edit script, in ascending order of [`ReversibleSplice::at`].
+    pub splices: Vec<ReversibleSplice<T>>,
+}
+
+impl<T> ReversibleSeqDelta<T> {
+    /// Whether the two sequences were identical, and so nothing needs sending.
+    pub fn is_empty(&self) -> bool {
+        self.splices.is_empty()
+    }
+
+    /// Drops the removed items, which only undoing needs, leaving the
+    /// [`SeqDelta`] that [`apply`] takes.
+    pub fn into_forward(self) -> SeqDelta<T> {
+        SeqDelta {
+            splices: self
+                .splices
+                .into_iter()
+                .map(|ReversibleSplice { at, remove, insert }| Splice {
+                    at,
+                    remove: remove.len(),
+                    insert,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<T> Default for ReversibleSeqDelta<T> {
+    fn default() -> Self {
+        ReversibleSeqDelta {
+            splices: Vec::new(),
+        }
+    }
+}
+
+impl<T> Invert for ReversibleSeqDelta<T> {
+    fn invert(self) -> Self {
+        // Each splice moves to where it landed in the new sequence, which is
+        // its old position shifted by what every earlier splice added or
+        // dropped. The order does not change, so the result stays sorted.
+        let mut shift = 0isize;
+        let splices = self
+            .splices
+            .into_iter()
+            .map(|ReversibleSplice { at, remove, insert }| {
+                let landed = (at as isize + shift) as usize;
+                shift += insert.len() as isize - remove.len() as isize;
+                ReversibleSplice {
+                    at: landed,
+                    remove: insert,
+                    insert: remove,
+                }
+            })
+            .collect();
+        ReversibleSeqDelta { splices }
+    }
+}
+
+/// The second delta's positions index what the first one produced, and are
+/// rebased onto the sequence the first one started from. Items the first
+/// delta inserts and the second removes appear in neither.
+///
+/// ```
+/// use delta_struct::seq::{apply, diff};
+/// use delta_struct::Compose;
+///
+/// let first = diff(vec![1, 2, 3], vec![0, 1, 2, 3]);
+/// let second = diff(vec![0, 1, 2, 3], vec![0, 1, 3, 4]);
+/// let both = Compose::compose(first, second);
+///
+/// let mut target = vec![1, 2, 3];
+/// apply(&mut target, both);
+/// assert_eq!(target, vec![0, 1, 3, 4]);
+/// ```
+impl<T> Compose for SeqDelta<T> {
+    fn compose(first: Self, second: Self) -> Self {
+        // A removal that only counts items is a removal of that many `()`s,
+        // which costs nothing to hold.
+        let edits = |delta: SeqDelta<T>| {
+            delta
+                .splices
+                .into_iter()
+                .map(|Splice { at, remove, insert }| Edit {
+                    at,
+                    remove: vec![(); remove],
+                    insert,
+                })
+                .collect()
+        };
+        let splices = compose_edits(edits(first), edits(second))
+            .into_iter()
+            .map(|Edit { at, remove, insert }| Splice {
+                at,
+                remove: remove.len(),
+                insert,
+            })
+            .collect();
+        SeqDelta { splices }
+    }
+}
+
+/// As for [`SeqDelta`]. Each removed item is kept from whichever delta
+/// removed it, so the result inverts to the composition of the inverses.
+impl<T> Compose for ReversibleSeqDelta<T> {
+    fn compose(first: Self, second: Self) -> Self {
+        let edits = |delta: ReversibleSeqDelta<T>| {
+            delta
+                .splices
+                .into_iter()
+                .map(|ReversibleSplice { at, remove, insert }| Edit { at, remove, insert })
+                .collect()
+        };
+        let splices = compose_edits(edits(first), edits(second))
+            .into_iter()
+            .map(|Edit { at, remove, insert }| ReversibleSplice { at, remove, insert })
+            .collect();
+        ReversibleSeqDelta { splices }
+    }
+}
+
+/// A splice of either delta type, with each removed item recorded as a `G`.
+pub(crate) struct Edit<T, G> {
+    pub(crate) at: usize,
+    pub(crate) remove: Vec<G>,
+    pub(crate) insert: Vec<T>,
+}
+
+/// A stretch of the sequence between two deltas: either old items the first
+/// delta kept, or items it inserted.
+enum Piece<T> {
+    Old(Range<usize>),
+    New(std::vec::IntoIter<T>),
+}
+
+impl<T> Piece<T> {
+    fn len(&self) -> usize {
+        match self {
+            Piece::Old(range) => range.end - range.start,
+            Piece::New(items) => items.len(),
+        }
+    }
+
+    /// Splits off the first `n` items, which must not be more than `len`.
+    fn split_front(&mut self, n: usize) -> Self {
+        match self {
+            Piece::Old(range) => {
+                let front = range.start..range.start + n;
+                range.start += n;
+                Piece::Old(front)
+            }
+            Piece::New(items) => Piece::New(items.by_ref().take(n).collect::<Vec<_>>().into_iter()),
+        }
+    }
+}
+
+pub(crate) fn compose_edits<T, G>(
+    first: Vec<Edit<T, G>>,
+    second: Vec<Edit<T, G>>,
+) -> Vec<Edit<T, G>> {
+    // Lay out what the first delta produced in terms of where each item came
+    // from. The last stretch of old items runs on past any real length, so
+    // the second delta never walks off the end.
+    let mut pieces = Vec::new();
+    let mut removed = Vec::new();
+    let mut cursor = 0;
+    for Edit { at, remove, insert } in first {
+        if at > cursor {
+            pieces.push(Piece::Old(cursor..at));
+        }
+        let at = at.max(cursor);
+        cursor = at + remove.len();
+        removed.extend((at..).zip(remove));
+        pieces.push(Piece::New(insert.into_iter()));
+    }
+    pieces.push(Piece::Old(cursor..usize::MAX));
+    let mut pieces = pieces.into_iter().filter(|piece| piece.len() > 0);
+
+    // Walk the second delta over that layout, keeping what it skips and
+    // noting which old items it removes. Inserted items it removes are simply
+    // gone.
+    let mut kept = Vec::new();
+    let mut position = 0;
+    let mut current = pieces.next();
+    for Edit { at, remove, insert } in second {
+        let at = at.max(position);
+        let mut skip = at - position;
+        position = at + remove.len();
+        let mut gone = remove.into_iter();
+        while skip + gone.len() > 0 {
+            let piece = match current.as_mut() {
+                Some(piece) => piece,
+                None => break,
+            };
+            let front =
+                piece.split_front(piece.len().min(if skip > 0 { skip } else { gone.len() }));
+            if skip > 0 {
+                skip -= front.len();
+                kept.push(front);
+            } else {
+                match front {
+                    Piece::Old(range) => removed.extend(range.zip(gone.by_ref())),
+                    Piece::New(items) => gone.by_ref().take(items.len()).for_each(drop),
+                }
+            }
+            if piece.len() == 0 {
+                current = pieces.next();
+            }
+        }
+        kept.push(Piece::New(insert.into_iter()));
+    }
+    kept.extend(current);
+    kept.extend(pieces);
+    removed.sort_by_key(|&(index, _)| index);
+
+    // Everything between two kept stretches of old items was removed, by one
+    // delta or the other, and everything inserted in between replaces it.
+    let mut removed = removed.into_iter().peekable();
+    let mut edits = Vec::new();
+    let mut insert = Vec::new();
+    let mut cursor = 0;
+    for piece in kept {
+        match piece {
+            Piece::New(items) => insert.extend(items),
+            Piece::Old(range) => {
+                if range.start > cursor || !insert.is_empty() {
+                    let mut remove = Vec::new();
+                    while let Some((_, item)) = removed.next_if(|&(index, _)| index < range.start) {
+                        remove.push(item);
+                    }
+                    edits.push(Edit {
+                        at: cursor,
+                        remove,
+                        insert: mem::take(&mut insert),
+                    });
+                }
+                cursor = range.end;
+            }
+        }
+    }
+    edits
+}
+
+/// One edit of a [`ReversibleSeqDelta`]: drop the `remove` items starting at
+/// `at`, then put `insert` in their place.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReversibleSplice<T> {
+    /// Where the edit starts, as an index into the old sequence.
+    pub at: usize,
+    /// The old items the edit drops.
+    pub remove: Vec<T>,
+    /// The items to put in their place.
+    pub insert: Vec<T>,
+}
+
+/// Which algorithm lines two sequences up, all by way of `similar`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Myers' algorithm, with the shortcuts `similar` takes on inputs that
+    /// would make it expensive. The default.
+    #[default]
+    Myers,
+    /// Patience diffing, which anchors on the items that occur once on each
+    /// side, and tends to read better where blocks were moved or rewritten.
+    Patience,
+    /// The classic longest-common-subsequence table, in O(n·m) time and
+    /// space. Best kept to short sequences, and a deadline makes it give up
+    /// on the whole diff at once.
+    Lcs,
+}
+
+/// How [`diff_with`] and its variants compute an edit script.
+///
+/// ```
+/// use delta_struct::seq::{Algorithm, DiffOptions};
+/// use std::time::Duration;
+///
+/// let options = DiffOptions {
+///     algorithm: Algorithm::Patience,
+///     deadline: Some(Duration::from_millis(50)),
+/// };
+/// # let _ = options;
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// The algorithm to run.
+    pub algorithm: Algorithm,
+    /// How long the diff may take, counted from when it starts. Once that
+    /// has passed, the algorithm stops looking for a short script and
+    /// replaces whatever it has left to compare wholesale. [`None`], the
+    /// default, lets it take as long as it needs.
+    pub deadline: Option<Duration>,
+}
+
 /// Records the edit script as index ranges, so nothing is cloned or owned
-/// until [`diff`] materializes the inserts from `new`.
+/// until [`diff`] or [`diff_ref`] materializes the inserts from `new`.
 #[derive(Default)]
 struct RangeHook {
     ops: Vec<(usize, usize, Range<usize>)>,
@@ -128,51 +475,231 @@ impl DiffHook for RangeHook {
 /// );
 /// ```
 pub fn diff<C, I>(old: C, new: C) -> SeqDelta<I>
+where
+    C: IntoIterator<Item = I>,
+    I: Hash + Eq,
+{
+    diff_with(old, new, DiffOptions::default())
+}
+
+/// Computes an edit script turning `old` into `new` as `options` say.
+///
+/// Past the deadline, if there is one, the script is no longer minimal, but
+/// it is still sorted and non-overlapping, and still produces `new`.
+///
+/// ```
+/// use delta_struct::seq::{apply, diff_with, Algorithm, DiffOptions};
+/// use std::time::Duration;
+///
+/// let old: Vec<u32> = (0..1000).collect();
+/// let new: Vec<u32> = (0..1000).rev().collect();
+/// let options = DiffOptions {
+///     algorithm: Algorithm::Myers,
+///     deadline: Some(Duration::from_millis(1)),
+/// };
+/// let delta = diff_with(old.clone(), new.clone(), options);
+///
+/// let mut target = old;
+/// apply(&mut target, delta);
+/// assert_eq!(target, new);
+/// ```
+pub fn diff_with<C, I>(old: C, new: C, options: DiffOptions) -> SeqDelta<I>
+where
+    C: IntoIterator<Item = I>,
+    I: Hash + Eq,
+{
+    diff_reversible_with(old, new, options).into_forward()
+}
+
+/// Computes the same delta as [`diff`], keeping the removed items so that it
+/// can be [inverted](Invert).
+pub fn diff_reversible<C, I>(old: C, new: C) -> ReversibleSeqDelta<I>
+where
+    C: IntoIterator<Item = I>,
+    I: Hash + Eq,
+{
+    diff_reversible_with(old, new, DiffOptions::default())
+}
+
+/// Computes the same delta as [`diff_with`], keeping the removed items so
+/// that it can be [inverted](Invert).
+pub fn diff_reversible_with<C, I>(old: C, new: C, options: DiffOptions) -> ReversibleSeqDelta<I>
 where
     C: IntoIterator<Item = I>,
     I: Hash + Eq,
 {
     let old: Vec<I> = old.into_iter().collect();
     let new: Vec<I> = new.into_iter().collect();
+    let ops = edit_script(&old, &new, options);
+    ReversibleSeqDelta {
+        splices: owned_splices(old, new, ops),
+    }
+}
+
+/// Turns an edit script over `old` and `new` into splices, moving the
+/// removed and inserted items out of the two.
+pub(crate) fn owned_splices<I>(
+    old: Vec<I>,
+    new: Vec<I>,
+    ops: Vec<(usize, usize, Range<usize>)>,
+) -> Vec<ReversibleSplice<I>> {
+    // The recorded ranges are ascending and non-overlapping in both
+    // coordinates, so the removed and inserted items can be pulled out of
+    // `old` and `new` in one pass each rather than indexed out (which would
+    // demand `Clone`).
+    let (mut old, mut new) = (old.into_iter(), new.into_iter());
+    let (mut old_cursor, mut new_cursor) = (0, 0);
+    ops.into_iter()
+        .map(|(at, remove, range)| {
+            old.by_ref().take(at - old_cursor).for_each(drop);
+            let remove = old.by_ref().take(remove).collect::<Vec<_>>();
+            old_cursor = at + remove.len();
+            new.by_ref().take(range.start - new_cursor).for_each(drop);
+            let insert = new.by_ref().take(range.end - range.start).collect();
+            new_cursor = range.end;
+            ReversibleSplice { at, remove, insert }
+        })
+        .collect()
+}
 
+/// Computes the same [`SeqDelta`] as [`diff`] from borrowed sequences,
+/// cloning only the items that ended up in an insert.
+///
+/// ```
+/// use delta_struct::seq::{diff_ref, Splice};
+///
+/// let (old, new) = (vec![1, 2, 3, 4], vec![1, 9, 3, 4]);
+/// let delta = diff_ref(&old, &new);
+/// assert_eq!(
+///     delta.splices,
+///     vec![Splice { at: 1, remove: 1, insert: vec![9] }],
+/// );
+/// ```
+pub fn diff_ref<'a, C, I>(old: &'a C, new: &'a C) -> SeqDelta<I>
+where
+    &'a C: IntoIterator<Item = &'a I>,
+    I: Hash + Eq + Clone + 'a,
+{
+    diff_ref_with(old, new, DiffOptions::default())
+}
+
+/// Computes the same [`SeqDelta`] as [`diff_with`] from borrowed sequences,
+/// as [`diff_ref`] does for [`diff`].
+pub fn diff_ref_with<'a, C, I>(old: &'a C, new: &'a C, options: DiffOptions) -> SeqDelta<I>
+where
+    &'a C: IntoIterator<Item = &'a I>,
+    I: Hash + Eq + Clone + 'a,
+{
+    let old: Vec<&I> = old.into_iter().collect();
+    let new: Vec<&I> = new.into_iter().collect();
+    let ops = edit_script(&old, &new, options);
+    SeqDelta {
+        splices: cloned_splices(&new, ops),
+    }
+}
+
+/// Turns an edit script over borrowed sequences into splices, cloning the
+/// inserted items out of `new`.
+pub(crate) fn cloned_splices<I: Clone>(
+    new: &[&I],
+    ops: Vec<(usize, usize, Range<usize>)>,
+) -> Vec<Splice<I>> {
+    ops.into_iter()
+        .map(|(at, remove, range)| Splice {
+            at,
+            remove,
+            insert: new[range].iter().map(|&item| item.clone()).collect(),
+        })
+        .collect()
+}
+
+/// Computes the same delta as [`diff_ref`], cloning the removed items too so
+/// that it can be [inverted](Invert).
+pub fn diff_ref_reversible<'a, C, I>(old: &'a C, new: &'a C) -> ReversibleSeqDelta<I>
+where
+    &'a C: IntoIterator<Item = &'a I>,
+    I: Hash + Eq + Clone + 'a,
+{
+    diff_ref_reversible_with(old, new, DiffOptions::default())
+}
+
+/// Computes the same delta as [`diff_ref_with`], cloning the removed items
+/// too so that it can be [inverted](Invert).
+pub fn diff_ref_reversible_with<'a, C, I>(
+    old: &'a C,
+    new: &'a C,
+    options: DiffOptions,
+) -> ReversibleSeqDelta<I>
+where
+    &'a C: IntoIterator<Item = &'a I>,
+    I: Hash + Eq + Clone + 'a,
+{
+    let old: Vec<&I> = old.into_iter().collect();
+    let new: Vec<&I> = new.into_iter().collect();
+    let ops = edit_script(&old, &new, options);
+    ReversibleSeqDelta {
+        splices: cloned_reversible_splices(&old, &new, ops),
+    }
+}
+
+/// Turns an edit script over borrowed sequences into splices, cloning both
+/// the removed and the inserted items.
+pub(crate) fn cloned_reversible_splices<I: Clone>(
+    old: &[&I],
+    new: &[&I],
+    ops: Vec<(usize, usize, Range<usize>)>,
+) -> Vec<ReversibleSplice<I>> {
+    let cloned = |items: &[&I]| items.iter().map(|&item| item.clone()).collect();
+    ops.into_iter()
+        .map(|(at, remove, range)| ReversibleSplice {
+            at,
+            remove: cloned(&old[at..at + remove]),
+            insert: cloned(&new[range]),
+        })
+        .collect()
+}
+
+/// Runs the algorithm `options` picks over the two sequences, returning each
+/// splice as `(at, remove, range of new to insert)`.
+pub(crate) fn edit_script<I: Hash + Eq>(
+    old: &[I],
+    new: &[I],
+    options: DiffOptions,
+) -> Vec<(usize, usize, Range<usize>)> {
+    let algorithm = match options.algorithm {
+        Algorithm::Myers => similar::Algorithm::Myers,
+        Algorithm::Patience => similar::Algorithm::Patience,
+        Algorithm::Lcs => similar::Algorithm::Lcs,
+    };
+    // A deadline too far off to represent is no deadline at all.
+    let deadline = options
+        .deadline
+        .and_then(|deadline| Instant::now().checked_add(deadline));
     // `Replace` coalesces an adjacent delete and insert into the single
     // `replace` call that maps onto one splice.
     let mut hook = Replace::new(RangeHook::default());
-    match myers::diff(&mut hook, &old[..], 0..old.len(), &new[..], 0..new.len()) {
+    match diff_slices_deadline(algorithm, &mut hook, old, new, deadline) {
         Ok(()) => {}
         Err(never) => match never {},
     }
-    let ops = hook.into_inner().ops;
-
-    // The recorded ranges are ascending and non-overlapping in new coordinates
-    // too, so the inserted items can be pulled out of `new` in one pass rather
-    // than indexed out (which would demand `Clone`).
-    let mut new = new.into_iter();
-    let mut cursor = 0;
-    let splices = ops
-        .into_iter()
-        .map(|(at, remove, range)| {
-            new.by_ref().take(range.start - cursor).for_each(drop);
-            let insert = new.by_ref().take(range.end - range.start).collect();
-            cursor = range.end;
-            Splice { at, remove, insert }
-        })
-        .collect();
-
-    SeqDelta { splices }
+    hook.into_inner().ops
 }
 
-/// Applies an edit script to `target` in place.
+/// Applies an edit script to `target` by taking it apart and collecting it
+/// again.
 ///
 /// Because splice positions are old-coordinates and monotonically increasing,
 /// this walks the old sequence once and rebuilds it; no random access, and no
-/// index arithmetic that could drift as edits land.
+/// index arithmetic that could drift as edits land. Every item moves, wherever
+/// the splices are, which is all an `IntoIterator + FromIterator` collection
+/// allows; [`apply_in_place`] does work proportional to the splices for a
+/// [`SpliceTarget`].
 ///
 /// A [`SeqDelta`] produced by [`diff`] always upholds the sorted,
 /// non-overlapping invariant. One that was hand-built or arrived over a wire
 /// might not, so out-of-order or overlong splices are clamped rather than
 /// allowed to panic; the result in that case is unspecified but the call
-/// still returns.
+/// still returns. Use [`try_apply`] to have such a delta refused instead.
 ///
 /// ```
 /// use delta_struct::seq::{apply, diff};
@@ -186,16 +713,253 @@ pub fn apply<C, I>(target: &mut C, delta: SeqDelta<I>)
 where
     C: IntoIterator<Item = I> + FromIterator<I>,
 {
-    let old = std::mem::replace(target, std::iter::empty().collect());
-    let mut old = old.into_iter();
+    rebuild(target, delta.splices);
+}
+
+/// Applies an edit script to `target` in place, through its
+/// [`SpliceTarget`] implementation. This is what an `ordered` field marked
+/// `in_place` is applied with.
+///
+/// It is a single forward pass from the first splice on, clamping splices as
+/// [`apply`] does. The items ahead of the first splice are never touched, so
+/// a splice near the end of a long `Vec` costs little more than the items it
+/// inserts.
+///
+/// ```
+/// use delta_struct::seq::{apply_in_place, diff};
+/// use std::collections::VecDeque;
+///
+/// let delta = diff(vec![1, 2, 3, 4], vec![1, 2, 3, 9]);
+/// let mut target: VecDeque<_> = vec![1, 2, 3, 4].into();
+/// apply_in_place(&mut target, delta);
+/// assert_eq!(target, vec![1, 2, 3, 9]);
+/// ```
+pub fn apply_in_place<C, I>(target: &mut C, delta: SeqDelta<I>)
+where
+    C: SpliceTarget<I>,
+{
+    target.apply_splices(delta.splices);
+}
+
+/// A sequence that can apply the splices of a [`SeqDelta`] to itself, for
+/// [`apply_in_place`].
+///
+/// `Vec`, `VecDeque` and `LinkedList` split themselves at the first splice
+/// and stream the rest back through the splices, and a `String`, as a
+/// sequence of `char`s, does the same from the first splice's byte offset.
+/// A `Vec` with only one splice hands it to [`Vec::splice`].
+///
+/// A collection of your own can fall back on [`rebuild`] if it has nothing
+/// better:
+///
+/// ```
+/// use delta_struct::seq::{self, SpliceTarget};
+/// use delta_struct::Splice;
+/// use std::iter::FromIterator;
+///
+/// struct Stack(Vec<u32>);
+///
+/// impl IntoIterator for Stack {
+///     type Item = u32;
+///     type IntoIter = std::vec::IntoIter<u32>;
+///
+///     fn into_iter(self) -> Self::IntoIter {
+///         self.0.into_iter()
+///     }
+/// }
+///
+/// impl FromIterator<u32> for Stack {
+///     fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+///         Stack(iter.into_iter().collect())
+///     }
+/// }
+///
+/// impl SpliceTarget<u32> for Stack {
+///     fn apply_splices(&mut self, splices: Vec<Splice<u32>>) {
+///         seq::rebuild(self, splices);
+///     }
+/// }
+///
+/// let mut stack = Stack(vec![1, 2, 3]);
+/// seq::apply_in_place(&mut stack, seq::diff(vec![1, 2, 3], vec![1, 3, 4]));
+/// assert_eq!(stack.0, vec![1, 3, 4]);
+/// ```
+pub trait SpliceTarget<I> {
+    /// Applies `splices`, sorted by [`Splice::at`] in old coordinates as a
+    /// [`SeqDelta`] holds them, clamping any that are out of order or run
+    /// past the end as [`apply`] describes.
+    fn apply_splices(&mut self, splices: Vec<Splice<I>>);
+}
+
+impl<T> SpliceTarget<T> for Vec<T> {
+    fn apply_splices(&mut self, mut splices: Vec<Splice<T>>) {
+        if splices.len() == 1 {
+            let Splice { at, remove, insert } = splices.pop().unwrap();
+            let at = at.min(self.len());
+            let end = at.saturating_add(remove).min(self.len());
+            self.splice(at..end, insert);
+            return;
+        }
+        if let Some(first) = splices.first() {
+            let offset = first.at.min(self.len());
+            let rest = self.split_off(offset);
+            stream(self, rest, offset, splices);
+        }
+    }
+}
+
+impl<T> SpliceTarget<T> for VecDeque<T> {
+    fn apply_splices(&mut self, splices: Vec<Splice<T>>) {
+        if let Some(first) = splices.first() {
+            let offset = first.at.min(self.len());
+            let rest = self.split_off(offset);
+            stream(self, rest, offset, splices);
+        }
+    }
+}
+
+impl<T> SpliceTarget<T> for LinkedList<T> {
+    fn apply_splices(&mut self, splices: Vec<Splice<T>>) {
+        if let Some(first) = splices.first() {
+            let offset = first.at.min(self.len());
+            let rest = self.split_off(offset);
+            stream(self, rest, offset, splices);
+        }
+    }
+}
+
+/// Splices count `char`s, not bytes: `at` is the position of a character.
+impl SpliceTarget<char> for String {
+    fn apply_splices(&mut self, splices: Vec<Splice<char>>) {
+        if let Some(first) = splices.first() {
+            let (offset, byte) = match self.char_indices().nth(first.at) {
+                Some((byte, _)) => (first.at, byte),
+                None => (self.chars().count(), self.len()),
+            };
+            let rest = self.split_off(byte);
+            stream(self, rest.chars(), offset, splices);
+        }
+    }
+}
+
+/// Applies `splices` to `target` by taking it apart and collecting it again,
+/// as [`apply`] does: every item moves twice, wherever the splices are. A
+/// [`SpliceTarget`] implementation for a collection that can do better
+/// should.
+pub fn rebuild<C, I>(target: &mut C, splices: Vec<Splice<I>>)
+where
+    C: IntoIterator<Item = I> + FromIterator<I>,
+{
+    let old = mem::replace(target, std::iter::empty().collect());
     let mut out: Vec<I> = Vec::new();
-    let mut cursor = 0;
-    for Splice { at, remove, insert } in delta.splices {
-        out.extend(old.by_ref().take(at.saturating_sub(cursor)));
-        old.by_ref().take(remove).for_each(drop);
+    stream(&mut out, old, 0, splices);
+    *target = out.into_iter().collect();
+}
+
+/// Extends `out` with `rest`, the items of the old sequence from `offset` on,
+/// as `splices` edit them.
+fn stream<I>(
+    out: &mut impl Extend<I>,
+    rest: impl IntoIterator<Item = I>,
+    offset: usize,
+    splices: Vec<Splice<I>>,
+) {
+    let mut rest = rest.into_iter();
+    let mut cursor = offset;
+    for Splice { at, remove, insert } in splices {
+        out.extend(rest.by_ref().take(at.saturating_sub(cursor)));
+        rest.by_ref().take(remove).for_each(drop);
         out.extend(insert);
-        cursor = at + remove;
+        cursor = at.saturating_add(remove);
     }
-    out.extend(old);
-    *target = out.into_iter().collect();
+    out.extend(rest);
+}
+
+/// Checks that the splices of `delta` are sorted, do not overlap, and stay
+/// within `target`, reporting the first that does not as
+/// [`Problem::UnsortedSplices`] or [`Problem::SpliceOutOfRange`].
+///
+/// Finding the length walks `target` once, so this is O(n) whatever the
+/// collection.
+pub fn check<'a, C, I>(target: &'a C, delta: &SeqDelta<I>) -> Result<(), ApplyError>
+where
+    &'a C: IntoIterator,
+{
+    check_with(
+        target,
+        delta
+            .splices
+            .iter()
+            .map(|splice| (splice.at, splice.remove)),
+    )
+}
+
+/// Checks a [`ReversibleSeqDelta`] as [`check`] does a [`SeqDelta`].
+pub fn check_reversible<'a, C, I>(
+    target: &'a C,
+    delta: &ReversibleSeqDelta<I>,
+) -> Result<(), ApplyError>
+where
+    &'a C: IntoIterator,
+{
+    check_with(
+        target,
+        delta
+            .splices
+            .iter()
+            .map(|splice| (splice.at, splice.remove.len())),
+    )
+}
+
+/// The check of either delta type, given each splice as `(at, remove)`.
+fn check_with<'a, C>(
+    target: &'a C,
+    splices: impl IntoIterator<Item = (usize, usize)>,
+) -> Result<(), ApplyError>
+where
+    &'a C: IntoIterator,
+{
+    check_splices(target.into_iter().count(), splices)
+}
+
+/// The check of splices, given each as `(at, remove)`, against a sequence of
+/// `len` items.
+pub(crate) fn check_splices(
+    len: usize,
+    splices: impl IntoIterator<Item = (usize, usize)>,
+) -> Result<(), ApplyError> {
+    let mut cursor = 0;
+    for (index, (at, remove)) in splices.into_iter().enumerate() {
+        if at < cursor {
+            return Err(ApplyError::new(Problem::UnsortedSplices { index }));
+        }
+        cursor = at.saturating_add(remove);
+        if cursor > len {
+            return Err(ApplyError::new(Problem::SpliceOutOfRange { index, len }));
+        }
+    }
+    Ok(())
+}
+
+/// Applies an edit script like [`apply`], but only if it passes [`check`];
+/// otherwise `target` is left as it was.
+///
+/// ```
+/// use delta_struct::seq::{diff, try_apply};
+/// use delta_struct::Problem;
+///
+/// let delta = diff(vec![1, 2, 3, 4], vec![1, 2, 3]);
+/// let mut target = vec![1, 2];
+/// let error = try_apply(&mut target, delta).unwrap_err();
+/// assert_eq!(error.problem, Problem::SpliceOutOfRange { index: 0, len: 2 });
+/// assert_eq!(target, vec![1, 2]);
+/// ```
+pub fn try_apply<C, I>(target: &mut C, delta: SeqDelta<I>) -> Result<(), ApplyError>
+where
+    C: IntoIterator<Item = I> + FromIterator<I>,
+    for<'a> &'a C: IntoIterator,
+{
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
 }
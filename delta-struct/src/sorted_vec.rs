@@ -0,0 +1,402 @@
+//! Collections kept as a sorted `Vec`, for `unordered` and `unordered-delta`
+//! fields.
+//!
+//! A plain [`Vec`] cannot be an `unordered` field, since it has no lookup
+//! faster than a scan. A [`SortedVec`] is a set, and a [`SortedVecMap`] a
+//! map, that keep their elements in a `Vec` sorted by key instead, so a
+//! lookup is a binary search, O(log n), over memory that is contiguous. Like
+//! a [`BTreeSet`](std::collections::BTreeSet) they iterate in key order,
+//! which their [`TryIndex::key_order`] says, so their diffs merge the two
+//! sides in a single pass.
+//!
+//! ```
+//! use delta_struct::{Delta, SortedVec, SortedVecMap};
+//! use std::iter::FromIterator;
+//!
+//! #[derive(Delta)]
+//! struct Host {
+//!     #[delta_struct(field_type = "unordered")]
+//!     tags: SortedVec<String>,
+//!     #[delta_struct(field_type = "unordered-delta")]
+//!     ports: SortedVecMap<String, u16>,
+//! }
+//!
+//! let host = |tags: &[&str], ports: &[(&str, u16)]| Host {
+//!     tags: tags.iter().map(|tag| tag.to_string()).collect(),
+//!     ports: ports.iter().map(|&(name, port)| (name.to_string(), port)).collect(),
+//! };
+//!
+//! let old = host(&["web", "eu"], &[("http", 80)]);
+//! let new = host(&["web", "us"], &[("http", 8080)]);
+//! let delta = Delta::delta(old, new).unwrap();
+//! assert_eq!(delta.tags.add, vec!["us".to_string()]);
+//! assert_eq!(delta.ports.change[0].delta, 8080);
+//!
+//! let tags = SortedVec::from_iter(vec![3, 1, 2, 1]);
+//! assert_eq!(tags.as_slice(), &[1, 2, 3]);
+//! ```
+//!
+//! What the layout costs is insertion and removal: each shifts the elements
+//! after it, so applying a delta that touches many elements of a long
+//! collection does much more moving than it would in a tree. Extending with
+//! many elements at once sorts them in together instead. They suit the small
+//! and mostly read collections of a wire-facing struct best.
+//!
+//! With the `serde` feature, a [`SortedVec`] serializes as a sequence and a
+//! [`SortedVecMap`] as a map, and either sorts what it deserializes.
+
+use crate::{TryIndex, TryIndexMut};
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+use std::mem;
+use std::slice;
+use std::vec;
+
+/// A set kept as a `Vec` sorted in ascending order, holding each element at
+/// most once.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SortedVec<T> {
+    items: Vec<T>,
+}
+
+impl<T> SortedVec<T> {
+    /// An empty set.
+    pub fn new() -> Self {
+        SortedVec { items: Vec::new() }
+    }
+
+    /// How many elements the set holds.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether the set holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Iterates over the elements in ascending order.
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    /// The elements, in ascending order.
+    pub fn as_slice(&self) -> &[T] {
+        &self.items
+    }
+
+    /// The `Vec` the elements are kept in, in ascending order.
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T: Ord> SortedVec<T> {
+    /// Whether the set holds `element`.
+    pub fn contains(&self, element: &T) -> bool {
+        self.items.binary_search(element).is_ok()
+    }
+
+    /// Adds `element` where it sorts, and returns whether it was not there
+    /// already. An element that was is left as it is.
+    pub fn insert(&mut self, element: T) -> bool {
+        match self.items.binary_search(&element) {
+            Ok(_) => false,
+            Err(at) => {
+                self.items.insert(at, element);
+                true
+            }
+        }
+    }
+
+    /// Removes `element`, and returns whether it was there.
+    pub fn remove(&mut self, element: &T) -> bool {
+        self.try_remove(element).is_some()
+    }
+}
+
+impl<T> Default for SortedVec<T> {
+    fn default() -> Self {
+        SortedVec::new()
+    }
+}
+
+/// Sorts the elements and drops all but the first of each run of equal ones.
+impl<T: Ord> From<Vec<T>> for SortedVec<T> {
+    fn from(mut items: Vec<T>) -> Self {
+        items.sort();
+        items.dedup();
+        SortedVec { items }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for SortedVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        SortedVec::from(Vec::from_iter(iter))
+    }
+}
+
+/// Appends the new elements and sorts them in, keeping an element already
+/// there over an equal new one, as a
+/// [`BTreeSet`](std::collections::BTreeSet) does.
+impl<T: Ord> Extend<T> for SortedVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        // The sort is stable, so each run of equal elements starts with the
+        // one that was there first.
+        self.items.extend(iter);
+        self.items.sort();
+        self.items.dedup();
+    }
+}
+
+impl<T> IntoIterator for SortedVec<T> {
+    type Item = T;
+    type IntoIter = vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a SortedVec<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<T: Ord> TryIndex<T> for SortedVec<T> {
+    type Output = T;
+
+    fn try_index(&self, index: &T) -> Option<&T> {
+        let at = self.items.binary_search(index).ok()?;
+        Some(&self.items[at])
+    }
+
+    fn try_remove(&mut self, index: &T) -> Option<T> {
+        let at = self.items.binary_search(index).ok()?;
+        Some(self.items.remove(at))
+    }
+
+    fn key_order(&self) -> Option<fn(&T, &T) -> Ordering> {
+        Some(T::cmp)
+    }
+}
+
+/// A map kept as a `Vec` of entries sorted in ascending order of key, holding
+/// each key at most once.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SortedVecMap<K, V> {
+    entries: Vec<(K, V)>,
+}
+
+impl<K, V> SortedVecMap<K, V> {
+    /// An empty map.
+    pub fn new() -> Self {
+        SortedVecMap {
+            entries: Vec::new(),
+        }
+    }
+
+    /// How many entries the map holds.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the map holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the entries in ascending order of key.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            entries: self.entries.iter(),
+        }
+    }
+
+    /// The entries, in ascending order of key.
+    pub fn as_slice(&self) -> &[(K, V)] {
+        &self.entries
+    }
+
+    /// The `Vec` the entries are kept in, in ascending order of key.
+    pub fn into_vec(self) -> Vec<(K, V)> {
+        self.entries
+    }
+}
+
+impl<K: Ord, V> SortedVecMap<K, V> {
+    /// Where the entry for `key` is, or where it would go.
+    fn search(&self, key: &K) -> Result<usize, usize> {
+        self.entries.binary_search_by(|(probe, _)| probe.cmp(key))
+    }
+
+    /// Borrows the value stored under `key`, if there is one.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.try_index(key)
+    }
+
+    /// Mutably borrows the value stored under `key`, if there is one.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.try_index_mut(key)
+    }
+
+    /// Stores `value` under `key`, and returns the value it replaced, if
+    /// there was one.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.search(&key) {
+            Ok(at) => Some(mem::replace(&mut self.entries[at].1, value)),
+            Err(at) => {
+                self.entries.insert(at, (key, value));
+                None
+            }
+        }
+    }
+
+    /// Removes the entry for `key`, and returns its value, if there was one.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.try_remove(key)
+    }
+}
+
+impl<K, V> Default for SortedVecMap<K, V> {
+    fn default() -> Self {
+        SortedVecMap::new()
+    }
+}
+
+/// Sorts the entries by key, and keeps the last of each run with equal keys,
+/// as collecting them into a [`BTreeMap`](std::collections::BTreeMap) would.
+impl<K: Ord, V> From<Vec<(K, V)>> for SortedVecMap<K, V> {
+    fn from(entries: Vec<(K, V)>) -> Self {
+        let mut map = SortedVecMap::new();
+        map.extend(entries);
+        map
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for SortedVecMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        SortedVecMap::from(Vec::from_iter(iter))
+    }
+}
+
+/// Appends the new entries and sorts them in, with a new entry's value
+/// replacing that of an entry already there under the same key, as a
+/// [`BTreeMap`](std::collections::BTreeMap) does.
+impl<K: Ord, V> Extend<(K, V)> for SortedVecMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.entries.extend(iter);
+        // The sort is stable, so the last of each run of equal keys is the
+        // newest, whose value moves into the entry that is kept.
+        self.entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.entries.dedup_by(|(key, value), (kept, kept_value)| {
+            let duplicate = key == kept;
+            if duplicate {
+                mem::swap(value, kept_value);
+            }
+            duplicate
+        });
+    }
+}
+
+impl<K, V> IntoIterator for SortedVecMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a SortedVecMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a [`SortedVecMap`] in ascending order of
+/// key, as `(&K, &V)` pairs like a std map's.
+#[derive(Clone, Debug)]
+pub struct Iter<'a, K, V> {
+    entries: slice::Iter<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K: Ord, V> TryIndex<K> for SortedVecMap<K, V> {
+    type Output = V;
+
+    fn try_index(&self, index: &K) -> Option<&V> {
+        let at = self.search(index).ok()?;
+        Some(&self.entries[at].1)
+    }
+
+    fn try_remove(&mut self, index: &K) -> Option<V> {
+        let at = self.search(index).ok()?;
+        Some(self.entries.remove(at).1)
+    }
+
+    fn key_order(&self) -> Option<fn(&K, &K) -> Ordering> {
+        Some(K::cmp)
+    }
+}
+
+impl<K: Ord, V> TryIndexMut<K> for SortedVecMap<K, V> {
+    fn try_index_mut(&mut self, index: &K) -> Option<&mut V> {
+        let at = self.search(index).ok()?;
+        Some(&mut self.entries[at].1)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for SortedVec<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.items.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de> + Ord> serde::Deserialize<'de> for SortedVec<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(SortedVec::from)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K: serde::Serialize, V: serde::Serialize> serde::Serialize for SortedVecMap<K, V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K, V> serde::Deserialize<'de> for SortedVecMap<K, V>
+where
+    K: serde::Deserialize<'de> + Ord,
+    V: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // A `BTreeMap` hands its entries back sorted and with unique keys.
+        let entries = std::collections::BTreeMap::deserialize(deserializer)?;
+        Ok(SortedVecMap {
+            entries: entries.into_iter().collect(),
+        })
+    }
+}
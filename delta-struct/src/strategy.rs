@@ -0,0 +1,96 @@
+//! Diffing a field your own way.
+//!
+//! When none of the built-in field types suits a field — a geometry that
+//! should travel as a transform, a bitset as the bits that flipped — implement
+//! [`Strategy`] for a type of your own and point the field at it with
+//! `#[delta_struct(with = "path::to::Type")]`. The field's delta is then the
+//! strategy's [`Delta`](Strategy::Delta), and the derive calls the strategy
+//! wherever it would otherwise call one of its own diffing modules:
+//!
+//! ```
+//! use delta_struct::{Delta, Strategy};
+//!
+//! /// Diffs a bitset as the bits that flipped.
+//! struct Flips;
+//!
+//! impl Strategy<u64> for Flips {
+//!     type Delta = u64;
+//!
+//!     fn diff(old: u64, new: u64) -> u64 {
+//!         old ^ new
+//!     }
+//!
+//!     fn apply(target: &mut u64, delta: u64) {
+//!         *target ^= delta;
+//!     }
+//!
+//!     fn is_empty(delta: &u64) -> bool {
+//!         *delta == 0
+//!     }
+//! }
+//!
+//! #[derive(Debug, Delta, PartialEq)]
+//! struct Permissions {
+//!     #[delta_struct(with = "Flips")]
+//!     granted: u64,
+//! }
+//!
+//! let delta = Delta::delta(Permissions { granted: 0b0110 }, Permissions { granted: 0b0011 });
+//! let delta = delta.unwrap();
+//! assert_eq!(delta.granted, 0b0101);
+//!
+//! let mut permissions = Permissions { granted: 0b0110 };
+//! permissions.apply_delta(delta);
+//! assert_eq!(permissions, Permissions { granted: 0b0011 });
+//! ```
+//!
+//! The rest of what the derive generates comes along as far as the
+//! strategy's delta allows: the delta type implements [`Compose`] if the
+//! strategy's delta does, a `reversible` container needs it to implement
+//! [`Invert`], and `DeltaRef` diffs through
+//! [`diff_ref`](Strategy::diff_ref), which clones both sides unless the
+//! strategy does better.
+//!
+//! [`Compose`]: crate::Compose
+//! [`Invert`]: crate::Invert
+
+use crate::ApplyError;
+
+/// A way of diffing values of type `T`, for a field marked
+/// `#[delta_struct(with = "...")]`.
+///
+/// The contract is the one every built-in field type keeps: applying
+/// `diff(old, new)` to a value equal to `old` leaves it equal to `new`, and
+/// the delta is empty exactly when there is nothing to apply.
+pub trait Strategy<T> {
+    /// What a change to a `T` is recorded as.
+    type Delta;
+
+    /// Computes what it would take to turn `old` into `new`.
+    fn diff(old: T, new: T) -> Self::Delta;
+
+    /// Like [`diff`](Strategy::diff), from borrows. Clones both sides and
+    /// diffs those unless overridden.
+    fn diff_ref(old: &T, new: &T) -> Self::Delta
+    where
+        T: Clone,
+    {
+        Self::diff(old.clone(), new.clone())
+    }
+
+    /// Applies `delta` to `target` in place.
+    fn apply(target: &mut T, delta: Self::Delta);
+
+    /// Whether `delta` changes nothing, so that the field counts as
+    /// unchanged.
+    fn is_empty(delta: &Self::Delta) -> bool;
+
+    /// Checks that `delta` fits `target`, for
+    /// [`try_apply_delta`](crate::Delta::try_apply_delta). Everything fits
+    /// unless overridden; [`Problem::Refused`](crate::Problem::Refused) is
+    /// there to say why something does not.
+    fn check(target: &T, delta: &Self::Delta) -> Result<(), ApplyError> {
+        let _ = (target, delta);
+        Ok(())
+    }
+}
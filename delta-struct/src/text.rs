@@ -0,0 +1,474 @@
+//! Diffing a `String` in place, behind the `text` field type.
+//!
+//! A `String` is a `scalar` by default, and so is resent whole whenever any of
+//! it changes — on every keystroke, for a document body. Marked
+//! `#[delta_struct(field_type = "text")]`, it is diffed with the same Myers
+//! machinery as an `ordered` field, over its characters or, with
+//! `granularity = "lines"`, over its lines, and the delta is a [`TextDelta`]
+//! of splices that rewrite only the stretches that changed:
+//!
+//! ```
+//! use delta_struct::text::TextSplice;
+//! use delta_struct::Delta;
+//!
+//! #[derive(Delta)]
+//! struct Document {
+//!     #[delta_struct(field_type = "text")]
+//!     body: String,
+//!     #[delta_struct(field_type = "text", granularity = "lines")]
+//!     script: String,
+//! }
+//!
+//! let document = |body: &str, script: &str| Document {
+//!     body: body.to_string(),
+//!     script: script.to_string(),
+//! };
+//!
+//! let old = document("naïve café", "let a = 1;\nlet b = 2;\n");
+//! let new = document("naïve cafés", "let a = 1;\nlet b = 3;\n");
+//! let delta = Delta::delta(old, new).unwrap();
+//! assert_eq!(
+//!     delta.body.splices,
+//!     vec![TextSplice { at: 12, remove: 0, insert: "s".to_string() }],
+//! );
+//! assert_eq!(
+//!     delta.script.splices,
+//!     vec![TextSplice { at: 11, remove: 11, insert: "let b = 3;\n".to_string() }],
+//! );
+//! ```
+//!
+//! Positions are byte offsets into the old text, as `String` indexes, and
+//! [`diff`] only ever puts them between two characters, so a splice can be
+//! used to slice the text it came from without panicking. [`check`] refuses
+//! one that would land inside a character.
+//!
+//! The derive emits calls to [`diff`] and [`apply`], and to [`check`] for
+//! [`Delta::try_apply_delta`](crate::Delta::try_apply_delta). A `reversible`
+//! type's field gets a [`ReversibleTextDelta`] instead, from
+//! [`diff_reversible`]. With the `unified-diff` feature, a delta can also be
+//! rendered as a [unified diff](TextDelta::unified_diff) for logs.
+
+use crate::check::{ApplyError, Problem};
+use crate::seq::{compose_edits, edit_script, DiffOptions, Edit};
+use crate::{Compose, Invert};
+use std::mem;
+use std::ops::Range;
+
+/// What a `text` field's diff treats as one item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Granularity {
+    /// Each character is an item, so an edit inside a line sends only the
+    /// characters that changed. The default.
+    Chars,
+    /// Each line, with its line ending, is an item, so any edit to a line
+    /// resends the line. Far fewer items to diff, for long texts.
+    Lines,
+}
+
+/// A positional diff between two texts: an ordered list of splices.
+///
+/// As with a [`SeqDelta`](crate::SeqDelta), splices are sorted by
+/// [`TextSplice::at`], never overlap, and index the *old* text.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct TextDelta {
+    /// The edit script, in ascending order of [`TextSplice::at`].
+    pub splices: Vec<TextSplice>,
+}
+
+impl TextDelta {
+    /// Whether the two texts were identical, and so nothing needs sending.
+    pub fn is_empty(&self) -> bool {
+        self.splices.is_empty()
+    }
+
+    /// Renders the change this delta makes to `old` as a unified diff over
+    /// lines, with three lines of context, for a log or a terminal.
+    ///
+    /// `old` is the text the delta was computed from; the delta itself only
+    /// holds what changed.
+    ///
+    /// ```
+    /// use delta_struct::text::{diff, Granularity};
+    ///
+    /// let (old, new) = ("one\ntwo\nthree\n", "one\n2\nthree\n");
+    /// let delta = diff(old, new, Granularity::Chars);
+    /// assert_eq!(
+    ///     delta.unified_diff(old),
+    ///     "--- old\n+++ new\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n",
+    /// );
+    /// ```
+    #[cfg(feature = "unified-diff")]
+    pub fn unified_diff(&self, old: &str) -> String {
+        let new = splice(
+            old,
+            self.splices
+                .iter()
+                .map(|splice| (splice.at, splice.remove, splice.insert.as_str())),
+        );
+        similar::udiff::unified_diff(
+            similar::Algorithm::Myers,
+            old,
+            &new,
+            3,
+            Some(("old", "new")),
+        )
+    }
+}
+
+/// One edit: drop `remove` bytes starting at `at`, then put `insert` in their
+/// place.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextSplice {
+    /// Where the edit starts, as a byte offset into the old text.
+    pub at: usize,
+    /// How many bytes of the old text the edit drops.
+    pub remove: usize,
+    /// The text to put in their place.
+    pub insert: String,
+}
+
+/// A [`TextDelta`] whose splices also keep the text they drop, so that it can
+/// be [inverted](Invert).
+///
+/// This is what a `text` field turns into on a `reversible` type.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct ReversibleTextDelta {
+    /// The edit script, in ascending order of [`ReversibleTextSplice::at`].
+    pub splices: Vec<ReversibleTextSplice>,
+}
+
+impl ReversibleTextDelta {
+    /// Whether the two texts were identical, and so nothing needs sending.
+    pub fn is_empty(&self) -> bool {
+        self.splices.is_empty()
+    }
+
+    /// Drops the removed text, which only undoing needs, leaving the
+    /// [`TextDelta`] that [`apply`] takes.
+    pub fn into_forward(self) -> TextDelta {
+        TextDelta {
+            splices: self
+                .splices
+                .into_iter()
+                .map(|ReversibleTextSplice { at, remove, insert }| TextSplice {
+                    at,
+                    remove: remove.len(),
+                    insert,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One edit of a [`ReversibleTextDelta`]: drop `remove` starting at `at`,
+/// then put `insert` in its place.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReversibleTextSplice {
+    /// Where the edit starts, as a byte offset into the old text.
+    pub at: usize,
+    /// The old text the edit drops.
+    pub remove: String,
+    /// The text to put in its place.
+    pub insert: String,
+}
+
+impl Invert for ReversibleTextDelta {
+    fn invert(self) -> Self {
+        // As for a `ReversibleSeqDelta`: each splice moves to where it landed
+        // in the new text.
+        let mut shift = 0isize;
+        let splices = self
+            .splices
+            .into_iter()
+            .map(|ReversibleTextSplice { at, remove, insert }| {
+                let landed = (at as isize + shift) as usize;
+                shift += insert.len() as isize - remove.len() as isize;
+                ReversibleTextSplice {
+                    at: landed,
+                    remove: insert,
+                    insert: remove,
+                }
+            })
+            .collect();
+        ReversibleTextDelta { splices }
+    }
+}
+
+/// The second delta's positions index what the first one produced, and are
+/// rebased onto the text the first one started from, as for a
+/// [`SeqDelta`](crate::SeqDelta).
+///
+/// ```
+/// use delta_struct::text::{apply, diff, Granularity};
+/// use delta_struct::Compose;
+///
+/// let first = diff("héllo", "héllo wörld", Granularity::Chars);
+/// let second = diff("héllo wörld", "hé wörld!", Granularity::Chars);
+/// let both = Compose::compose(first, second);
+///
+/// let mut target = "héllo".to_string();
+/// apply(&mut target, both);
+/// assert_eq!(target, "hé wörld!");
+/// ```
+impl Compose for TextDelta {
+    fn compose(first: Self, second: Self) -> Self {
+        // Composed byte by byte, since splices split the text between bytes.
+        let edits = |delta: TextDelta| {
+            delta
+                .splices
+                .into_iter()
+                .map(|TextSplice { at, remove, insert }| Edit {
+                    at,
+                    remove: vec![(); remove],
+                    insert: insert.into_bytes(),
+                })
+                .collect()
+        };
+        let splices = compose_edits(edits(first), edits(second))
+            .into_iter()
+            .map(|Edit { at, remove, insert }| TextSplice {
+                at,
+                remove: remove.len(),
+                insert: from_composed(insert),
+            })
+            .collect();
+        TextDelta { splices }
+    }
+}
+
+/// As for [`TextDelta`], keeping the text each delta removes.
+impl Compose for ReversibleTextDelta {
+    fn compose(first: Self, second: Self) -> Self {
+        let edits = |delta: ReversibleTextDelta| {
+            delta
+                .splices
+                .into_iter()
+                .map(|ReversibleTextSplice { at, remove, insert }| Edit {
+                    at,
+                    remove: remove.into_bytes(),
+                    insert: insert.into_bytes(),
+                })
+                .collect()
+        };
+        let splices = compose_edits(edits(first), edits(second))
+            .into_iter()
+            .map(|Edit { at, remove, insert }| ReversibleTextSplice {
+                at,
+                remove: from_composed(remove),
+                insert: from_composed(insert),
+            })
+            .collect();
+        ReversibleTextDelta { splices }
+    }
+}
+
+/// Turns composed bytes back into text. The bytes are pieces of the deltas'
+/// own text, cut where the deltas' positions fall, so they are whole
+/// characters unless one of the deltas put a position inside one.
+fn from_composed(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes)
+        .unwrap_or_else(|error| String::from_utf8_lossy(error.as_bytes()).into_owned())
+}
+
+/// Computes the splices turning `old` into `new`, diffing items of the given
+/// granularity.
+///
+/// Returns an empty [`TextDelta`] when the two are identical.
+///
+/// ```
+/// use delta_struct::text::{diff, Granularity, TextSplice};
+///
+/// let delta = diff("a cat", "a bat", Granularity::Chars);
+/// assert_eq!(
+///     delta.splices,
+///     vec![TextSplice { at: 2, remove: 1, insert: "b".to_string() }],
+/// );
+/// ```
+pub fn diff(old: &str, new: &str, granularity: Granularity) -> TextDelta {
+    let splices = byte_splices(old, new, granularity)
+        .map(|(remove, insert)| TextSplice {
+            at: remove.start,
+            remove: remove.end - remove.start,
+            insert: new[insert].to_string(),
+        })
+        .collect();
+    TextDelta { splices }
+}
+
+/// Computes the same delta as [`diff`], keeping the removed text so that it
+/// can be [inverted](Invert).
+pub fn diff_reversible(old: &str, new: &str, granularity: Granularity) -> ReversibleTextDelta {
+    let splices = byte_splices(old, new, granularity)
+        .map(|(remove, insert)| ReversibleTextSplice {
+            at: remove.start,
+            remove: old[remove].to_string(),
+            insert: new[insert].to_string(),
+        })
+        .collect();
+    ReversibleTextDelta { splices }
+}
+
+/// Runs Myers over the items of both texts, returning each splice as the
+/// byte range of `old` it removes and the byte range of `new` it inserts.
+fn byte_splices<'a>(
+    old: &'a str,
+    new: &'a str,
+    granularity: Granularity,
+) -> impl Iterator<Item = (Range<usize>, Range<usize>)> + 'a {
+    let (old_items, new_items) = (items(old, granularity), items(new, granularity));
+    let (old_bounds, new_bounds) = (bounds(&old_items), bounds(&new_items));
+    edit_script(&old_items, &new_items, DiffOptions::default())
+        .into_iter()
+        .map(move |(at, remove, insert)| {
+            (
+                old_bounds[at]..old_bounds[at + remove],
+                new_bounds[insert.start]..new_bounds[insert.end],
+            )
+        })
+}
+
+/// Splits `text` into the items a diff of the given granularity compares.
+fn items(text: &str, granularity: Granularity) -> Vec<&str> {
+    match granularity {
+        Granularity::Chars => text
+            .char_indices()
+            .map(|(at, c)| &text[at..at + c.len_utf8()])
+            .collect(),
+        Granularity::Lines => text.split_inclusive('\n').collect(),
+    }
+}
+
+/// The byte offset each item starts at, followed by the end of the text.
+fn bounds(items: &[&str]) -> Vec<usize> {
+    let mut at = 0;
+    let mut bounds = vec![0];
+    bounds.extend(items.iter().map(|item| {
+        at += item.len();
+        at
+    }));
+    bounds
+}
+
+/// Applies a text delta to `target` in place.
+///
+/// As with [`seq::apply`](crate::seq::apply), a delta that breaks the
+/// invariants [`diff`] upholds has its splices clamped rather than allowed to
+/// panic — here also back to the nearest character boundary. Use
+/// [`try_apply`] to have such a delta refused instead.
+///
+/// ```
+/// use delta_struct::text::{apply, diff, Granularity};
+///
+/// let delta = diff("a cat", "a bat", Granularity::Chars);
+/// let mut target = "a cat".to_string();
+/// apply(&mut target, delta);
+/// assert_eq!(target, "a bat");
+/// ```
+pub fn apply(target: &mut String, delta: TextDelta) {
+    let old = mem::take(target);
+    *target = splice(
+        &old,
+        delta
+            .splices
+            .iter()
+            .map(|splice| (splice.at, splice.remove, splice.insert.as_str())),
+    );
+}
+
+/// Rebuilds `old` with each `(at, remove, insert)` splice made, in one
+/// forward pass.
+fn splice<'a>(old: &str, splices: impl Iterator<Item = (usize, usize, &'a str)>) -> String {
+    // The last character boundary at or before `at`, and within `old`.
+    let boundary = |at: usize| {
+        let mut at = at.min(old.len());
+        while !old.is_char_boundary(at) {
+            at -= 1;
+        }
+        at
+    };
+    let mut out = String::with_capacity(old.len());
+    let mut cursor = 0;
+    for (at, remove, insert) in splices {
+        let at = boundary(at.max(cursor));
+        out.push_str(&old[cursor..at]);
+        out.push_str(insert);
+        cursor = boundary(at.saturating_add(remove));
+    }
+    out.push_str(&old[cursor..]);
+    out
+}
+
+/// Checks that the splices of `delta` are sorted, do not overlap, stay within
+/// `target`, and start and end between two characters, reporting the first
+/// that does not as [`Problem::UnsortedSplices`],
+/// [`Problem::SpliceOutOfRange`] or [`Problem::SplitsChar`].
+pub fn check(target: &str, delta: &TextDelta) -> Result<(), ApplyError> {
+    check_with(
+        target,
+        delta
+            .splices
+            .iter()
+            .map(|splice| (splice.at, splice.remove)),
+    )
+}
+
+/// Checks a [`ReversibleTextDelta`] as [`check`] does a [`TextDelta`].
+pub fn check_reversible(target: &str, delta: &ReversibleTextDelta) -> Result<(), ApplyError> {
+    check_with(
+        target,
+        delta
+            .splices
+            .iter()
+            .map(|splice| (splice.at, splice.remove.len())),
+    )
+}
+
+/// The check of either delta type, given each splice as `(at, remove)`.
+fn check_with(
+    target: &str,
+    splices: impl IntoIterator<Item = (usize, usize)>,
+) -> Result<(), ApplyError> {
+    let mut cursor = 0;
+    for (index, (at, remove)) in splices.into_iter().enumerate() {
+        if at < cursor {
+            return Err(ApplyError::new(Problem::UnsortedSplices { index }));
+        }
+        cursor = at.saturating_add(remove);
+        if cursor > target.len() {
+            return Err(ApplyError::new(Problem::SpliceOutOfRange {
+                index,
+                len: target.len(),
+            }));
+        }
+        if !target.is_char_boundary(at) || !target.is_char_boundary(cursor) {
+            return Err(ApplyError::new(Problem::SplitsChar { index }));
+        }
+    }
+    Ok(())
+}
+
+/// Applies a text delta like [`apply`], but only if it passes [`check`];
+/// otherwise `target` is left as it was.
+///
+/// ```
+/// use delta_struct::text::{try_apply, TextDelta, TextSplice};
+/// use delta_struct::Problem;
+///
+/// // Byte 1 is inside the `é`.
+/// let delta = TextDelta {
+///     splices: vec![TextSplice { at: 1, remove: 1, insert: "e".to_string() }],
+/// };
+/// let mut target = "é".to_string();
+/// let error = try_apply(&mut target, delta).unwrap_err();
+/// assert_eq!(error.problem, Problem::SplitsChar { index: 0 });
+/// assert_eq!(target, "é");
+/// ```
+pub fn try_apply(target: &mut String, delta: TextDelta) -> Result<(), ApplyError> {
+    check(target, &delta)?;
+    apply(target, delta);
+    Ok(())
+}
@@ -41,7 +41,7 @@
 //! assert_eq!(receiver.get().port, 8080);
 //! ```
 
-use crate::{fingerprint_of, Delta, Fingerprint};
+use crate::{fingerprint_of, Delta, DeltaRef, Fingerprint};
 use std::fmt;
 
 /// A delta, plus everything needed to tell whether it belongs here.
@@ -98,6 +98,22 @@ impl<T> Versioned<T> {
     pub fn into_inner(self) -> T {
         self.value
     }
+
+    /// Stamps `delta` as the step to the next version, or leaves the version
+    /// where it is when there is no delta.
+    fn stamp<D>(&mut self, base: u64, result: u64, delta: Option<D>) -> Option<VersionedDelta<D>> {
+        delta.map(|delta| {
+            let from = self.version;
+            self.version += 1;
+            VersionedDelta {
+                from,
+                to: self.version,
+                base,
+                result,
+                delta,
+            }
+        })
+    }
 }
 
 impl<T: Fingerprint> Versioned<T> {
@@ -115,22 +131,26 @@ impl<T: Delta + Fingerprint + Clone> Versioned<T> {
     /// and no number.
     ///
     /// `T: Clone` is needed because [`Delta::delta`] consumes both sides and
-    /// the new value has to be kept as well as diffed.
+    /// the new value has to be kept as well as diffed. A type that derives
+    /// [`DeltaRef`] can use [`commit_ref`](Versioned::commit_ref) instead.
     pub fn commit(&mut self, new: T) -> Option<VersionedDelta<T::Output>> {
         let base = fingerprint_of(&self.value);
         let result = fingerprint_of(&new);
         let old = std::mem::replace(&mut self.value, new.clone());
-        Delta::delta(old, new).map(|delta| {
-            let from = self.version;
-            self.version += 1;
-            VersionedDelta {
-                from,
-                to: self.version,
-                base,
-                result,
-                delta,
-            }
-        })
+        self.stamp(base, result, Delta::delta(old, new))
+    }
+}
+
+impl<T: DeltaRef + Fingerprint> Versioned<T> {
+    /// Moves to `new` like [`commit`](Versioned::commit), but diffs through
+    /// [`DeltaRef::delta_ref`], so only the parts that changed are cloned and
+    /// `T` need not be `Clone` at all.
+    pub fn commit_ref(&mut self, new: T) -> Option<VersionedDelta<T::Output>> {
+        let base = fingerprint_of(&self.value);
+        let result = fingerprint_of(&new);
+        let delta = DeltaRef::delta_ref(&self.value, &new);
+        self.value = new;
+        self.stamp(base, result, delta)
     }
 }
 